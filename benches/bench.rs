@@ -1,29 +1,53 @@
 use ca_formats::{macrocell::Macrocell, rle::Rle};
 use criterion::{criterion_group, criterion_main, Criterion};
-use hashlife::World;
-use std::time::Duration;
+use hashlife::{view::Viewport, NodeStore, Rule, SmallWorld, World};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 fn run_pattern(pattern: &str, step_log2: u8, steps: u32) {
     let rle = Rle::new(pattern).unwrap();
     let mut world = World::from_rle(rle).unwrap();
     world.set_step(step_log2);
-    for _ in 0..steps {
-        world.step();
-    }
+    world.step_many(steps);
 }
 
-fn test_gc(with_gc: bool) {
+fn gc_world() -> World {
     let macrocell = Macrocell::new(include_str!("../patterns/metapixel-galaxy.mc")).unwrap();
     let mut world = World::from_macrocell(macrocell).unwrap();
     world.set_step(12);
-    for _ in 0..16 {
-        world.step();
-    }
+    world.step_many(16);
+    world
+}
+
+fn test_gc(with_gc: bool) {
+    let mut world = gc_world();
     if with_gc {
         world.garbage_collect();
     }
 }
 
+// The metapixel galaxy, fitted to a full HD screen, in a root of level 60.
+fn render_world() -> (World, Viewport) {
+    let macrocell = Macrocell::new(include_str!("../patterns/metapixel-galaxy.mc")).unwrap();
+    let mut world = World::from_macrocell(macrocell).unwrap();
+    let mut viewport = Viewport::new(1920, 1080);
+    viewport.fit(world.bound());
+    world.set_cell(1 << 58, 1 << 58, true);
+    (world, viewport)
+}
+
+fn load_macrocell(from_bytes: bool) {
+    let macrocell = include_str!("../patterns/metapixel-galaxy.mc");
+    let world = if from_bytes {
+        World::from_macrocell_bytes(macrocell.as_bytes()).unwrap()
+    } else {
+        World::from_macrocell(Macrocell::new(macrocell).unwrap()).unwrap()
+    };
+    assert!(world.population() > 0);
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     let mut group = c.benchmark_group("All tests");
     group.warm_up_time(Duration::from_secs(10));
@@ -55,7 +79,91 @@ fn criterion_benchmark(c: &mut Criterion) {
             b.iter(|| run_pattern(include_str!("../patterns/Sierpinski-builder.rle"), 20, 16))
         })
         .bench_function("without_gc", |b| b.iter(|| test_gc(false)))
-        .bench_function("with_gc", |b| b.iter(|| test_gc(true)));
+        .bench_function("with_gc", |b| b.iter(|| test_gc(true)))
+        // Only the collection, without stepping to the garbage.
+        .bench_function("gc_pause", |b| {
+            let world = gc_world();
+            b.iter_custom(|iters| {
+                let mut pauses = Duration::ZERO;
+                for _ in 0..iters {
+                    let mut world = world.clone();
+                    let start = Instant::now();
+                    world.garbage_collect();
+                    pauses += start.elapsed();
+                }
+                pauses
+            })
+        })
+        // The bound of a freshly loaded pattern, whose populations are not
+        // computed yet, as when fitting it to the screen.
+        .bench_function("bound", |b| {
+            let macrocell = include_str!("../patterns/metapixel-galaxy.mc");
+            let world = World::from_macrocell(Macrocell::new(macrocell).unwrap()).unwrap();
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    let world = world.clone();
+                    let start = Instant::now();
+                    assert!(world.bound().is_some());
+                    elapsed += start.elapsed();
+                }
+                elapsed
+            })
+        })
+        .bench_function("render_deep", |b| {
+            let (world, viewport) = render_world();
+            b.iter(|| {
+                let mut squares = 0;
+                viewport.render(&world, |_, _, _| squares += 1);
+                squares
+            })
+        })
+        .bench_function("glider_world", |b| {
+            let glider = World::from_bytes(b"bo$2bo$3o!").unwrap();
+            b.iter(|| {
+                let mut world = glider.clone();
+                world.step_many(1000);
+                world.population()
+            })
+        })
+        // One step at a time, so that each generation is written to nodes.
+        .bench_function("glider_world_steps", |b| {
+            let glider = World::from_bytes(b"bo$2bo$3o!").unwrap();
+            b.iter(|| {
+                let mut world = glider.clone();
+                for _ in 0..1000 {
+                    world.step();
+                }
+                world.population()
+            })
+        })
+        // Worlds in a shared store are always stepped as nodes.
+        .bench_function("glider_world_nodes", |b| {
+            let glider = World::from_bytes(b"bo$2bo$3o!").unwrap();
+            b.iter(|| {
+                let store = Arc::new(NodeStore::new(Rule::default()));
+                let mut world = World::new_in(&store);
+                world.paste(&glider, (0, 0));
+                world.step_many(1000);
+                world.population()
+            })
+        })
+        .bench_function("glider_small", |b| {
+            let glider = World::from_bytes(b"bo$2bo$3o!").unwrap();
+            let small = SmallWorld::from_world(&glider).unwrap();
+            b.iter(|| {
+                let mut small = small.clone();
+                for _ in 0..1000 {
+                    small.step();
+                }
+                small.population()
+            })
+        })
+        .bench_function("default_world", |b| {
+            b.iter(|| World::default().population())
+        })
+        .bench_function("load_macrocell", |b| b.iter(|| load_macrocell(false)))
+        .bench_function("load_macrocell_bytes", |b| b.iter(|| load_macrocell(true)));
 
     group.finish();
 }
@@ -0,0 +1,29 @@
+// Rasterizes a pattern on a second thread while the main thread reads its
+// statistics. Both only need `&World`, since `World` is `Sync`.
+//
+// Run with `cargo run --example render_thread`.
+
+use hashlife::World;
+use std::thread;
+
+fn main() {
+    let mut world = World::from_bytes(include_bytes!("../patterns/totalperiodic.mc")).unwrap();
+    world.step_by(1000);
+    let bound = world.bound().unwrap();
+
+    let world = &world;
+    thread::scope(|scope| {
+        let render = scope.spawn(move || world.rasterize(bound, 2));
+        println!(
+            "Generation {}, population {}",
+            world.get_generation(),
+            world.population()
+        );
+        let raster = render.join().unwrap().expect("The image is too large.");
+        let lit = raster.pixels.iter().filter(|&&p| p > 0).count();
+        println!(
+            "Rendered {}x{} pixels, {} of them lit",
+            raster.width, raster.height, lit
+        );
+    });
+}
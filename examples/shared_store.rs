@@ -0,0 +1,77 @@
+// Runs 100 soups that differ in a single cell, first each in its own world,
+// then all in one `NodeStore`, and compares the number of nodes.
+//
+// Run with `cargo run --release --example shared_store`.
+
+use hashlife::{NodeStore, World};
+use std::sync::Arc;
+
+const SOUPS: usize = 100;
+const SIZE: i64 = 16;
+const GENERATIONS: u64 = 1 << 10;
+
+// A xorshift generator, so that the soups are the same on every run.
+fn soup(seed: u64) -> Vec<(i64, i64)> {
+    let mut state = seed;
+    let mut cells = Vec::new();
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            if state & 1 != 0 {
+                cells.push((x, y));
+            }
+        }
+    }
+    cells
+}
+
+// The base soup with the cell `i` flipped.
+fn variant(base: &[(i64, i64)], i: usize) -> Vec<(i64, i64)> {
+    let cell = (i as i64 % SIZE, i as i64 / SIZE);
+    let mut cells: Vec<_> = base.iter().copied().filter(|&c| c != cell).collect();
+    if cells.len() == base.len() {
+        cells.push(cell);
+    }
+    cells
+}
+
+fn run(world: &mut World, cells: Vec<(i64, i64)>) {
+    world.set_cells(cells, true);
+    world.step_by(GENERATIONS);
+    world.garbage_collect();
+}
+
+fn main() {
+    let base = soup(0x2545_f491_4f6c_dd1d);
+
+    let mut separate_nodes = 0;
+    let mut separate_memory = 0;
+    for i in 0..SOUPS {
+        let mut world = World::default();
+        run(&mut world, variant(&base, i));
+        separate_nodes += world.stats().nodes;
+        separate_memory += world.memory_usage();
+    }
+
+    let store = Arc::new(NodeStore::new("B3/S23".parse().unwrap()));
+    let mut worlds: Vec<_> = (0..SOUPS).map(|_| World::new_in(&store)).collect();
+    for (i, world) in worlds.iter_mut().enumerate() {
+        run(world, variant(&base, i));
+    }
+    let shared_nodes = worlds[0].stats().nodes;
+    let shared_memory = worlds[0].memory_usage();
+
+    println!("{} soups, {} generations each:", SOUPS, GENERATIONS);
+    println!(
+        "  separate worlds: {} nodes, {} KiB",
+        separate_nodes,
+        separate_memory / 1024
+    );
+    println!(
+        "  shared store:    {} nodes, {} KiB",
+        shared_nodes,
+        shared_memory / 1024
+    );
+}
@@ -0,0 +1,30 @@
+// Prints, for each bundled pattern, the number of nodes and how many times
+// fewer nodes there would be if the nodes were stored up to rotation and
+// reflection.
+//
+// Run with `cargo run --release --features experimental-symmetry --example symmetry`.
+
+use hashlife::World;
+
+const PATTERNS: [&str; 7] = [
+    "metapixel-galaxy.mc",
+    "totalperiodic.mc",
+    "demonoid-c512-hashlife-friendly.mc.gz",
+    "c4-diag-switch-engines.rle",
+    "switch-engine-breeder.rle",
+    "zigzag-wickstretcher.rle",
+    "Sierpinski-builder.rle",
+];
+
+fn main() {
+    for name in PATTERNS {
+        let mut world = World::from_file(format!("patterns/{}", name)).unwrap();
+        world.garbage_collect();
+        println!(
+            "{:<40} {:>8} nodes, ratio {:.2}",
+            name,
+            world.stats().nodes,
+            world.symmetry_compression_ratio()
+        );
+    }
+}
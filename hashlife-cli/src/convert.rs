@@ -0,0 +1,153 @@
+use crate::{format_from_name, format_from_path, load, parse_rule, save, Failure, STDIO};
+use hashlife::{Format, Rule};
+
+const USAGE: &str = "\
+Usage: hashlife convert [OPTIONS] INPUT OUTPUT
+
+Reads an RLE, Macrocell, Plaintext or Life 1.06 pattern (possibly gzipped), or
+the standard input if INPUT is -, and writes it to OUTPUT, or to the standard
+output if OUTPUT is -. The output is gzipped if OUTPUT ends in .gz.
+
+Options:
+  --to FORMAT     rle, mc, cells or life106 (default: from the extension of
+                  OUTPUT: .rle, .mc, .cells, .lif or .life)
+  --center        Move the center of the bounding box to the origin
+  --trim          Move the top-left corner of the bounding box to the origin
+  --rule RULE     Override the rule of the pattern, e.g. B36/S23
+  --help          Print this help
+";
+
+// Where to move the pattern before writing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Placement {
+    Keep,
+    Center,
+    Trim,
+}
+
+struct Options {
+    input: String,
+    output: String,
+    format: Format,
+    placement: Placement,
+    rule: Option<Rule>,
+}
+
+// Returns `None` on `--help`.
+fn parse<I: Iterator<Item = String>>(args: I) -> Result<Option<Options>, Failure> {
+    let mut paths = Vec::new();
+    let mut format = None;
+    let mut placement = Placement::Keep;
+    let mut rule = None;
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| Failure::Usage(format!("{} requires a value", arg), USAGE))
+        };
+        match arg.as_str() {
+            "--help" | "-h" => return Ok(None),
+            "--to" => {
+                let name = value()?;
+                format = Some(format_from_name(&name).ok_or_else(|| {
+                    Failure::Usage(
+                        format!(
+                            "Unknown format {}, expected rle, mc, cells or life106",
+                            name
+                        ),
+                        USAGE,
+                    )
+                })?);
+            }
+            "--center" | "--trim" if placement != Placement::Keep => {
+                return Err(Failure::Usage(
+                    "--center and --trim cannot be used together".to_string(),
+                    USAGE,
+                ))
+            }
+            "--center" => placement = Placement::Center,
+            "--trim" => placement = Placement::Trim,
+            "--rule" => rule = Some(parse_rule(&value()?)?),
+            _ if arg.starts_with('-') && arg != STDIO => {
+                return Err(Failure::Usage(format!("Unknown option {}", arg), USAGE))
+            }
+            _ if paths.len() == 2 => {
+                return Err(Failure::Usage(
+                    format!("Unexpected argument {}", arg),
+                    USAGE,
+                ))
+            }
+            _ => paths.push(arg),
+        }
+    }
+    let output = match paths.pop() {
+        Some(output) if !paths.is_empty() => output,
+        _ => {
+            return Err(Failure::Usage(
+                "Expected an input and an output".to_string(),
+                USAGE,
+            ))
+        }
+    };
+    let format = match format.or_else(|| format_from_path(&output)) {
+        Some(format) => format,
+        None => {
+            return Err(Failure::Usage(
+                format!("Cannot tell the format of {}, use --to", output),
+                USAGE,
+            ))
+        }
+    };
+    Ok(Some(Options {
+        input: paths.pop().unwrap(),
+        output,
+        format,
+        placement,
+        rule,
+    }))
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), Failure> {
+    let options = match parse(args)? {
+        Some(options) => options,
+        None => {
+            print!("{}", USAGE);
+            return Ok(());
+        }
+    };
+    let mut world = load(&options.input, options.rule.as_ref())?;
+    if let Some((left, right, top, bottom)) = world.bound() {
+        let offset = match options.placement {
+            Placement::Keep => (0, 0),
+            Placement::Center => (-left - (right - left) / 2, -top - (bottom - top) / 2),
+            Placement::Trim => (-left, -top),
+        };
+        if offset != (0, 0) {
+            world.translate(offset);
+        }
+    }
+    save(&world, &options.output, options.format)?;
+
+    // The summary goes to the standard error when the pattern goes to the
+    // standard output.
+    let size = match world.bound() {
+        Some((left, right, top, bottom)) => format!(
+            "{}x{}",
+            right.wrapping_sub(left) as u64,
+            bottom.wrapping_sub(top) as u64
+        ),
+        None => "empty".to_string(),
+    };
+    let summary = format!(
+        "Wrote {}: {}, population {}",
+        options.output,
+        size,
+        world.population()
+    );
+    if options.output == STDIO {
+        eprintln!("{}", summary);
+    } else {
+        println!("{}", summary);
+    }
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use crate::{
+    format_bound, format_name, json_bound, json_string, parse_pattern, read_input, Failure, STDIO,
+};
+use hashlife::Format;
+
+const USAGE: &str = "\
+Usage: hashlife info [OPTIONS] FILE...
+
+Prints the format, the rule, the bounding box, the population, the number of
+nodes and the level of the root node of each pattern, and the generation
+stored in Macrocell files, without running them. FILE can be - for the
+standard input. A file that cannot be read is reported, and the others are
+still printed; the exit code is then the one of the first failure.
+
+Options:
+  --json          Print one JSON object per line
+  --help          Print this help
+";
+
+// Returns `None` on `--help`.
+fn parse<I: Iterator<Item = String>>(args: I) -> Result<Option<(Vec<String>, bool)>, Failure> {
+    let mut paths = Vec::new();
+    let mut json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--help" | "-h" => return Ok(None),
+            "--json" => json = true,
+            _ if arg.starts_with('-') && arg != STDIO => {
+                return Err(Failure::Usage(format!("Unknown option {}", arg), USAGE))
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return Err(Failure::Usage("No input file given".to_string(), USAGE));
+    }
+    Ok(Some((paths, json)))
+}
+
+// Everything comes from the quadtree, so that huge patterns are never
+// expanded.
+fn print_info(path: &str, json: bool) -> Result<(), Failure> {
+    let (bytes, gzipped) = read_input(path)?;
+    let format = Format::detect(&bytes);
+    let world = parse_pattern(&bytes, path, None)?;
+    let rule = world.get_rule().rulestring().unwrap_or("Custom rule");
+    let stats = world.stats();
+    // Only Macrocell stores the generation.
+    let generation = (format == Format::Macrocell).then(|| world.get_generation());
+    if json {
+        println!(
+            "{{\"file\": {}, \"format\": {}, \"gzipped\": {}, \"rule\": {}, \"bound\": {}, \
+             \"population\": {}, \"nodes\": {}, \"level\": {}, \"generation\": {}}}",
+            json_string(path),
+            json_string(format_name(format)),
+            gzipped,
+            json_string(rule),
+            json_bound(world.bound()),
+            world.population(),
+            stats.nodes,
+            stats.root_level,
+            generation.map_or("null".to_string(), |g| g.to_string()),
+        );
+    } else {
+        println!("File        {}", path);
+        println!(
+            "Format      {}{}",
+            format_name(format),
+            if gzipped { " (gzipped)" } else { "" }
+        );
+        println!("Rule        {}", rule);
+        println!("Bound       {}", format_bound(world.bound()));
+        println!("Population  {}", world.population());
+        println!("Nodes       {}", stats.nodes);
+        println!("Level       {}", stats.root_level);
+        if let Some(generation) = generation {
+            println!("Generation  {}", generation);
+        }
+    }
+    Ok(())
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), Failure> {
+    let (paths, json) = match parse(args)? {
+        Some(options) => options,
+        None => {
+            print!("{}", USAGE);
+            return Ok(());
+        }
+    };
+    let mut exit_code = None;
+    for (i, path) in paths.iter().enumerate() {
+        if i > 0 && !json {
+            println!();
+        }
+        if let Err(e) = print_info(path, json) {
+            eprintln!("Error: {}: {}", path, e);
+            exit_code.get_or_insert(e.exit_code());
+        }
+    }
+    exit_code.map_or(Ok(()), |code| Err(Failure::Reported(code)))
+}
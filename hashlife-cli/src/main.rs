@@ -0,0 +1,356 @@
+mod convert;
+mod info;
+mod run;
+
+use flate2::{bufread::GzDecoder, write::GzEncoder, Compression};
+use hashlife::{
+    ca_formats::{macrocell::Macrocell, rle::Rle},
+    Format, LoadError, Rule, World,
+};
+use std::{
+    env::args,
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs::File,
+    io::{self, BufWriter, Error as IoError, IsTerminal, Read, Write},
+    ops::{ControlFlow, Deref},
+    process::exit,
+};
+
+const USAGE: &str = "\
+Usage: hashlife COMMAND [OPTIONS]
+
+Commands:
+  run       Advance a pattern, and print its statistics
+  convert   Convert a pattern to another format
+  info      Print the statistics of patterns without running them
+  help      Print this help
+
+Run `hashlife COMMAND --help` for the options of a command.
+
+Input files can also be HTTP or HTTPS URLs.
+
+Exit codes: 0 on success, 1 on invalid arguments, I/O or download errors, 2
+if the pattern cannot be parsed, 3 if its rule is not supported.
+";
+
+// The path that stands for the standard input or output.
+const STDIO: &str = "-";
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+// Why a command failed, which decides the exit code.
+#[derive(Debug)]
+pub enum Failure {
+    // An invalid argument, with the usage of the command.
+    Usage(String, &'static str),
+    Io(IoError),
+    // The server cannot be reached, or answers with an error.
+    #[cfg(feature = "http")]
+    Download(String),
+    Parse(String),
+    Rule(String),
+    // Already printed, with the exit code.
+    Reported(i32),
+}
+
+impl Failure {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Failure::Usage(..) | Failure::Io(_) => 1,
+            #[cfg(feature = "http")]
+            Failure::Download(_) => 1,
+            Failure::Parse(_) => 2,
+            Failure::Rule(_) => 3,
+            Failure::Reported(code) => *code,
+        }
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Failure::Usage(message, _) => write!(f, "{}", message),
+            Failure::Io(e) => write!(f, "{}", e),
+            #[cfg(feature = "http")]
+            Failure::Download(message) => write!(f, "{}", message),
+            Failure::Parse(message) | Failure::Rule(message) => write!(f, "{}", message),
+            Failure::Reported(_) => Ok(()),
+        }
+    }
+}
+
+impl From<IoError> for Failure {
+    fn from(e: IoError) -> Self {
+        Failure::Io(e)
+    }
+}
+
+pub fn parse_number<T: std::str::FromStr>(s: &str, usage: &'static str) -> Result<T, Failure> {
+    s.parse()
+        .map_err(|_| Failure::Usage(format!("Invalid number {}", s), usage))
+}
+
+pub fn parse_rule(s: &str) -> Result<Rule, Failure> {
+    s.parse()
+        .map_err(|e| Failure::Rule(format!("Unsupported rule {}: {}", s, e)))
+}
+
+// The rule in the header of an RLE or Macrocell pattern.
+fn header_rule(bytes: &[u8]) -> Option<String> {
+    if bytes.starts_with(b"[M2]") {
+        Macrocell::new(bytes).ok()?.rule().map(str::to_string)
+    } else {
+        Rle::new(bytes).ok()?.header_data()?.rule.clone()
+    }
+}
+
+#[cfg(feature = "http")]
+fn download(url: &str) -> Result<Vec<u8>, Failure> {
+    hashlife::http::download(url).map_err(|e| match e {
+        LoadError::Io(e) => Failure::Io(e),
+        e => Failure::Download(format!("Cannot download {}: {}", url, e)),
+    })
+}
+
+#[cfg(not(feature = "http"))]
+fn download(url: &str) -> Result<Vec<u8>, Failure> {
+    Err(Failure::Io(IoError::new(
+        io::ErrorKind::Unsupported,
+        format!("Cannot download {}: built without the http feature", url),
+    )))
+}
+
+// The content of an input, read into memory or mapped.
+pub enum InputBytes {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Deref for InputBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            InputBytes::Owned(bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            InputBytes::Mapped(map) => map,
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+fn read_file(path: &str) -> Result<InputBytes, Failure> {
+    let file = File::open(path)?;
+    // Safety: the file must not be modified while it is mapped, which is
+    // assumed for the short life of a command, as with any other reader.
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    Ok(InputBytes::Mapped(map))
+}
+
+#[cfg(not(feature = "mmap"))]
+fn read_file(path: &str) -> Result<InputBytes, Failure> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    Ok(InputBytes::Owned(bytes))
+}
+
+// Reads a file, the standard input for `-`, or an HTTP or HTTPS URL, and
+// decompresses it if it is gzipped. Also returns whether it was.
+pub fn read_input(path: &str) -> Result<(InputBytes, bool), Failure> {
+    let bytes = if path == STDIO {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        InputBytes::Owned(bytes)
+    } else if is_url(path) {
+        InputBytes::Owned(download(path)?)
+    } else {
+        read_file(path)?
+    };
+    if !bytes.starts_with(GZIP_MAGIC) {
+        return Ok((bytes, false));
+    }
+    let mut decompressed = Vec::new();
+    GzDecoder::new(&bytes[..]).read_to_end(&mut decompressed)?;
+    Ok((InputBytes::Owned(decompressed), true))
+}
+
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+// Reads a pattern in any supported format, possibly gzipped, from a file, a
+// URL, or from the standard input for `-`.
+pub fn load(path: &str, rule: Option<&Rule>) -> Result<World, Failure> {
+    let (bytes, _) = read_input(path)?;
+    parse_pattern(&bytes, path, rule)
+}
+
+// Shows the percentage of the pattern that is parsed, if the standard error
+// is a terminal.
+fn load_with_progress(bytes: &[u8], path: &str) -> Result<World, LoadError> {
+    let interactive = io::stderr().is_terminal();
+    let mut shown = None;
+    let result = World::from_bytes_with_progress(bytes, |progress| {
+        let percent = progress.bytes.unwrap_or_default() * 100 / bytes.len() as u64;
+        if interactive && shown != Some(percent) {
+            eprint!("\rLoading {}: {}%", path, percent);
+            shown = Some(percent);
+        }
+        ControlFlow::Continue(())
+    });
+    if shown.is_some() {
+        // Clears the line.
+        eprint!("\r\x1b[K");
+    }
+    result
+}
+
+// `World` falls back to B3/S23 when the rule of the pattern is not supported,
+// so the rule is checked here first, unless another rule is given.
+pub fn parse_pattern(bytes: &[u8], path: &str, rule: Option<&Rule>) -> Result<World, Failure> {
+    if rule.is_none() {
+        if let Some(rulestring) = header_rule(bytes) {
+            parse_rule(&rulestring)?;
+        }
+    }
+    let mut world = load_with_progress(bytes, path).map_err(|e| match e {
+        LoadError::Io(e) => Failure::Io(e),
+        e => Failure::Parse(format!("Cannot parse {}: {}", path, e)),
+    })?;
+    if let Some(rule) = rule {
+        world.set_rule(rule.clone());
+    }
+    Ok(world)
+}
+
+pub fn format_from_name(name: &str) -> Option<Format> {
+    match name {
+        "rle" => Some(Format::Rle),
+        "mc" => Some(Format::Macrocell),
+        "cells" => Some(Format::Plaintext),
+        "life106" => Some(Format::Life106),
+        _ => None,
+    }
+}
+
+// Chooses the format from the extension, ignoring a `.gz` extension.
+pub fn format_from_path(path: &str) -> Option<Format> {
+    let path = path.strip_suffix(".gz").unwrap_or(path);
+    match path.rsplit_once('.')?.1 {
+        "rle" => Some(Format::Rle),
+        "mc" => Some(Format::Macrocell),
+        "cells" => Some(Format::Plaintext),
+        "lif" | "life" => Some(Format::Life106),
+        _ => None,
+    }
+}
+
+pub fn format_name(format: Format) -> &'static str {
+    match format {
+        Format::Rle => "RLE",
+        Format::Macrocell => "Macrocell",
+        Format::Plaintext => "Plaintext",
+        Format::Life106 => "Life 1.06",
+    }
+}
+
+fn write_pattern<W: Write>(world: &World, format: Format, writer: W) -> io::Result<()> {
+    match format {
+        Format::Rle => world.write_rle(writer),
+        Format::Macrocell => world.write_macrocell(writer),
+        Format::Plaintext => world.write_plaintext(writer),
+        Format::Life106 => world.write_life106(writer),
+    }
+}
+
+// Bound: (left, right, top, bottom).
+pub fn format_bound(bound: Option<(i64, i64, i64, i64)>) -> String {
+    match bound {
+        Some((left, right, top, bottom)) => format!(
+            "{}..{} x {}..{} ({}x{})",
+            left,
+            right,
+            top,
+            bottom,
+            right.wrapping_sub(left) as u64,
+            bottom.wrapping_sub(top) as u64
+        ),
+        None => "empty".to_string(),
+    }
+}
+
+pub fn json_bound(bound: Option<(i64, i64, i64, i64)>) -> String {
+    match bound {
+        Some((left, right, top, bottom)) => format!(
+            "{{\"left\": {}, \"right\": {}, \"top\": {}, \"bottom\": {}}}",
+            left, right, top, bottom
+        ),
+        None => "null".to_string(),
+    }
+}
+
+// Quotes a string for JSON.
+pub fn json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+// Writes the world to a file, or to the standard output for `-`, gzipped if
+// the path ends in `.gz`.
+pub fn save(world: &World, path: &str, format: Format) -> Result<(), Failure> {
+    if path == STDIO {
+        let stdout = io::stdout();
+        let mut writer = BufWriter::new(stdout.lock());
+        write_pattern(world, format, &mut writer)?;
+        writer.flush()?;
+    } else if path.ends_with(".gz") && format == Format::Macrocell {
+        let file = BufWriter::new(File::create(path)?);
+        world.write_macrocell_gz(file, Compression::default())?;
+    } else if path.ends_with(".gz") {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        write_pattern(world, format, &mut encoder)?;
+        encoder.finish()?.flush()?;
+    } else {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_pattern(world, format, &mut writer)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut args = args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("run") => run::main(args),
+        Some("convert") => convert::main(args),
+        Some("info") => info::main(args),
+        Some("help" | "--help" | "-h") => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        Some(command) => Err(Failure::Usage(
+            format!("Unknown command {}", command),
+            USAGE,
+        )),
+        None => Err(Failure::Usage("No command given".to_string(), USAGE)),
+    };
+    if let Err(e) = result {
+        match &e {
+            Failure::Usage(message, usage) => eprint!("Error: {}\n\n{}", message, usage),
+            Failure::Reported(_) => {}
+            e => eprintln!("Error: {}", e),
+        }
+        exit(e.exit_code());
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{
+    format_bound, format_from_path, json_bound, load, parse_number, parse_rule, save, Failure,
+    STDIO,
+};
+use hashlife::Format;
+use hashlife::{Rule, World};
+use std::time::Instant;
+
+const USAGE: &str = "\
+Usage: hashlife run [OPTIONS] FILE
+
+Reads an RLE, Macrocell, Plaintext or Life 1.06 pattern (possibly gzipped) from
+a file, an HTTP or HTTPS URL, or the standard input if FILE is -, advances it,
+and prints the generation, the population, the bounding box, the number of
+nodes and the time taken.
+
+Options:
+  --gens N        Number of generations to run (default: 0)
+  --step K        Run in steps of 2^K generations, then the rest in smaller
+                  steps (default: one step for each bit of N)
+  --rule RULE     Override the rule of the pattern, e.g. B36/S23
+  --save FILE     Write the final state: RLE for .rle, Macrocell otherwise,
+                  gzipped for .gz, the standard output for -
+  --json          Print the statistics as JSON
+  --csv N         Print the generation and the population every N
+                  generations as CSV instead of the statistics; with --step,
+                  only at the end of the steps that reach a multiple of N
+  --help          Print this help
+";
+
+#[derive(Default)]
+struct Options {
+    input: Option<String>,
+    gens: u64,
+    step: Option<u8>,
+    rule: Option<Rule>,
+    save: Option<String>,
+    json: bool,
+    csv: Option<u64>,
+}
+
+// Returns `None` on `--help`.
+fn parse<I: Iterator<Item = String>>(args: I) -> Result<Option<Options>, Failure> {
+    let mut options = Options::default();
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| Failure::Usage(format!("{} requires a value", arg), USAGE))
+        };
+        match arg.as_str() {
+            "--help" | "-h" => return Ok(None),
+            "--gens" => options.gens = parse_number(&value()?, USAGE)?,
+            "--step" => options.step = Some(parse_number(&value()?, USAGE)?),
+            "--rule" => options.rule = Some(parse_rule(&value()?)?),
+            "--save" => options.save = Some(value()?),
+            "--json" => options.json = true,
+            "--csv" => match parse_number(&value()?, USAGE)? {
+                0 => return Err(Failure::Usage("--csv must be positive".to_string(), USAGE)),
+                every => options.csv = Some(every),
+            },
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(Failure::Usage(format!("Unknown option {}", arg), USAGE))
+            }
+            _ if options.input.is_some() => {
+                return Err(Failure::Usage(
+                    format!("Unexpected argument {}", arg),
+                    USAGE,
+                ))
+            }
+            _ => options.input = Some(arg),
+        }
+    }
+    if options.input.is_none() {
+        return Err(Failure::Usage("No input file given".to_string(), USAGE));
+    }
+    Ok(Some(options))
+}
+
+// Advances `generations` generations, in steps of `2^step` generations if a
+// step is given.
+fn advance(world: &mut World, generations: u64, step: Option<u8>) {
+    let step = match step {
+        Some(step) => step,
+        None => {
+            world.step_by(generations);
+            return;
+        }
+    };
+    let target = world.get_generation().saturating_add(generations);
+    world.set_step(step);
+    let step_size = 1u64.checked_shl(step as u32).unwrap_or(u64::MAX);
+    let mut steps = (target - world.get_generation()) / step_size;
+    while steps > 0 {
+        let k = steps.min(u32::MAX as u64) as u32;
+        world.step_many(k);
+        steps -= k as u64;
+    }
+    world.step_to(target);
+}
+
+fn print_table(world: &World, seconds: f64) {
+    println!("Generation  {}", world.get_generation());
+    println!("Population  {}", world.population());
+    println!("Bound       {}", format_bound(world.bound()));
+    println!("Nodes       {}", world.stats().nodes);
+    println!("Time        {:.3}s", seconds);
+}
+
+fn print_csv(world: &World) {
+    println!("generation,population");
+    for (generation, population) in world.population_history() {
+        println!("{},{}", generation, population);
+    }
+}
+
+fn print_json(world: &World, seconds: f64) {
+    println!(
+        "{{\"generation\": {}, \"population\": {}, \"bound\": {}, \"nodes\": {}, \"time\": {:.6}}}",
+        world.get_generation(),
+        world.population(),
+        json_bound(world.bound()),
+        world.stats().nodes,
+        seconds
+    );
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), Failure> {
+    let options = match parse(args)? {
+        Some(options) => options,
+        None => {
+            print!("{}", USAGE);
+            return Ok(());
+        }
+    };
+    let input = options.input.as_deref().unwrap_or_default();
+    let mut world = load(input, options.rule.as_ref())?;
+
+    if let Some(every) = options.csv {
+        world.record_population(every);
+    }
+    let start = Instant::now();
+    advance(&mut world, options.gens, options.step);
+    let seconds = start.elapsed().as_secs_f64();
+
+    // With `--save -`, the pattern goes to the standard output instead of
+    // the statistics.
+    if let Some(path) = &options.save {
+        let format = format_from_path(path).unwrap_or(Format::Macrocell);
+        save(&world, path, format)?;
+        if path == STDIO {
+            return Ok(());
+        }
+    }
+    if options.csv.is_some() {
+        print_csv(&world);
+    } else if options.json {
+        print_json(&world, seconds);
+    } else {
+        print_table(&world, seconds);
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    process::{Command, Output, Stdio},
+    thread,
+};
+
+const GLIDER: &str = "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+
+// Runs the binary with the given arguments and standard input.
+fn hashlife(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_hashlife"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The binary may exit before reading its input, e.g. on invalid
+    // arguments, so a broken pipe is not an error.
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn run_json() {
+    let output = hashlife(
+        &["run", "-", "--gens", "100", "--step", "3", "--json"],
+        GLIDER,
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("{\"generation\": 100, \"population\": 5, "));
+    assert!(
+        stdout.contains("\"bound\": {\"left\": 25, \"right\": 28, \"top\": 25, \"bottom\": 28}")
+    );
+}
+
+#[test]
+fn run_save() {
+    let output = hashlife(&["run", "-", "--gens", "4", "--save", "-"], GLIDER);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("[M2] (hashlife)\n#R B3/S23\n#G 4\n"));
+}
+
+#[test]
+fn run_csv() {
+    let output = hashlife(&["run", "-", "--gens", "25", "--csv", "10"], GLIDER);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "generation,population\n0,5\n10,5\n20,5\n");
+}
+
+#[test]
+fn exit_codes() {
+    assert_eq!(hashlife(&["run"], "").status.code(), Some(1));
+    assert_eq!(
+        hashlife(&["run", "-"], "x = 3, y = 3\nb?o!").status.code(),
+        Some(2)
+    );
+    assert_eq!(
+        hashlife(&["run", "-", "--rule", "B3/S23/Q"], GLIDER)
+            .status
+            .code(),
+        Some(3)
+    );
+    let unsupported = "x = 1, y = 1, rule = NotARule\no!\n";
+    assert_eq!(hashlife(&["run", "-"], unsupported).status.code(), Some(3));
+    assert!(hashlife(&["run", "-", "--rule", "B36/S23"], unsupported)
+        .status
+        .success());
+}
+
+#[test]
+fn convert() {
+    let output = hashlife(&["convert", "-", "-", "--to", "cells", "--trim"], GLIDER);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "! (hashlife)\n.O\n..O\nOOO\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Wrote -: 3x3, population 5\n"
+    );
+    let life106 = "#Life 1.06\n10 10\n11 10\n12 10\n";
+    let output = hashlife(&["convert", "-", "-", "--to", "rle", "--center"], life106);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "#CXRLE Pos=-1,0 Gen=0\nx = 3, y = 1, rule = B3/S23\n3o!\n"
+    );
+    let output = hashlife(&["convert", "-", "out.txt"], GLIDER);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn info() {
+    let output = hashlife(&["info", "-"], GLIDER);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(
+        "File        -\nFormat      RLE\nRule        B3/S23\nBound       0..3 x 0..3 (3x3)\n\
+         Population  5\n"
+    ));
+    assert!(!stdout.contains("Generation"));
+    let mc = "[M2] (hashlife)\n#R B36/S23\n#G 42\n.*$..*$***$\n";
+    let output = hashlife(&["info", "--json", "-"], mc);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(
+        "{\"file\": \"-\", \"format\": \"Macrocell\", \"gzipped\": false, \"rule\": \"B36/S23\", "
+    ));
+    assert!(stdout.contains("\"population\": 5, "));
+    assert!(stdout.ends_with("\"generation\": 42}\n"));
+    let output = hashlife(&["info", "-", "nonexistent.rle"], GLIDER);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("Error: nonexistent.rle: "));
+}
+
+#[test]
+fn run_url() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/glider.rle", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+            GLIDER.len(),
+            GLIDER
+        )
+        .unwrap();
+    });
+    let output = hashlife(&["run", &url, "--gens", "4", "--json"], "");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("{\"generation\": 4, \"population\": 5, "));
+
+    // The server is gone.
+    let output = hashlife(&["run", &url], "");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with(&format!("Error: Cannot download {}: ", url)));
+}
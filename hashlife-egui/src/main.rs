@@ -0,0 +1,327 @@
+use eframe::egui::{
+    self, pos2, Color32, ColorImage, Rect, Sense, TextureHandle, TextureOptions, Vec2,
+};
+use egui_plot::{Line, Plot};
+use hashlife::{ca_formats::rle::Rle, view::Viewport, Rule, World};
+use std::{
+    env::args,
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    process::exit,
+};
+
+// The number of points kept in the population plot.
+const HISTORY_LEN: usize = 1000;
+const PATTERN_EXTENSIONS: &[&str] = &["rle", "mc", "cells", "lif", "life", "gz"];
+
+// Reads the pattern file, or makes a small default pattern.
+fn make_world(input: Option<&str>) -> Result<World, Box<dyn Error>> {
+    let world = if let Some(path) = input {
+        World::from_file(path)?
+    } else {
+        let rle = Rle::new("b2o$2o$bo!")?;
+        World::from_rle(rle)?
+    };
+    Ok(world)
+}
+
+// RLE for `.rle`, Macrocell otherwise.
+fn save(world: &World, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    if path.extension().is_some_and(|extension| extension == "rle") {
+        world.write_rle(&mut writer)?;
+    } else {
+        world.write_macrocell(&mut writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+struct App {
+    world: World,
+    view: Viewport,
+    // The view is fitted to the pattern once the size of the canvas is known.
+    fit_pending: bool,
+    texture: Option<TextureHandle>,
+    is_running: bool,
+    // Points `[generation, population]` for the plot.
+    history: Vec<[f64; 2]>,
+    rule_text: String,
+    rule_error: Option<String>,
+    goto_text: String,
+    // The last error of loading, saving or going to a generation.
+    message: Option<String>,
+}
+
+impl App {
+    fn new(world: World) -> Self {
+        let mut app = App {
+            rule_text: world
+                .get_rule()
+                .rulestring()
+                .unwrap_or_default()
+                .to_string(),
+            world,
+            view: Viewport::new(1, 1),
+            fit_pending: true,
+            texture: None,
+            is_running: false,
+            history: Vec::new(),
+            rule_error: None,
+            goto_text: String::new(),
+            message: None,
+        };
+        app.record();
+        app
+    }
+
+    fn set_world(&mut self, world: World) {
+        *self = App {
+            view: self.view,
+            texture: self.texture.take(),
+            ..App::new(world)
+        };
+    }
+
+    // Adds the current population to the plot.
+    fn record(&mut self) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.remove(0);
+        }
+        self.history.push([
+            self.world.get_generation() as f64,
+            self.world.population() as f64,
+        ]);
+    }
+
+    fn step(&mut self) {
+        self.world.step();
+        self.record();
+    }
+
+    fn apply_rule(&mut self) {
+        match self.rule_text.parse::<Rule>() {
+            Ok(rule) => {
+                self.world.set_rule(rule);
+                self.rule_error = None;
+            }
+            Err(e) => self.rule_error = Some(e.to_string()),
+        }
+    }
+
+    fn goto(&mut self) {
+        match self.goto_text.trim().parse::<u64>() {
+            Ok(generation) if generation >= self.world.get_generation() => {
+                self.world.step_to(generation);
+                self.record();
+                self.message = None;
+            }
+            Ok(_) => self.message = Some("Cannot go backwards".to_string()),
+            Err(e) => self.message = Some(format!("Invalid generation: {}", e)),
+        }
+    }
+
+    fn load(&mut self) {
+        let path = rfd::FileDialog::new()
+            .add_filter("Patterns", PATTERN_EXTENSIONS)
+            .pick_file();
+        if let Some(path) = path {
+            match World::from_file(&path) {
+                Ok(world) => self.set_world(world),
+                Err(e) => self.message = Some(format!("Cannot load {}: {}", path.display(), e)),
+            }
+        }
+    }
+
+    fn save(&mut self) {
+        let path = rfd::FileDialog::new()
+            .add_filter("Macrocell", &["mc"])
+            .add_filter("RLE", &["rle"])
+            .save_file();
+        if let Some(path) = path {
+            self.message = save(&self.world, &path)
+                .err()
+                .map(|e| format!("Cannot save {}: {}", path.display(), e));
+        }
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Load…").clicked() {
+                self.load();
+            }
+            if ui.button("Save…").clicked() {
+                self.save();
+            }
+        });
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            let label = if self.is_running { "Pause" } else { "Run" };
+            if ui.button(label).clicked() {
+                self.is_running = !self.is_running;
+            }
+            if ui.button("Step").clicked() {
+                self.step();
+            }
+            if ui.button("Fit").clicked() {
+                self.view.fit(self.world.bound());
+            }
+        });
+        let mut step = self.world.get_step();
+        ui.add(egui::Slider::new(&mut step, 0..=32).text("Step 2^"));
+        if step != self.world.get_step() {
+            self.world.set_step(step);
+        }
+        ui.horizontal(|ui| {
+            let response = ui.text_edit_singleline(&mut self.goto_text);
+            let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("Go to").clicked() || entered {
+                self.goto();
+            }
+        });
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            let response = ui.text_edit_singleline(&mut self.rule_text);
+            let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("Set rule").clicked() || entered {
+                self.apply_rule();
+            }
+        });
+        if let Some(error) = &self.rule_error {
+            ui.colored_label(Color32::RED, error);
+        }
+        ui.separator();
+
+        let stats = self.world.stats();
+        egui::Grid::new("stats").show(ui, |ui| {
+            ui.label("Generation");
+            ui.label(self.world.get_generation().to_string());
+            ui.end_row();
+            ui.label("Population");
+            ui.label(self.world.population().to_string());
+            ui.end_row();
+            ui.label("Nodes");
+            ui.label(format!("{} / {}", stats.nodes, stats.gc_threshold));
+            ui.end_row();
+            ui.label("Level");
+            ui.label(stats.root_level.to_string());
+            ui.end_row();
+            ui.label("Memory");
+            ui.label(format!(
+                "{:.1} MiB",
+                self.world.memory_usage() as f64 / (1 << 20) as f64
+            ));
+            ui.end_row();
+        });
+        if let Some(message) = &self.message {
+            ui.colored_label(Color32::RED, message);
+        }
+        ui.separator();
+
+        Plot::new("population")
+            .height(160.0)
+            .allow_scroll(false)
+            .show(ui, |plot| {
+                plot.line(Line::new("Population", self.history.clone()));
+            });
+    }
+
+    // Draws the pattern with `Viewport::rasterize`, at the full resolution of
+    // the screen. Dragging moves the view, and scrolling zooms about the
+    // pointer.
+    fn canvas(&mut self, ui: &mut egui::Ui) {
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::drag());
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        let size = response.rect.size() * pixels_per_point;
+        let (width, height) = (size.x as u32, size.y as u32);
+        if width == 0 || height == 0 {
+            return;
+        }
+        if (width, height) != (self.view.width(), self.view.height()) {
+            self.view.resize(width, height);
+        }
+        if self.fit_pending {
+            self.view.fit(self.world.bound());
+            self.fit_pending = false;
+        }
+
+        let delta = response.drag_delta() * pixels_per_point;
+        self.view.pan(delta.x as i64, delta.y as i64);
+        if let Some(pointer) = response.hover_pos() {
+            let scroll = ui.input(|i| i.raw_scroll_delta.y);
+            if scroll != 0.0 {
+                let pixel = (pointer - response.rect.min) * pixels_per_point;
+                self.view
+                    .zoom_at((pixel.x as i64, pixel.y as i64), scroll.signum() as i32);
+            }
+        }
+
+        let raster = match self.view.rasterize(&self.world) {
+            Some(raster) => raster,
+            None => return,
+        };
+        let image = ColorImage::from_gray(
+            [raster.width as usize, raster.height as usize],
+            &raster.pixels,
+        );
+        let texture = match &mut self.texture {
+            Some(texture) => {
+                texture.set(image, TextureOptions::NEAREST);
+                texture
+            }
+            None => self.texture.insert(ui.ctx().load_texture(
+                "pattern",
+                image,
+                TextureOptions::NEAREST,
+            )),
+        };
+        let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+        let rect = Rect::from_min_size(
+            response.rect.min,
+            Vec2::new(width as f32, height as f32) / pixels_per_point,
+        );
+        painter.image(texture.id(), rect, uv, Color32::WHITE);
+    }
+}
+
+impl eframe::App for App {
+    // While running, one step is taken per frame.
+    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+        if self.is_running {
+            self.step();
+            ctx.request_repaint();
+        }
+        egui::SidePanel::left("controls")
+            .resizable(false)
+            .show(ctx, |ui| self.controls(ui));
+        egui::CentralPanel::default()
+            .frame(egui::Frame::NONE)
+            .show(ctx, |ui| self.canvas(ui));
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let input = args().nth(1);
+    if matches!(input.as_deref(), Some("--help" | "-h")) {
+        println!("Usage: hashlife-egui [path/to/an/rle/or/macrocell/file]");
+        return Ok(());
+    }
+    let world = make_world(input.as_deref())?;
+    eframe::run_native(
+        "HashLife",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Ok(Box::new(App::new(world)))),
+    )?;
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        exit(1);
+    }
+}
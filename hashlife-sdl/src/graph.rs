@@ -0,0 +1,116 @@
+use crate::{theme::Theme, with_alpha};
+use hashlife::{view::Viewport, World};
+use sdl2::{
+    rect::{Point, Rect},
+    render::Canvas,
+    video::Window,
+};
+
+// The size of the graph and its distance to the corner of the window, in
+// pixels.
+const WIDTH: u32 = 240;
+const HEIGHT: u32 = 96;
+const MARGIN: i32 = 8;
+// When there are more samples, every other one is dropped, and the interval
+// between them is doubled.
+const MAX_SAMPLES: usize = 2 * WIDTH as usize;
+
+// The population over the generations, in the bottom-left corner. The world
+// records a sample at least once per step, which are moved here.
+pub struct Graph {
+    samples: Vec<(u64, u64)>,
+    // The minimum number of generations between samples.
+    every: u64,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Graph {
+            samples: Vec::new(),
+            every: 1,
+        }
+    }
+
+    // Forgets the samples, e.g. when another pattern is loaded.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.every = 1;
+    }
+
+    // Records at most about `MAX_SAMPLES` samples until `target`, so that a
+    // long jump is not split into tiny steps.
+    pub fn prepare_jump(&mut self, world: &mut World, target: u64) {
+        let generations = target.saturating_sub(world.get_generation());
+        let every = (generations / MAX_SAMPLES as u64).max(1);
+        self.every = self.every.max(every.next_power_of_two());
+        self.record(world);
+    }
+
+    fn record(&self, world: &mut World) {
+        let step_size = 1u64.checked_shl(world.get_step() as u32).unwrap_or(1 << 63);
+        world.record_population(self.every.max(step_size));
+    }
+
+    // Takes the new samples from the world, and keeps it recording.
+    pub fn update(&mut self, world: &mut World) {
+        for &(generation, population) in world.population_history() {
+            // After a rewind, the samples from the future are dropped.
+            while self
+                .samples
+                .last()
+                .is_some_and(|&(last, _)| last >= generation)
+            {
+                self.samples.pop();
+            }
+            self.samples.push((generation, population));
+        }
+        world.clear_population_history();
+        while self.samples.len() > MAX_SAMPLES {
+            let mut i = 0;
+            self.samples.retain(|_| {
+                i += 1;
+                i % 2 == 1
+            });
+            self.every *= 2;
+        }
+        self.record(world);
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        theme: &Theme,
+        window: &Viewport,
+    ) -> Result<(), String> {
+        let height = window.height() as i32;
+        if window.width() < WIDTH + 2 * MARGIN as u32 || height < HEIGHT as i32 + 2 * MARGIN {
+            return Ok(());
+        }
+        let rect = Rect::new(MARGIN, height - HEIGHT as i32 - MARGIN, WIDTH, HEIGHT);
+        canvas.set_draw_color(with_alpha(theme.bg, 224));
+        canvas.fill_rect(rect)?;
+        if let (Some(&(first, _)), Some(&(last, _))) = (self.samples.first(), self.samples.last()) {
+            let max = self
+                .samples
+                .iter()
+                .map(|&(_, p)| p)
+                .max()
+                .unwrap_or(0)
+                .max(1);
+            let span = (last - first).max(1) as f64;
+            let points: Vec<_> = self
+                .samples
+                .iter()
+                .map(|&(generation, population)| {
+                    let x = (generation - first) as f64 / span * (WIDTH - 1) as f64;
+                    let y = (1.0 - population as f64 / max as f64) * (HEIGHT - 1) as f64;
+                    Point::new(rect.x() + x as i32, rect.y() + y as i32)
+                })
+                .collect();
+            canvas.set_draw_color(theme.fg);
+            canvas.draw_lines(points.as_slice())?;
+        }
+        canvas.set_draw_color(theme.grid);
+        canvas.draw_rect(rect)
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{options::Options, save_world};
+use hashlife::World;
+use std::{
+    error::Error,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+fn report(world: &World, start: &Instant) {
+    println!(
+        "Gen: {}\tPop: {}\tTime: {:.3}s",
+        world.get_generation(),
+        world.population(),
+        start.elapsed().as_secs_f64(),
+    );
+}
+
+// Runs `options.gens` generations, or up to generation `options.goto`,
+// without SDL, in steps of `2^options.step` generations, printing the
+// progress at the given interval.
+pub fn run(world: World, options: &Options) -> Result<(), Box<dyn Error>> {
+    let mut world = world;
+    world.set_step(options.step);
+    let interval = Duration::from_secs_f64(options.report.max(0.0));
+    let target = match options.goto {
+        Some(target) if target < world.get_generation() => {
+            return Err(format!(
+                "Cannot go back to generation {} from generation {}",
+                target,
+                world.get_generation()
+            )
+            .into())
+        }
+        Some(target) => target,
+        None => world.get_generation().saturating_add(options.gens),
+    };
+    let start = Instant::now();
+    let mut last_report = start;
+    report(&world, &start);
+
+    let step_size = 1u64.checked_shl(options.step as u32).unwrap_or(u64::MAX);
+    // The steps between reports are run together, as many as the last batch
+    // suggests fit in the time left until the next report.
+    let mut batch = 1;
+    loop {
+        let steps = (target - world.get_generation()) / step_size;
+        if steps == 0 {
+            break;
+        }
+        let batch_start = Instant::now();
+        let summary = world.step_many(steps.min(batch as u64) as u32);
+        if last_report.elapsed() >= interval {
+            report(&world, &start);
+            last_report = Instant::now();
+        }
+        let per_step = batch_start.elapsed().as_nanos() / summary.steps.max(1) as u128;
+        let until_report = interval.saturating_sub(last_report.elapsed()).as_nanos();
+        batch = (until_report / per_step.max(1)).clamp(1, u32::MAX as u128) as u32;
+    }
+    world.step_to(target);
+    report(&world, &start);
+
+    if let Some(path) = &options.save {
+        save_world(&world, Path::new(path))?;
+        println!("Saved to {}", path);
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use crate::options::HudLevel;
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::{Canvas, Texture, TextureCreator},
+    ttf::{self, Font, Sdl2TtfContext},
+    video::{Window, WindowContext},
+};
+use std::path::{Path, PathBuf};
+
+// Tried in order when no font is given.
+const FONT_PATHS: [&str; 6] = [
+    "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationMono-Regular.ttf",
+    "/System/Library/Fonts/Menlo.ttc",
+    "C:\\Windows\\Fonts\\consola.ttf",
+];
+// The font size and the margins, in window points.
+const FONT_SIZE: f32 = 14.0;
+const MARGIN: f32 = 8.0;
+const PADDING: f32 = 4.0;
+
+// Text in a corner of the window. Each line is rendered to a texture, which
+// is only rendered again when the line changes.
+pub struct Hud {
+    ttf: &'static Sdl2TtfContext,
+    creator: &'static TextureCreator<WindowContext>,
+    path: PathBuf,
+    font: Font<'static, 'static>,
+    // Output pixels per window point, which is more than 1 on HiDPI screens.
+    ratio: f32,
+    pub level: HudLevel,
+    // The color of the rendered lines.
+    color: Color,
+    lines: Vec<(String, Texture<'static>)>,
+}
+
+fn load_font(
+    ttf: &'static Sdl2TtfContext,
+    path: &Path,
+    ratio: f32,
+) -> Result<Font<'static, 'static>, String> {
+    ttf.load_font(path, (FONT_SIZE * ratio).round() as u16)
+}
+
+fn output_ratio(canvas: &Canvas<Window>) -> f32 {
+    crate::hidpi_ratio(canvas).0 as f32
+}
+
+impl Hud {
+    // The context and the texture creator live as long as the program, so
+    // that the font and the textures can be kept in the `App`.
+    pub fn new(
+        canvas: &Canvas<Window>,
+        font: Option<&str>,
+        level: HudLevel,
+    ) -> Result<Self, String> {
+        let path = match font {
+            Some(path) => PathBuf::from(path),
+            None => FONT_PATHS
+                .iter()
+                .map(PathBuf::from)
+                .find(|path| path.exists())
+                .ok_or("No monospace font found, use --font")?,
+        };
+        let ttf: &'static _ = Box::leak(Box::new(ttf::init().map_err(|e| e.to_string())?));
+        let creator: &'static _ = Box::leak(Box::new(canvas.texture_creator()));
+        let ratio = output_ratio(canvas);
+        let font = load_font(ttf, &path, ratio)?;
+        Ok(Hud {
+            ttf,
+            creator,
+            path,
+            font,
+            ratio,
+            level,
+            color: Color::WHITE,
+            lines: Vec::new(),
+        })
+    }
+
+    pub fn cycle(&mut self) {
+        self.level = match self.level {
+            HudLevel::Off => HudLevel::Minimal,
+            HudLevel::Minimal => HudLevel::Full,
+            HudLevel::Full => HudLevel::Off,
+        };
+    }
+
+    // Draws the lines in the top-left corner, over a translucent box.
+    pub fn draw(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        lines: &[String],
+        fg: Color,
+        bg: Color,
+    ) -> Result<(), String> {
+        let ratio = output_ratio(canvas);
+        if ratio != self.ratio {
+            self.font = load_font(self.ttf, &self.path, ratio)?;
+            self.ratio = ratio;
+            self.lines.clear();
+        }
+        if fg != self.color {
+            self.color = fg;
+            self.lines.clear();
+        }
+        self.lines.truncate(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            if self.lines.get(i).is_some_and(|(cached, _)| cached == line) {
+                continue;
+            }
+            let surface = self
+                .font
+                .render(line)
+                .blended(fg)
+                .map_err(|e| e.to_string())?;
+            let texture = self
+                .creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())?;
+            if i < self.lines.len() {
+                self.lines[i] = (line.clone(), texture);
+            } else {
+                self.lines.push((line.clone(), texture));
+            }
+        }
+
+        let margin = (MARGIN * ratio) as i32;
+        let padding = (PADDING * ratio) as i32;
+        let sizes: Vec<_> = self
+            .lines
+            .iter()
+            .map(|(_, texture)| {
+                let query = texture.query();
+                (query.width, query.height)
+            })
+            .collect();
+        let width = sizes.iter().map(|&(w, _)| w).max().unwrap_or(0);
+        let height: u32 = sizes.iter().map(|&(_, h)| h).sum();
+        if width == 0 {
+            return Ok(());
+        }
+        canvas.set_draw_color(Color::RGBA(bg.r, bg.g, bg.b, 160));
+        canvas.fill_rect(Rect::new(
+            margin,
+            margin,
+            width + 2 * padding as u32,
+            height + 2 * padding as u32,
+        ))?;
+        let mut y = margin + padding;
+        for ((_, texture), &(w, h)) in self.lines.iter().zip(&sizes) {
+            canvas.copy(texture, None, Rect::new(margin + padding, y, w, h))?;
+            y += h as i32;
+        }
+        Ok(())
+    }
+}
@@ -1,235 +1,1755 @@
-use flate2::read::GzDecoder;
+mod graph;
+mod headless;
+#[cfg(feature = "ttf")]
+mod hud;
+mod minimap;
+mod options;
+mod theme;
+mod worker;
+
+use graph::Graph;
 use hashlife::{
-    ca_formats::{macrocell::Macrocell, rle::Rle},
-    World,
+    ca_formats::rle::Rle,
+    http::download,
+    view::{Raster, Viewport},
+    Compression, LoadOptions, LoadProgress, Rule, Snapshot, World,
 };
+use minimap::Minimap;
+#[cfg(feature = "ttf")]
+use options::HudLevel;
+use options::{FollowTarget, Options};
 use sdl2::{
     self,
     event::{Event, WindowEvent},
-    keyboard::Keycode,
-    mouse::MouseState,
-    pixels::Color,
-    rect::Rect,
-    render::Canvas,
-    video::Window,
-    Sdl,
+    keyboard::{Keycode, Mod},
+    mouse::{MouseButton, MouseState},
+    pixels::{Color, PixelFormatEnum},
+    rect::{Point, Rect},
+    render::{BlendMode, Canvas},
+    video::{FullscreenType, Window},
+    Sdl, VideoSubsystem,
 };
 use std::{
     env::args,
     error::Error,
-    fs::File,
-    thread::sleep,
-    time::{Duration, Instant},
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, ErrorKind, Read, Write},
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver},
+        MutexGuard,
+    },
+    thread::{sleep, spawn},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use theme::Theme;
+use worker::Worker;
 
-const SCALE_OFFSET: u8 = 4;
-const MAX_SCALE: u8 = 63 + SCALE_OFFSET;
 const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+const MAX_IMAGE_SIZE: u32 = 16384;
+// Setting the window title can be slow on some platforms.
+const TITLE_INTERVAL: Duration = Duration::from_millis(250);
+// When zoomed out, a pixel is shaded by the density of its block, raised to
+// this power so that sparse blocks are still visible.
+const DENSITY_GAMMA: f64 = 0.3;
+const DENSITY_BUCKETS: usize = 16;
+// Pressing F twice within this time fits the pattern with a margin.
+const DOUBLE_PRESS: Duration = Duration::from_millis(400);
+// The margin around the pattern on the second F, as a fraction of its size.
+const FIT_MARGIN: f64 = 0.1;
+// With a target speed, generations that are due are not carried over for
+// longer than this, so that the simulation does not race to catch up after
+// a slow step.
+const MAX_LAG: f64 = 0.25;
+// At max speed, the time between redraws, and how long to wait for a step
+// before polling the events again.
+const MAX_SPEED_FRAME_TIME: Duration = Duration::from_millis(100);
+const MAX_SPEED_WAIT: Duration = Duration::from_millis(5);
+// How often the file is checked for changes with `--watch`.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+// The lowest target speed, in generations per second.
+const MIN_SPEED: f64 = 1.0 / 64.0;
+// The interval over which the actual speed is measured.
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+// When following, the view moves by this fraction of the distance to the
+// target every frame, and not at all within the dead zone, in pixels.
+const FOLLOW_DAMPING: f64 = 0.2;
+const FOLLOW_DEAD_ZONE: i64 = 2;
+// The number of recent bounds that the drift of a followed pattern is fitted
+// over.
+const FOLLOW_BOUNDS: usize = 64;
+// The smallest size of a cell in pixels at which the grid is drawn.
+const GRID_MIN_SIZE: u32 = 8;
 
-fn make_world() -> Result<World, Box<dyn Error>> {
-    let arg = args().nth(1);
-    if let Some(path) = arg {
-        let file = File::open(&path)?;
-        if path.ends_with(".gz") {
-            let unzipped = GzDecoder::new(file);
-            if path.ends_with(".mc.gz") {
-                let macrocell = Macrocell::new_from_file(unzipped)?;
-                Ok(World::from_macrocell(macrocell)?)
-            } else {
-                let rle = Rle::new_from_file(unzipped)?;
-                Ok(World::from_rle(rle)?)
-            }
-        } else if path.ends_with(".mc") {
-            let macrocell = Macrocell::new_from_file(file)?;
-            Ok(World::from_macrocell(macrocell)?)
-        } else {
-            let rle = Rle::new_from_file(file)?;
-            Ok(World::from_rle(rle)?)
+// Formats a number with thousands separators, e.g., `1,234,567`.
+fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut result = String::with_capacity(digits.len() * 4 / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            result.push(',');
         }
+        result.push(c);
+    }
+    result
+}
+
+// Abbreviates numbers from a million on, e.g., `1.2M`.
+fn abbreviate(n: u64) -> String {
+    const SUFFIXES: [&str; 5] = ["M", "G", "T", "P", "E"];
+    if n < 1_000_000 {
+        return thousands(n);
+    }
+    let mut value = n as f64 / 1e6;
+    let mut suffix = 0;
+    while value >= 1000.0 && suffix + 1 < SUFFIXES.len() {
+        value /= 1000.0;
+        suffix += 1;
+    }
+    format!("{:.1}{}", value, SUFFIXES[suffix])
+}
+
+// Rates of at least one generation per second are rounded.
+fn format_rate(rate: f64) -> String {
+    if rate >= 1.0 {
+        abbreviate(rate.round() as u64)
+    } else {
+        format!("{:.3}", rate)
+    }
+}
+
+fn mebibytes(bytes: usize) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1 << 20) as f64)
+}
+
+// The input path that stands for the standard input.
+const STDIN: &str = "-";
+
+fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+// Reads the pattern file, the standard input for `-`, downloads a URL, or
+// makes a small default pattern, and overrides its rule if one is given.
+// `progress` is called while a file is parsed, and can cancel it.
+fn make_world<F>(
+    input: Option<&str>,
+    rule: Option<&Rule>,
+    progress: F,
+) -> Result<World, Box<dyn Error>>
+where
+    F: FnMut(LoadProgress) -> ControlFlow<()>,
+{
+    // RLE patterns are centered, so that the window opens on them.
+    let options = LoadOptions {
+        center_rle: true,
+        ..LoadOptions::default()
+    };
+    let mut world = if input == Some(STDIN) {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        World::from_reader_with_options(&bytes[..], options, progress)?
+    } else if let Some(url) = input.filter(|path| is_url(path)) {
+        World::from_reader_with_options(&download(url)?[..], options, progress)?
+    } else if let Some(path) = input {
+        World::from_reader_with_options(File::open(path)?, options, progress)?
     } else {
         let rle = Rle::new("b2o$2o$bo!")?;
-        Ok(World::from_rle(rle)?)
+        World::from_rle_centered(rle)?
+    };
+    if let Some(rule) = rule {
+        world.set_rule(rule.clone());
     }
+    Ok(world)
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    if path == STDIN || is_url(path) {
+        return None;
+    }
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// Returns the path to save the world at the given generation: the input file
+// name, without its extensions, followed by the generation. The output is
+// gzipped if the input was. A URL is saved in the current directory.
+fn save_path(input: Option<&str>, generation: u64) -> PathBuf {
+    let input = input.map(|path| {
+        if is_url(path) {
+            path.rsplit('/').next().unwrap_or_default()
+        } else {
+            path
+        }
+    });
+    match input.filter(|&path| path != STDIN && !path.is_empty()) {
+        Some(path) => {
+            let gzipped = path.ends_with(".gz");
+            let stem = Path::new(path.strip_suffix(".gz").unwrap_or(path)).with_extension("");
+            let extension = if gzipped { "mc.gz" } else { "mc" };
+            let mut name = stem.into_os_string();
+            name.push(format!(".gen{}.{}", generation, extension));
+            PathBuf::from(name)
+        }
+        None => PathBuf::from(format!("hashlife.gen{}.mc", generation)),
+    }
+}
+
+pub(crate) fn save_world(world: &World, path: &Path) -> Result<(), Box<dyn Error>> {
+    let file = BufWriter::new(File::create(path)?);
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        world.write_macrocell_gz(file, Compression::default())?;
+    } else {
+        let mut file = file;
+        world.write_macrocell(&mut file)?;
+        file.flush()?;
+    }
+    Ok(())
+}
+
+// Writes a PNG image to a new file named after the current time. A suffix is
+// added if the file already exists.
+fn write_png(
+    width: u32,
+    height: u32,
+    color: png::ColorType,
+    data: &[u8],
+) -> Result<PathBuf, Box<dyn Error>> {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut suffix = 0;
+    let (path, file) = loop {
+        let path = if suffix == 0 {
+            PathBuf::from(format!("hashlife-{}.png", time))
+        } else {
+            PathBuf::from(format!("hashlife-{}-{}.png", time, suffix))
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => break (path, file),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => suffix += 1,
+            Err(e) => return Err(e.into()),
+        }
+    };
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+    writer.finish()?;
+    Ok(path)
+}
+
+type Action = Box<dyn FnOnce(&mut App)>;
+
+// (left, right, top, bottom), with `right` and `bottom` excluded.
+type Bound = (i64, i64, i64, i64);
+
+// Statistics of the world, kept for the title while the worker is busy.
+#[derive(Default)]
+struct Stats {
+    generation: u64,
+    step: u8,
+    population: u64,
+    history_len: usize,
+    history_capacity: usize,
 }
 
 struct App {
-    world: World,
+    worker: Worker,
+    // Actions that need the world, postponed until the current step is done.
+    pending: Vec<Action>,
+    // The file to load, and whether it is reloaded. Loading waits until the
+    // events are handled, so that it can poll them to be cancelled.
+    load_request: Option<(Option<String>, bool)>,
+    stats: Stats,
+    input: Option<String>,
     sdl: Sdl,
+    video_subsystem: VideoSubsystem,
     canvas: Canvas<Window>,
     is_running: bool,
     need_update: bool,
-    width: i64,
-    height: i64,
-    left: i64,
-    top: i64,
-    scale: u8,
+    view: Viewport,
+    edit_mode: bool,
+    paint: Option<(bool, (i64, i64))>,
+    hover: Option<(i64, i64)>,
+    paste: Option<World>,
+    status: Option<String>,
+    saving: Option<Receiver<String>>,
+    selection: Option<Bound>,
+    show_bound: bool,
+    // The bound of the pattern, and the generation when it was computed.
+    bound_cache: Option<(u64, Option<Bound>)>,
+    last_fit: Option<Instant>,
+    follow: bool,
+    follow_target: FollowTarget,
+    auto_fit: bool,
+    // The centroid of the pattern, and the generation when it was computed.
+    centroid_cache: Option<(u64, Option<(f64, f64)>)>,
+    selecting: Option<(i64, i64)>,
+    verbose: bool,
+    density: bool,
+    theme: Theme,
+    // The text typed at the go-to-generation prompt, while it is open.
+    prompt: Option<String>,
+    // The target generation while the worker is advancing to it.
+    jumping: Option<u64>,
+    // The pattern when it was last loaded or saved.
+    loaded: Snapshot,
+    // The pattern after the last edit.
+    edited: Snapshot,
+    title: String,
+    title_time: Instant,
+    // Set when the title should be updated without waiting for the interval.
+    title_dirty: bool,
+    // The pixel under the mouse, and a description of what is under it.
+    mouse: Option<(i64, i64)>,
+    cursor_info: Option<String>,
+    show_info: bool,
+    show_minimap: bool,
+    minimap: Minimap,
+    show_graph: bool,
+    graph: Graph,
+    #[cfg(feature = "ttf")]
+    hud: Option<hud::Hud>,
+    fps: f32,
+    // The rule given on the command line, which is kept on reload.
+    rule: Option<Rule>,
+    watch: bool,
+    // The modification time of the file when it was loaded.
+    modified: Option<SystemTime>,
+    watch_time: Instant,
+    // The target speed in generations per second; `None` means one step per
+    // frame.
+    speed: Option<f64>,
+    max_speed: bool,
+    last_redraw: Instant,
+    // Generations that are due at the target speed.
+    budget: f64,
+    tick: Instant,
+    // The measured speed in generations per second, and the time and the
+    // generation when the measurement started.
+    rate: f64,
+    rate_start: (Instant, u64),
+    // The details shown by the info readout, and the generation when they
+    // were computed.
+    info_cache: Option<(u64, String)>,
+}
+
+// The rectangle covering the cells in the bound, clamped to just outside of
+// the window, so that huge bounds do not overflow `i32`.
+fn screen_rect(view: &Viewport, bound: Bound) -> Option<Rect> {
+    let (left, right, top, bottom) = bound;
+    let size = 1i64 << (-view.scale()).max(0);
+    let clamp = |(x, y): (i64, i64)| {
+        (
+            x.clamp(-1, view.width() as i64 + 1) as i32,
+            y.clamp(-1, view.height() as i64 + 1) as i32,
+        )
+    };
+    let (x0, y0) = clamp(view.world_to_screen(left, top));
+    let (x1, y1) = view.world_to_screen(right - 1, bottom - 1);
+    let (x1, y1) = clamp((x1.saturating_add(size), y1.saturating_add(size)));
+    (x1 > x0 && y1 > y0).then(|| Rect::new(x0, y0, (x1 - x0) as u32, (y1 - y0) as u32))
+}
+
+// Output pixels per window point, horizontally and vertically, which is more
+// than 1 on HiDPI screens.
+pub(crate) fn hidpi_ratio(canvas: &Canvas<Window>) -> (f64, f64) {
+    let (width, height) = canvas.window().size();
+    match canvas.output_size() {
+        Ok((output_width, output_height)) if width > 0 && height > 0 => (
+            output_width as f64 / width as f64,
+            output_height as f64 / height as f64,
+        ),
+        _ => (1.0, 1.0),
+    }
+}
+
+fn point_to_pixel(x: i32, y: i32, ratio: (f64, f64)) -> (i32, i32) {
+    (
+        (x as f64 * ratio.0).floor() as i32,
+        (y as f64 * ratio.1).floor() as i32,
+    )
+}
+
+// Mouse events are in window points, while the viewport is in output pixels.
+fn event_to_pixels(event: &mut Event, ratio: (f64, f64)) {
+    match event {
+        Event::MouseButtonDown { x, y, .. } | Event::MouseButtonUp { x, y, .. } => {
+            (*x, *y) = point_to_pixel(*x, *y, ratio);
+        }
+        Event::MouseMotion {
+            x, y, xrel, yrel, ..
+        } => {
+            (*x, *y) = point_to_pixel(*x, *y, ratio);
+            (*xrel, *yrel) = point_to_pixel(*xrel, *yrel, ratio);
+        }
+        _ => {}
+    }
+}
+
+fn with_alpha(color: Color, alpha: u8) -> Color {
+    Color::RGBA(color.r, color.g, color.b, alpha)
+}
+
+fn ctrl(keymod: Mod) -> bool {
+    keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+}
+
+fn shift(keymod: Mod) -> bool {
+    keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD)
 }
 
 impl App {
-    fn new(world: World) -> Result<Self, Box<dyn Error>> {
+    fn new(world: World, options: Options) -> Result<Self, Box<dyn Error>> {
+        let mut world = world;
+        let loaded = world.snapshot();
+        let edited = world.snapshot();
         let sdl = sdl2::init()?;
         let video_subsystem = sdl.video()?;
         let window = video_subsystem
-            .window("HashLife", 800, 600)
+            .window("HashLife", options.size.0, options.size.1)
             .resizable()
+            .allow_highdpi()
             .build()?;
-        let canvas = window.into_canvas().build()?;
+        let mut canvas = window.into_canvas().build()?;
+        canvas.set_blend_mode(BlendMode::Blend);
 
-        let is_running = false;
+        let is_running = options.running;
         let need_update = true;
 
-        let width = canvas.viewport().width() as i64;
-        let height = canvas.viewport().height() as i64;
-        let left = -width / 2;
-        let top = -height / 2;
-        let scale = 0;
+        let view = Viewport::new(canvas.viewport().width(), canvas.viewport().height());
+        let modified = options.input.as_deref().and_then(modified_time);
         let mut app = App {
-            world,
+            worker: Worker::new(world),
+            pending: Vec::new(),
+            load_request: None,
+            stats: Stats::default(),
+            input: options.input,
             sdl,
+            video_subsystem,
             canvas,
             is_running,
             need_update,
-            width,
-            height,
-            left,
-            top,
-            scale,
+            view,
+            edit_mode: false,
+            paint: None,
+            hover: None,
+            paste: None,
+            status: None,
+            saving: None,
+            selection: None,
+            selecting: None,
+            show_bound: false,
+            bound_cache: None,
+            last_fit: None,
+            follow: options.follow.is_some(),
+            follow_target: options.follow.unwrap_or(FollowTarget::Center),
+            auto_fit: options.auto_fit,
+            centroid_cache: None,
+            verbose: options.verbose,
+            density: true,
+            theme: options.theme,
+            prompt: None,
+            jumping: None,
+            loaded,
+            edited,
+            title: String::new(),
+            title_time: Instant::now(),
+            title_dirty: false,
+            mouse: None,
+            cursor_info: None,
+            show_info: false,
+            show_minimap: false,
+            minimap: Minimap::new(),
+            show_graph: false,
+            graph: Graph::new(),
+            #[cfg(feature = "ttf")]
+            hud: None,
+            fps: 0.0,
+            rule: options.rule,
+            watch: options.watch,
+            modified,
+            watch_time: Instant::now(),
+            speed: options.speed,
+            max_speed: options.max_speed,
+            last_redraw: Instant::now(),
+            budget: 0.0,
+            tick: Instant::now(),
+            rate: 0.0,
+            rate_start: (Instant::now(), 0),
+            info_cache: None,
         };
 
-        app.fit();
+        #[cfg(feature = "ttf")]
+        match hud::Hud::new(&app.canvas, options.font.as_deref(), options.hud) {
+            Ok(hud) => app.hud = Some(hud),
+            Err(e) => app.status = Some(format!("No HUD: {}", e)),
+        }
+
+        if options.fit {
+            app.fit();
+        }
         app.update_canvas();
+        if let Some(target) = options.goto {
+            app.goto(target);
+        }
 
         Ok(app)
     }
 
+    fn world(&self) -> MutexGuard<'_, World> {
+        self.worker.world()
+    }
+
+    // Runs `f` now if the worker is idle, or after the current step.
+    fn when_idle<F: FnOnce(&mut App) + 'static>(&mut self, f: F) {
+        if self.worker.busy() {
+            self.pending.push(Box::new(f));
+        } else {
+            f(self);
+        }
+    }
+
+    // Handles the end of a step, waiting for it for at most `timeout`.
+    fn check_worker(&mut self, timeout: Duration) {
+        if let Some(completed) = self.worker.poll(timeout) {
+            let jumping = self.jumping.take();
+            if !completed {
+                self.status = Some(
+                    if jumping.is_some() {
+                        "Jump cancelled"
+                    } else {
+                        "Step cancelled"
+                    }
+                    .to_string(),
+                );
+            } else if let Some(target) = jumping {
+                self.status = Some(format!("Reached generation {}", thousands(target)));
+            }
+            for f in std::mem::take(&mut self.pending) {
+                f(self);
+            }
+            self.need_update = true;
+        }
+    }
+
+    fn cancel_step(&mut self) {
+        self.is_running = false;
+        self.worker.cancel();
+    }
+
+    // Advances to the target generation on the worker, or goes back to it if
+    // the history reaches back so far.
+    fn goto(&mut self, target: u64) {
+        self.is_running = false;
+        self.when_idle(move |app| {
+            let mut world = app.worker.world();
+            if !world.rewind_to(target) {
+                app.status = Some(format!(
+                    "Cannot go back to generation {}",
+                    thousands(target)
+                ));
+            }
+            let generation = world.get_generation();
+            if app.show_graph {
+                app.graph.prepare_jump(&mut world, target);
+            }
+            drop(world);
+            if generation < target {
+                app.worker.goto(target);
+                app.jumping = Some(target);
+            }
+            app.need_update = true;
+        });
+    }
+
+    fn start_prompt(&mut self) {
+        self.is_running = false;
+        self.prompt = Some(String::new());
+        self.video_subsystem.text_input().start();
+    }
+
+    fn close_prompt(&mut self) -> Option<String> {
+        self.video_subsystem.text_input().stop();
+        self.prompt.take()
+    }
+
+    // Handles a key while the prompt is open: digits are typed, Enter
+    // confirms and Esc closes it.
+    fn prompt_event(&mut self, event: Event) {
+        let prompt = self.prompt.get_or_insert_with(String::new);
+        match event {
+            Event::TextInput { text, .. } => {
+                prompt.extend(text.chars().filter(char::is_ascii_digit));
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Backspace),
+                ..
+            } => {
+                prompt.pop();
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Return | Keycode::KpEnter),
+                ..
+            } => {
+                let text = self.close_prompt().unwrap_or_default();
+                match text.parse() {
+                    Ok(target) => self.goto(target),
+                    Err(_) => self.status = Some(format!("Invalid generation {:?}", text)),
+                }
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => {
+                self.close_prompt();
+            }
+            _ => {}
+        }
+    }
+
+    // Fits the pattern in the window; pressing twice in a row leaves a
+    // margin around it.
     fn fit(&mut self) {
-        if let Some(bound) = self.world.bound() {
-            let pattern_width = bound.1 - bound.0;
-            let pattern_height = bound.3 - bound.2;
-            let center = ((bound.0 + bound.1) / 2, (bound.2 + bound.3) / 2);
-            let scale_diff = (pattern_width as f64 / self.width as f64)
-                .max(pattern_height as f64 / self.height as f64)
-                .log2()
-                .ceil() as i32;
-            self.scale = (SCALE_OFFSET as i32 + scale_diff)
-                .max(0)
-                .min(MAX_SCALE as i32) as u8;
-            if self.scale >= SCALE_OFFSET {
-                self.left = (center.0 >> (self.scale - SCALE_OFFSET)) - self.width / 2;
-                self.top = (center.1 >> (self.scale - SCALE_OFFSET)) - self.height / 2;
+        let double = self.last_fit.is_some_and(|t| t.elapsed() < DOUBLE_PRESS);
+        self.last_fit = if double { None } else { Some(Instant::now()) };
+        self.when_idle(move |app| {
+            let bound = app.cached_bound().map(|(left, right, top, bottom)| {
+                if double {
+                    let dx = ((right - left) as f64 * FIT_MARGIN).ceil() as i64;
+                    let dy = ((bottom - top) as f64 * FIT_MARGIN).ceil() as i64;
+                    (
+                        left.saturating_sub(dx),
+                        right.saturating_add(dx),
+                        top.saturating_sub(dy),
+                        bottom.saturating_add(dy),
+                    )
+                } else {
+                    (left, right, top, bottom)
+                }
+            });
+            app.view.fit(bound);
+            app.need_update = true;
+        });
+    }
+
+    // Only recomputes the bound when the generation has changed, or after
+    // `invalidate_caches`. Must not be called while the worker is busy.
+    fn cached_bound(&mut self) -> Option<Bound> {
+        let world = self.worker.world();
+        let generation = world.get_generation();
+        match self.bound_cache {
+            Some((cached, bound)) if cached == generation => bound,
+            _ => {
+                let bound = world.bound();
+                self.bound_cache = Some((generation, bound));
+                bound
+            }
+        }
+    }
+
+    fn cached_centroid(&mut self) -> Option<(f64, f64)> {
+        let world = self.worker.world();
+        let generation = world.get_generation();
+        match self.centroid_cache {
+            Some((cached, centroid)) if cached == generation => centroid,
+            _ => {
+                let centroid = world.centroid();
+                self.centroid_cache = Some((generation, centroid));
+                centroid
+            }
+        }
+    }
+
+    fn invalidate_caches(&mut self) {
+        self.bound_cache = None;
+        self.centroid_cache = None;
+        self.info_cache = None;
+        self.minimap.invalidate();
+    }
+
+    // Decides how many generations to run in this frame: one step per frame
+    // without a target speed, otherwise as many steps as are due, possibly
+    // none.
+    fn advance(&mut self) {
+        let elapsed = self.tick.elapsed().as_secs_f64();
+        self.tick = Instant::now();
+        if !self.is_running {
+            self.budget = 0.0;
+            return;
+        }
+        let speed = match self.speed {
+            Some(speed) if !self.max_speed => speed,
+            _ => return self.worker.step(),
+        };
+        self.budget += speed * elapsed;
+        if self.worker.busy() {
+            return;
+        }
+        let step_size = (1u64 << self.world().get_step().min(63)) as f64;
+        self.budget = self.budget.min(step_size.max(speed * MAX_LAG));
+        let steps = (self.budget / step_size).floor();
+        if steps >= 1.0 {
+            self.budget -= steps * step_size;
+            if steps == 1.0 {
+                self.worker.step();
             } else {
-                self.left = (center.0 << (SCALE_OFFSET - self.scale)) - self.width / 2;
-                self.top = (center.1 << (SCALE_OFFSET - self.scale)) - self.height / 2;
+                self.worker.step_by((steps * step_size) as u64);
             }
+        }
+    }
+
+    // Multiplies the target speed by the factor; the first time, starting
+    // from the measured speed.
+    fn change_speed(&mut self, factor: f64) {
+        let current = match self.speed {
+            Some(speed) => speed,
+            None if self.rate > 0.0 => self.rate,
+            None => (1u64 << self.stats.step.min(63)) as f64 / FRAME_TIME.as_secs_f64(),
+        };
+        let speed = (current * factor).max(MIN_SPEED);
+        self.speed = Some(speed);
+        self.status = Some(format!("Speed: {} gen/s", format_rate(speed)));
+        self.title_dirty = true;
+    }
+
+    fn unlimited_speed(&mut self) {
+        self.speed = None;
+        self.status = Some("Speed: one step per frame".to_string());
+        self.title_dirty = true;
+    }
+
+    // At max speed, the loop does not sleep, and only redraws a few times a
+    // second, so that the time goes into the simulation.
+    fn toggle_max_speed(&mut self) {
+        self.max_speed = !self.max_speed;
+        if self.max_speed {
+            self.is_running = true;
+        }
+        self.status = Some(
+            if self.max_speed {
+                "Max speed"
+            } else {
+                "Normal speed"
+            }
+            .to_string(),
+        );
+        self.title_dirty = true;
+        self.need_update = true;
+    }
+
+    fn toggle_info(&mut self) {
+        self.show_info = !self.show_info;
+        self.title_dirty = true;
+    }
+
+    // The file name, the rule, the size of the bounding box, the number of
+    // nodes and the memory usage. Only recomputed when the world changed; while
+    // the worker is busy, the last details are kept.
+    fn info(&mut self) -> String {
+        if self.worker.busy() {
+            return self
+                .info_cache
+                .as_ref()
+                .map(|(_, info)| info.clone())
+                .unwrap_or_default();
+        }
+        let generation = self.world().get_generation();
+        if let Some((cached, info)) = &self.info_cache {
+            if *cached == generation {
+                return info.clone();
+            }
+        }
+        let size = match self.cached_bound() {
+            Some((left, right, top, bottom)) => format!(
+                "{}x{}",
+                thousands(right.wrapping_sub(left) as u64),
+                thousands(bottom.wrapping_sub(top) as u64)
+            ),
+            None => "empty".to_string(),
+        };
+        let name = self
+            .input
+            .as_deref()
+            .map(|path| match path {
+                STDIN => "stdin".to_string(),
+                _ => Path::new(path)
+                    .file_name()
+                    .map_or(path.into(), |name| name.to_string_lossy())
+                    .into_owned(),
+            })
+            .unwrap_or_else(|| "Untitled".to_string());
+        let world = self.world();
+        let stats = world.stats();
+        let info = format!(
+            "{} - {} - Box {} - Nodes {} / {} - Mem {}",
+            name,
+            world.get_rule().rulestring().unwrap_or("Custom rule"),
+            size,
+            abbreviate(stats.nodes as u64),
+            abbreviate(stats.gc_threshold as u64),
+            mebibytes(world.memory_usage()),
+        );
+        drop(world);
+        self.info_cache = Some((generation, info.clone()));
+        info
+    }
+
+    fn toggle_follow(&mut self) {
+        self.follow = !self.follow;
+        if !self.follow {
+            self.when_idle(|app| {
+                app.world().record_bounds(0);
+            });
+        }
+        self.status = Some(if self.follow {
+            format!("Following the {}", self.follow_target_name())
         } else {
-            self.scale = 0;
-            self.left = -self.width / 2;
-            self.top = -self.height / 2;
+            "Stopped following".to_string()
+        });
+        self.need_update = true;
+    }
+
+    fn switch_follow_target(&mut self) {
+        self.follow_target = match self.follow_target {
+            FollowTarget::Center => FollowTarget::Centroid,
+            FollowTarget::Centroid => FollowTarget::Center,
+        };
+        self.status = Some(format!("Follow the {}", self.follow_target_name()));
+        self.need_update = true;
+    }
+
+    fn follow_target_name(&self) -> &'static str {
+        match self.follow_target {
+            FollowTarget::Center => "center",
+            FollowTarget::Centroid => "centroid",
+        }
+    }
+
+    // Moves the view a step towards the followed point, and zooms out one
+    // level if the pattern has outgrown the window in auto-fit mode. While
+    // running, the point is moved ahead by the drift of the pattern, so that
+    // the damping does not leave the view behind a moving pattern.
+    fn update_follow(&mut self) {
+        if !self.follow || self.worker.busy() {
+            return;
+        }
+        let drift = {
+            let mut world = self.world();
+            world.record_bounds(FOLLOW_BOUNDS);
+            world.estimate_drift()
+        };
+        let lead = match drift {
+            Some((dx, dy)) if self.is_running => {
+                let generations = self.rate * FRAME_TIME.as_secs_f64() / FOLLOW_DAMPING;
+                (dx * generations, dy * generations)
+            }
+            _ => (0.0, 0.0),
+        };
+        let (left, right, top, bottom) = match self.cached_bound() {
+            Some(bound) => bound,
+            None => return,
+        };
+        let target = match self.follow_target {
+            FollowTarget::Center => Some((
+                (left as f64 + right as f64 - 1.0) / 2.0,
+                (top as f64 + bottom as f64 - 1.0) / 2.0,
+            )),
+            FollowTarget::Centroid => self.cached_centroid(),
+        };
+        let (width, height) = (self.view.width() as i64, self.view.height() as i64);
+        if self.auto_fit {
+            let (x0, y0) = self.view.world_to_screen(left, top);
+            let (x1, y1) = self.view.world_to_screen(right - 1, bottom - 1);
+            if x1 - x0 >= width || y1 - y0 >= height {
+                self.zoom_center(-1);
+            }
         }
+        if let Some((x, y)) = target {
+            let (x, y) = (x + lead.0, y + lead.1);
+            let (x, y) = self
+                .view
+                .world_to_screen(x.round() as i64, y.round() as i64);
+            let damp = |d: i64| {
+                if d.abs() <= FOLLOW_DEAD_ZONE {
+                    0
+                } else {
+                    (d as f64 * FOLLOW_DAMPING).round() as i64
+                }
+            };
+            let (dx, dy) = (damp(x - width / 2), damp(y - height / 2));
+            if dx != 0 || dy != 0 {
+                self.view.pan(-dx, -dy);
+                self.need_update = true;
+            }
+        }
+    }
+
+    fn toggle_bound(&mut self) {
+        self.show_bound = !self.show_bound;
         self.need_update = true;
     }
 
-    fn window_resize(&mut self, x: i64, y: i64) {
-        self.left -= x / 2 - self.width / 2;
-        self.top -= y / 2 - self.height / 2;
-        self.width = x;
-        self.height = y;
+    fn toggle_minimap(&mut self) {
+        self.show_minimap = !self.show_minimap;
         self.need_update = true;
     }
 
-    fn one_step(&mut self) {
-        self.is_running = false;
-        self.world.step();
+    // The population is only recorded while the graph is shown.
+    fn toggle_graph(&mut self) {
+        self.show_graph = !self.show_graph;
+        self.when_idle(|app| {
+            let mut world = app.world();
+            if !app.show_graph {
+                world.record_population(0).clear_population_history();
+            }
+            drop(world);
+            app.graph.clear();
+            app.need_update = true;
+        });
+    }
+
+    fn on_minimap(&self, x: i32, y: i32) -> bool {
+        self.show_minimap && self.minimap.cell_at(&self.view, x, y).is_some()
+    }
+
+    // Centers the view on the cell clicked on the minimap.
+    fn minimap_click(&mut self, x: i32, y: i32) {
+        let cell = match self.minimap.cell_at(&self.view, x, y) {
+            Some(cell) => cell,
+            None => return,
+        };
+        if self.follow {
+            self.follow = false;
+            self.status = Some("Follow suspended, press T to resume".to_string());
+        }
+        let (x, y) = self.view.world_to_screen(cell.0, cell.1);
+        let (width, height) = (self.view.width() as i64, self.view.height() as i64);
+        self.view.pan(width / 2 - x, height / 2 - y);
         self.need_update = true;
     }
 
-    fn faster(&mut self) {
-        let step = self.world.get_step();
-        if step < std::u8::MAX {
-            self.world.set_step(step + 1);
+    // The size in the event is in window points, so the output size is used
+    // instead.
+    fn window_resize(&mut self) {
+        if let Ok((width, height)) = self.canvas.output_size() {
+            self.view.resize(width, height);
             self.need_update = true;
         }
     }
 
-    fn slower(&mut self) {
-        let step = self.world.get_step();
-        if step > 0 {
-            self.world.set_step(step - 1);
-            self.need_update = true;
+    fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        if let Err(e) = window.set_fullscreen(fullscreen) {
+            self.status = Some(format!("Cannot toggle fullscreen: {}", e));
+            self.title_dirty = true;
         }
     }
 
+    fn one_step(&mut self) {
+        self.is_running = false;
+        self.worker.step();
+    }
+
+    fn rewind(&mut self) {
+        self.is_running = false;
+        self.when_idle(|app| {
+            if app.world().rewind() {
+                app.need_update = true;
+            }
+        });
+    }
+
+    fn faster(&mut self) {
+        self.when_idle(|app| {
+            let mut world = app.world();
+            let step = world.get_step();
+            if step < u8::MAX {
+                world.set_step(step + 1);
+                drop(world);
+                app.need_update = true;
+            }
+        });
+    }
+
+    fn slower(&mut self) {
+        self.when_idle(|app| {
+            let mut world = app.world();
+            let step = world.get_step();
+            if step > 0 {
+                world.set_step(step - 1);
+                drop(world);
+                app.need_update = true;
+            }
+        });
+    }
+
+    // Moving by hand suspends following the pattern.
     fn move_canvas(&mut self, x: i64, y: i64) {
-        self.left -= x;
-        self.top -= y;
+        if self.follow {
+            self.follow = false;
+            self.status = Some("Follow suspended, press T to resume".to_string());
+        }
+        self.view.pan(x, y);
         self.need_update = true;
     }
 
+    // Zooms in by `levels` (out if negative), keeping the cell under the
+    // given pixel fixed.
+    fn zoom(&mut self, pixel: (i64, i64), levels: i32) {
+        let scale = self.view.scale();
+        self.view.zoom_at(pixel, levels);
+        if self.view.scale() != scale {
+            self.need_update = true;
+        }
+    }
+
+    fn zoom_center(&mut self, levels: i32) {
+        let center = (self.view.width() as i64 / 2, self.view.height() as i64 / 2);
+        self.zoom(center, levels);
+    }
+
+    // Zooms about the mouse; two levels at a time with Ctrl.
     fn rescale(&mut self, y: i32, mouse_state: MouseState) {
-        let (mouse_x, mouse_y) = (mouse_state.x() as i64, mouse_state.y() as i64);
-        let new_scale = (self.scale as i32 - y).max(0).min(MAX_SCALE as i32) as u8;
-        if new_scale > self.scale {
-            self.left = ((self.left + mouse_x) >> (new_scale - self.scale)) - mouse_x;
-            self.top = ((self.top + mouse_y) >> (new_scale - self.scale)) - mouse_y;
+        let ratio = hidpi_ratio(&self.canvas);
+        let (px, py) = point_to_pixel(mouse_state.x(), mouse_state.y(), ratio);
+        let mouse = (px as i64, py as i64);
+        let levels = if ctrl(self.sdl.keyboard().mod_state()) {
+            2 * y
+        } else {
+            y
+        };
+        self.zoom(mouse, levels);
+    }
+
+    // Restores the pattern after the last edit, or the pattern when it was
+    // last loaded or saved. The step and the view are kept.
+    fn reset(&mut self, to_loaded: bool) {
+        self.is_running = false;
+        self.when_idle(move |app| {
+            let snapshot = if to_loaded { app.loaded } else { app.edited };
+            app.world().restore(snapshot);
+            app.invalidate_caches();
+            if to_loaded {
+                app.mark_edited();
+            }
+            app.need_update = true;
+        });
+    }
+
+    fn mark_edited(&mut self) {
+        let mut world = self.worker.world();
+        world.release(self.edited);
+        self.edited = world.snapshot();
+        drop(world);
+        self.invalidate_caches();
+    }
+
+    fn load_file(&mut self, path: String) {
+        self.load(Some(path), false);
+    }
+
+    // Reads the current file again, with the rule given on the command line,
+    // and keeps the view.
+    fn reload(&mut self) {
+        if self.input.as_deref() == Some(STDIN) {
+            self.status = Some("Cannot reload the standard input".to_string());
+            self.title_dirty = true;
         } else {
-            self.left = ((self.left + mouse_x) << (self.scale - new_scale)) - mouse_x;
-            self.top = ((self.top + mouse_y) << (self.scale - new_scale)) - mouse_y;
+            self.load(self.input.clone(), true);
+        }
+    }
+
+    fn load(&mut self, input: Option<String>, reload: bool) {
+        self.load_request = Some((input, reload));
+    }
+
+    // Shows the progress of loading in the title, and cancels it on `Esc`.
+    fn loading_progress(
+        &mut self,
+        name: &str,
+        size: Option<u64>,
+        progress: LoadProgress,
+    ) -> ControlFlow<()> {
+        if self.title_time.elapsed() < TITLE_INTERVAL {
+            return ControlFlow::Continue(());
+        }
+        self.title_time = Instant::now();
+        let done = match (progress.bytes, size) {
+            (Some(bytes), Some(size)) if size > 0 => format!("{}%", bytes * 100 / size),
+            _ => format!("{} cells or nodes", thousands(progress.items)),
+        };
+        let title = format!("HashLife - Loading {}: {} - Esc to cancel", name, done);
+        let _ = self.canvas.window_mut().set_title(&title);
+        // The main loop has dropped its event pump.
+        if let Ok(mut events) = self.sdl.event_pump() {
+            for event in events.poll_iter() {
+                if let Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } = event
+                {
+                    return ControlFlow::Break(());
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    // Replaces the world with the pattern in the file, keeping the step, the
+    // history capacity and the GC threshold. The simulation stays paused. On
+    // error, the current world is kept.
+    fn start_load(&mut self, input: Option<String>, reload: bool) {
+        self.is_running = false;
+        self.worker.cancel();
+        self.when_idle(move |app| {
+            let name = input.as_deref().unwrap_or("the default pattern");
+            let rule = if reload { app.rule.clone() } else { None };
+            let size = input
+                .as_deref()
+                .and_then(|path| fs::metadata(path).ok())
+                .map(|metadata| metadata.len());
+            let result = make_world(input.as_deref(), rule.as_ref(), |progress| {
+                app.loading_progress(name, size, progress)
+            });
+            // The title shows the progress.
+            app.title_dirty = true;
+            match result {
+                Ok(mut world) => {
+                    let old = app.worker.world();
+                    world
+                        .set_step(old.get_step())
+                        .set_history_capacity(old.history_capacity())
+                        .set_gc_threshold(old.get_gc_threshold());
+                    drop(old);
+                    let mut world = app.worker.set_world(world);
+                    app.graph.clear();
+                    app.loaded = world.snapshot();
+                    app.edited = world.snapshot();
+                    drop(world);
+                    app.invalidate_caches();
+                    app.status = Some(format!(
+                        "{} {}",
+                        if reload { "Reloaded" } else { "Loaded" },
+                        name
+                    ));
+                    app.modified = input.as_deref().and_then(modified_time);
+                    app.input = input;
+                    app.paint = None;
+                    app.paste = None;
+                    app.selection = None;
+                    app.selecting = None;
+                    if !reload {
+                        app.fit();
+                    }
+                }
+                Err(e) => app.status = Some(format!("Cannot load {}: {}", name, e)),
+            }
+            app.need_update = true;
+        });
+    }
+
+    // With `--watch`, reloads the file when its modification time changes.
+    fn check_watch(&mut self) {
+        if !self.watch || self.watch_time.elapsed() < WATCH_INTERVAL {
+            return;
+        }
+        self.watch_time = Instant::now();
+        let modified = self.input.as_deref().and_then(modified_time);
+        if modified.is_some() && modified != self.modified {
+            self.modified = modified;
+            self.reload();
         }
-        self.scale = new_scale;
+    }
+
+    fn invert_colors(&mut self) {
+        self.theme.invert();
         self.need_update = true;
     }
 
+    fn toggle_density(&mut self) {
+        self.density ^= true;
+        self.need_update = true;
+    }
+
+    fn toggle_edit_mode(&mut self) {
+        self.edit_mode ^= true;
+        self.paint = None;
+        self.hover = None;
+        self.need_update = true;
+    }
+
+    fn start_paste(&mut self) {
+        let pattern = self
+            .video_subsystem
+            .clipboard()
+            .clipboard_text()
+            .map_err(|e| e.to_string())
+            .and_then(|text| {
+                let rle = Rle::new(text.as_str()).map_err(|e| e.to_string())?;
+                World::from_rle(rle).map_err(|e| e.to_string())
+            });
+        match pattern {
+            Ok(pattern) if pattern.population() > 0 => {
+                self.paste = Some(pattern);
+                self.status = Some("Click to paste, Esc to cancel".to_string());
+            }
+            Ok(_) => self.status = Some("Clipboard pattern is empty".to_string()),
+            Err(e) => self.status = Some(format!("Cannot paste: {}", e)),
+        }
+        self.need_update = true;
+    }
+
+    fn paste_offset(pattern: &World, hover: Option<(i64, i64)>) -> Option<(i64, i64)> {
+        let (left, _, top, _) = pattern.bound()?;
+        let (x, y) = hover?;
+        Some((x - left, y - top))
+    }
+
+    fn commit_paste(&mut self) {
+        if let Some(pattern) = self.paste.take() {
+            if let Some(offset) = Self::paste_offset(&pattern, self.hover) {
+                self.is_running = false;
+                self.when_idle(move |app| {
+                    app.world().paste(&pattern, offset);
+                    app.mark_edited();
+                    app.need_update = true;
+                });
+            }
+            self.status = None;
+            self.need_update = true;
+        }
+    }
+
+    fn cancel_paste(&mut self) {
+        self.paste = None;
+        self.status = None;
+        self.need_update = true;
+    }
+
+    // Copies the selection, or the whole pattern if nothing is selected.
+    fn copy(&mut self) {
+        self.when_idle(|app| {
+            let rle = match app.selection {
+                Some(bound) => app.world().extract(bound).to_rle(),
+                None => app.world().to_rle(),
+            };
+            let result = app.video_subsystem.clipboard().set_clipboard_text(&rle);
+            app.status = Some(match result {
+                Ok(()) if app.selection.is_some() => "Copied selection to clipboard".to_string(),
+                Ok(()) => "Copied pattern to clipboard".to_string(),
+                Err(e) => format!("Cannot copy: {}", e),
+            });
+            app.need_update = true;
+        });
+    }
+
+    fn cut(&mut self) {
+        if self.selection.is_some() {
+            self.copy();
+            self.clear_selection();
+        }
+    }
+
+    fn clear_selection(&mut self) {
+        if let Some(bound) = self.selection {
+            self.is_running = false;
+            self.when_idle(move |app| {
+                app.world().clear_region(bound);
+                app.mark_edited();
+                app.need_update = true;
+            });
+        }
+    }
+
+    fn select_all(&mut self) {
+        self.when_idle(|app| {
+            let bound = app.world().bound();
+            app.selection = bound;
+            app.need_update = true;
+        });
+    }
+
+    fn deselect(&mut self) {
+        self.selection = None;
+        self.need_update = true;
+    }
+
+    fn start_selection(&mut self, x: i32, y: i32) {
+        let cell = self.view.screen_to_world(x as i64, y as i64);
+        self.selecting = Some(cell);
+        self.update_selection(cell);
+    }
+
+    // The selection is the smallest rectangle containing the anchor and the
+    // cell under the mouse.
+    fn update_selection(&mut self, cell: (i64, i64)) {
+        if let Some((x, y)) = self.selecting {
+            self.selection = Some((
+                x.min(cell.0),
+                x.max(cell.0) + 1,
+                y.min(cell.1),
+                y.max(cell.1) + 1,
+            ));
+            self.need_update = true;
+        }
+    }
+
+    // Saves the world in a separate thread, so that saving a big universe
+    // does not freeze the window.
+    fn save(&mut self) {
+        if self.saving.is_some() {
+            return;
+        }
+        self.is_running = false;
+        self.when_idle(Self::start_saving);
+    }
+
+    fn start_saving(&mut self) {
+        let mut current = self.worker.world();
+        let path = save_path(self.input.as_deref(), current.get_generation());
+        let world = current.clone();
+        current.release(self.loaded);
+        self.loaded = current.snapshot();
+        drop(current);
+        let (sender, receiver) = channel();
+        spawn(move || {
+            let message = match save_world(&world, &path) {
+                Ok(()) => format!("Saved to {}", path.display()),
+                Err(e) => format!("Cannot save to {}: {}", path.display(), e),
+            };
+            let _ = sender.send(message);
+        });
+        self.saving = Some(receiver);
+        self.status = Some("Saving...".to_string());
+        self.need_update = true;
+    }
+
+    fn check_saving(&mut self) {
+        if let Some(receiver) = &self.saving {
+            if let Ok(message) = receiver.try_recv() {
+                self.saving = None;
+                self.status = Some(message);
+                self.need_update = true;
+            }
+        }
+    }
+
+    // Saves what is currently shown in the window.
+    fn screenshot(&mut self) {
+        self.when_idle(Self::take_screenshot);
+    }
+
+    fn take_screenshot(&mut self) {
+        self.draw();
+        let (width, height) = self.canvas.output_size().unwrap_or((0, 0));
+        let result = self
+            .canvas
+            .read_pixels(None, PixelFormatEnum::RGB24)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|data| write_png(width, height, png::ColorType::Rgb, &data));
+        self.canvas.present();
+        self.status = Some(match result {
+            Ok(path) => format!("Saved screenshot to {}", path.display()),
+            Err(e) => format!("Cannot save screenshot: {}", e),
+        });
+        self.need_update = true;
+    }
+
+    // Renders the whole pattern at the current scale, or at a smaller scale
+    // if the image would be too large.
+    fn export_image(&mut self) {
+        self.when_idle(Self::write_image);
+    }
+
+    fn write_image(&mut self) {
+        let world = self.worker.world();
+        let result = match world.bound() {
+            Some(bound) => {
+                let (left, right, top, bottom) = bound;
+                let span = (right - left).max(bottom - top) as i128;
+                let size_at = |scale: i8| {
+                    if scale >= 0 {
+                        span >> scale
+                    } else {
+                        span << -scale
+                    }
+                };
+                let mut scale = self.view.scale();
+                while scale < Viewport::MAX_SCALE && size_at(scale) >= MAX_IMAGE_SIZE as i128 {
+                    scale += 1;
+                }
+                match world.rasterize(bound, scale) {
+                    Some(Raster {
+                        width,
+                        height,
+                        pixels,
+                    }) => write_png(width, height, png::ColorType::Grayscale, &pixels),
+                    None => Err("the image is too large".into()),
+                }
+            }
+            None => Err("the pattern is empty".into()),
+        };
+        drop(world);
+        self.status = Some(match result {
+            Ok(path) => format!("Exported pattern to {}", path.display()),
+            Err(e) => format!("Cannot export pattern: {}", e),
+        });
+        self.need_update = true;
+    }
+
+    fn start_paint(&mut self, button: MouseButton, x: i32, y: i32) {
+        let cell = self.view.screen_to_world(x as i64, y as i64);
+        if button != MouseButton::Left && button != MouseButton::Right {
+            return;
+        }
+        self.is_running = false;
+        self.when_idle(move |app| {
+            let mut world = app.worker.world();
+            let state = button == MouseButton::Left && !world.get_cell(cell.0, cell.1);
+            world.set_cell(cell.0, cell.1, state);
+            drop(world);
+            app.mark_edited();
+            app.paint = Some((state, cell));
+            app.need_update = true;
+        });
+    }
+
+    fn stop_paint(&mut self) {
+        self.selecting = None;
+        self.when_idle(|app| app.paint = None);
+    }
+
+    // Describes the cell under the mouse, or the block under it when zoomed
+    // out. The state is only shown when the worker is idle.
+    fn update_cursor_info(&mut self) {
+        let info = self.mouse.map(|(px, py)| {
+            let (x, y) = self.view.screen_to_world(px, py);
+            let scale = self.view.scale();
+            let idle = !self.worker.busy();
+            if scale <= 0 {
+                let state = if !idle {
+                    ""
+                } else if self.world().get_cell(x, y) {
+                    ": alive"
+                } else {
+                    ": dead"
+                };
+                format!("Cell ({}, {}){}", x, y, state)
+            } else {
+                let size = 1i64 << scale;
+                let (right, bottom) = (x.saturating_add(size), y.saturating_add(size));
+                let mut info = format!("Block ({}..{}, {}..{})", x, right, y, bottom);
+                if idle {
+                    let population = self.world().population_in((x, right, y, bottom));
+                    info.push_str(&format!(": pop {}", thousands(population)));
+                }
+                info
+            }
+        });
+        if info != self.cursor_info {
+            self.cursor_info = info;
+            self.title_dirty = true;
+        }
+    }
+
+    fn mouse_move(&mut self, x: i32, y: i32) {
+        self.mouse = Some((x as i64, y as i64));
+        self.update_cursor_info();
+        let cell = self.view.screen_to_world(x as i64, y as i64);
+        self.update_selection(cell);
+        if !self.edit_mode && self.paste.is_none() {
+            return;
+        }
+        if self.edit_mode {
+            self.when_idle(move |app| {
+                if let Some((state, last)) = app.paint {
+                    if last != cell {
+                        app.world().draw_line(last, cell, state);
+                        app.mark_edited();
+                        app.paint = Some((state, cell));
+                        app.need_update = true;
+                    }
+                }
+            });
+        }
+        if self.hover != Some(cell) {
+            self.hover = Some(cell);
+            self.need_update = true;
+        }
+    }
+
     fn update_canvas(&mut self) {
+        self.draw();
+        #[cfg(feature = "ttf")]
+        self.draw_hud();
+        self.canvas.present();
+    }
+
+    #[cfg(feature = "ttf")]
+    fn hud_lines(&self, level: HudLevel) -> Vec<String> {
+        let mut lines = vec![
+            format!("Gen  {}", thousands(self.stats.generation)),
+            format!("Pop  {}", thousands(self.stats.population)),
+        ];
+        if level == HudLevel::Full {
+            lines.push(format!("Step 2^{}", self.stats.step));
+            lines.push(format!("Zoom 1:2^{}", self.view.scale()));
+            lines.push(format!("FPS  {:.0}", self.fps));
+        }
+        lines
+    }
+
+    #[cfg(feature = "ttf")]
+    fn draw_hud(&mut self) {
+        let level = match &self.hud {
+            Some(hud) if hud.level != HudLevel::Off => hud.level,
+            _ => return,
+        };
+        self.update_stats();
+        let lines = self.hud_lines(level);
+        let (fg, bg) = (self.theme.fg, self.theme.bg);
+        if let Some(hud) = &mut self.hud {
+            if let Err(e) = hud.draw(&mut self.canvas, &lines, fg, bg) {
+                self.status = Some(format!("Cannot draw the HUD: {}", e));
+                self.hud = None;
+            }
+        }
+    }
+
+    fn cycle_hud(&mut self) {
+        #[cfg(feature = "ttf")]
+        if let Some(hud) = &mut self.hud {
+            hud.cycle();
+            self.need_update = true;
+            return;
+        }
+        self.status = Some(if cfg!(feature = "ttf") {
+            "The HUD is not available".to_string()
+        } else {
+            "The HUD needs the ttf feature".to_string()
+        });
+        self.title_dirty = true;
+    }
+
+    fn draw(&mut self) {
+        let bound = if self.show_bound || self.show_minimap {
+            self.cached_bound()
+        } else {
+            None
+        };
+        let mut world = self.worker.world();
         let canvas = &mut self.canvas;
-        let left = self.left;
-        let top = self.top;
+        let view = &self.view;
+
+        let theme = &self.theme;
 
-        canvas.set_draw_color(Color::BLACK);
+        canvas.set_draw_color(theme.bg);
         canvas.clear();
-        canvas.set_draw_color(Color::WHITE);
+        canvas.set_draw_color(theme.fg);
 
-        let bound = (left, left + self.width, top, top + self.height);
+        if self.density && self.view.scale() > 0 {
+            // Points are grouped by brightness, so that the draw color is only
+            // changed once per group.
+            let mut buckets = vec![Vec::new(); DENSITY_BUCKETS];
+            self.view.render_with_density(&world, |x, y, _, density| {
+                let bucket = density.powf(DENSITY_GAMMA) * (DENSITY_BUCKETS - 1) as f64;
+                buckets[bucket.ceil() as usize].push(Point::new(x as i32, y as i32));
+            });
+            for (i, points) in buckets.iter().enumerate().filter(|(_, p)| !p.is_empty()) {
+                let t = 0.25 + 0.75 * i as f64 / (DENSITY_BUCKETS - 1) as f64;
+                canvas.set_draw_color(theme.mix(t));
+                canvas.draw_points(points.as_slice()).unwrap();
+            }
+        } else {
+            self.view.render(&world, |x, y, size| {
+                if size == 1 {
+                    canvas.draw_point((x as i32, y as i32)).unwrap();
+                } else {
+                    canvas
+                        .fill_rect(Rect::new(x as i32, y as i32, size, size))
+                        .unwrap();
+                }
+            });
+        }
 
-        if self.scale >= SCALE_OFFSET {
-            self.world
-                .for_nodes(self.scale - SCALE_OFFSET, bound, |x, y| {
+        let size = 1u32 << (-self.view.scale()).max(0);
+
+        if size >= GRID_MIN_SIZE {
+            // The origin of the screen is the top-left corner of a cell.
+            canvas.set_draw_color(theme.grid);
+            let (width, height) = (self.view.width() as i32, self.view.height() as i32);
+            let (x0, y0) = self.view.world_to_screen(0, 0);
+            let (x0, y0) = (x0.rem_euclid(size as i64), y0.rem_euclid(size as i64));
+            for x in (x0 as i32..width).step_by(size as usize) {
+                canvas.draw_line((x, 0), (x, height)).unwrap();
+            }
+            for y in (y0 as i32..height).step_by(size as usize) {
+                canvas.draw_line((0, y), (width, y)).unwrap();
+            }
+        }
+
+        if let Some(pattern) = &self.paste {
+            if let Some((dx, dy)) = Self::paste_offset(pattern, self.hover) {
+                canvas.set_draw_color(with_alpha(theme.paste, 128));
+                for (x, y) in pattern.living_cells_all() {
+                    let (x, y) = self.view.world_to_screen(x + dx, y + dy);
                     canvas
-                        .draw_point(((x - left) as i32, (y - top) as i32))
+                        .fill_rect(Rect::new(x as i32, y as i32, size, size))
                         .unwrap();
-                });
-        } else {
-            let neg_scale = SCALE_OFFSET - self.scale;
-            let bound = (
-                (bound.0 >> neg_scale) - 1,
-                (bound.1 >> neg_scale) + 1,
-                (bound.2 >> neg_scale) - 1,
-                (bound.3 >> neg_scale) + 1,
+                }
+            }
+        }
+
+        if let Some(rect) = self.selection.and_then(|s| screen_rect(view, s)) {
+            canvas.set_draw_color(with_alpha(theme.selection, 64));
+            canvas.fill_rect(rect).unwrap();
+            canvas.set_draw_color(with_alpha(theme.selection, 192));
+            canvas.draw_rect(rect).unwrap();
+        }
+
+        // The outline of the bound is drawn just outside of the cells.
+        let outline = if self.show_bound { bound } else { None };
+        if let Some(rect) = outline.and_then(|b| screen_rect(view, b)) {
+            canvas.set_draw_color(theme.bound);
+            let outline = Rect::new(
+                rect.x() - 1,
+                rect.y() - 1,
+                rect.width() + 2,
+                rect.height() + 2,
             );
+            canvas.draw_rect(outline).unwrap();
+        }
 
-            self.world.for_nodes(0, bound, |x, y| {
-                canvas
-                    .fill_rect(Rect::new(
-                        ((x << neg_scale) - left) as i32,
-                        ((y << neg_scale) - top) as i32,
-                        1 << neg_scale,
-                        1 << neg_scale,
-                    ))
-                    .unwrap();
-            });
+        if let Some((x, y)) = self.hover {
+            let (x, y) = self.view.world_to_screen(x, y);
+            canvas.set_draw_color(theme.hover);
+            canvas
+                .draw_rect(Rect::new(x as i32 - 1, y as i32 - 1, size + 2, size + 2))
+                .unwrap();
+        }
+
+        if self.show_minimap {
+            self.minimap.update(&world, bound, self.is_running);
+            self.minimap.draw(canvas, theme, view).unwrap();
+        }
+        if self.show_graph {
+            self.graph.update(&mut world);
+            self.graph.draw(canvas, theme, view).unwrap();
+        }
+    }
+
+    fn update_stats(&mut self) {
+        if self.jumping.is_some() {
+            self.stats.generation = self.worker.progress();
+        }
+        if !self.worker.busy() {
+            let world = self.worker.world();
+            let generation = world.get_generation();
+            let (start, start_generation) = self.rate_start;
+            if !self.is_running || generation < start_generation {
+                self.rate = 0.0;
+                self.rate_start = (Instant::now(), generation);
+            } else if start.elapsed() >= RATE_INTERVAL {
+                self.rate = (generation - start_generation) as f64 / start.elapsed().as_secs_f64();
+                self.rate_start = (Instant::now(), generation);
+            }
+            self.stats = Stats {
+                generation: world.get_generation(),
+                step: world.get_step(),
+                population: world.population(),
+                history_len: world.history_len(),
+                history_capacity: world.history_capacity(),
+            };
         }
+    }
 
-        canvas.present();
+    fn update_title(&mut self, fps: f32) {
+        if !self.title_dirty && self.prompt.is_none() && self.title_time.elapsed() < TITLE_INTERVAL
+        {
+            return;
+        }
+        self.update_stats();
+        let mut title = if self.show_info {
+            let info = self.info();
+            format!(
+                "HashLife - {} - Gen {} - Step 2^{} - Pop {}",
+                info,
+                thousands(self.stats.generation),
+                self.stats.step,
+                thousands(self.stats.population),
+            )
+        } else {
+            self.status_title(fps)
+        };
+        if let Some(status) = &self.status {
+            title.push_str(" - ");
+            title.push_str(status);
+        }
+        if let Some(prompt) = &self.prompt {
+            title.push_str(&format!(" - Go to generation: {}_", prompt));
+        }
+        if title != self.title {
+            // The title cannot contain a nul byte, so this never fails.
+            let _ = self.canvas.window_mut().set_title(&title);
+            self.title = title;
+        }
+        self.title_time = Instant::now();
+        self.title_dirty = false;
+    }
+
+    fn status_title(&self, fps: f32) -> String {
+        let mut title = format!(
+            "HashLife - {} - Gen {} - Step 2^{} - Pop {} - Scale 1:2^{} - {:.0} fps",
+            if let Some(target) = self.jumping {
+                format!("Jumping to {}", thousands(target))
+            } else if self.is_running && self.max_speed {
+                "Running at max speed".to_string()
+            } else if self.is_running {
+                "Running".to_string()
+            } else if self.worker.busy() {
+                "Stepping".to_string()
+            } else {
+                "Paused".to_string()
+            },
+            thousands(self.stats.generation),
+            self.stats.step,
+            abbreviate(self.stats.population),
+            self.view.scale(),
+            fps,
+        );
+        if self.stats.history_capacity > 0 {
+            title.push_str(&format!(" - Rewind {}", self.stats.history_len));
+        }
+        if self.is_running && self.rate > 0.0 {
+            title.push_str(&format!(" - {} gen/s", format_rate(self.rate)));
+        }
+        if let Some(speed) = self.speed {
+            title.push_str(&format!(" (target {})", format_rate(speed)));
+        }
+        if let Some(info) = &self.cursor_info {
+            title.push_str(" - ");
+            title.push_str(info);
+        }
+        title
     }
 
     fn log(&self, now: &Instant) {
         eprintln!(
-            "{}\tGen: {:?}\tStep: 2^{:?}\tPop: {:?}\tScale: 1:2^{:?}\tFps: {:?}",
+            "{}\tGen: {:?}\tStep: 2^{:?}\tPop: {:?}\tScale: 1:2^{:?}\tFps: {:?}\t{}",
             if self.is_running { "Running" } else { "Paused" },
-            self.world.get_generation(),
-            self.world.get_step(),
-            self.world.population(),
-            self.scale as i32 - SCALE_OFFSET as i32,
+            self.stats.generation,
+            self.stats.step,
+            self.stats.population,
+            self.view.scale(),
             1.0 / now.elapsed().as_secs_f32(),
+            self.status.as_deref().unwrap_or(""),
         );
     }
 
@@ -239,18 +1759,116 @@ impl App {
         'mainloop: loop {
             let mut events = self.sdl.event_pump()?;
             let mouse_state = events.mouse_state();
+            let ratio = hidpi_ratio(&self.canvas);
 
-            for event in events.poll_iter() {
+            for mut event in events.poll_iter() {
+                event_to_pixels(&mut event, ratio);
                 match event {
+                    event @ (Event::KeyDown { .. } | Event::TextInput { .. })
+                        if self.prompt.is_some() =>
+                    {
+                        self.prompt_event(event)
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } if self.worker.busy() => self.cancel_step(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } if self.paste.is_some() => self.cancel_paste(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } if self.selection.is_some() => self.deselect(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::V),
+                        keymod,
+                        ..
+                    } if ctrl(keymod) => self.start_paste(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::C),
+                        keymod,
+                        ..
+                    } if ctrl(keymod) => self.copy(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::X),
+                        keymod,
+                        ..
+                    } if ctrl(keymod) => self.cut(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::A),
+                        keymod,
+                        ..
+                    } if ctrl(keymod) => self.select_all(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Delete),
+                        ..
+                    } => self.clear_selection(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::S),
+                        keymod,
+                        ..
+                    } if ctrl(keymod) => self.save(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::P),
+                        keymod,
+                        ..
+                    }
+                    | Event::KeyDown {
+                        keycode: Some(Keycode::F12),
+                        keymod,
+                        ..
+                    } if shift(keymod) => self.export_image(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::P),
+                        ..
+                    }
+                    | Event::KeyDown {
+                        keycode: Some(Keycode::F12),
+                        ..
+                    } => self.screenshot(),
+                    Event::MouseButtonDown {
+                        mouse_btn: MouseButton::Left,
+                        x,
+                        y,
+                        ..
+                    } if self.on_minimap(x, y) => self.minimap_click(x, y),
+                    Event::MouseButtonDown {
+                        mouse_btn: MouseButton::Left,
+                        ..
+                    } if self.paste.is_some() => self.commit_paste(),
+                    Event::MouseButtonDown {
+                        mouse_btn: MouseButton::Left,
+                        x,
+                        y,
+                        ..
+                    } if shift(self.sdl.keyboard().mod_state()) => self.start_selection(x, y),
                     Event::Quit { .. }
                     | Event::KeyDown {
                         keycode: Option::Some(Keycode::Escape),
                         ..
                     } => break 'mainloop,
+                    Event::DropFile { filename, .. } => self.load_file(filename),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F5),
+                        ..
+                    } => self.reload(),
                     Event::Window {
-                        win_event: WindowEvent::SizeChanged(x, y),
+                        win_event: WindowEvent::SizeChanged(..),
                         ..
-                    } => self.window_resize(x as i64, y as i64),
+                    } => self.window_resize(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F11),
+                        ..
+                    } => self.toggle_fullscreen(),
+                    Event::Window {
+                        win_event: WindowEvent::Leave,
+                        ..
+                    } => {
+                        self.mouse = None;
+                        self.update_cursor_info();
+                    }
                     Event::KeyDown {
                         keycode: Some(Keycode::Space),
                         ..
@@ -259,6 +1877,26 @@ impl App {
                         keycode: Some(Keycode::Return),
                         ..
                     } => self.is_running ^= true,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Backspace),
+                        ..
+                    } => self.rewind(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::RightBracket),
+                        ..
+                    } => self.change_speed(2.0),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::LeftBracket),
+                        ..
+                    } => self.change_speed(0.5),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Backslash),
+                        ..
+                    } => self.unlimited_speed(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Tab),
+                        ..
+                    } => self.toggle_max_speed(),
                     Event::KeyDown {
                         keycode: Some(Keycode::Plus),
                         ..
@@ -303,38 +1941,133 @@ impl App {
                         keycode: Some(Keycode::Down),
                         ..
                     } => self.move_canvas(0, -10),
-                    Event::MouseMotion { xrel, yrel, .. } => {
-                        if mouse_state.left() {
+                    Event::KeyDown {
+                        keycode: Some(Keycode::E),
+                        ..
+                    } => self.toggle_edit_mode(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::B),
+                        keymod,
+                        ..
+                    } if shift(keymod) => self.toggle_bound(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::B),
+                        ..
+                    } => self.toggle_density(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::H),
+                        ..
+                    } => self.cycle_hud(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::M),
+                        ..
+                    } => self.toggle_minimap(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::O),
+                        ..
+                    } => self.toggle_graph(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::I),
+                        keymod,
+                        ..
+                    } if shift(keymod) => self.invert_colors(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::I),
+                        ..
+                    } => self.toggle_info(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::R),
+                        keymod,
+                        ..
+                    } => self.reset(shift(keymod)),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Home),
+                        ..
+                    } => self.reset(false),
+                    Event::MouseButtonDown {
+                        mouse_btn, x, y, ..
+                    } if self.edit_mode => self.start_paint(mouse_btn, x, y),
+                    Event::MouseButtonUp { .. } => self.stop_paint(),
+                    Event::MouseMotion {
+                        x, y, xrel, yrel, ..
+                    } => {
+                        self.mouse_move(x, y);
+                        if mouse_state.middle()
+                            || (!self.edit_mode && self.selecting.is_none() && mouse_state.left())
+                        {
                             self.move_canvas(xrel as i64, yrel as i64);
                         }
                     }
                     Event::MouseWheel { y, .. } => self.rescale(y, mouse_state),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Z | Keycode::PageUp),
+                        ..
+                    } => self.zoom_center(1),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::X | Keycode::PageDown),
+                        ..
+                    } => self.zoom_center(-1),
                     Event::KeyDown {
                         keycode: Some(Keycode::F),
                         ..
                     } => self.fit(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::G),
+                        ..
+                    } => self.start_prompt(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::T),
+                        keymod,
+                        ..
+                    } if shift(keymod) => self.switch_follow_target(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::T),
+                        ..
+                    } => self.toggle_follow(),
                     _ => {}
                 }
             }
+            drop(events);
 
-            if self.is_running {
-                self.world.step();
-                self.need_update = true;
+            if let Some((input, reload)) = self.load_request.take() {
+                self.start_load(input, reload);
             }
+            self.check_saving();
+            self.check_worker(Duration::ZERO);
+            self.check_watch();
+            self.update_follow();
 
-            if self.need_update {
+            // While the worker is busy, the last frame stays on the screen.
+            let redraw = self.need_update
+                && !self.worker.busy()
+                && !(self.max_speed && self.last_redraw.elapsed() < MAX_SPEED_FRAME_TIME);
+            if redraw {
                 self.update_canvas();
+                self.last_redraw = Instant::now();
+                // The view or the world may have changed under the mouse.
+                self.update_cursor_info();
             }
 
+            self.advance();
+
+            // At max speed, instead of sleeping, wait for the step to finish,
+            // so that the next one can start right away.
             let time_taken = now.elapsed();
-            if FRAME_TIME > time_taken {
+            if self.max_speed {
+                self.check_worker(MAX_SPEED_WAIT);
+            } else if FRAME_TIME > time_taken {
                 sleep(FRAME_TIME - time_taken);
             }
 
-            if self.need_update {
-                self.log(&now);
+            self.fps = 1.0 / now.elapsed().as_secs_f32();
+            self.update_title(self.fps);
+            if redraw {
+                if self.verbose {
+                    self.update_stats();
+                    self.log(&now);
+                }
+                self.need_update = false;
             }
-            self.need_update = false;
             now = Instant::now();
         }
         Ok(())
@@ -342,8 +2075,23 @@ impl App {
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let world = make_world()?;
+    let options = Options::from_args(args());
+    let mut world = make_world(options.input.as_deref(), options.rule.as_ref(), |_| {
+        ControlFlow::Continue(())
+    })?;
+    if let Some(gc_threshold) = options.gc_threshold {
+        world.set_gc_threshold(gc_threshold);
+    }
+
+    if options.headless {
+        return headless::run(world, &options);
+    }
+
+    world.step_by(options.gens);
+    world
+        .set_step(options.step)
+        .set_history_capacity(options.history);
 
-    let mut app = App::new(world)?;
+    let mut app = App::new(world, options)?;
     app.run()
 }
@@ -0,0 +1,131 @@
+use crate::{screen_rect, theme::Theme, with_alpha, Bound};
+use hashlife::{view::Viewport, World};
+use sdl2::{
+    rect::{Point, Rect},
+    render::Canvas,
+    video::Window,
+};
+
+// The size of the minimap and its distance to the corner of the window, in
+// pixels.
+const SIZE: u32 = 160;
+const MARGIN: i32 = 8;
+// While running, the minimap is rendered again at most once in this many
+// frames.
+const REFRESH_FRAMES: u32 = 10;
+
+// A small rendering of the whole pattern in the bottom-right corner, with the
+// part that is shown in the window outlined.
+pub struct Minimap {
+    // Maps the minimap to the world, so that the pattern fits in it.
+    view: Viewport,
+    // The living pixels, relative to the top-left corner of the minimap.
+    points: Vec<Point>,
+    // The generation when the minimap was rendered; `None` after an edit.
+    generation: Option<u64>,
+    // Frames since the minimap was rendered.
+    age: u32,
+}
+
+impl Minimap {
+    pub fn new() -> Self {
+        Minimap {
+            view: Viewport::new(SIZE, SIZE),
+            points: Vec::new(),
+            generation: None,
+            age: 0,
+        }
+    }
+
+    // Renders the minimap again on the next update.
+    pub fn invalidate(&mut self) {
+        self.generation = None;
+    }
+
+    // Where the minimap is drawn, if the window is large enough for it.
+    pub fn rect(&self, window: &Viewport) -> Option<Rect> {
+        let (width, height) = (window.width() as i32, window.height() as i32);
+        let size = SIZE as i32;
+        (width >= size + 2 * MARGIN && height >= size + 2 * MARGIN)
+            .then(|| Rect::new(width - size - MARGIN, height - size - MARGIN, SIZE, SIZE))
+    }
+
+    // Renders the pattern again if it has changed, but only every few frames
+    // while running.
+    pub fn update(&mut self, world: &World, bound: Option<Bound>, running: bool) {
+        self.age = self.age.saturating_add(1);
+        let generation = world.get_generation();
+        match self.generation {
+            Some(rendered) if rendered == generation => return,
+            Some(_) if running && self.age < REFRESH_FRAMES => return,
+            _ => {}
+        }
+        self.generation = Some(generation);
+        self.age = 0;
+        self.points.clear();
+        // An empty world only shows the outline of the window.
+        self.view = Viewport::new(SIZE, SIZE);
+        self.view.fit(bound);
+        if bound.is_none() {
+            return;
+        }
+        // The raster starts at the cell containing the top-left pixel, which
+        // may be partially outside of the minimap when zoomed in.
+        let visible = self.view.visible_bound();
+        let raster = match world.rasterize(visible, self.view.scale()) {
+            Some(raster) => raster,
+            None => return,
+        };
+        let (x0, y0) = self.view.world_to_screen(visible.0, visible.2);
+        for (i, &pixel) in raster.pixels.iter().enumerate() {
+            let x = x0 + (i as u32 % raster.width) as i64;
+            let y = y0 + (i as u32 / raster.width) as i64;
+            if pixel > 0 && (0..SIZE as i64).contains(&x) && (0..SIZE as i64).contains(&y) {
+                self.points.push(Point::new(x as i32, y as i32));
+            }
+        }
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        theme: &Theme,
+        window: &Viewport,
+    ) -> Result<(), String> {
+        let rect = match self.rect(window) {
+            Some(rect) => rect,
+            None => return Ok(()),
+        };
+        canvas.set_draw_color(with_alpha(theme.bg, 224));
+        canvas.fill_rect(rect)?;
+        canvas.set_draw_color(theme.fg);
+        let points: Vec<_> = self
+            .points
+            .iter()
+            .map(|p| p.offset(rect.x(), rect.y()))
+            .collect();
+        canvas.draw_points(points.as_slice())?;
+
+        // The window may extend far beyond the pattern, so its outline is
+        // clipped to the minimap.
+        if let Some(outline) = screen_rect(&self.view, window.visible_bound()) {
+            canvas.set_clip_rect(rect);
+            canvas.set_draw_color(theme.viewport);
+            let mut outline = outline;
+            outline.offset(rect.x(), rect.y());
+            canvas.draw_rect(outline)?;
+            canvas.set_clip_rect(None);
+        }
+        canvas.set_draw_color(theme.grid);
+        canvas.draw_rect(rect)
+    }
+
+    // The cell under a pixel of the window, if it is on the minimap.
+    pub fn cell_at(&self, window: &Viewport, x: i32, y: i32) -> Option<(i64, i64)> {
+        let rect = self.rect(window)?;
+        rect.contains_point((x, y)).then(|| {
+            self.view
+                .screen_to_world((x - rect.x()) as i64, (y - rect.y()) as i64)
+        })
+    }
+}
@@ -0,0 +1,294 @@
+use crate::theme::{parse_color, Theme};
+use hashlife::Rule;
+use std::{env::Args, process::exit};
+
+pub const USAGE: &str = "\
+Usage: hashlife-sdl [OPTIONS] [FILE]
+
+Opens an RLE, Macrocell, Plaintext or Life 1.06 file (possibly gzipped), an
+HTTP or HTTPS URL, the standard input if FILE is -, or a small default pattern.
+
+Options:
+  --step N            Initial step: 2^N generations per frame (default: 0)
+  --rule RULE         Override the rule of the pattern, e.g. B36/S23
+  --gens N            Run N generations before showing the window
+  --goto N            Advance to generation N after showing the window,
+                      or in headless mode, instead of --gens
+  --size WxH          Window size (default: 800x600)
+  --speed N           Target speed in generations per second (default: one
+                      step per frame)
+  --max-speed         Start running as fast as possible, redrawing ~10 times
+                      per second
+  --paused            Start paused (default)
+  --running           Start running
+  --gc-threshold N    Number of nodes that triggers garbage collection
+  --fit               Fit the pattern in the window (default)
+  --no-fit            Center the view on the origin instead
+  --follow TARGET     Start following the pattern: center (of the bounding
+                      box) or centroid
+  --auto-fit          When following, zoom out if the pattern outgrows the window
+  --history N         Number of steps that can be rewound, 0 to disable (default: 64)
+  --theme THEME       Color theme: dark (default) or light
+  --bg COLOR          Background color, e.g. #000000
+  --fg COLOR          Color of the living cells
+  --grid COLOR        Color of the grid, drawn when zoomed in
+  --selection COLOR   Color of the selection
+  --watch             Reload the file whenever it changes
+  --hud LEVEL         On-screen text: off, minimal (default) or full; needs the
+                      ttf feature
+  --font FILE         TrueType font for the HUD (default: a system monospace font)
+  --verbose           Print the status to stderr whenever the view changes
+  --headless          Run without a window, print the progress, and exit
+  --report SECONDS    Interval between progress lines in headless mode (default: 1)
+  --save FILE         Save the final state as Macrocell in headless mode
+  --help              Print this help
+
+Key bindings:
+  Enter               Start / stop running
+  Space               Next step
+  Backspace           Rewind one step
+  ] / [               Double / halve the target speed
+  \\                   Back to one step per frame
+  Tab                 Toggle max speed
+  + / =               Faster
+  -                   Slower
+  WASD / Arrows       Move
+  Mouse drag          Move (outside of edit mode)
+  Mouse wheel         Zoom in / out (two levels at a time with Ctrl)
+  Z / X               Zoom in / out about the center (or PageUp / PageDown)
+  E                   Toggle edit mode: left click / drag to toggle / paint,
+                      right click / drag to erase, middle drag to move
+  F11                 Toggle fullscreen
+  F5                  Reload the file, keeping the view and the --rule
+  F                   Fit pattern (twice quickly: with a margin)
+  T                   Toggle following the pattern (moving suspends it)
+  Shift+T             Follow the bounding box center / the centroid
+  G                   Go to a generation (type it, Enter to go, Esc to cancel)
+  B                   Toggle density shading when zoomed out
+  Shift+B             Toggle the bounding box of the pattern
+  M                   Toggle the minimap (click on it to go there)
+  H                   Cycle the HUD: off / minimal / full (ttf feature)
+  I                   Toggle the pattern info: file, rule, bounding box,
+                      nodes and memory usage
+  Shift+I             Invert the colors
+  R / Home            Reset to the pattern after the last edit
+  Shift+R             Reset to the pattern when it was last loaded or saved
+  Shift+drag          Select a rectangle (Esc to deselect)
+  Ctrl+A              Select the whole pattern
+  Ctrl+C              Copy the selection (or the whole pattern) as RLE
+  Ctrl+X              Cut the selection
+  Delete              Clear the selection
+  Ctrl+V              Paste RLE (click to place, Esc to cancel)
+  Ctrl+S              Save the universe as Macrocell
+  P / F12             Save a screenshot as PNG
+  Shift+P / Shift+F12 Export the whole pattern as PNG
+  Esc                 Cancel the running step, or quit
+";
+
+// The point that the view follows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FollowTarget {
+    // The center of the bounding box.
+    Center,
+    Centroid,
+}
+
+// How much text the HUD shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HudLevel {
+    Off,
+    Minimal,
+    Full,
+}
+
+pub struct Options {
+    pub input: Option<String>,
+    pub step: u8,
+    pub rule: Option<Rule>,
+    pub gens: u64,
+    pub goto: Option<u64>,
+    pub size: (u32, u32),
+    pub speed: Option<f64>,
+    pub max_speed: bool,
+    pub running: bool,
+    pub gc_threshold: Option<usize>,
+    pub fit: bool,
+    pub follow: Option<FollowTarget>,
+    pub auto_fit: bool,
+    pub history: usize,
+    pub theme: Theme,
+    pub watch: bool,
+    pub hud: HudLevel,
+    pub font: Option<String>,
+    pub verbose: bool,
+    pub headless: bool,
+    pub report: f64,
+    pub save: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            input: None,
+            step: 0,
+            rule: None,
+            gens: 0,
+            goto: None,
+            size: (800, 600),
+            speed: None,
+            max_speed: false,
+            running: false,
+            gc_threshold: None,
+            fit: true,
+            follow: None,
+            auto_fit: false,
+            history: 64,
+            theme: Theme::default(),
+            watch: false,
+            hud: HudLevel::Minimal,
+            font: None,
+            verbose: false,
+            headless: false,
+            report: 1.0,
+            save: None,
+        }
+    }
+}
+
+impl Options {
+    // Parses the command line. Prints the usage and exits on `--help` or on
+    // an invalid argument.
+    pub fn from_args(args: Args) -> Self {
+        match Self::parse(args.skip(1)) {
+            Ok(Some(options)) => options,
+            Ok(None) => {
+                print!("{}", USAGE);
+                exit(0);
+            }
+            Err(e) => {
+                eprint!("Error: {}\n\n{}", e, USAGE);
+                exit(1);
+            }
+        }
+    }
+
+    // Returns `None` on `--help`.
+    fn parse<I: Iterator<Item = String>>(args: I) -> Result<Option<Self>, String> {
+        let mut options = Options::default();
+        // Individual colors override the theme, wherever they are given.
+        let mut colors = Vec::new();
+        let mut args = args;
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("{} requires a value", arg))
+            };
+            match arg.as_str() {
+                "--help" | "-h" => return Ok(None),
+                "--step" => options.step = parse_number(&value()?)?,
+                "--rule" => {
+                    let rule = value()?;
+                    options.rule = Some(
+                        rule.parse()
+                            .map_err(|e| format!("Invalid rule {}: {}", rule, e))?,
+                    );
+                }
+                "--gens" => options.gens = parse_number(&value()?)?,
+                "--goto" => options.goto = Some(parse_number(&value()?)?),
+                "--size" => options.size = parse_size(&value()?)?,
+                "--speed" => {
+                    let speed: f64 = parse_number(&value()?)?;
+                    if !(speed > 0.0 && speed.is_finite()) {
+                        return Err(format!("Invalid speed {}", speed));
+                    }
+                    options.speed = Some(speed);
+                }
+                "--max-speed" => {
+                    options.max_speed = true;
+                    options.running = true;
+                }
+                "--paused" => options.running = false,
+                "--running" => options.running = true,
+                "--gc-threshold" => options.gc_threshold = Some(parse_number(&value()?)?),
+                "--fit" => options.fit = true,
+                "--no-fit" => options.fit = false,
+                "--follow" => {
+                    options.follow = Some(match value()?.as_str() {
+                        "center" => FollowTarget::Center,
+                        "centroid" => FollowTarget::Centroid,
+                        target => {
+                            return Err(format!(
+                                "Invalid follow target {}, expected center or centroid",
+                                target
+                            ))
+                        }
+                    })
+                }
+                "--auto-fit" => options.auto_fit = true,
+                "--history" => options.history = parse_number(&value()?)?,
+                "--theme" => options.theme = Theme::from_name(&value()?)?,
+                "--bg" | "--fg" | "--grid" | "--selection" => {
+                    colors.push((arg.clone(), parse_color(&value()?)?))
+                }
+                "--watch" => options.watch = true,
+                "--hud" | "--font" if cfg!(not(feature = "ttf")) => {
+                    return Err(format!("{} requires the ttf feature", arg))
+                }
+                "--hud" => {
+                    options.hud = match value()?.as_str() {
+                        "off" => HudLevel::Off,
+                        "minimal" => HudLevel::Minimal,
+                        "full" => HudLevel::Full,
+                        level => {
+                            return Err(format!(
+                                "Invalid HUD level {}, expected off, minimal or full",
+                                level
+                            ))
+                        }
+                    }
+                }
+                "--font" => options.font = Some(value()?),
+                "--verbose" => options.verbose = true,
+                "--headless" => options.headless = true,
+                "--report" => options.report = parse_number(&value()?)?,
+                "--save" => options.save = Some(value()?),
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(format!("Unknown option {}", arg))
+                }
+                _ if options.input.is_some() => return Err(format!("Unexpected argument {}", arg)),
+                _ => options.input = Some(arg),
+            }
+        }
+        for (arg, color) in colors {
+            let theme = &mut options.theme;
+            match arg.as_str() {
+                "--bg" => theme.bg = color,
+                "--fg" => theme.fg = color,
+                "--grid" => theme.grid = color,
+                _ => theme.selection = color,
+            }
+        }
+        if options.goto.is_some() && options.gens > 0 {
+            return Err("--goto and --gens cannot be used together".to_string());
+        }
+        if options.save.is_some() && !options.headless {
+            return Err("--save requires --headless".to_string());
+        }
+        Ok(Some(options))
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("Invalid number {}", s))
+}
+
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = s
+        .split_once('x')
+        .ok_or_else(|| format!("Invalid size {}, expected WxH", s))?;
+    let size = (parse_number(width)?, parse_number(height)?);
+    if size.0 == 0 || size.1 == 0 {
+        return Err(format!("Invalid size {}", s));
+    }
+    Ok(size)
+}
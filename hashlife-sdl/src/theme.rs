@@ -0,0 +1,99 @@
+use sdl2::pixels::Color;
+
+// The colors used to draw the canvas.
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    pub bg: Color,
+    pub fg: Color,
+    // Lines between the cells, drawn when zoomed in far enough.
+    pub grid: Color,
+    // Drawn translucent over the selected rectangle.
+    pub selection: Color,
+    // The outline of the bounding box of the pattern.
+    pub bound: Color,
+    // The pattern being pasted.
+    pub paste: Color,
+    // The cell under the mouse.
+    pub hover: Color,
+    // The outline of the window on the minimap.
+    pub viewport: Color,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Theme {
+            bg: Color::BLACK,
+            fg: Color::WHITE,
+            grid: Color::RGB(40, 40, 40),
+            selection: Color::RGB(0, 128, 255),
+            bound: Color::RGB(255, 160, 0),
+            paste: Color::RGB(0, 255, 0),
+            hover: Color::RED,
+            viewport: Color::YELLOW,
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            bg: Color::WHITE,
+            fg: Color::BLACK,
+            grid: Color::RGB(215, 215, 215),
+            selection: Color::RGB(0, 96, 224),
+            bound: Color::RGB(224, 112, 0),
+            paste: Color::RGB(0, 160, 0),
+            hover: Color::RED,
+            viewport: Color::RGB(176, 128, 0),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "dark" => Ok(Theme::dark()),
+            "light" => Ok(Theme::light()),
+            _ => Err(format!("Unknown theme {}, expected light or dark", name)),
+        }
+    }
+
+    // Swaps the background and the foreground, and inverts the grid.
+    pub fn invert(&mut self) {
+        std::mem::swap(&mut self.bg, &mut self.fg);
+        self.grid = Color::RGB(255 - self.grid.r, 255 - self.grid.g, 255 - self.grid.b);
+    }
+
+    // Interpolates between the background (`t = 0`) and the foreground
+    // (`t = 1`).
+    pub fn mix(&self, t: f64) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Color::RGB(
+            lerp(self.bg.r, self.fg.r),
+            lerp(self.bg.g, self.fg.g),
+            lerp(self.bg.b, self.fg.b),
+        )
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+// Parses a color written as `#RRGGBB` or `#RGB`; the `#` is optional.
+pub fn parse_color(s: &str) -> Result<Color, String> {
+    let error = || format!("Invalid color {}, expected #RRGGBB or #RGB", s);
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(error());
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap();
+    match hex.len() {
+        3 => Ok(Color::RGB(digit(0) * 17, digit(1) * 17, digit(2) * 17)),
+        6 => Ok(Color::RGB(
+            digit(0) * 16 + digit(1),
+            digit(2) * 16 + digit(3),
+            digit(4) * 16 + digit(5),
+        )),
+        _ => Err(error()),
+    }
+}
@@ -0,0 +1,168 @@
+use hashlife::World;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{spawn, JoinHandle},
+    time::Duration,
+};
+
+enum Command {
+    Step,
+    StepBy(u64),
+    Goto(u64),
+    Quit,
+}
+
+// Advances to the target generation with `step_to`, in decreasing powers of
+// two, so that the progress can be reported between them.
+fn goto(world: &mut World, target: u64, progress: &AtomicU64) -> bool {
+    while world.get_generation() < target {
+        let remaining = target - world.get_generation();
+        let chunk = 1 << (63 - remaining.leading_zeros());
+        if !world.step_to(world.get_generation() + chunk) {
+            return false;
+        }
+        progress.store(world.get_generation(), Ordering::Relaxed);
+    }
+    true
+}
+
+// Steps the world on a separate thread, so that the window stays responsive
+// during long steps.
+//
+// The world is shared behind a mutex, which the worker only holds during a
+// step. While the worker is busy, the UI must not lock the world, and should
+// postpone whatever needs it until `poll` reports that the step is done.
+pub struct Worker {
+    world: Arc<Mutex<World>>,
+    commands: Sender<Command>,
+    done: Receiver<bool>,
+    cancel: Arc<AtomicBool>,
+    progress: Arc<AtomicU64>,
+    busy: bool,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    pub fn new(world: World) -> Self {
+        let mut world = world;
+        let cancel = Arc::new(AtomicBool::new(false));
+        world.set_cancel_flag(Some(cancel.clone()));
+        let world = Arc::new(Mutex::new(world));
+        let (commands, command_receiver) = channel();
+        let (done_sender, done) = channel();
+        let progress = Arc::new(AtomicU64::new(0));
+        let shared = world.clone();
+        let shared_progress = progress.clone();
+        let handle = spawn(move || loop {
+            let completed = match command_receiver.recv() {
+                Ok(Command::Step) => shared.lock().unwrap().try_step().is_ok(),
+                Ok(Command::StepBy(generations)) => shared.lock().unwrap().step_by(generations),
+                Ok(Command::Goto(target)) => {
+                    goto(&mut shared.lock().unwrap(), target, &shared_progress)
+                }
+                Ok(Command::Quit) | Err(_) => break,
+            };
+            if done_sender.send(completed).is_err() {
+                break;
+            }
+        });
+        Worker {
+            world,
+            commands,
+            done,
+            cancel,
+            progress,
+            busy: false,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn busy(&self) -> bool {
+        self.busy
+    }
+
+    // Must not be called while the worker is busy, or it blocks until the
+    // step is done.
+    pub fn world(&self) -> MutexGuard<'_, World> {
+        self.world.lock().unwrap()
+    }
+
+    // Replaces the world, and returns it locked. Must not be called while the
+    // worker is busy.
+    pub fn set_world(&self, world: World) -> MutexGuard<'_, World> {
+        let mut world = world;
+        world.set_cancel_flag(Some(self.cancel.clone()));
+        let mut guard = self.world();
+        *guard = world;
+        guard
+    }
+
+    fn send(&mut self, command: Command) {
+        if !self.busy {
+            self.cancel.store(false, Ordering::Relaxed);
+            self.busy = self.commands.send(command).is_ok();
+        }
+    }
+
+    // Starts a step, unless one is already running.
+    pub fn step(&mut self) {
+        self.send(Command::Step);
+    }
+
+    // Starts advancing by the given number of generations, unless a step is
+    // already running.
+    pub fn step_by(&mut self, generations: u64) {
+        self.send(Command::StepBy(generations));
+    }
+
+    // Starts advancing to the target generation, unless a step is already
+    // running.
+    pub fn goto(&mut self, target: u64) {
+        if !self.busy {
+            let generation = self.world().get_generation();
+            self.progress.store(generation, Ordering::Relaxed);
+            self.send(Command::Goto(target));
+        }
+    }
+
+    // The generation reached by the running `goto`.
+    pub fn progress(&self) -> u64 {
+        self.progress.load(Ordering::Relaxed)
+    }
+
+    // Interrupts the running step, if any.
+    pub fn cancel(&self) {
+        if self.busy {
+            self.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // Returns whether the step was completed, when a step has just finished,
+    // waiting for at most `timeout`.
+    pub fn poll(&mut self, timeout: Duration) -> Option<bool> {
+        if !self.busy {
+            return None;
+        }
+        let completed = if timeout.is_zero() {
+            self.done.try_recv().ok()?
+        } else {
+            self.done.recv_timeout(timeout).ok()?
+        };
+        self.busy = false;
+        Some(completed)
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.cancel();
+        let _ = self.commands.send(Command::Quit);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
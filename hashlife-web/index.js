@@ -0,0 +1,135 @@
+import init, { WasmWorld } from "./pkg/hashlife_web.js";
+
+// Same as `Viewport::MIN_SCALE` and `Viewport::MAX_SCALE`.
+const MIN_SCALE = -4;
+const MAX_SCALE = 62;
+
+const canvas = document.getElementById("canvas");
+const context = canvas.getContext("2d");
+const status = document.getElementById("status");
+
+let world = null;
+let running = false;
+// The cell at the center of the canvas, and the scale: one pixel covers
+// 2^scale cells, or one cell covers 2^-scale pixels if it is negative.
+let center = { x: 0, y: 0 };
+let scale = 0;
+
+// Centers the pattern, at the smallest scale at which it fits.
+function fit() {
+  const bound = world.bound();
+  if (!bound) {
+    center = { x: 0, y: 0 };
+    scale = 0;
+    return;
+  }
+  const [left, right, top, bottom] = bound;
+  center = { x: Math.floor((left + right) / 2), y: Math.floor((top + bottom) / 2) };
+  const ratio = Math.max((right - left) / canvas.width, (bottom - top) / canvas.height);
+  scale = Math.min(Math.max(Math.ceil(Math.log2(ratio)), MIN_SCALE), MAX_SCALE);
+}
+
+function load(text) {
+  try {
+    const loaded = WasmWorld.from_rle(text);
+    if (world) {
+      world.free();
+    }
+    world = loaded;
+    world.set_step(Number(document.getElementById("step-log2").value));
+    fit();
+  } catch (e) {
+    status.textContent = `Cannot load the pattern: ${e.message}`;
+  }
+}
+
+function draw() {
+  if (!world) {
+    return;
+  }
+  const pixels = world.render(canvas.width, canvas.height, center.x, center.y, scale);
+  const image = new ImageData(new Uint8ClampedArray(pixels.buffer), canvas.width, canvas.height);
+  context.putImageData(image, 0, 0);
+  status.textContent = `Generation ${world.generation()}, population ${world.population()}`;
+}
+
+function frame() {
+  if (running && world) {
+    world.step();
+  }
+  draw();
+  requestAnimationFrame(frame);
+}
+
+function resize() {
+  canvas.width = canvas.clientWidth;
+  canvas.height = canvas.clientHeight;
+}
+
+// Zooms in by one level per wheel notch, keeping the cell under the mouse
+// fixed.
+canvas.addEventListener("wheel", (event) => {
+  event.preventDefault();
+  const newScale = Math.min(Math.max(scale + Math.sign(event.deltaY), MIN_SCALE), MAX_SCALE);
+  const dx = event.offsetX - canvas.width / 2;
+  const dy = event.offsetY - canvas.height / 2;
+  const factor = 2 ** scale - 2 ** newScale;
+  center = { x: center.x + dx * factor, y: center.y + dy * factor };
+  scale = newScale;
+});
+
+canvas.addEventListener("pointerdown", (event) => {
+  canvas.setPointerCapture(event.pointerId);
+  canvas.style.cursor = "grabbing";
+});
+
+canvas.addEventListener("pointermove", (event) => {
+  if (canvas.hasPointerCapture(event.pointerId)) {
+    center = {
+      x: center.x - event.movementX * 2 ** scale,
+      y: center.y - event.movementY * 2 ** scale,
+    };
+  }
+});
+
+canvas.addEventListener("pointerup", (event) => {
+  canvas.releasePointerCapture(event.pointerId);
+  canvas.style.cursor = "grab";
+});
+
+document.getElementById("load").addEventListener("click", () => {
+  load(document.getElementById("rle").value);
+});
+
+document.getElementById("file").addEventListener("change", async (event) => {
+  const file = event.target.files[0];
+  if (file) {
+    const text = await file.text();
+    document.getElementById("rle").value = text;
+    load(text);
+  }
+});
+
+document.getElementById("run").addEventListener("click", (event) => {
+  running = !running;
+  event.target.textContent = running ? "Pause" : "Run";
+});
+
+document.getElementById("step").addEventListener("click", () => {
+  if (world) {
+    world.step();
+  }
+});
+
+document.getElementById("step-log2").addEventListener("change", (event) => {
+  if (world) {
+    world.set_step(Number(event.target.value));
+  }
+});
+
+window.addEventListener("resize", resize);
+
+await init();
+resize();
+load(document.getElementById("rle").value);
+requestAnimationFrame(frame);
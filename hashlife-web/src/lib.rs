@@ -0,0 +1,79 @@
+use hashlife::{ca_formats::rle::Rle, view::Viewport, World};
+use js_sys::Uint8Array;
+use wasm_bindgen::prelude::*;
+
+// The colors of living and dead cells, as RGBA.
+const ALIVE: [u8; 4] = [255, 255, 255, 255];
+const DEAD: [u8; 4] = [0, 0, 0, 255];
+
+// A world that can be used from JavaScript.
+//
+// JavaScript has no 64-bit integers except `BigInt`, so coordinates,
+// populations and generations are passed as `f64`. They are exact up to 2^53.
+#[wasm_bindgen]
+pub struct WasmWorld {
+    world: World,
+}
+
+#[wasm_bindgen]
+impl WasmWorld {
+    // Loads an RLE pattern; the rule in its header is used if it is
+    // supported, B3/S23 otherwise.
+    pub fn from_rle(text: &str) -> Result<WasmWorld, JsError> {
+        let rle = Rle::new(text)?;
+        let world = World::from_rle(rle)?;
+        Ok(WasmWorld { world })
+    }
+
+    // Advances `2^step` generations.
+    pub fn step(&mut self) {
+        self.world.step();
+    }
+
+    pub fn set_step(&mut self, step: u8) {
+        self.world.set_step(step);
+    }
+
+    pub fn generation(&self) -> f64 {
+        self.world.get_generation() as f64
+    }
+
+    pub fn population(&self) -> f64 {
+        self.world.population() as f64
+    }
+
+    // Returns `[left, right, top, bottom]`, or `undefined` if the world is
+    // empty.
+    pub fn bound(&self) -> Option<Vec<f64>> {
+        let (left, right, top, bottom) = self.world.bound()?;
+        Some(vec![left as f64, right as f64, top as f64, bottom as f64])
+    }
+
+    // Renders the world into an RGBA buffer of `canvas_width * canvas_height`
+    // pixels, that can be put into an `ImageData`. The cell `(center_x,
+    // center_y)` is at the center of the canvas; `scale` is as in `Viewport`.
+    pub fn render(
+        &self,
+        canvas_width: u32,
+        canvas_height: u32,
+        center_x: f64,
+        center_y: f64,
+        scale: i8,
+    ) -> Uint8Array {
+        let (width, height) = (canvas_width as i64, canvas_height as i64);
+        let mut view = Viewport::new(canvas_width, canvas_height);
+        view.zoom_at((width / 2, height / 2), -(scale as i32));
+        let (x, y) = view.world_to_screen(center_x as i64, center_y as i64);
+        view.pan(width / 2 - x, height / 2 - y);
+
+        // An empty buffer if the canvas is too large.
+        let buffer: Vec<u8> = view
+            .rasterize(&self.world)
+            .map(|raster| raster.pixels)
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|&pixel| if pixel > 0 { ALIVE } else { DEAD })
+            .collect();
+        Uint8Array::from(buffer.as_slice())
+    }
+}
@@ -0,0 +1,21 @@
+// Runs with `wasm-pack test --node`.
+#![cfg(target_arch = "wasm32")]
+
+use hashlife_web::WasmWorld;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn render_glider() {
+    let mut world = WasmWorld::from_rle("x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!").unwrap();
+    world.set_step(2);
+    world.step();
+    assert_eq!(world.generation(), 4.0);
+    assert_eq!(world.bound(), Some(vec![1.0, 4.0, 1.0, 4.0]));
+    // The cell (2, 2) is at the center of a 4x4 canvas, and the cell (3, 2)
+    // on its right is alive.
+    let pixels = world.render(4, 4, 2.0, 2.0, 0).to_vec();
+    assert_eq!(pixels.len(), 64);
+    assert_eq!(&pixels[44..48], &[255, 255, 255, 255]);
+    let alive = pixels.chunks(4).filter(|p| p[0] == 255).count();
+    assert_eq!(alive, 5);
+}
@@ -0,0 +1,274 @@
+use hashlife::{ca_formats::rle::Rle, view::Viewport, World};
+use softbuffer::{Context, Surface};
+use std::{env::args, error::Error, num::NonZeroU32, process::exit, rc::Rc};
+use winit::{
+    application::ApplicationHandler,
+    dpi::PhysicalSize,
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{Key, NamedKey},
+    window::{Window, WindowId},
+};
+
+const USAGE: &str = "Usage: hashlife-winit [path/to/an/rle/or/macrocell/file]";
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 600;
+// The colors of living and dead cells, as 0RGB.
+const ALIVE: u32 = 0xffffff;
+const DEAD: u32 = 0x000000;
+// How far the arrow keys move the view, in pixels.
+const MOVE_STEP: i64 = 32;
+
+// Reads the pattern file, or makes a small default pattern.
+fn make_world(input: Option<&str>) -> Result<World, Box<dyn Error>> {
+    let world = if let Some(path) = input {
+        World::from_file(path)?
+    } else {
+        let rle = Rle::new("b2o$2o$bo!")?;
+        World::from_rle(rle)?
+    };
+    Ok(world)
+}
+
+struct Graphics {
+    window: Rc<Window>,
+    surface: Surface<Rc<Window>, Rc<Window>>,
+}
+
+struct App {
+    world: World,
+    view: Viewport,
+    graphics: Option<Graphics>,
+    is_running: bool,
+    // The last position of the cursor, and whether the view is being dragged.
+    cursor: (i64, i64),
+    dragging: bool,
+}
+
+impl App {
+    fn new(world: World) -> Self {
+        let mut view = Viewport::new(WIDTH, HEIGHT);
+        view.fit(world.bound());
+        App {
+            world,
+            view,
+            graphics: None,
+            is_running: false,
+            cursor: (0, 0),
+            dragging: false,
+        }
+    }
+
+    fn redraw(&mut self) {
+        if let Some(graphics) = &self.graphics {
+            graphics.window.request_redraw();
+        }
+    }
+
+    fn update_title(&self) {
+        if let Some(graphics) = &self.graphics {
+            graphics.window.set_title(&format!(
+                "HashLife - {} - Gen {} - Step 2^{} - Pop {} - Scale 1:2^{}",
+                if self.is_running { "Running" } else { "Paused" },
+                self.world.get_generation(),
+                self.world.get_step(),
+                self.world.population(),
+                self.view.scale(),
+            ));
+        }
+    }
+
+    // Copies the raster of the viewport to the window.
+    fn draw(&mut self) -> Result<(), Box<dyn Error>> {
+        let graphics = match &mut self.graphics {
+            Some(graphics) => graphics,
+            None => return Ok(()),
+        };
+        let raster = match self.view.rasterize(&self.world) {
+            Some(raster) => raster,
+            None => return Ok(()),
+        };
+        let (width, height) = match (
+            NonZeroU32::new(raster.width),
+            NonZeroU32::new(raster.height),
+        ) {
+            (Some(width), Some(height)) => (width, height),
+            _ => return Ok(()),
+        };
+        graphics.surface.resize(width, height)?;
+        let mut buffer = graphics.surface.buffer_mut()?;
+        for (target, &pixel) in buffer.iter_mut().zip(&raster.pixels) {
+            *target = if pixel > 0 { ALIVE } else { DEAD };
+        }
+        buffer.present()?;
+        Ok(())
+    }
+
+    fn step(&mut self) {
+        self.world.step();
+        self.update_title();
+        self.redraw();
+    }
+
+    fn set_step(&mut self, step: u8) {
+        self.world.set_step(step);
+        self.update_title();
+    }
+
+    fn zoom(&mut self, pixel: (i64, i64), delta: i32) {
+        self.view.zoom_at(pixel, delta);
+        self.update_title();
+        self.redraw();
+    }
+
+    fn pan(&mut self, dx: i64, dy: i64) {
+        self.view.pan(dx, dy);
+        self.redraw();
+    }
+
+    fn center(&self) -> (i64, i64) {
+        (self.view.width() as i64 / 2, self.view.height() as i64 / 2)
+    }
+
+    fn key(&mut self, key: Key, event_loop: &ActiveEventLoop) {
+        match key.as_ref() {
+            Key::Named(NamedKey::Escape) => event_loop.exit(),
+            Key::Named(NamedKey::Enter) => {
+                self.is_running = !self.is_running;
+                self.update_title();
+            }
+            Key::Named(NamedKey::Space) => self.step(),
+            Key::Named(NamedKey::ArrowLeft) => self.pan(MOVE_STEP, 0),
+            Key::Named(NamedKey::ArrowRight) => self.pan(-MOVE_STEP, 0),
+            Key::Named(NamedKey::ArrowUp) => self.pan(0, MOVE_STEP),
+            Key::Named(NamedKey::ArrowDown) => self.pan(0, -MOVE_STEP),
+            Key::Named(NamedKey::PageUp) => self.zoom(self.center(), 1),
+            Key::Named(NamedKey::PageDown) => self.zoom(self.center(), -1),
+            Key::Character(c) => match c.to_lowercase().as_str() {
+                "+" | "=" => self.set_step(self.world.get_step().saturating_add(1)),
+                "-" => self.set_step(self.world.get_step().saturating_sub(1)),
+                "a" => self.pan(MOVE_STEP, 0),
+                "d" => self.pan(-MOVE_STEP, 0),
+                "w" => self.pan(0, MOVE_STEP),
+                "s" => self.pan(0, -MOVE_STEP),
+                "z" => self.zoom(self.center(), 1),
+                "x" => self.zoom(self.center(), -1),
+                "f" => {
+                    self.view.fit(self.world.bound());
+                    self.update_title();
+                    self.redraw();
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.graphics.is_some() {
+            return;
+        }
+        let attributes = Window::default_attributes()
+            .with_title("HashLife")
+            .with_inner_size(PhysicalSize::new(WIDTH, HEIGHT));
+        let graphics = event_loop
+            .create_window(attributes)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|window| {
+                let window = Rc::new(window);
+                let context = Context::new(window.clone())?;
+                let surface = Surface::new(&context, window.clone())?;
+                Ok(Graphics { window, surface })
+            });
+        match graphics {
+            Ok(graphics) => {
+                let size = graphics.window.inner_size();
+                self.view.resize(size.width, size.height);
+                self.graphics = Some(graphics);
+                self.update_title();
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::RedrawRequested => {
+                if let Err(e) = self.draw() {
+                    eprintln!("Error: {}", e);
+                    event_loop.exit();
+                }
+            }
+            WindowEvent::Resized(size) => {
+                self.view.resize(size.width, size.height);
+                self.redraw();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.key(logical_key, event_loop),
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => self.dragging = state == ElementState::Pressed,
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = (position.x as i64, position.y as i64);
+                if self.dragging {
+                    self.pan(cursor.0 - self.cursor.0, cursor.1 - self.cursor.1);
+                }
+                self.cursor = cursor;
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let y = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y as f64,
+                    MouseScrollDelta::PixelDelta(position) => position.y,
+                };
+                if y != 0.0 {
+                    self.zoom(self.cursor, y.signum() as i32);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // While running, one step is taken per frame.
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if self.is_running {
+            self.step();
+            event_loop.set_control_flow(ControlFlow::Poll);
+        } else {
+            event_loop.set_control_flow(ControlFlow::Wait);
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let input = args().nth(1);
+    if matches!(input.as_deref(), Some("--help" | "-h")) {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let world = make_world(input.as_deref())?;
+    let event_loop = EventLoop::new()?;
+    event_loop.run_app(&mut App::new(world))?;
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        exit(1);
+    }
+}
@@ -0,0 +1,228 @@
+use crate::world::{Node, NodeId, Nodes, World};
+use rustc_hash::FxHashMap;
+
+// Accumulates the number of changed cells per tile, see
+// `World::enable_activity_map`.
+#[derive(Clone, Debug)]
+pub(crate) struct Activity {
+    cell_log2: u8,
+    map: FxHashMap<(i64, i64), u64>,
+}
+
+impl World {
+    // After each step, adds the number of cells that changed during the step
+    // to their tiles of `2^cell_log2 * 2^cell_log2` cells. The tile `(i, j)`
+    // covers the cells from `(i << cell_log2, j << cell_log2)`. With a step
+    // size larger than one generation, only the changes between the start
+    // and the end of each step are seen.
+    //
+    // The counts are computed from the roots before and after the step,
+    // without visiting the cells: identical subtrees are the same node and
+    // are skipped at once, a subtree that is empty on one side adds the
+    // population of the other, and the number of changes inside a subtree
+    // that fits in a tile is memoized for each pair of nodes. So the cost of
+    // a step is about the number of distinct pairs of nodes that differ,
+    // which is small for a pattern that is mostly still, plus one entry for
+    // each tile that changes.
+    //
+    // Enabling it again with another tile size clears the map.
+    pub fn enable_activity_map(&mut self, cell_log2: u8) -> &mut Self {
+        match &self.activity {
+            Some(activity) if activity.cell_log2 == cell_log2 => {}
+            _ => {
+                self.activity = Some(Box::new(Activity {
+                    cell_log2: cell_log2.min(62),
+                    map: FxHashMap::default(),
+                }))
+            }
+        }
+        self
+    }
+
+    pub fn disable_activity_map(&mut self) -> &mut Self {
+        self.activity = None;
+        self
+    }
+
+    // The number of changed cells in each tile that has changed, or `None` if
+    // the map is not enabled.
+    pub fn activity_map(&self) -> Option<&FxHashMap<(i64, i64), u64>> {
+        self.activity.as_ref().map(|activity| &activity.map)
+    }
+
+    pub fn clear_activity_map(&mut self) {
+        if let Some(activity) = &mut self.activity {
+            activity.map.clear();
+        }
+    }
+
+    // Called in a step, with the root after it, whose level is one less.
+    // Before the step, all cells are in the middle half of the root, which is
+    // compared to the new root.
+    pub(crate) fn record_activity(&mut self, new_root: Node) {
+        let mut activity = match self.activity.take() {
+            Some(activity) => activity,
+            None => return,
+        };
+        let center = match self.root {
+            Node::Leaf(_) => unreachable!(),
+            Node::NodeId(id) => {
+                let data = &self[id];
+                match (data.nw(), data.ne(), data.sw(), data.se()) {
+                    (Node::Leaf(nw), Node::Leaf(ne), Node::Leaf(sw), Node::Leaf(se)) => Node::Leaf(
+                        (nw & 0x0033) << 10
+                            | (ne & 0x00cc) << 6
+                            | (sw & 0x3300) >> 6
+                            | (se & 0xcc00) >> 10,
+                    ),
+                    (nw, ne, sw, se) => {
+                        let nw = self[node_id(nw)].se();
+                        let ne = self[node_id(ne)].sw();
+                        let sw = self[node_id(sw)].ne();
+                        let se = self[node_id(se)].nw();
+                        Node::NodeId(self.find_node(nw, ne, sw, se))
+                    }
+                }
+            }
+        };
+        self.nodes.activity_rec(
+            center,
+            new_root,
+            (0, 0),
+            activity.cell_log2,
+            &mut FxHashMap::default(),
+            &mut activity.map,
+        );
+        self.activity = Some(activity);
+    }
+}
+
+fn node_id(node: Node) -> NodeId {
+    match node {
+        Node::NodeId(id) => id,
+        Node::Leaf(_) => unreachable!(),
+    }
+}
+
+impl Nodes {
+    // `old` and `new` have the same level, and are centered at `center`.
+    fn activity_rec(
+        &self,
+        old: Node,
+        new: Node,
+        center: (i64, i64),
+        cell_log2: u8,
+        memo: &mut FxHashMap<(Node, Node), u64>,
+        map: &mut FxHashMap<(i64, i64), u64>,
+    ) {
+        if old == new {
+            return;
+        }
+        let level = self.node_level(old);
+        if level <= cell_log2 {
+            let half = 1 << (level - 1);
+            let tile = (
+                (center.0 - half) >> cell_log2,
+                (center.1 - half) >> cell_log2,
+            );
+            *map.entry(tile).or_insert(0) += self.changes(old, new, memo);
+            return;
+        }
+        match (old, new) {
+            (Node::Leaf(old), Node::Leaf(new)) => {
+                let changes = old ^ new;
+                for bit in (0..16).filter(|bit| changes & 1 << bit != 0) {
+                    let x = center.0 + 1 - bit % 4;
+                    let y = center.1 + 1 - bit / 4;
+                    *map.entry((x >> cell_log2, y >> cell_log2)).or_insert(0) += 1;
+                }
+            }
+            (Node::NodeId(old), Node::NodeId(new)) => {
+                let size = 1 << (level - 2);
+                let (old, new) = (&self[old], &self[new]);
+                let (x, y) = center;
+                for (old, new, center) in [
+                    (old.nw(), new.nw(), (x - size, y - size)),
+                    (old.ne(), new.ne(), (x + size, y - size)),
+                    (old.sw(), new.sw(), (x - size, y + size)),
+                    (old.se(), new.se(), (x + size, y + size)),
+                ] {
+                    self.activity_rec(old, new, center, cell_log2, memo, map);
+                }
+            }
+            _ => unreachable!("Both nodes must have the same level."),
+        }
+    }
+
+    // The number of cells that differ between two nodes of the same level.
+    fn changes(&self, old: Node, new: Node, memo: &mut FxHashMap<(Node, Node), u64>) -> u64 {
+        if old == new {
+            return 0;
+        }
+        let (old_population, new_population) =
+            (self.node_population(old), self.node_population(new));
+        if old_population == 0 || new_population == 0 {
+            return old_population + new_population;
+        }
+        let (old_id, new_id) = match (old, new) {
+            (Node::Leaf(old), Node::Leaf(new)) => return (old ^ new).count_ones() as u64,
+            (Node::NodeId(old), Node::NodeId(new)) => (old, new),
+            _ => unreachable!("Both nodes must have the same level."),
+        };
+        if let Some(&changes) = memo.get(&(old, new)) {
+            return changes;
+        }
+        let (a, b) = (&self[old_id], &self[new_id]);
+        let changes = self.changes(a.nw(), b.nw(), memo)
+            + self.changes(a.ne(), b.ne(), memo)
+            + self.changes(a.sw(), b.sw(), memo)
+            + self.changes(a.se(), b.se(), memo);
+        memo.insert((old, new), changes);
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_activity_map() {
+        let mut world = World::from_bytes(b"5b3o!").unwrap();
+        world
+            .translate((0, 5))
+            .set_cells([(100, 100), (101, 100)], true);
+        world.enable_activity_map(4);
+        world.step();
+        world.step();
+        // The domino far away dies in the first step.
+        let map = world.activity_map().unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&(0, 0)], 8);
+        assert_eq!(map[&(6, 6)], 2);
+
+        // Compared with the changed cells, in steps of 8 generations.
+        let mut world = World::from_bytes(b"b2o$2o$bo!").unwrap();
+        world.set_step(3).enable_activity_map(1);
+        let mut expected = FxHashMap::default();
+        for _ in 0..20 {
+            let before = world.cells_vec().into_iter().collect::<HashSet<_>>();
+            world.step();
+            let after = world.cells_vec().into_iter().collect::<HashSet<_>>();
+            for &(x, y) in before.symmetric_difference(&after) {
+                *expected.entry((x >> 1, y >> 1)).or_insert(0) += 1;
+            }
+        }
+        assert_eq!(world.activity_map(), Some(&expected));
+        // Looking ahead and coming back adds nothing.
+        world.growth_rate(256);
+        world.detect_global_cycle(100);
+        assert_eq!(world.activity_map(), Some(&expected));
+
+        world.clear_activity_map();
+        assert!(world.activity_map().unwrap().is_empty());
+        world.disable_activity_map().step();
+        assert_eq!(world.activity_map(), None);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::world::{Leaf, Node, Nodes, World};
+use rustc_hash::FxHashMap;
+
+// The ages of the living cells, as 8 quadtrees in the same nodes as the
+// pattern: bit `i` of the age of a cell is the cell in `planes[i]`. The
+// planes all have the same level, which is at least that of the root.
+#[derive(Clone, Debug)]
+pub(crate) struct Ages {
+    planes: [Node; 8],
+    // The ages are not updated meanwhile, but the planes stay alive.
+    paused: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum BitOp {
+    And,
+    Or,
+    Xor,
+    AndNot,
+}
+
+impl Ages {
+    pub(crate) fn planes(&self) -> &[Node] {
+        &self.planes
+    }
+}
+
+type Memo = FxHashMap<(BitOp, Node, Node), Node>;
+
+impl World {
+    // Keeps the age of every living cell from now on: `1` for a cell that
+    // was just born, one more for each generation it survives, up to `255`.
+    // The cells that are alive now start at `0`, and so do cells that are
+    // set later, until their first step. Restoring a snapshot does not
+    // restore the ages.
+    //
+    // This is expensive. Ages change every generation, so every step is
+    // split into single generations, as with exact extremes, and each
+    // generation does about 30 boolean operations on the quadtrees of the
+    // age bits, on all the nodes where the pattern is alive, not only where
+    // it changes, until the ages saturate at `255`. The nodes are shared,
+    // so still lifes that are old enough cost little. Disabling drops the
+    // quadtrees, and the steps are as fast as before.
+    pub fn enable_age_tracking(&mut self) -> &mut Self {
+        self.ages = Some(Box::new(Ages {
+            planes: [Node::Leaf(0); 8],
+            paused: false,
+        }));
+        self
+    }
+
+    pub fn disable_age_tracking(&mut self) {
+        self.ages = None;
+    }
+
+    // Stops or resumes updating the ages, e.g. while `growth_rate` looks
+    // ahead. Returns whether they were paused.
+    pub(crate) fn pause_ages(&mut self, paused: bool) -> bool {
+        match &mut self.ages {
+            Some(ages) => std::mem::replace(&mut ages.paused, paused),
+            None => false,
+        }
+    }
+
+    pub(crate) fn updates_ages(&self) -> bool {
+        self.ages.as_ref().is_some_and(|ages| !ages.paused)
+    }
+
+    // The roots of the age quadtrees, which are kept alive by GC.
+    pub(crate) fn age_planes(&self) -> &[Node] {
+        self.ages.as_ref().map_or(&[], |ages| ages.planes())
+    }
+
+    // `None` if the cell is dead, or if the ages are not tracked.
+    pub fn cell_age(&self, x: i64, y: i64) -> Option<u8> {
+        let ages = self.ages.as_ref()?;
+        let nodes = self.nodes();
+        if !nodes.get_cell_rec(self.root, x, y) {
+            return None;
+        }
+        Some(ages.planes.iter().enumerate().fold(0, |age, (i, &plane)| {
+            age | (nodes.get_cell_rec(plane, x, y) as u8) << i
+        }))
+    }
+
+    // Bound: (left, right, top, bottom).
+    // Calls `f` with every living cell in the bound and its age, which is
+    // `0` if the ages are not tracked. Each nonempty leaf is looked up once
+    // in each age quadtree.
+    pub fn for_cells_with_age<F>(&self, bound: (i64, i64, i64, i64), f: F)
+    where
+        F: FnMut(i64, i64, u8),
+    {
+        let mut f = f;
+        // Collected first, since the nodes of a shared store cannot be
+        // locked twice.
+        let leaves = self.leaves(bound).collect::<Vec<_>>();
+        let nodes = self.nodes();
+        for (x, y, leaf) in leaves {
+            let mut planes = [0; 8];
+            for (plane, &node) in planes.iter_mut().zip(self.age_planes()) {
+                *plane = nodes.leaf_at(node, x + 2, y + 2);
+            }
+            for bit in 0..16 {
+                if leaf & 0x8000 >> bit != 0 {
+                    let age = planes.iter().enumerate().fold(0, |age, (i, &plane)| {
+                        age | ((plane & 0x8000 >> bit != 0) as u8) << i
+                    });
+                    f(x + bit % 4, y + bit / 4, age);
+                }
+            }
+        }
+    }
+
+    // Called after each step of one generation from `old` to `new`.
+    pub(crate) fn record_ages(&mut self, old: Node, new: Node) {
+        let mut planes = match &self.ages {
+            Some(ages) if !ages.paused => ages.planes,
+            _ => return,
+        };
+        debug_assert_eq!(self.step, 0, "Ages need steps of one generation");
+        let level = [old, new, planes[0]]
+            .iter()
+            .map(|&node| self.node_level(node))
+            .max()
+            .unwrap()
+            .max(3);
+        let [old, new] = [old, new].map(|node| self.pad(node, level));
+        for plane in &mut planes {
+            *plane = self.pad(*plane, level);
+        }
+
+        let memo = &mut FxHashMap::default();
+        let survivors = self.bit_op(BitOp::And, old, new, memo);
+        let births = self.bit_op(BitOp::AndNot, new, old, memo);
+        let saturated = planes[1..].iter().fold(planes[0], |all, &plane| {
+            self.bit_op(BitOp::And, all, plane, memo)
+        });
+        // Adds one to the ages of the survivors, with a carry.
+        let mut carry = self.bit_op(BitOp::AndNot, survivors, saturated, memo);
+        for plane in &mut planes {
+            let bit = self.bit_op(BitOp::Xor, *plane, carry, memo);
+            carry = self.bit_op(BitOp::And, *plane, carry, memo);
+            *plane = self.bit_op(BitOp::And, bit, survivors, memo);
+        }
+        planes[0] = self.bit_op(BitOp::Or, planes[0], births, memo);
+        self.ages = Some(Box::new(Ages {
+            planes,
+            paused: false,
+        }));
+    }
+
+    fn pad(&mut self, mut node: Node, level: u8) -> Node {
+        while self.node_level(node) < level {
+            node = self.expand_node(node);
+        }
+        node
+    }
+
+    // Both nodes have the same level.
+    fn bit_op(&mut self, op: BitOp, a: Node, b: Node, memo: &mut Memo) -> Node {
+        let (a_id, b_id) = match (a, b) {
+            (Node::Leaf(a), Node::Leaf(b)) => {
+                return Node::Leaf(match op {
+                    BitOp::And => a & b,
+                    BitOp::Or => a | b,
+                    BitOp::Xor => a ^ b,
+                    BitOp::AndNot => a & !b,
+                });
+            }
+            (Node::NodeId(a), Node::NodeId(b)) => (a, b),
+            _ => unreachable!("Both nodes must have the same level."),
+        };
+        let empty = |node| self.node_population(node) == 0;
+        match op {
+            BitOp::And if empty(a) || a == b => return a,
+            BitOp::And if empty(b) => return b,
+            BitOp::Or | BitOp::Xor | BitOp::AndNot if empty(b) => return a,
+            BitOp::Or | BitOp::Xor if empty(a) => return b,
+            BitOp::Or if a == b => return a,
+            BitOp::AndNot if empty(a) => return a,
+            BitOp::Xor | BitOp::AndNot if a == b => {
+                return self.empty_node(self[a_id].level());
+            }
+            _ => {}
+        }
+        if let Some(&node) = memo.get(&(op, a, b)) {
+            return node;
+        }
+        let (x, y) = (&self[a_id], &self[b_id]);
+        let children = [
+            (x.nw(), y.nw()),
+            (x.ne(), y.ne()),
+            (x.sw(), y.sw()),
+            (x.se(), y.se()),
+        ];
+        let [nw, ne, sw, se] = children.map(|(a, b)| self.bit_op(op, a, b, memo));
+        let node = Node::NodeId(self.find_node(nw, ne, sw, se));
+        memo.insert((op, a, b), node);
+        node
+    }
+}
+
+impl Nodes {
+    // The leaf that contains the cell, relative to the center of the node.
+    fn leaf_at(&self, node: Node, x: i64, y: i64) -> Leaf {
+        if self.node_population(node) == 0 {
+            return 0;
+        }
+        let node_size = 1 << (self.node_level(node) - 2);
+        if x >= 2 * node_size || x < -2 * node_size || y >= 2 * node_size || y < -2 * node_size {
+            return 0;
+        }
+        match node {
+            Node::Leaf(leaf) => leaf,
+            Node::NodeId(id) => match (x.is_negative(), y.is_negative()) {
+                (true, true) => self.leaf_at(self[id].nw(), x + node_size, y + node_size),
+                (false, true) => self.leaf_at(self[id].ne(), x - node_size, y + node_size),
+                (true, false) => self.leaf_at(self[id].sw(), x + node_size, y - node_size),
+                (false, false) => self.leaf_at(self[id].se(), x - node_size, y - node_size),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ages() {
+        // A block and a blinker.
+        let mut world = World::from_bytes(b"2o$2o$4b3o!").unwrap();
+        assert_eq!(world.cell_age(0, 0), None);
+        world.enable_age_tracking();
+        assert_eq!(world.cell_age(0, 0), Some(0));
+        world.step_by(9);
+        world.set_step(1).step();
+        assert_eq!(world.get_generation(), 11);
+        assert_eq!(world.get_step(), 1);
+        assert_eq!(world.cell_age(0, 0), Some(11));
+        // The blinker is vertical, and its ends were just born.
+        assert_eq!(world.cell_age(5, 2), Some(11));
+        assert_eq!(world.cell_age(5, 1), Some(1));
+        assert_eq!(world.cell_age(4, 2), None);
+        world.set_cell(10, 10, true);
+        assert_eq!(world.cell_age(10, 10), Some(0));
+
+        let mut ages = Vec::new();
+        world.for_cells_with_age((-10, 6, -10, 10), |x, y, age| ages.push((x, y, age)));
+        ages.sort_unstable();
+        assert_eq!(
+            ages,
+            [
+                (0, 0, 11),
+                (0, 1, 11),
+                (1, 0, 11),
+                (1, 1, 11),
+                (5, 1, 1),
+                (5, 2, 11),
+                (5, 3, 1)
+            ]
+        );
+
+        world.set_step(8).step();
+        world.garbage_collect();
+        assert_eq!(world.cell_age(1, 1), Some(255));
+        assert_eq!(world.cell_age(5, 2), Some(255));
+        assert_eq!(world.cell_age(10, 10), None);
+
+        world.disable_age_tracking();
+        assert_eq!(world.cell_age(1, 1), None);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{rule::Rule, world::World};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+// `to_apgcode` gives up on patterns whose period is longer.
+const MAX_PERIOD: usize = 1 << 12;
+const CHARS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+type Cell = (i64, i64);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidApgcode {
+    // The prefix is not `xs`, `xp` or `xq` followed by a number and `_`.
+    Prefix,
+    // A character that is not part of the extended Wechsler format.
+    Character(char),
+}
+
+impl Display for InvalidApgcode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            InvalidApgcode::Prefix => write!(f, "Unsupported apgcode prefix"),
+            InvalidApgcode::Character(c) => write!(f, "Invalid character {:?} in apgcode", c),
+        }
+    }
+}
+
+impl Error for InvalidApgcode {}
+
+// Moves the cells so that their bounding box starts at `(0, 0)`, and sorts
+// them. Also returns the top-left corner of the bounding box.
+fn normalize(mut cells: Vec<Cell>) -> Option<(Vec<Cell>, Cell)> {
+    let left = cells.iter().map(|&(x, _)| x).min()?;
+    let top = cells.iter().map(|&(_, y)| y).min()?;
+    for (x, y) in &mut cells {
+        *x -= left;
+        *y -= top;
+    }
+    cells.sort_unstable();
+    Some((cells, (left, top)))
+}
+
+// The extended Wechsler format: the pattern is cut into strips of 5 rows,
+// separated by `z`, and each column of a strip is a character, whose bit `i`
+// is the cell in row `i`. Runs of empty columns are shortened to `0`, `w`,
+// `x`, or `y` followed by the length minus 4, and trailing ones are omitted.
+fn wechsler(cells: &[Cell]) -> String {
+    let width = cells.iter().map(|&(x, _)| x + 1).max().unwrap_or(0) as usize;
+    let height = cells.iter().map(|&(_, y)| y + 1).max().unwrap_or(0) as usize;
+    let mut strips = vec![vec![0u8; width]; height.div_ceil(5)];
+    for &(x, y) in cells {
+        strips[y as usize / 5][x as usize] |= 1 << (y % 5);
+    }
+    let mut code = String::new();
+    for (i, strip) in strips.iter().enumerate() {
+        if i > 0 {
+            code.push('z');
+        }
+        let mut zeros = 0;
+        for &column in strip {
+            if column == 0 {
+                zeros += 1;
+                continue;
+            }
+            while zeros > 39 {
+                code.push_str("yz");
+                zeros -= 39;
+            }
+            match zeros {
+                0 => {}
+                1 => code.push('0'),
+                2 => code.push('w'),
+                3 => code.push('x'),
+                _ => {
+                    code.push('y');
+                    code.push(CHARS[zeros - 4] as char);
+                }
+            }
+            zeros = 0;
+            code.push(CHARS[column as usize] as char);
+        }
+    }
+    code
+}
+
+// The shortest code of the 8 orientations of the phase, and the first in
+// alphabetical order among the shortest ones.
+fn canonical_wechsler(phase: &[Cell]) -> String {
+    let orientations: [fn(Cell) -> Cell; 8] = [
+        |(x, y)| (x, y),
+        |(x, y)| (-x, y),
+        |(x, y)| (x, -y),
+        |(x, y)| (-x, -y),
+        |(x, y)| (y, x),
+        |(x, y)| (-y, x),
+        |(x, y)| (y, -x),
+        |(x, y)| (-y, -x),
+    ];
+    orientations
+        .iter()
+        .map(|orientation| {
+            let cells = phase.iter().map(|&cell| orientation(cell)).collect();
+            wechsler(&normalize(cells).unwrap().0)
+        })
+        .min_by(|a, b| (a.len(), a).cmp(&(b.len(), b)))
+        .unwrap()
+}
+
+impl World {
+    // Catagolue's name of a still life (`xs`), an oscillator (`xp`) or a
+    // spaceship (`xq`), e.g. `xq4_153` for the glider. The period is found
+    // by running a copy of the pattern one generation at a time, and the
+    // code is the canonical one among all phases and orientations.
+    //
+    // Returns `None` for an empty pattern, and for a pattern that does not
+    // come back, possibly moved, within 4096 generations.
+    pub fn to_apgcode(&self) -> Option<String> {
+        let (cells, _) = normalize(self.cells_vec())?;
+        let mut world = World::new(self.rule.clone());
+        world.set_cells(cells.iter().copied(), true);
+        let mut phases = vec![cells];
+        let mut moves = None;
+        for _ in 0..MAX_PERIOD {
+            world.step();
+            let (phase, offset) = normalize(world.cells_vec())?;
+            if phase == phases[0] {
+                moves = Some(offset != (0, 0));
+                break;
+            }
+            phases.push(phase);
+        }
+        let prefix = match (moves?, phases.len()) {
+            (false, 1) => format!("xs{}", phases[0].len()),
+            (false, period) => format!("xp{}", period),
+            (true, period) => format!("xq{}", period),
+        };
+        let code = phases
+            .iter()
+            .map(|phase| canonical_wechsler(phase))
+            .min_by(|a, b| (a.len(), a).cmp(&(b.len(), b)))
+            .unwrap();
+        Some(format!("{}_{}", prefix, code))
+    }
+
+    // Decodes an `xs`, `xp` or `xq` apgcode. The top-left corner of the
+    // bounding box of the pattern is at the origin. The number in the prefix
+    // is not checked.
+    pub fn from_apgcode(apgcode: &str, rule: Rule) -> Result<Self, InvalidApgcode> {
+        let (prefix, code) = apgcode.split_once('_').ok_or(InvalidApgcode::Prefix)?;
+        let number = ["xs", "xp", "xq"]
+            .iter()
+            .find_map(|class| prefix.strip_prefix(class))
+            .ok_or(InvalidApgcode::Prefix)?;
+        if number.is_empty() || !number.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(InvalidApgcode::Prefix);
+        }
+        let value = |c: char| {
+            CHARS
+                .iter()
+                .position(|&d| d as char == c)
+                .ok_or(InvalidApgcode::Character(c))
+        };
+        let mut cells = Vec::new();
+        let (mut x, mut strip) = (0, 0);
+        let mut chars = code.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                'w' => x += 2,
+                'x' => x += 3,
+                'y' => x += 4 + value(chars.next().ok_or(InvalidApgcode::Character(c))?)? as i64,
+                'z' => {
+                    x = 0;
+                    strip += 1;
+                }
+                c => {
+                    let column = value(c)?;
+                    for i in 0..5 {
+                        if column & 1 << i != 0 {
+                            cells.push((x, 5 * strip + i));
+                        }
+                    }
+                    x += 1;
+                }
+            }
+        }
+        let mut world = World::new(rule);
+        world.set_cells(cells, true);
+        Ok(world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each world is dropped at once: they are large, and the test runs on a
+    // small stack.
+    fn encode(rle: &str) -> Option<String> {
+        World::from_bytes(rle.as_bytes()).unwrap().to_apgcode()
+    }
+
+    fn decode(apgcode: &str) -> Result<Option<String>, InvalidApgcode> {
+        World::from_apgcode(apgcode, "B3/S23".parse().unwrap()).map(|world| world.to_apgcode())
+    }
+
+    #[test]
+    fn test_apgcode() {
+        for (apgcode, rle) in [
+            ("xs4_33", "2o$2o!"),
+            ("xp2_7", "3o!"),
+            ("xq4_153", "bo$2bo$3o!"),
+            ("xq4_6frc", "bo2bo$o4b$o3bo$4o!"),
+        ] {
+            assert_eq!(encode(rle).as_deref(), Some(apgcode));
+            assert_eq!(decode(apgcode).unwrap().as_deref(), Some(apgcode));
+        }
+        // A glider leaving a block, and a domino that dies.
+        assert_eq!(encode("2o$2o2$4bo$5bo$3b3o!"), None);
+        assert_eq!(encode("2o!"), None);
+        assert_eq!(World::default().to_apgcode(), None);
+
+        assert_eq!(decode("xs4"), Err(InvalidApgcode::Prefix));
+        assert_eq!(
+            decode("yl144_1_16_afb5f3db909e60548f086e22ee3353ac"),
+            Err(InvalidApgcode::Prefix)
+        );
+        assert_eq!(decode("xs4_3A"), Err(InvalidApgcode::Character('A')));
+    }
+}
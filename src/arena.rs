@@ -0,0 +1,207 @@
+use std::ops::{Index, IndexMut};
+
+const CHUNK_LOG2: u32 = 16;
+const CHUNK_SIZE: usize = 1 << CHUNK_LOG2;
+
+// The end of the free list.
+const NO_SLOT: usize = usize::MAX;
+
+#[derive(Clone, Debug)]
+enum Slot<T> {
+    // The next free slot.
+    Vacant(usize),
+    Occupied(T),
+}
+
+// Values in chunks of `2^16` slots that never grow once allocated, so adding
+// a value never moves or copies the others, unlike a `Vec` or a `Slab` that
+// doubles. The slot of a value is its key, `chunk * 2^16 + index`, and the
+// slots of removed values are reused first.
+#[derive(Clone, Debug)]
+pub(crate) struct Arena<T> {
+    chunks: Vec<Vec<Slot<T>>>,
+    len: usize,
+    // Slots in use or on the free list, from the start.
+    end: usize,
+    next_free: usize,
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Arena {
+            chunks: Vec::new(),
+            len: 0,
+            end: 0,
+            next_free: NO_SLOT,
+        }
+    }
+}
+
+impl<T> Arena<T> {
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    // The slots that are allocated, which bound the keys.
+    pub(crate) fn capacity(&self) -> usize {
+        self.chunks.len() * CHUNK_SIZE
+    }
+
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        let free = self.capacity() - self.len;
+        let needed = additional.saturating_sub(free);
+        for _ in 0..needed.div_ceil(CHUNK_SIZE) {
+            self.chunks.push(Vec::with_capacity(CHUNK_SIZE));
+        }
+    }
+
+    // Keeps the chunks, like `Vec::clear`.
+    pub(crate) fn clear(&mut self) {
+        self.chunks.iter_mut().for_each(Vec::clear);
+        self.len = 0;
+        self.end = 0;
+        self.next_free = NO_SLOT;
+    }
+
+    #[cfg_attr(not(feature = "background-gc"), allow(dead_code))]
+    pub(crate) fn get(&self, key: usize) -> Option<&T> {
+        match self.chunks.get(key >> CHUNK_LOG2)?.get(key % CHUNK_SIZE)? {
+            Slot::Occupied(value) => Some(value),
+            Slot::Vacant(_) => None,
+        }
+    }
+
+    // Same as indexing, without checking that the slot exists and is
+    // occupied, see the `fast-index` feature.
+    //
+    // # Safety
+    //
+    // The key must be of a value in the arena, i.e., returned by `insert`
+    // and neither removed by `retain` nor cleared since.
+    #[cfg(all(feature = "fast-index", not(miri)))]
+    pub(crate) unsafe fn get_unchecked(&self, key: usize) -> &T {
+        let chunk = self.chunks.get_unchecked(key >> CHUNK_LOG2);
+        match chunk.get_unchecked(key % CHUNK_SIZE) {
+            Slot::Occupied(value) => value,
+            Slot::Vacant(_) => std::hint::unreachable_unchecked(),
+        }
+    }
+
+    // Same as `get_unchecked`, with the same safety requirements.
+    #[cfg(all(feature = "fast-index", not(miri)))]
+    pub(crate) unsafe fn get_unchecked_mut(&mut self, key: usize) -> &mut T {
+        let chunk = self.chunks.get_unchecked_mut(key >> CHUNK_LOG2);
+        match chunk.get_unchecked_mut(key % CHUNK_SIZE) {
+            Slot::Occupied(value) => value,
+            Slot::Vacant(_) => std::hint::unreachable_unchecked(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, value: T) -> usize {
+        self.len += 1;
+        if self.next_free != NO_SLOT {
+            let key = self.next_free;
+            let slot = &mut self.chunks[key >> CHUNK_LOG2][key % CHUNK_SIZE];
+            match std::mem::replace(slot, Slot::Occupied(value)) {
+                Slot::Vacant(next) => self.next_free = next,
+                Slot::Occupied(_) => unreachable!("Free slots must be vacant."),
+            }
+            return key;
+        }
+        let key = self.end;
+        self.end += 1;
+        let chunk = key >> CHUNK_LOG2;
+        if chunk == self.chunks.len() {
+            self.chunks.push(Vec::with_capacity(CHUNK_SIZE));
+        }
+        self.chunks[chunk].push(Slot::Occupied(value));
+        key
+    }
+
+    // Removes the values for which `f` returns `false`. Their slots are
+    // reused from the lowest key.
+    pub(crate) fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize, &mut T) -> bool,
+    {
+        let mut next_free = NO_SLOT;
+        for key in (0..self.end).rev() {
+            let slot = &mut self.chunks[key >> CHUNK_LOG2][key % CHUNK_SIZE];
+            if let Slot::Occupied(value) = slot {
+                if f(key, value) {
+                    continue;
+                }
+                self.len -= 1;
+            }
+            *slot = Slot::Vacant(next_free);
+            next_free = key;
+        }
+        self.next_free = next_free;
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> {
+        self.chunks
+            .iter_mut()
+            .flatten()
+            .enumerate()
+            .filter_map(|(key, slot)| match slot {
+                Slot::Occupied(value) => Some((key, value)),
+                Slot::Vacant(_) => None,
+            })
+    }
+}
+
+impl<T> Index<usize> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, key: usize) -> &Self::Output {
+        match &self.chunks[key >> CHUNK_LOG2][key % CHUNK_SIZE] {
+            Slot::Occupied(value) => value,
+            Slot::Vacant(_) => panic!("Invalid key {}", key),
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Arena<T> {
+    fn index_mut(&mut self, key: usize) -> &mut Self::Output {
+        match &mut self.chunks[key >> CHUNK_LOG2][key % CHUNK_SIZE] {
+            Slot::Occupied(value) => value,
+            Slot::Vacant(_) => panic!("Invalid key {}", key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arena() {
+        let mut arena = Arena::default();
+        let keys = (0..CHUNK_SIZE as u64 + 10)
+            .map(|i| arena.insert(i))
+            .collect::<Vec<_>>();
+        assert_eq!(arena.len(), CHUNK_SIZE + 10);
+        assert_eq!(arena.capacity(), 2 * CHUNK_SIZE);
+        assert_eq!(arena[keys[CHUNK_SIZE + 3]], CHUNK_SIZE as u64 + 3);
+
+        arena.retain(|_, value| *value % 2 == 0);
+        assert_eq!(arena.len(), CHUNK_SIZE / 2 + 5);
+        assert!(arena.iter_mut().all(|(key, value)| *value == key as u64));
+        // The freed slots are reused from the lowest.
+        assert_eq!(arena.insert(100), 1);
+        assert_eq!(arena.insert(200), 3);
+        arena[3] += 1;
+        assert_eq!(arena[3], 201);
+        #[cfg(all(feature = "fast-index", not(miri)))]
+        unsafe {
+            *arena.get_unchecked_mut(3) += 1;
+            assert_eq!(*arena.get_unchecked(3), 202);
+        }
+
+        arena.clear();
+        assert_eq!(arena.len(), 0);
+        assert_eq!(arena.insert(0), 0);
+        assert_eq!(arena.capacity(), 2 * CHUNK_SIZE);
+    }
+}
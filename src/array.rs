@@ -0,0 +1,98 @@
+use crate::{recording::Op, world::World};
+use ndarray::{Array2, ArrayView2};
+
+impl World {
+    // Copies the cells inside the bound `(left, right, top, bottom)` into an
+    // array indexed by `[y - top, x - left]`. The cells are read a 4x4 leaf
+    // at a time, so empty parts of the region cost nothing.
+    pub fn to_array2(&self, bound: (i64, i64, i64, i64)) -> Array2<bool> {
+        let (left, right, top, bottom) = bound;
+        let width = right.saturating_sub(left).max(0) as usize;
+        let height = bottom.saturating_sub(top).max(0) as usize;
+        let mut array = Array2::from_elem((height, width), false);
+        self.for_leaves(bound, |x, y, leaf| {
+            for row in 0..4 {
+                for column in 0..4 {
+                    if leaf & 0x8000 >> (4 * row + column) != 0 {
+                        let i = (y + row - top) as usize;
+                        let j = (x + column - left) as usize;
+                        array[[i, j]] = true;
+                    }
+                }
+            }
+        });
+        array
+    }
+
+    // Sets the cells of the region whose top-left corner is `(x, y)` from an
+    // array indexed by `[row, column]`, so that the cell `(x + column, y +
+    // row)` is alive if and only if the entry is `true`. Cells outside of the
+    // region are not changed. The region is cleared, then each run of living
+    // cells in a row is filled at once.
+    pub fn set_from_array2(&mut self, x: i64, y: i64, array: &ArrayView2<bool>) -> &mut Self {
+        let (width, height) = (array.ncols() as i64, array.nrows() as i64);
+        let mut runs = Vec::new();
+        for (row, cells) in array.rows().into_iter().enumerate() {
+            let y = y + row as i64;
+            let mut start = None;
+            for (column, &cell) in cells.iter().chain(&[false]).enumerate() {
+                let column = x + column as i64;
+                match (start, cell) {
+                    (None, true) => start = Some(column),
+                    (Some(left), false) => {
+                        runs.push((left, column, y, y + 1));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.clear_region((x, x + width, y, y + height));
+        self.apply(Op::FillRegions(runs, true));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn test_array2_round_trip() {
+        let glider = array![
+            [false, true, false],
+            [false, false, true],
+            [true, true, true],
+        ];
+        let mut world = World::default();
+        world.set_from_array2(-2, -1, &glider.view());
+        assert_eq!(world.population(), 5);
+        assert_eq!(world.to_array2((-2, 1, -1, 2)), glider);
+
+        // A larger bound pads the array with dead cells.
+        let padded = world.to_array2((-3, 2, -2, 3));
+        assert_eq!(padded.shape(), &[5, 5]);
+        assert_eq!(padded.slice(ndarray::s![1..4, 1..4]), glider);
+
+        // Writing an array overwrites the whole region, and nothing else.
+        world.set_cell(5, 5, true);
+        world.set_from_array2(-2, -1, &Array2::from_elem((3, 3), false).view());
+        assert_eq!(world.population(), 1);
+        assert!(world.get_cell(5, 5));
+
+        world.set_step(2);
+        world.set_from_array2(-2, -1, &glider.view());
+        world.set_cell(5, 5, false);
+        world.step();
+        assert_eq!(world.to_array2((-1, 2, 0, 3)), glider);
+
+        // Recorded, so that a replay overwrites the region too.
+        let recording = world.start_recording();
+        world.set_from_array2(0, 0, &glider.view());
+        let mut replayed = World::default();
+        replayed.draw_rect((0, 3, 0, 3), true, true);
+        recording.replay(&mut replayed);
+        assert_eq!(replayed.to_array2((0, 3, 0, 3)), glider);
+    }
+}
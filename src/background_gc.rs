@@ -0,0 +1,166 @@
+// Garbage collection with the marking on another thread, so that a large
+// world does not pause for the whole collection.
+//
+// `World::spawn_background_gc` copies the links of every slot of the arena,
+// i.e., the keys of the children and of the cached step, which is a linear
+// pass over memory, and marks the copy on a new thread from the roots at
+// that moment. The world keeps stepping and editing meanwhile: nodes are
+// only added, and the nodes that exist never move, so the copy stays valid
+// for them.
+//
+// At the next safepoint after the marking is done, the end of a step, the
+// marks are completed on the world's thread: the nodes created since the
+// copy are marked, and so are the unmarked nodes that the current roots and
+// those new nodes reach, which `find_node` may have found again in the hash
+// table, and the cached steps of fewer generations, which are not in the
+// copy. This only visits the nodes that were not marked already. Then the
+// unmarked nodes are swept, and so are the cached steps that point to them.
+//
+// The pause is thus the copy, the remarking and the sweep, rather than the
+// whole marking. A stop-the-world collection, e.g. when the number of nodes
+// reaches the threshold, or clearing the nodes, cancels the marking. Worlds
+// in a shared store are not supported.
+
+use crate::world::World;
+use std::{
+    fmt::{Debug, Formatter, Result as FmtResult},
+    thread::{self, JoinHandle},
+};
+
+// The keys of the children and of the cached step of `2^(level - 2)`
+// generations of a node, `NO_KEY` for leaves and for no step.
+pub(crate) type Links = [u32; 5];
+
+pub(crate) const NO_KEY: u32 = u32::MAX;
+
+// For each slot: `None` if it was vacant, whether it is reachable otherwise.
+type Marks = Vec<Option<bool>>;
+
+#[derive(Default)]
+pub(crate) struct BackgroundGc {
+    marker: Option<JoinHandle<Marks>>,
+}
+
+impl Debug for BackgroundGc {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("BackgroundGc")
+            .field("running", &self.marker.is_some())
+            .finish()
+    }
+}
+
+// A copy of a world does not share the marking.
+impl Clone for BackgroundGc {
+    fn clone(&self) -> Self {
+        BackgroundGc::default()
+    }
+}
+
+fn mark(links: Vec<Option<Links>>, roots: Vec<u32>) -> Marks {
+    let mut marks: Marks = links.iter().map(|links| links.map(|_| false)).collect();
+    let mut stack = roots;
+    while let Some(key) = stack.pop() {
+        if key == NO_KEY {
+            continue;
+        }
+        if let Some(mark @ Some(false)) = marks.get_mut(key as usize) {
+            *mark = Some(true);
+            stack.extend(links[key as usize].iter().flatten());
+        }
+    }
+    marks
+}
+
+impl World {
+    // Starts marking the reachable nodes on another thread. They are swept at
+    // the end of the first step after the marking is done, or by
+    // `finish_background_gc`.
+    //
+    // Returns `false` if a marking is already running, or if the world is
+    // in a shared store, in which case nothing is done.
+    pub fn spawn_background_gc(&mut self) -> bool {
+        if self.shared.is_some() || self.background_gc.marker.is_some() {
+            return false;
+        }
+        let (links, roots) = self.nodes.links(&self.gc_roots());
+        self.background_gc.marker = Some(thread::spawn(move || mark(links, roots)));
+        true
+    }
+
+    pub fn is_background_gc_running(&self) -> bool {
+        self.background_gc.marker.is_some()
+    }
+
+    // Waits for the marking, and sweeps now. Returns `false` if no marking
+    // was running.
+    pub fn finish_background_gc(&mut self) -> bool {
+        match self.background_gc.marker.take() {
+            Some(marker) => {
+                self.sweep_background(marker);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Called at the end of each step.
+    pub(crate) fn poll_background_gc(&mut self) {
+        if let Some(marker) = &self.background_gc.marker {
+            if marker.is_finished() {
+                self.finish_background_gc();
+            }
+        }
+    }
+
+    // The keys that the marking refers to are about to become invalid.
+    pub(crate) fn cancel_background_gc(&mut self) {
+        self.background_gc.marker = None;
+    }
+
+    fn sweep_background(&mut self, marker: JoinHandle<Marks>) {
+        let copied = marker.join().expect("The marking thread panicked.");
+        let marks = self.nodes.remark(&self.gc_roots(), &copied);
+        self.nodes.sweep(&marks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_background_gc() {
+        let mut world = World::from_bytes(b"b2o$2o$bo!").unwrap();
+        let mut expected = world.clone();
+        world.step_by(100);
+        expected.step_by(100);
+        for _ in 0..20 {
+            assert!(world.spawn_background_gc());
+            assert!(!world.spawn_background_gc());
+            // Nodes are created and found again while marking.
+            for _ in 0..5 {
+                world.step();
+                expected.step();
+            }
+            world.finish_background_gc();
+            assert_eq!(world.population(), expected.population());
+        }
+        assert_eq!(world.cells_vec(), expected.cells_vec());
+        assert!(!world.finish_background_gc());
+
+        // Without steps in between, the same nodes as a stop-the-world
+        // collection are left.
+        let nodes = world.stats().nodes;
+        let mut expected = world.clone();
+        world.spawn_background_gc();
+        assert!(world.finish_background_gc());
+        expected.garbage_collect();
+        assert!(world.stats().nodes < nodes);
+        assert_eq!(world.stats().nodes, expected.stats().nodes);
+
+        // A stop-the-world collection cancels the marking.
+        world.spawn_background_gc();
+        world.garbage_collect();
+        assert!(!world.is_background_gc_running());
+    }
+}
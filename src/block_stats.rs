@@ -0,0 +1,330 @@
+use crate::world::{Leaf, Node, NodeId, Nodes, World};
+use rustc_hash::FxHashMap;
+
+// The number of buckets of the densities in `BlockStats`.
+pub const DENSITY_BUCKETS: usize = 16;
+
+// The texture of a pattern, see `World::block_stats`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockStats {
+    // The blocks entirely inside the bound, by density: bucket `i` holds the
+    // densities from `i / 16` to `(i + 1) / 16`, the last one also the full
+    // blocks, and the first one also the empty blocks.
+    pub histogram: [u64; DENSITY_BUCKETS],
+    pub blocks: u64,
+    pub empty_blocks: u64,
+    // The Shannon entropy, in bits, of the values of the 4x4 leaves entirely
+    // inside the bound, including the empty ones: 0 for an empty or uniform
+    // region, and at most 16.
+    pub leaf_entropy: f64,
+    pub distinct_leaves: usize,
+}
+
+fn bucket(population: u64, block_log2: u8) -> usize {
+    let density = population as f64 / (2.0f64).powi(2 * block_log2 as i32);
+    ((density * DENSITY_BUCKETS as f64) as usize).min(DENSITY_BUCKETS - 1)
+}
+
+// The number of aligned blocks of size `2^log2` entirely inside the bound.
+fn whole_blocks(bound: (i64, i64, i64, i64), log2: u8) -> u64 {
+    let count = |start: i64, end: i64| {
+        let size = 1i128 << log2;
+        let first = (start as i128 + size - 1).div_euclid(size);
+        let last = (end as i128).div_euclid(size);
+        (last - first).max(0) as u128
+    };
+    let (left, right, top, bottom) = bound;
+    let blocks = count(left, right).saturating_mul(count(top, bottom));
+    blocks.min(u64::MAX as u128) as u64
+}
+
+// The nonempty nodes entirely inside the bound, with their multiplicities.
+struct Counts {
+    // By level, from 3.
+    nodes: Vec<FxHashMap<NodeId, u64>>,
+    leaves: FxHashMap<Leaf, u64>,
+}
+
+impl Counts {
+    // Bound: (left, right, top, bottom), relative to the center of the node.
+    fn collect(&mut self, nodes: &Nodes, node: Node, bound: (i64, i64, i64, i64)) {
+        if nodes.node_population(node) == 0 {
+            return;
+        }
+        let node_size = 1 << (nodes.node_level(node) - 2);
+        let (left, right, top, bottom) = bound;
+        if left >= 2 * node_size
+            || right <= -2 * node_size
+            || top >= 2 * node_size
+            || bottom <= -2 * node_size
+        {
+            return;
+        }
+        let whole = left <= -2 * node_size
+            && right >= 2 * node_size
+            && top <= -2 * node_size
+            && bottom >= 2 * node_size;
+        match node {
+            Node::Leaf(leaf) if whole => *self.leaves.entry(leaf).or_insert(0) += 1,
+            Node::Leaf(_) => {}
+            Node::NodeId(id) if whole => {
+                *self.nodes[nodes[id].level() as usize]
+                    .entry(id)
+                    .or_insert(0) += 1
+            }
+            Node::NodeId(id) => {
+                let data = &nodes[id];
+                for (child, sx, sy) in [
+                    (data.nw(), -1, -1),
+                    (data.ne(), 1, -1),
+                    (data.sw(), -1, 1),
+                    (data.se(), 1, 1),
+                ] {
+                    let bound = (
+                        left.saturating_sub(sx * node_size),
+                        right.saturating_sub(sx * node_size),
+                        top.saturating_sub(sy * node_size),
+                        bottom.saturating_sub(sy * node_size),
+                    );
+                    self.collect(nodes, child, bound);
+                }
+            }
+        }
+    }
+}
+
+impl World {
+    // Bound: (left, right, top, bottom).
+    // The densities of the aligned blocks of `2^block_log2` cells square,
+    // and the entropy of the 4x4 leaves, e.g. to tell an agar from ash or
+    // from chaos. Only the blocks and the leaves entirely inside the bound
+    // are counted, so it should be aligned to both.
+    //
+    // Each distinct node is visited once: the multiplicity of a node, i.e.,
+    // how many times it occurs inside the bound, is added to its children,
+    // level by level from the top, until the blocks and the leaves. The
+    // empty ones are counted from the area. So the time is about the number
+    // of distinct nodes, however large and repetitive the pattern is.
+    pub fn block_stats(&self, bound: (i64, i64, i64, i64), block_log2: u8) -> BlockStats {
+        debug_assert!(block_log2 < 63, "Level of a block must be < 63");
+        let mut histogram = [0; DENSITY_BUCKETS];
+        let mut nonempty_blocks = 0;
+        // A leaf as the root is not aligned, and is split like `expand`.
+        let root_level = self.node_level(self.root).max(3);
+
+        let nodes = self.nodes();
+        let mut counts = Counts {
+            nodes: vec![FxHashMap::default(); root_level as usize + 1],
+            leaves: FxHashMap::default(),
+        };
+        match self.root {
+            Node::Leaf(leaf) => {
+                for (child, sx, sy) in [
+                    ((leaf & 0xcc00) >> 10, -1, -1),
+                    ((leaf & 0x3300) >> 6, 1, -1),
+                    ((leaf & 0x00cc) << 6, -1, 1),
+                    ((leaf & 0x0033) << 10, 1, 1),
+                ] {
+                    let (dx, dy) = (2 * sx, 2 * sy);
+                    let bound = (bound.0 - dx, bound.1 - dx, bound.2 - dy, bound.3 - dy);
+                    counts.collect(&nodes, Node::Leaf(child), bound);
+                }
+            }
+            root => counts.collect(&nodes, root, bound),
+        }
+        for level in (3..=root_level).rev() {
+            for (id, multiplicity) in std::mem::take(&mut counts.nodes[level as usize]) {
+                let data = &nodes[id];
+                // The root is not aligned either.
+                if level == block_log2 && level < root_level {
+                    histogram[bucket(nodes.population(id), block_log2)] += multiplicity;
+                    nonempty_blocks += multiplicity;
+                }
+                for child in [data.nw(), data.ne(), data.sw(), data.se()] {
+                    match child {
+                        _ if nodes.node_population(child) == 0 => {}
+                        Node::Leaf(leaf) => *counts.leaves.entry(leaf).or_insert(0) += multiplicity,
+                        Node::NodeId(id) => {
+                            *counts.nodes[level as usize - 1].entry(id).or_insert(0) += multiplicity
+                        }
+                    }
+                }
+            }
+        }
+        drop(nodes);
+
+        if block_log2 <= 2 {
+            // Blocks inside the leaves, as masks of their cells.
+            let size = 1 << block_log2;
+            let mut masks = Vec::new();
+            for y in (0..4).step_by(size) {
+                for x in (0..4).step_by(size) {
+                    let mask = (0..16)
+                        .filter(|bit| (bit % 4) / size == x / size && (bit / 4) / size == y / size)
+                        .fold(0u16, |mask, bit| mask | 0x8000 >> bit);
+                    masks.push(mask);
+                }
+            }
+            for (&leaf, &multiplicity) in &counts.leaves {
+                for mask in &masks {
+                    let population = (leaf & mask).count_ones() as u64;
+                    if population > 0 {
+                        histogram[bucket(population, block_log2)] += multiplicity;
+                        nonempty_blocks += multiplicity;
+                    }
+                }
+            }
+        } else if block_log2 >= root_level {
+            // Blocks larger than the root, which is in the 4 around the
+            // origin.
+            for (i, j) in [(-1, -1), (0, -1), (-1, 0), (0, 0)] {
+                let block = (
+                    i << block_log2,
+                    (i + 1) << block_log2,
+                    j << block_log2,
+                    (j + 1) << block_log2,
+                );
+                if block.0 < bound.0 || block.1 > bound.1 || block.2 < bound.2 || block.3 > bound.3
+                {
+                    continue;
+                }
+                let population = self.population_in(block);
+                if population > 0 {
+                    histogram[bucket(population, block_log2)] += 1;
+                    nonempty_blocks += 1;
+                }
+            }
+        }
+        let blocks = whole_blocks(bound, block_log2);
+        let empty_blocks = blocks.saturating_sub(nonempty_blocks);
+        histogram[0] += empty_blocks;
+
+        let nonempty_leaves = counts.leaves.values().sum::<u64>();
+        let empty_leaves = whole_blocks(bound, 2).saturating_sub(nonempty_leaves);
+        let total = (nonempty_leaves + empty_leaves) as f64;
+        let leaf_entropy = counts
+            .leaves
+            .values()
+            .chain(Some(&empty_leaves))
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / total;
+                -p * p.log2()
+            })
+            .sum::<f64>();
+
+        BlockStats {
+            histogram,
+            blocks,
+            empty_blocks,
+            leaf_entropy,
+            distinct_leaves: counts.leaves.len() + (empty_leaves > 0) as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts every block and leaf with `population_in` and `get_cell`.
+    fn brute_force(world: &World, bound: (i64, i64, i64, i64), block_log2: u8) -> BlockStats {
+        let size = 1 << block_log2;
+        let mut histogram = [0; DENSITY_BUCKETS];
+        let (mut blocks, mut empty_blocks) = (0, 0);
+        let aligned = |start: i64, end: i64| {
+            let first = (start + size - 1).div_euclid(size) * size;
+            (first..end - size + 1).step_by(size as usize)
+        };
+        for y in aligned(bound.2, bound.3) {
+            for x in aligned(bound.0, bound.1) {
+                let population = world.population_in((x, x + size, y, y + size));
+                histogram[bucket(population, block_log2)] += 1;
+                blocks += 1;
+                empty_blocks += (population == 0) as u64;
+            }
+        }
+        let mut leaves = FxHashMap::default();
+        for y in (bound.2..bound.3).step_by(4) {
+            for x in (bound.0..bound.1).step_by(4) {
+                let leaf = (0..16).fold(0u16, |leaf, bit| {
+                    leaf | (world.get_cell(x + bit % 4, y + bit / 4) as u16) << (15 - bit)
+                });
+                *leaves.entry(leaf).or_insert(0u64) += 1;
+            }
+        }
+        let total = leaves.values().sum::<u64>() as f64;
+        let leaf_entropy = leaves
+            .values()
+            .map(|&count| {
+                let p = count as f64 / total;
+                -p * p.log2()
+            })
+            .sum();
+        BlockStats {
+            histogram,
+            blocks,
+            empty_blocks,
+            leaf_entropy,
+            distinct_leaves: leaves.len(),
+        }
+    }
+
+    fn soup() -> World {
+        let mut world = World::default();
+        let mut state = 1u64;
+        for y in 0..40 {
+            for x in 0..40 {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                if state >> 63 != 0 {
+                    world.set_cell(x, y, true);
+                }
+            }
+        }
+        world.step_by(50);
+        world
+    }
+
+    #[test]
+    fn test_block_stats() {
+        let mut world = World::default();
+        world.fill_region((0, 32, 0, 32), true);
+        let stats = world.block_stats((0, 64, 0, 64), 3);
+        assert_eq!(stats.histogram[DENSITY_BUCKETS - 1], 16);
+        assert_eq!(stats.histogram[0], 48);
+        assert_eq!((stats.blocks, stats.empty_blocks), (64, 48));
+        // A quarter of the leaves are full, the rest empty.
+        assert!((stats.leaf_entropy - 0.8112781244591328).abs() < 1e-12);
+        assert_eq!(stats.distinct_leaves, 2);
+
+        // A glider in a leaf as the root.
+        let world = World::from_bytes(b"bo$2bo$3o!").unwrap();
+        let bound = (-8, 8, -8, 8);
+        for block_log2 in 0..5 {
+            assert_eq!(
+                world.block_stats(bound, block_log2),
+                brute_force(&world, bound, block_log2)
+            );
+        }
+
+        let world = soup();
+        for (bound, block_log2) in [
+            ((-32, 64, -16, 48), 0),
+            ((-32, 64, -16, 48), 1),
+            ((-32, 64, -16, 48), 2),
+            ((-32, 64, -16, 48), 3),
+            ((-32, 64, 0, 48), 4),
+            ((-512, 512, -512, 512), 9),
+        ] {
+            let stats = world.block_stats(bound, block_log2);
+            let expected = brute_force(&world, bound, block_log2);
+            assert_eq!(stats.histogram, expected.histogram);
+            assert_eq!(stats.blocks, expected.blocks);
+            assert_eq!(stats.empty_blocks, expected.empty_blocks);
+            assert_eq!(stats.distinct_leaves, expected.distinct_leaves);
+            assert!((stats.leaf_entropy - expected.leaf_entropy).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use crate::world::{Leaf, Node, NodeId, Nodes, World};
+use rustc_hash::FxHashMap;
+use std::{
+    ops::{BitAnd, BitOr, BitXor},
+    sync::Arc,
+};
+
+// How `World::combine` merges the cells of two worlds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BoolOp {
+    Or,
+    And,
+    Xor,
+    AndNot,
+}
+
+impl BoolOp {
+    fn leaf(self, a: Leaf, b: Leaf) -> Leaf {
+        match self {
+            BoolOp::Or => a | b,
+            BoolOp::And => a & b,
+            BoolOp::Xor => a ^ b,
+            BoolOp::AndNot => a & !b,
+        }
+    }
+}
+
+impl World {
+    // The living cells of both worlds, with the rule, the step size and the
+    // generation of `self`, as for the other operations below. The result
+    // is in the same store as `self`, if any.
+    pub fn bitor(&self, other: &World) -> World {
+        self.combine(other, BoolOp::Or)
+    }
+
+    // The cells that are alive in both worlds.
+    pub fn bitand(&self, other: &World) -> World {
+        self.combine(other, BoolOp::And)
+    }
+
+    // The cells that are alive in exactly one of the worlds.
+    pub fn bitxor(&self, other: &World) -> World {
+        self.combine(other, BoolOp::Xor)
+    }
+
+    // The cells of `self` that are dead in `other`.
+    pub fn difference(&self, other: &World) -> World {
+        self.combine(other, BoolOp::AndNot)
+    }
+
+    fn combine(&self, other: &World, op: BoolOp) -> World {
+        let mut world = match self.store() {
+            Some(store) => World::new_in(store),
+            None => World::new(self.rule.clone()),
+        };
+        world.rule = self.rule.clone();
+        world.step = self.step;
+        world.generation = self.generation;
+        let (a, b) = (world.import(self), world.import(other));
+        world.with_nodes(|world| {
+            let [a, b] = world.align_levels([a, b]);
+            world.root = world.combine_rec(a, b, op, &mut FxHashMap::default());
+        });
+        world
+    }
+
+    // Expands the nodes to the level of the highest one, keeping their
+    // centers, as for roots.
+    pub(crate) fn align_levels<const N: usize>(&mut self, mut nodes: [Node; N]) -> [Node; N] {
+        let level = nodes.iter().map(|&node| self.node_level(node)).max();
+        for node in nodes.iter_mut() {
+            while Some(self.node_level(*node)) < level {
+                *node = self.expand_node(*node);
+            }
+        }
+        nodes
+    }
+
+    // The root of `other`, with its nodes copied into the nodes of `self`
+    // unless they are already there.
+    pub(crate) fn import(&mut self, other: &World) -> Node {
+        let same_store = match (self.store(), other.store()) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        };
+        if same_store {
+            return other.root;
+        }
+        let nodes = other.nodes();
+        self.with_nodes(|world| world.import_rec(&nodes, other.root, &mut FxHashMap::default()))
+    }
+
+    fn import_rec(
+        &mut self,
+        nodes: &Nodes,
+        node: Node,
+        memo: &mut FxHashMap<NodeId, Node>,
+    ) -> Node {
+        let id = match node {
+            Node::Leaf(_) => return node,
+            Node::NodeId(id) => id,
+        };
+        if let Some(&node) = memo.get(&id) {
+            return node;
+        }
+        let data = &nodes[id];
+        let children = [data.nw(), data.ne(), data.sw(), data.se()];
+        let [nw, ne, sw, se] = children.map(|child| self.import_rec(nodes, child, memo));
+        let node = Node::NodeId(self.find_node(nw, ne, sw, se));
+        memo.insert(id, node);
+        node
+    }
+
+    // Both nodes have the same level. Shared and empty subtrees are not
+    // visited, and the pairs of nodes that occur more than once are only
+    // combined once.
+    pub(crate) fn combine_rec(
+        &mut self,
+        a: Node,
+        b: Node,
+        op: BoolOp,
+        memo: &mut FxHashMap<(Node, Node), Node>,
+    ) -> Node {
+        let empty = |world: &mut World| {
+            let level = world.node_level(a);
+            world.empty_node(level)
+        };
+        if a == b {
+            return match op {
+                BoolOp::Or | BoolOp::And => a,
+                BoolOp::Xor | BoolOp::AndNot => empty(self),
+            };
+        }
+        if self.node_population(b) == 0 {
+            return match op {
+                BoolOp::Or | BoolOp::Xor | BoolOp::AndNot => a,
+                BoolOp::And => b,
+            };
+        }
+        if self.node_population(a) == 0 {
+            return match op {
+                BoolOp::Or | BoolOp::Xor => b,
+                BoolOp::And | BoolOp::AndNot => a,
+            };
+        }
+        let (a, b) = match (a, b) {
+            (Node::Leaf(a), Node::Leaf(b)) => return Node::Leaf(op.leaf(a, b)),
+            (Node::NodeId(a), Node::NodeId(b)) => (a, b),
+            _ => unreachable!("Nodes of the same level are both leaves or neither."),
+        };
+        if let Some(&node) = memo.get(&(Node::NodeId(a), Node::NodeId(b))) {
+            return node;
+        }
+        let children = |id: NodeId| {
+            let data = &self[id];
+            [data.nw(), data.ne(), data.sw(), data.se()]
+        };
+        let (nodes_a, nodes_b) = (children(a), children(b));
+        let [nw, ne, sw, se] =
+            [0, 1, 2, 3].map(|i| self.combine_rec(nodes_a[i], nodes_b[i], op, memo));
+        let node = Node::NodeId(self.find_node(nw, ne, sw, se));
+        memo.insert((Node::NodeId(a), Node::NodeId(b)), node);
+        node
+    }
+}
+
+impl BitOr for &World {
+    type Output = World;
+
+    fn bitor(self, other: &World) -> World {
+        World::bitor(self, other)
+    }
+}
+
+impl BitAnd for &World {
+    type Output = World;
+
+    fn bitand(self, other: &World) -> World {
+        World::bitand(self, other)
+    }
+}
+
+impl BitXor for &World {
+    type Output = World;
+
+    fn bitxor(self, other: &World) -> World {
+        World::bitxor(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::NodeStore;
+    use std::collections::BTreeSet;
+
+    // About half of the cells of a square of the given size, at the offset.
+    fn random_world(seed: &mut u64, size: u64, offset: i64) -> World {
+        let mut world = World::default();
+        for y in 0..size as i64 {
+            for x in 0..size as i64 {
+                *seed ^= *seed << 13;
+                *seed ^= *seed >> 7;
+                *seed ^= *seed << 17;
+                if *seed & 1 != 0 {
+                    world.set_cell(x + offset, y + offset, true);
+                }
+            }
+        }
+        world
+    }
+
+    fn cells(world: &World) -> BTreeSet<(i64, i64)> {
+        world.cells_vec().into_iter().collect()
+    }
+
+    #[test]
+    fn test_boolean_ops() {
+        let mut seed = 1;
+        for i in 0..20 {
+            // Different sizes and offsets, so that the roots have different
+            // levels.
+            let a = random_world(&mut seed, 4 + i, -(i as i64));
+            let b = random_world(&mut seed, 3 + 2 * i, i as i64 / 2);
+            let (cells_a, cells_b) = (cells(&a), cells(&b));
+            assert_eq!(cells(&a.bitor(&b)), &cells_a | &cells_b);
+            assert_eq!(cells(&(&a | &b)), &cells_a | &cells_b);
+            assert_eq!(cells(&(&a & &b)), &cells_a & &cells_b);
+            assert_eq!(cells(&(&a ^ &b)), &cells_a ^ &cells_b);
+            assert_eq!(cells(&a.difference(&b)), &cells_a - &cells_b);
+            assert_eq!(cells(&a.difference(&a)), BTreeSet::new());
+            assert_eq!(cells(&(&a & &World::default())), BTreeSet::new());
+        }
+    }
+
+    #[test]
+    fn test_boolean_ops_in_store() {
+        let store = Arc::new(NodeStore::new(Default::default()));
+        let mut seed = 7;
+        let mut a = World::new_in(&store);
+        a.paste(&random_world(&mut seed, 16, 0), (0, 0));
+        let mut b = World::new_in(&store);
+        b.paste(&random_world(&mut seed, 16, 8), (0, 0));
+        b.set_generation(5);
+        let (cells_a, cells_b) = (cells(&a), cells(&b));
+        let xor = &a ^ &b;
+        assert_eq!(cells(&xor), &cells_a ^ &cells_b);
+        assert_eq!(xor.get_generation(), 0);
+        // A world with its own nodes is copied into the store.
+        let own = random_world(&mut seed, 10, -3);
+        assert_eq!(cells(&(&a | &own)), &cells_a | &cells(&own));
+        assert_eq!(cells(&(&own & &b)), &cells(&own) & &cells_b);
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{components::Connectivity, world::World};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+// The usual objects of Life ash: apgcode, name, plural.
+const NAMES: &[(&str, &str, &str)] = &[
+    ("xs4_33", "block", "blocks"),
+    ("xs6_696", "beehive", "beehives"),
+    ("xs7_2596", "loaf", "loaves"),
+    ("xs5_253", "boat", "boats"),
+    ("xs6_356", "ship", "ships"),
+    ("xs4_252", "tub", "tubs"),
+    ("xs8_6996", "pond", "ponds"),
+    ("xs7_25ac", "long boat", "long boats"),
+    ("xs6_25a4", "barge", "barges"),
+    ("xs6_bd", "snake", "snakes"),
+    ("xs6_39c", "aircraft carrier", "aircraft carriers"),
+    ("xs7_178c", "eater 1", "eater 1s"),
+    ("xs8_69ic", "mango", "mangoes"),
+    ("xs8_35ac", "long ship", "long ships"),
+    ("xs9_31ego", "integral sign", "integral signs"),
+    ("xp2_7", "blinker", "blinkers"),
+    ("xp2_7e", "toad", "toads"),
+    ("xp2_318c", "beacon", "beacons"),
+    (
+        "xp3_co9nas0san9oczgoldlo0oldlogz1047210127401",
+        "pulsar",
+        "pulsars",
+    ),
+    ("xq4_153", "glider", "gliders"),
+    (
+        "xq4_6frc",
+        "lightweight spaceship",
+        "lightweight spaceships",
+    ),
+    (
+        "xq4_27dee6",
+        "middleweight spaceship",
+        "middleweight spaceships",
+    ),
+    (
+        "xq4_27deee6",
+        "heavyweight spaceship",
+        "heavyweight spaceships",
+    ),
+];
+
+// The common name of an object, e.g. `glider` for `xq4_153`.
+pub fn object_name(apgcode: &str) -> Option<&'static str> {
+    NAMES
+        .iter()
+        .find(|&&(code, _, _)| code == apgcode)
+        .map(|&(_, name, _)| name)
+}
+
+// The objects of a pattern, see `World::census`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Census {
+    // The number of objects of each apgcode.
+    pub objects: BTreeMap<String, u64>,
+    // Objects that are larger than the limit, or that are not still lifes,
+    // oscillators or spaceships, e.g. ash that is still active.
+    pub unidentified: u64,
+}
+
+impl Census {
+    fn add(&mut self, apgcode: Option<String>) {
+        match apgcode {
+            Some(apgcode) => *self.objects.entry(apgcode).or_insert(0) += 1,
+            None => self.unidentified += 1,
+        }
+    }
+
+    pub fn count(&self, apgcode: &str) -> u64 {
+        self.objects.get(apgcode).copied().unwrap_or(0)
+    }
+}
+
+// The most common objects first, then by apgcode, e.g.
+// `112 blocks, 63 blinkers, 1 glider`.
+// Objects without a name are written as their apgcode.
+impl Display for Census {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut objects = self.objects.iter().collect::<Vec<_>>();
+        objects.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let mut first = true;
+        for (apgcode, &count) in objects {
+            let name = NAMES.iter().find(|&&(code, _, _)| code == apgcode);
+            let name = match name {
+                Some(&(_, name, _)) if count == 1 => name,
+                Some(&(_, _, plural)) => plural,
+                None => apgcode,
+            };
+            write!(f, "{}{} {}", if first { "" } else { ", " }, count, name)?;
+            first = false;
+        }
+        if self.unidentified > 0 {
+            write!(
+                f,
+                "{}{} unidentified",
+                if first { "" } else { ", " },
+                self.unidentified
+            )?;
+        } else if first {
+            write!(f, "empty")?;
+        }
+        Ok(())
+    }
+}
+
+// Whether the parts of a pattern of the given period evolve as if they were
+// alone, i.e., they are separate objects that are only close to each other.
+fn independent(world: &World, parts: &[World], period: u64) -> bool {
+    let fresh = |world: &World| {
+        let mut fresh = World::new(world.rule.clone());
+        fresh.set_cells(world.cells_vec(), true);
+        fresh
+    };
+    let mut whole = fresh(world);
+    let mut parts = parts.iter().map(fresh).collect::<Vec<_>>();
+    for _ in 0..period {
+        whole.step();
+        let mut cells = Vec::new();
+        for part in &mut parts {
+            part.step();
+            cells.extend(part.cells_vec());
+        }
+        cells.sort_unstable();
+        let mut expected = whole.cells_vec();
+        expected.sort_unstable();
+        if cells != expected {
+            return false;
+        }
+    }
+    true
+}
+
+impl World {
+    // Counts the objects of the pattern, by their apgcodes, e.g. of the ash
+    // of a methuselah that has stabilized. Escaping gliders and other
+    // spaceships are counted wherever they are.
+    //
+    // The pattern is split into components with `Connectivity::Gap`, so that
+    // oscillators made of separate parts, such as the pulsar, are one object.
+    // A component is split again with `Connectivity::Moore` when it is not
+    // recognized, e.g. a glider leaving a block, or when its parts evolve
+    // independently, e.g. a block next to a beehive. Components with more
+    // than `max_object_size` cells are not run, and counted as unidentified.
+    pub fn census(&self, max_object_size: u64) -> Census {
+        let mut census = Census::default();
+        for component in self.components(Connectivity::Gap) {
+            if component.population() > max_object_size {
+                census.unidentified += 1;
+                continue;
+            }
+            let apgcode = component.to_apgcode();
+            let parts = component.components(Connectivity::Moore);
+            let split = match &apgcode {
+                Some(apgcode) => {
+                    parts.len() > 1 && independent(&component, &parts, period(apgcode))
+                }
+                None => true,
+            };
+            if !split {
+                census.add(apgcode);
+                continue;
+            }
+            for part in parts {
+                census.add(part.to_apgcode());
+            }
+        }
+        census
+    }
+}
+
+// The period in an `xs`, `xp` or `xq` apgcode.
+fn period(apgcode: &str) -> u64 {
+    if apgcode.starts_with("xs") {
+        return 1;
+    }
+    let (prefix, _) = apgcode.split_once('_').unwrap();
+    prefix[2..].parse().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_census() {
+        let mut world = World::default();
+        for (rle, x, y) in [
+            ("2o$2o!", 0, 0),
+            ("2o$2o!", 20, 0),
+            ("2o$2o!", 40, 0),
+            ("3o!", 0, 20),
+            ("bo$2bo$3o!", 20, 20),
+            // A beehive, and a block one cell away.
+            ("b2o$o2bo$b2o$5b2o$5b2o!", 40, 20),
+            // A domino, which dies.
+            ("2o!", 0, 40),
+            (
+                "2b3o3b3o2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2$2b3o3b3o$o4bobo4bo$\
+                 o4bobo4bo$o4bobo4bo2$2b3o3b3o!",
+                20,
+                40,
+            ),
+        ] {
+            world.paste(&World::from_bytes(rle.as_bytes()).unwrap(), (x, y));
+        }
+        let census = world.census(100);
+        assert_eq!(census.count("xs4_33"), 4);
+        assert_eq!(census.count("xs6_696"), 1);
+        assert_eq!(census.count("xp2_7"), 1);
+        assert_eq!(census.count("xq4_153"), 1);
+        assert_eq!(census.unidentified, 1);
+        assert_eq!(
+            census.to_string(),
+            "4 blocks, 1 blinker, 1 pulsar, 1 glider, 1 beehive, 1 unidentified"
+        );
+        assert_eq!(object_name("xq4_153"), Some("glider"));
+
+        // The beehive and the block next to it are one component of 10 cells.
+        let census = world.census(4);
+        assert_eq!(census.count("xs4_33"), 3);
+        assert_eq!(census.count("xp2_7"), 1);
+        assert_eq!(census.unidentified, 4);
+        assert_eq!(World::default().census(100).to_string(), "empty");
+    }
+}
@@ -0,0 +1,194 @@
+use crate::world::World;
+use rustc_hash::FxHashMap;
+
+// Which living cells belong to the same component.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connectivity {
+    // Cells that touch, even diagonally.
+    Moore,
+    // Cells at most 2 apart horizontally and vertically, i.e., with at most
+    // one empty cell between them, as in Golly's object tools. A blinker
+    // next to a block at that distance is one object.
+    Gap,
+}
+
+impl Connectivity {
+    fn radius(self) -> usize {
+        match self {
+            Connectivity::Moore => 1,
+            Connectivity::Gap => 2,
+        }
+    }
+}
+
+// The 3x3 leaves around a leaf, as 12 rows of 12 bits, whose highest bit is
+// the leftmost cell. The leaf itself is in the middle.
+type Block = [u16; 12];
+
+// Places a leaf at the block position `(i, j)`, each from 0 to 2.
+fn place(leaf: u16, i: usize, j: usize) -> Block {
+    let mut block = [0; 12];
+    for y in 0..4 {
+        block[4 * j + y] = (leaf >> (12 - 4 * y) & 0xf) << (4 * (2 - i));
+    }
+    block
+}
+
+fn extract(block: &Block, i: usize, j: usize) -> u16 {
+    (0..4).fold(0, |leaf, y| {
+        leaf | (block[4 * j + y] >> (4 * (2 - i)) & 0xf) << (12 - 4 * y)
+    })
+}
+
+// The cells at most `radius` apart from the living cells, horizontally and
+// vertically.
+fn dilate(block: &Block, radius: usize) -> Block {
+    let mut rows = *block;
+    for row in &mut rows {
+        let original = *row;
+        for k in 1..=radius {
+            *row |= original << k | original >> k;
+        }
+        *row &= 0xfff;
+    }
+    let mut dilated = [0; 12];
+    for (y, row) in dilated.iter_mut().enumerate() {
+        let (start, end) = (y.saturating_sub(radius), (y + radius).min(11));
+        *row = rows[start..=end].iter().fold(0, |row, &other| row | other);
+    }
+    dilated
+}
+
+// Splits the living cells of a leaf into the parts that are connected inside
+// the leaf.
+fn split_leaf(leaf: u16, radius: usize) -> Vec<u16> {
+    let mut parts = Vec::new();
+    let mut rest = leaf;
+    while rest != 0 {
+        let mut part = rest & rest.wrapping_neg();
+        loop {
+            let grown = extract(&dilate(&place(part, 1, 1), radius), 1, 1) & leaf;
+            if grown == part {
+                break;
+            }
+            part = grown;
+        }
+        parts.push(part);
+        rest &= !part;
+    }
+    parts
+}
+
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+impl World {
+    // Splits the living cells into connected components, each in its own
+    // world with the same rule and generation, at the same positions. The
+    // components are ordered by their topmost, then leftmost cell.
+    //
+    // The cells are first split inside each 4x4 leaf, and then the parts are
+    // joined with those of the neighboring leaves, so every cell is only
+    // visited as part of a bit mask.
+    pub fn components(&self, connectivity: Connectivity) -> Vec<World> {
+        let radius = connectivity.radius();
+        let bound = match self.bound() {
+            Some(bound) => bound,
+            None => return Vec::new(),
+        };
+        // The parts of each leaf, as the range of their indices in `parts`.
+        let mut leaves = FxHashMap::default();
+        let mut parts = Vec::new();
+        self.for_leaves(bound, |x, y, leaf| {
+            let start = parts.len();
+            parts.extend(
+                split_leaf(leaf, radius)
+                    .into_iter()
+                    .map(|part| (x, y, part)),
+            );
+            leaves.insert((x >> 2, y >> 2), start..parts.len());
+        });
+
+        let mut parents = (0..parts.len()).collect::<Vec<_>>();
+        for (i, &(x, y, part)) in parts.iter().enumerate() {
+            let dilated = dilate(&place(part, 1, 1), radius);
+            for j in 0..3 {
+                for k in 0..3 {
+                    let near = extract(&dilated, k, j);
+                    let key = ((x >> 2) + k as i64 - 1, (y >> 2) + j as i64 - 1);
+                    if near == 0 || (j, k) == (1, 1) {
+                        continue;
+                    }
+                    if let Some(range) = leaves.get(&key) {
+                        for other in range.clone().filter(|&other| parts[other].2 & near != 0) {
+                            let (a, b) = (find(&mut parents, i), find(&mut parents, other));
+                            parents[a.max(b)] = a.min(b);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut components = Vec::new();
+        let mut indices = FxHashMap::default();
+        let mut cells = Vec::new();
+        for (i, &(x, y, part)) in parts.iter().enumerate() {
+            let root = find(&mut parents, i);
+            let index = *indices.entry(root).or_insert_with(|| {
+                cells.push(Vec::new());
+                cells.len() - 1
+            });
+            for bit in 0..16 {
+                if part & 0x8000 >> bit != 0 {
+                    cells[index].push((x + bit % 4, y + bit / 4));
+                }
+            }
+        }
+        // By the topmost, then leftmost cell.
+        cells.sort_unstable_by_key(|cells| cells.iter().map(|&(x, y)| (y, x)).min());
+        for cells in cells {
+            let mut world = World::new_with_step(self.rule.clone(), self.step);
+            world.generation = self.generation;
+            world.set_cells(cells, true);
+            components.push(world);
+        }
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_components() {
+        // A block, a glider and a blinker, far apart.
+        let rle = "2o$2o10$20bo$21bo$19b3o10$40b3o!";
+        let world = World::from_bytes(rle.as_bytes()).unwrap();
+        let populations = |connectivity| {
+            let mut populations = world
+                .components(connectivity)
+                .iter()
+                .map(World::population)
+                .collect::<Vec<_>>();
+            populations.sort_unstable();
+            populations
+        };
+        assert_eq!(populations(Connectivity::Moore), [3, 4, 5]);
+        assert_eq!(populations(Connectivity::Gap), [3, 4, 5]);
+
+        // A blinker with one empty cell between it and a block.
+        let world = World::from_bytes(b"2o$2o$3b3o!").unwrap();
+        let components = world.components(Connectivity::Moore);
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0].bound(), Some((0, 2, 0, 2)));
+        assert_eq!(components[1].bound(), Some((3, 6, 2, 3)));
+        assert_eq!(world.components(Connectivity::Gap).len(), 1);
+        assert!(World::default().components(Connectivity::Moore).is_empty());
+    }
+}
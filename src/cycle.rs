@@ -0,0 +1,164 @@
+use crate::{
+    snapshot::Snapshot,
+    world::{Node, World},
+};
+
+impl World {
+    // Shrinks the root to the smallest centered node of level at least 3
+    // that contains all the cells, so that equal patterns have equal roots.
+    fn canonicalize_root(&mut self) {
+        self.with_nodes(|world| {
+            if let Node::Leaf(_) = world.root {
+                world.expand();
+            }
+            while let Node::NodeId(id) = world.root {
+                let data = &world[id];
+                if data.level() <= 3 {
+                    break;
+                }
+                let population = world.nodes.population(id);
+                let children = [data.nw(), data.ne(), data.sw(), data.se()];
+                let [nw, ne, sw, se] = children.map(|child| match child {
+                    Node::NodeId(id) => &world[id],
+                    Node::Leaf(_) => unreachable!(),
+                });
+                let center = [nw.se(), ne.sw(), sw.ne(), se.nw()];
+                let inner: u64 = center.iter().map(|&node| world.node_population(node)).sum();
+                if inner != population {
+                    break;
+                }
+                let [nw, ne, sw, se] = center;
+                world.root = Node::NodeId(world.find_node(nw, ne, sw, se));
+            }
+        });
+    }
+
+    // A step of one generation, after which the root is canonical.
+    fn cycle_step(&mut self) -> bool {
+        let completed = self.try_step().is_ok();
+        self.canonicalize_root();
+        completed
+    }
+
+    // Replaces the snapshot with one of the state a generation later.
+    fn advance_snapshot(&mut self, snapshot: &mut Snapshot) -> bool {
+        self.restore(*snapshot);
+        let completed = self.cycle_step();
+        self.release(*snapshot);
+        *snapshot = self.snapshot();
+        completed
+    }
+
+    // Runs the whole universe one generation at a time for at most
+    // `max_steps` generations, and returns `(preperiod, period)` if it comes
+    // back to an earlier state, i.e., the same cells at the same positions.
+    // A pattern with a spaceship never does. Since the nodes are
+    // hash-consed, comparing two states is comparing two roots, once they
+    // are shrunk to a canonical size.
+    //
+    // Brent's algorithm only keeps two states, pinned as snapshots, so the
+    // memory does not grow with the period. The pattern, the generation, the
+    // step size and the histories are restored afterwards. Returns `None` if
+    // no cycle is found, or if interrupted by the cancel flag.
+    pub fn detect_global_cycle(&mut self, max_steps: u64) -> Option<(u64, u64)> {
+        self.with_trackers_suspended(|world| {
+            world.set_step(0);
+            world.canonicalize_root();
+            let start = world.snapshot();
+            let result = world.brent(start, max_steps);
+            world.release(start);
+            result
+        })
+    }
+
+    fn brent(&mut self, start: Snapshot, max_steps: u64) -> Option<(u64, u64)> {
+        // The period: the hare is compared with the tortoise, which jumps to
+        // the hare whenever the number of steps since reaches a power of 2.
+        let mut tortoise = self.snapshot();
+        let (mut power, mut period, mut steps) = (1u64, 0u64, 0u64);
+        let mut found = false;
+        while steps < max_steps {
+            if !self.cycle_step() {
+                break;
+            }
+            steps += 1;
+            period += 1;
+            if Some(self.root) == self.snapshot_root(tortoise) {
+                found = true;
+                break;
+            }
+            if period == power {
+                self.release(tortoise);
+                tortoise = self.snapshot();
+                power *= 2;
+                period = 0;
+            }
+        }
+        self.release(tortoise);
+        if !found {
+            return None;
+        }
+
+        // The preperiod: the first state that is the same as the one a period
+        // later. Both are advanced together, switching between them.
+        self.restore(start);
+        let mut first = self.snapshot();
+        for _ in 0..period {
+            if !self.cycle_step() {
+                self.release(first);
+                return None;
+            }
+        }
+        let mut second = self.snapshot();
+        let mut preperiod = 0;
+        let result = loop {
+            if self.snapshot_root(first) == self.snapshot_root(second) {
+                break Some((preperiod, period));
+            }
+            if !self.advance_snapshot(&mut first) || !self.advance_snapshot(&mut second) {
+                break None;
+            }
+            preperiod += 1;
+        };
+        self.release(first);
+        self.release(second);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle(rle: &str, max_steps: u64) -> Option<(u64, u64)> {
+        let mut world = World::from_bytes(rle.as_bytes()).unwrap();
+        world.set_step(2).set_history_capacity(4);
+        let cells = world.cells_vec();
+        let cycle = world.detect_global_cycle(max_steps);
+        assert_eq!(world.get_generation(), 0);
+        assert_eq!(world.get_step(), 2);
+        assert_eq!(world.history_len(), 0);
+        assert_eq!(world.cells_vec(), cells);
+        cycle
+    }
+
+    #[test]
+    fn test_detect_global_cycle() {
+        assert_eq!(cycle("2o$2o!", 10), Some((0, 1)));
+        assert_eq!(cycle("3o!", 10), Some((0, 2)));
+        // Becomes a block, and a domino that dies.
+        assert_eq!(cycle("2o$o!", 10), Some((1, 1)));
+        assert_eq!(cycle("2o!", 10), Some((1, 1)));
+        // A block and a pulsar.
+        assert_eq!(
+            cycle(
+                "2o$2o4$2b3o3b3o2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2$2b3o3b3o$\
+                 o4bobo4bo$o4bobo4bo$o4bobo4bo2$2b3o3b3o!",
+                10
+            ),
+            Some((0, 3))
+        );
+        assert_eq!(cycle("bo$2bo$3o!", 1000), None);
+        assert_eq!(cycle("3o!", 1), None);
+    }
+}
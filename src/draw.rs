@@ -0,0 +1,417 @@
+use crate::{
+    recording::Op,
+    world::{Node, World},
+};
+use rustc_hash::FxHashMap;
+
+// Coordinates are computed in `i128`, so that lines and circles whose sizes
+// do not fit in `i64` are drawn too, and then clamped to the universe.
+fn clamp(coordinate: i128) -> i64 {
+    coordinate.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+// The runs of cells of a line that advances by `major >= 0` cells along its
+// longer axis while it advances by `minor` cells, with `0 <= minor <= major`,
+// along the other one, as `(k, start, end)`: the cells from `start` to `end`
+// (exclusive) along the longer axis are `k` cells away along the other one.
+// The cell `i` is `i * minor / major` away, rounded half up, so the run `k`
+// starts at `ceil((2k - 1) * major / (2 * minor))`, which is computed by
+// adding `2 * major` at each run to keep the numbers small.
+fn line_runs(major: i128, minor: i128) -> Vec<(i128, i128, i128)> {
+    if minor == 0 {
+        return vec![(0, 0, major + 1)];
+    }
+    let divisor = 2 * minor;
+    let (step, step_rem) = (2 * major / divisor, 2 * major % divisor);
+    let (mut quotient, mut rem) = (major / divisor, major % divisor);
+    let mut runs = Vec::with_capacity(minor as usize + 1);
+    let mut start = 0;
+    for k in 0..=minor {
+        let end = if k == minor {
+            major + 1
+        } else {
+            quotient + (rem > 0) as i128
+        };
+        runs.push((k, start, end));
+        start = end;
+        quotient += step;
+        rem += step_rem;
+        if rem >= divisor {
+            quotient += 1;
+            rem -= divisor;
+        }
+    }
+    runs
+}
+
+// The largest `w` with `w^2 + dy^2 <= r^2`, or -1 if there is none.
+fn half_width(r: i128, dy: i128) -> i128 {
+    if dy.abs() > r {
+        return -1;
+    }
+    let mut w = ((r * r - dy * dy) as f64).sqrt() as i128;
+    while w * w + dy * dy > r * r {
+        w -= 1;
+    }
+    while (w + 1) * (w + 1) + dy * dy <= r * r {
+        w += 1;
+    }
+    w
+}
+
+impl World {
+    // Draws a line with Bresenham's algorithm, including both endpoints. The
+    // cells do not depend on which endpoint comes first, and are filled a run
+    // at a time, so a long horizontal or vertical line is cheap whatever its
+    // length.
+    pub fn draw_line(&mut self, from: (i64, i64), to: (i64, i64), state: bool) -> &mut Self {
+        let (dx, dy) = (to.0 as i128 - from.0 as i128, to.1 as i128 - from.1 as i128);
+        let steep = dy.abs() > dx.abs();
+        // Along the longer axis first, from the smaller end.
+        let swap = |(x, y): (i64, i64)| {
+            if steep {
+                (y as i128, x as i128)
+            } else {
+                (x as i128, y as i128)
+            }
+        };
+        let (mut start, mut end) = (swap(from), swap(to));
+        if start.0 > end.0 {
+            std::mem::swap(&mut start, &mut end);
+        }
+        let minor = end.1 - start.1;
+        let runs = line_runs(end.0 - start.0, minor.abs()).into_iter();
+        let bounds = runs.map(|(k, first, last)| {
+            let (low, high) = (clamp(start.0 + first), clamp(start.0 + last));
+            let other = start.1 + minor.signum() * k;
+            let (near, far) = (clamp(other), clamp(other + 1));
+            if steep {
+                (near, far, low, high)
+            } else {
+                (low, high, near, far)
+            }
+        });
+        self.apply(Op::FillRegions(bounds.collect(), state));
+        self
+    }
+
+    // Pastes the living cells of another world, translated by the offset.
+    // Cells that are already alive stay alive.
+    pub fn paste(&mut self, other: &World, offset: (i64, i64)) -> &mut Self {
+        self.apply(Op::Paste(other.living_cells_all().collect(), offset));
+        self
+    }
+
+    // Bound: (left, right, top, bottom).
+    // Returns a new world with the same rule and generation, containing only
+    // the living cells inside the bound, at the same positions.
+    pub fn extract(&self, bound: (i64, i64, i64, i64)) -> World {
+        let mut world = World::new_with_step(self.rule.clone(), self.step);
+        world.generation = self.generation;
+        let mut cells = Vec::new();
+        self.for_living_cells_ordered(bound, |x, y| cells.push((x, y)));
+        world.set_cells(cells, true);
+        world
+    }
+
+    // Moves every living cell by the offset. The cells that would leave the
+    // universe are lost.
+    pub fn translate(&mut self, offset: (i64, i64)) -> &mut Self {
+        self.apply(Op::Translate(offset));
+        self
+    }
+
+    // The quadtree is moved as a whole: the new root is read from the old one
+    // at an offset, see `World::shift_rec`, so that the cells are never
+    // listed.
+    pub(crate) fn translate_unrecorded(&mut self, offset: (i64, i64)) {
+        let (left, right, top, bottom) = match self.bound() {
+            Some(bound) if offset != (0, 0) => bound,
+            _ => return,
+        };
+        let (dx, dy) = (offset.0 as i128, offset.1 as i128);
+        // The moved pattern, and the offset, fit in a root of this level.
+        let fits = |half: i128| {
+            dx.abs() <= half
+                && dy.abs() <= half
+                && -half <= left as i128 + dx
+                && right as i128 + dx <= half
+                && -half <= top as i128 + dy
+                && bottom as i128 + dy <= half
+        };
+        let mut level = self.node_level(self.root);
+        while level < 64 && !fits(1 << (level - 1)) {
+            level += 1;
+        }
+        self.with_nodes(|world| {
+            // The quarters of the expanded root are the size of the new root,
+            // and the new root starts `half - offset` from their corner.
+            world.expand_to_level(level + 1);
+            let data = match world.root {
+                Node::NodeId(id) => &world[id],
+                Node::Leaf(_) => unreachable!("The root cannot be a leaf after expansion."),
+            };
+            let quarters = [data.nw(), data.ne(), data.sw(), data.se()];
+            let half = 1 << (level - 1);
+            let memo = &mut FxHashMap::default();
+            world.root = world.shift_rec(quarters, (half - dx, half - dy), memo);
+        });
+    }
+
+    // Bound: (left, right, top, bottom).
+    // Kills every cell inside the bound.
+    pub fn clear_region(&mut self, bound: (i64, i64, i64, i64)) -> &mut Self {
+        self.apply(Op::ClearRegion(bound));
+        self
+    }
+
+    pub(crate) fn clear_region_unrecorded(&mut self, bound: (i64, i64, i64, i64)) {
+        if let Some((left, right, top, bottom)) = self.bound() {
+            let bound = (
+                bound.0.max(left),
+                bound.1.min(right),
+                bound.2.max(top),
+                bound.3.min(bottom),
+            );
+            self.fill_region(bound, false);
+        }
+    }
+
+    // Bound: (left, right, top, bottom).
+    pub fn draw_rect(
+        &mut self,
+        bound: (i64, i64, i64, i64),
+        filled: bool,
+        state: bool,
+    ) -> &mut Self {
+        let (left, right, top, bottom) = bound;
+        let width = right as i128 - left as i128;
+        let height = bottom as i128 - top as i128;
+        let bounds = if filled || width <= 2 || height <= 2 {
+            vec![bound]
+        } else {
+            vec![
+                (left, right, top, top + 1),
+                (left, right, bottom - 1, bottom),
+                (left, left + 1, top + 1, bottom - 1),
+                (right - 1, right, top + 1, bottom - 1),
+            ]
+        };
+        self.apply(Op::FillRegions(bounds, state));
+        self
+    }
+
+    // The disk consists of the cells `(x, y)` with `x^2 + y^2 <= radius^2`,
+    // relative to the center. The circle consists of the cells of the disk
+    // with at least one orthogonal neighbor outside of the disk.
+    pub fn draw_circle(
+        &mut self,
+        center: (i64, i64),
+        radius: u32,
+        filled: bool,
+        state: bool,
+    ) -> &mut Self {
+        let (cx, cy) = (center.0 as i128, center.1 as i128);
+        let r = radius as i128;
+        let row = |dy: i128, left: i128, right: i128| {
+            (
+                clamp(cx + left),
+                clamp(cx + right),
+                clamp(cy + dy),
+                clamp(cy + dy + 1),
+            )
+        };
+        let mut bounds = Vec::new();
+        for dy in -r..=r {
+            let w = half_width(r, dy);
+            if filled {
+                bounds.push(row(dy, -w, w + 1));
+                continue;
+            }
+            // The cells with `inner < |dx| <= w`, and at least `dx = ±w`.
+            let inner = half_width(r, dy - 1).min(half_width(r, dy + 1));
+            let min = (inner + 1).min(w);
+            if min == 0 {
+                bounds.push(row(dy, -w, w + 1));
+            } else {
+                bounds.push(row(dy, -w, -min + 1));
+                bounds.push(row(dy, min, w + 1));
+            }
+        }
+        self.apply(Op::FillRegions(bounds, state));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_line() {
+        let mut world = World::default();
+        world.draw_line((0, 0), (9999, 0), true);
+        assert_eq!(world.population(), 10000);
+        assert_eq!(world.bound(), Some((0, 10000, 0, 1)));
+        let mut world = World::default();
+        world.draw_line((5, 3), (0, 0), true);
+        assert_eq!(world.population(), 6);
+        world.draw_line((0, 0), (5, 3), false);
+        assert_eq!(world.population(), 0);
+        let mut world = World::default();
+        world.draw_line((-7, 20), (-7, -20), true);
+        assert_eq!(world.population(), 41);
+
+        // Every line is 8-connected, with one cell per column or row along
+        // its longer axis, whichever endpoint comes first.
+        for &(from, to) in &[((0, 0), (7, 2)), ((3, -1), (-4, 6)), ((2, 9), (-1, -8))] {
+            let mut world = World::default();
+            world.draw_line(from, to, true);
+            let length = (to.0 - from.0).abs().max((to.1 - from.1).abs());
+            assert_eq!(world.population(), length as u64 + 1);
+            assert!(world.get_cell(from.0, from.1) && world.get_cell(to.0, to.1));
+            world.draw_line(to, from, false);
+            assert_eq!(world.population(), 0);
+        }
+    }
+
+    #[test]
+    fn test_draw_line_far() {
+        let (left, right) = (i64::MIN / 2, i64::MAX / 2);
+        let mut world = World::default();
+        world.draw_line((left, 0), (right, 0), true);
+        assert_eq!(world.population(), 1 << 63);
+        assert!(world.get_cell(left, 0) && world.get_cell(right, 0));
+        assert!(!world.get_cell(left - 1, 0) && !world.get_cell(right + 1, 0));
+
+        let far = 1 << 40;
+        let mut world = World::default();
+        world.draw_line((5, far), (8, -far), true);
+        assert_eq!(world.population(), (2 * far + 1) as u64);
+        assert!(world.get_cell(5, far) && world.get_cell(8, -far));
+        world.draw_line((8, -far), (5, far), false);
+        assert_eq!(world.population(), 0);
+    }
+
+    #[test]
+    fn test_draw_rect() {
+        let mut world = World::default();
+        world.draw_rect((-3, 7, 10, 15), true, true);
+        assert_eq!(world.population(), 50);
+        world.draw_rect((-2, 6, 11, 14), true, false);
+        assert_eq!(world.population(), 26);
+        let mut outline = World::default();
+        outline.draw_rect((-3, 7, 10, 15), false, true);
+        assert_eq!(outline.population(), 26);
+        assert_eq!(outline.bound(), world.bound());
+        let mut world = World::default();
+        world.draw_rect((-1000, 1000, -1000, 1000), true, true);
+        assert_eq!(world.population(), 4_000_000);
+    }
+
+    #[test]
+    fn test_draw_rect_far() {
+        let (left, right) = (i64::MIN / 2 - 10, i64::MAX / 2 + 10);
+        let mut world = World::default();
+        world.draw_rect((left, right, -3, 4), false, true);
+        world.draw_rect((left, right, -3, -2), true, false);
+        world.draw_rect((left, right, 3, 4), true, false);
+        world.draw_rect((left, left + 1, -2, 3), true, false);
+        assert_eq!(world.population(), 5);
+        world.draw_rect((right - 1, right, -2, 3), true, false);
+        assert_eq!(world.population(), 0);
+    }
+
+    #[test]
+    fn test_draw_circle() {
+        let counts = [(0, 1, 1), (1, 5, 4), (2, 13, 8), (5, 81, 28)];
+        for &(radius, disk, circle) in counts.iter() {
+            let mut world = World::default();
+            world.draw_circle((3, -4), radius, true, true);
+            assert_eq!(world.population(), disk);
+            let r = radius as i64;
+            assert_eq!(world.bound(), Some((3 - r, 4 + r, -4 - r, -3 + r)));
+            let mut world = World::default();
+            world.draw_circle((3, -4), radius, false, true);
+            assert_eq!(world.population(), circle);
+        }
+    }
+
+    #[test]
+    fn test_draw_circle_far() {
+        let r = u32::MAX as i128;
+        assert_eq!(half_width(r, 0), r);
+        assert_eq!(half_width(r, r), 0);
+        assert_eq!(half_width(r, r + 1), -1);
+        for &dy in &[1, 12345, r / 2, r - 1] {
+            let w = half_width(r, dy);
+            assert!(w * w + dy * dy <= r * r && (w + 1) * (w + 1) + dy * dy > r * r);
+        }
+
+        let center = (i64::MAX - 100, i64::MIN + 100);
+        let mut world = World::default();
+        world.draw_circle(center, 5, true, true);
+        assert_eq!(world.population(), 81);
+        world.draw_circle(center, 5, false, false);
+        assert_eq!(world.population(), 81 - 28);
+    }
+
+    #[test]
+    fn test_paste() {
+        let mut glider = World::default();
+        glider.set_cells(vec![(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)], true);
+        let mut world = World::default();
+        world.set_cell(100, 100, true);
+        world.paste(&glider, (100, 98)).paste(&glider, (-50, -50));
+        assert_eq!(world.population(), 10);
+        assert_eq!(world.bound(), Some((-50, 103, -50, 101)));
+    }
+
+    #[test]
+    fn test_extract_and_clear_region() {
+        let mut world = World::default();
+        world.draw_rect((0, 10, 0, 10), false, true);
+        let part = world.extract((-5, 3, 5, 100));
+        assert_eq!(part.population(), 7);
+        assert_eq!(part.bound(), Some((0, 3, 5, 10)));
+        world.clear_region((-5, 3, 5, 100));
+        assert_eq!(world.population(), 36 - 7);
+        world.paste(&part, (0, 0));
+        assert_eq!(world.population(), 36);
+        world.clear_region((i64::MIN, i64::MAX, i64::MIN, i64::MAX));
+        assert_eq!(world.population(), 0);
+    }
+
+    #[test]
+    fn test_translate() {
+        let mut world = World::default();
+        world.draw_rect((0, 10, 0, 10), false, true);
+        world.translate((-105, 3));
+        assert_eq!(world.population(), 36);
+        assert_eq!(world.bound(), Some((-105, -95, 3, 13)));
+
+        // Offsets that are and are not aligned with the nodes, far and near.
+        let mut world = World::from_bytes(b"b2o$2o$bo5$10b3o!").unwrap();
+        world.set_cell(-7, 20, true);
+        let mut cells = world.cells_vec();
+        cells.sort_unstable();
+        let offsets = [
+            (1, 0),
+            (-3, 5),
+            (64, -128),
+            (1 << 40, 3 - (1 << 50)),
+            (i64::MAX - 20, 0),
+        ];
+        for &(dx, dy) in offsets.iter() {
+            let mut moved = world.clone();
+            moved.translate((dx, dy));
+            let mut moved_cells = moved.cells_vec();
+            moved_cells.sort_unstable();
+            let expected = cells.iter().map(|&(x, y)| (x + dx, y + dy));
+            assert_eq!(moved_cells, expected.collect::<Vec<_>>());
+            moved.translate((-dx, -dy));
+            assert_eq!(moved.population(), world.population());
+            assert_eq!(moved.bound(), world.bound());
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{rule::Rule, world::World};
+
+// `(left, right, top, bottom)`, as `World::bound`.
+type Bound = (i64, i64, i64, i64);
+
+// The cells of a 3x3 neighborhood, as in `small::life_like`, that are next
+// to the bound for a cell just outside it, on the left, the right, the top
+// and the bottom: the right column, the left column, the bottom row and the
+// top row.
+const INSIDE: [[u16; 3]; 4] = [[2, 5, 8], [0, 3, 6], [6, 7, 8], [0, 1, 2]];
+
+// Whether a cell can ever be born just outside each side of a bound, in the
+// order of a bound. Such a cell only has living neighbors in the row or the
+// column of the bound next to it, and none at all further out, so a side
+// where none of those neighborhoods gives birth never moves, e.g. every side
+// in rules without births on fewer than 4 neighbors. Rules with B0 are not
+// considered.
+pub(crate) fn escaping_sides(rule: &Rule) -> [bool; 4] {
+    INSIDE.map(|cells| {
+        (1..8u16).any(|subset| {
+            let neighborhood = (0..3)
+                .filter(|i| subset & 1 << i != 0)
+                .fold(0u16, |neighborhood, i| neighborhood | 1 << cells[i]);
+            // The 3x3 cells at the top-left of a leaf.
+            let leaf = (0..9)
+                .filter(|i| neighborhood & 1 << i != 0)
+                .fold(0u16, |leaf, i| leaf | 0x8000 >> (i / 3 * 4 + i % 3));
+            rule.rule_table[leaf as usize] & 0x20 != 0
+        })
+    })
+}
+
+// The bound inflated by `distance` on the sides that move, saturating at the
+// edges of the largest universe.
+fn inflate(bound: Bound, distance: u64, moves: [bool; 4]) -> Bound {
+    let by = |side| if moves[side] { distance } else { 0 };
+    let (left, right, top, bottom) = bound;
+    (
+        left.saturating_sub_unsigned(by(0)),
+        right.saturating_add_unsigned(by(1)),
+        top.saturating_sub_unsigned(by(2)),
+        bottom.saturating_add_unsigned(by(3)),
+    )
+}
+
+impl World {
+    // A bound that the pattern cannot leave within `generations`
+    // generations, whatever the rule: the current one, inflated by one cell
+    // per generation on each side, the speed of light. Cheap enough to size
+    // a window or a buffer before stepping. Returns `None` for an empty
+    // world.
+    pub fn max_extent_after(&self, generations: u64) -> Option<Bound> {
+        Some(inflate(self.bound()?, generations, [true; 4]))
+    }
+
+    // Same as `max_extent_after`, but the sides past which no cell can be
+    // born in the rule of the world stay where they are, see
+    // `escaping_sides`. Without births on 1 neighbor, a cell can still be
+    // born next to a side at every generation, e.g. the dominoes of B2/S
+    // move at the speed of light, so only the rules where some side cannot
+    // move at all are tighter.
+    pub fn rule_extent_after(&self, generations: u64) -> Option<Bound> {
+        Some(inflate(
+            self.bound()?,
+            generations,
+            escaping_sides(&self.rule),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Whether `inner` is inside `outer`.
+    fn contains(outer: Bound, inner: Bound) -> bool {
+        outer.0 <= inner.0 && inner.1 <= outer.1 && outer.2 <= inner.2 && inner.3 <= outer.3
+    }
+
+    #[test]
+    fn test_max_extent_after() {
+        let patterns: [&[u8]; 4] = [
+            b"bo$2bo$3o!",
+            b"b2o$2o$bo!",
+            b"x = 2, y = 1, rule = B2/S\n2o!",
+            b"x = 3, y = 3, rule = B45/S1234\n3o$obo$3o!",
+        ];
+        for &pattern in patterns.iter() {
+            let mut world = World::from_bytes(pattern).unwrap();
+            world.set_step(0);
+            let start = world.clone();
+            for generations in 1..=40 {
+                world.step();
+                let bound = world.bound().unwrap();
+                let extent = start.max_extent_after(generations).unwrap();
+                let rule_extent = start.rule_extent_after(generations).unwrap();
+                assert!(contains(extent, rule_extent));
+                assert!(contains(rule_extent, bound));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_extent_after() {
+        // The dominoes of Seeds move up and down at the speed of light.
+        let mut world = World::from_bytes(b"x = 2, y = 1, rule = B2/S\n2o!").unwrap();
+        let extent = world.rule_extent_after(8).unwrap();
+        assert_eq!(extent, world.max_extent_after(8).unwrap());
+        world.step_by(8);
+        let bound = world.bound().unwrap();
+        assert_eq!((bound.2, bound.3), (extent.2, extent.3));
+
+        // Nothing is born next to a side without births on at most 3
+        // neighbors.
+        let world = World::from_bytes(b"x = 3, y = 1, rule = B45/S1234\n3o!").unwrap();
+        assert_eq!(world.rule_extent_after(100), world.bound());
+        assert_eq!(World::new(Rule::default()).max_extent_after(1), None);
+
+        let mut world = World::new(Rule::default());
+        world.set_cell(5, -5, true);
+        assert_eq!(
+            world.max_extent_after(u64::MAX),
+            Some((i64::MIN, i64::MAX, i64::MIN, i64::MAX))
+        );
+    }
+}
@@ -1,8 +1,154 @@
-use crate::world::{Leaf, Node, NodeId, QuadChildren, World};
+use crate::{
+    recording::Op,
+    world::{Leaf, Node, NodeId, QuadChildren, World},
+};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+// The cancel flag is only checked for nodes of at least this level, where a
+// step takes long enough for the check to be negligible.
+const CANCEL_CHECK_LEVEL: u8 = 8;
+
+// Why a step was abandoned, see `World::try_step`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepError {
+    // The cancel flag became `true`.
+    Cancelled,
+    // The number of nodes reached the limit even after garbage collection,
+    // see `World::set_node_limit`.
+    NodeLimit,
+}
+
+impl Display for StepError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            StepError::Cancelled => write!(f, "The step was cancelled"),
+            StepError::NodeLimit => write!(f, "The step needs more nodes than the limit"),
+        }
+    }
+}
+
+impl Error for StepError {}
+
+// What `World::step_many` did. In a shared store, the nodes and the garbage
+// collections are counted for the whole store.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StepSummary {
+    // Fewer than requested if a step was interrupted.
+    pub steps: u32,
+    pub generations: u64,
+    // Including the nodes that were collected since.
+    pub nodes_created: u64,
+    pub garbage_collections: u64,
+    // Why the steps stopped early, if they did.
+    pub interrupted: Option<StepError>,
+}
 
 impl World {
+    // Panics if the node limit is reached, see `set_node_limit`. A step
+    // interrupted by the cancel flag is silently abandoned.
     pub fn step(&mut self) {
-        while self.node_level(self.root) <= self.step + 1 || self.should_expand() {
+        if let Err(StepError::NodeLimit) = self.try_step() {
+            panic!("The step needs more than {} nodes.", self.get_node_limit());
+        }
+    }
+
+    // Same as `step`, but returns an error if the step is interrupted by the
+    // cancel flag or by the node limit. An interrupted step leaves the
+    // pattern and the generation unchanged, but keeps the results computed
+    // so far in the cache.
+    pub fn try_step(&mut self) -> Result<(), StepError> {
+        if self.apply(Op::Step) {
+            Ok(())
+        } else if self.node_limit_reached {
+            Err(StepError::NodeLimit)
+        } else {
+            Err(StepError::Cancelled)
+        }
+    }
+
+    pub(crate) fn step_unrecorded(&mut self) -> bool {
+        if self.step > 0 && (self.single_generations() || self.edit_within(1 << self.step)) {
+            let snapshot = self.snapshot();
+            let edits = self.scheduled_edits.clone();
+            let completed = self.step_by_unrecorded(1 << self.step);
+            if !completed {
+                self.restore(snapshot);
+                self.scheduled_edits = edits;
+            }
+            self.release(snapshot);
+            return completed;
+        }
+        let completed = self.with_nodes(World::step_root);
+        if completed {
+            self.apply_due_edits();
+        }
+        completed
+    }
+
+    fn step_root(&mut self) -> bool {
+        if self.shared.is_some() {
+            self.nodes.use_cache_for(&self.rule);
+        }
+        self.step_root_from(&mut 0)
+    }
+
+    // Steps the root, as a bitboard if it is small enough, see `step_small`,
+    // or else after expanding it to at least `level`, which is then set to
+    // the level it was expanded to.
+    fn step_root_from(&mut self, level: &mut u8) -> bool {
+        let (root, generation) = (self.root, self.generation);
+        let new_root = match self.step_small().or_else(|| self.step_nodes(level)) {
+            Some(new_root) => new_root,
+            None => return false,
+        };
+        self.record_activity(new_root);
+        self.record_ages(root, new_root);
+        self.record_history(root, generation);
+        self.root = new_root;
+        self.generation += 1 << self.step;
+        self.sample_population(generation);
+        self.sample_bound();
+        self.sample_checkpoint(root, generation);
+        self.check_gc();
+        true
+    }
+
+    // Steps the root as nodes, see `step_root_from`, and returns the new root,
+    // or `None` if the step is interrupted.
+    fn step_nodes(&mut self, level: &mut u8) -> Option<Node> {
+        let root = self.root;
+        self.node_limit_reached = false;
+        let mut new_root = self.expand_and_step(*level);
+        if self.node_limit_reached {
+            // The old root is still valid, so the garbage can be collected
+            // and the step tried again, with what is left of the cache.
+            self.root = root;
+            self.cancelled = false;
+            self.node_limit_reached = false;
+            self.garbage_collect();
+            new_root = self.expand_and_step(*level);
+        }
+        if self.cancelled {
+            self.cancelled = false;
+            self.root = root;
+            self.check_gc();
+            return None;
+        }
+        *level = self.node_level(self.root);
+        Some(new_root)
+    }
+
+    // Steps the root after expanding it, without replacing it.
+    fn expand_and_step(&mut self, level: u8) -> Node {
+        self.expand_to_level(level.max(self.step + 2));
+        while self.should_expand() || !self.result_contains_extent() {
             self.expand()
         }
         debug_assert!(
@@ -12,9 +158,137 @@ impl World {
             },
             "The root cannot be a leaf after expansion."
         );
-        self.root = self.step_node(self.root);
-        self.generation += 1 << self.step;
-        self.check_gc();
+        self.step_node(self.root)
+    }
+
+    // Whether the result of the step, the center half of the root, contains
+    // everything the pattern can reach meanwhile, see `rule_extent_after`.
+    // Otherwise the cells that move at the speed of light, e.g. in B2 rules,
+    // would be lost. The root is never expanded past the largest universe.
+    //
+    // After `should_expand`, the pattern is in the center quarter of the
+    // root, so only the steps of the largest size for the root need the
+    // bound.
+    fn result_contains_extent(&self) -> bool {
+        let level = self.node_level(self.root);
+        if level >= 64 || self.step + 2 < level {
+            return true;
+        }
+        let half = 1 << (level - 2);
+        self.rule_extent_after(1 << self.step)
+            .is_none_or(|(left, right, top, bottom)| {
+                -half <= left && right <= half && -half <= top && bottom <= half
+            })
+    }
+
+    // Same as `k` calls of `try_step`, stopping at the first one that is
+    // interrupted, but cheaper for many small steps: the nodes are checked
+    // out of a shared store once, and the root is expanded straight to the
+    // level of the previous step, so that `should_expand` only has to be
+    // looped over when the pattern grows. A small pattern is stepped as a
+    // bitboard, and only written to the nodes at the end, see
+    // `step_small_many`. The steps are recorded as a single `Op::StepBy`.
+    pub fn step_many(&mut self, k: u32) -> StepSummary {
+        let (created, collections) = {
+            let nodes = self.nodes();
+            (nodes.created, nodes.collections)
+        };
+        let generation = self.generation;
+        let steps = self.record_steps(|world| {
+            if (world.step > 0 && world.single_generations()) || !world.scheduled_edits.is_empty() {
+                return (0..k).take_while(|_| world.step_unrecorded()).count() as u32;
+            }
+            world.with_nodes(|world| {
+                if world.shared.is_some() {
+                    world.nodes.use_cache_for(&world.rule);
+                }
+                let steps = world.step_small_many(k);
+                let mut level = 0;
+                steps
+                    + (steps..k)
+                        .take_while(|_| world.step_root_from(&mut level))
+                        .count() as u32
+            })
+        });
+        let error = if self.node_limit_reached {
+            StepError::NodeLimit
+        } else {
+            StepError::Cancelled
+        };
+        let nodes = self.nodes();
+        StepSummary {
+            steps,
+            generations: self.generation - generation,
+            nodes_created: nodes.created - created,
+            garbage_collections: nodes.collections - collections,
+            interrupted: (steps < k).then_some(error),
+        }
+    }
+
+    // Whether every generation must be stepped, for exact extremes or for
+    // the ages of the cells, see `track_extremes` and `enable_age_tracking`.
+    pub(crate) fn single_generations(&self) -> bool {
+        self.exact_extremes() || self.updates_ages()
+    }
+
+    // Lets another thread interrupt long steps: `try_step` returns
+    // `StepError::Cancelled`, and `step_by` and `step_to` return `false`,
+    // soon after the flag becomes `true`. The flag is never reset by the
+    // world.
+    pub fn set_cancel_flag(&mut self, flag: Option<Arc<AtomicBool>>) -> &mut Self {
+        self.cancel = flag;
+        self
+    }
+
+    // Advances exactly `generations` generations, with one step for each bit
+    // of `generations` that is set, or of the generations up to each
+    // multiple where the population is recorded. The step size is restored
+    // afterwards. Returns `false` if interrupted by the cancel flag or by
+    // the node limit, in which case only some of the steps are done.
+    pub fn step_by(&mut self, generations: u64) -> bool {
+        self.apply(Op::StepBy(generations))
+    }
+
+    pub(crate) fn step_by_unrecorded(&mut self, generations: u64) -> bool {
+        let step = self.step;
+        let mut remaining = generations;
+        let mut completed = true;
+        while completed && remaining > 0 {
+            let chunk = self.generations_to_sample(remaining);
+            completed = self.step_bits(chunk);
+            remaining -= chunk;
+            if completed {
+                self.apply_due_edits();
+            }
+        }
+        if self.step != step {
+            self.set_step(step);
+        }
+        completed
+    }
+
+    fn step_bits(&mut self, generations: u64) -> bool {
+        for k in (0..64).rev() {
+            if generations & 1 << k != 0 {
+                if self.step != k {
+                    self.set_step(k);
+                }
+                if self.try_step().is_err() {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    // Advances to the given generation. Returns `false` if the generation is
+    // in the past, in which case nothing is done, or if interrupted by the
+    // cancel flag.
+    pub fn step_to(&mut self, generation: u64) -> bool {
+        match generation.checked_sub(self.generation) {
+            Some(generations) => self.step_by(generations),
+            None => false,
+        }
     }
 
     fn step_node(&mut self, node: Node) -> Node {
@@ -24,25 +298,48 @@ impl World {
         }
     }
 
-    fn step_leaf(&self, leaf: Leaf) -> Leaf {
+    pub(crate) fn step_leaf(&self, leaf: Leaf) -> Leaf {
         self.rule.rule_table[leaf as usize] as Leaf
     }
 
     fn step_id(&mut self, id: NodeId) -> Node {
-        let data = &self[id];
-        if let Some(node) = data.cache_step {
+        if let Some(node) = self.nodes.cached_step(id, self.step) {
             return node;
         }
-        let max = self.step + 2 >= data.level;
-        let node = match data.children {
+        let level = self[id].level();
+        if level >= CANCEL_CHECK_LEVEL
+            && self
+                .cancel
+                .as_ref()
+                .is_some_and(|flag| flag.load(Ordering::Relaxed))
+        {
+            self.cancelled = true;
+        }
+        if !self.cancelled && self.over_node_limit() {
+            self.cancelled = true;
+            self.node_limit_reached = true;
+        }
+        // Once cancelled, the results are wrong, but still have the right
+        // level. They are never cached.
+        if self.cancelled {
+            return self.empty_node(level - 1);
+        }
+        let max = self.step + 2 >= level;
+        let node = match self[id].children() {
+            #[cfg(not(feature = "strip-table"))]
             QuadChildren::Leaf { nw, ne, sw, se } => self.step_quad_leaf(nw, ne, sw, se, max),
+            #[cfg(feature = "strip-table")]
+            QuadChildren::Leaf { nw, ne, sw, se } => self.step_quad_strips(nw, ne, sw, se, max),
             QuadChildren::NodeId { nw, ne, sw, se } => self.step_quad(nw, ne, sw, se, max),
         };
-        self[id].cache_step = Some(node);
+        if !self.cancelled {
+            self.nodes.set_cached_step(id, self.step, node);
+        }
         node
     }
 
-    fn step_quad_leaf(&self, nw: Leaf, ne: Leaf, sw: Leaf, se: Leaf, max: bool) -> Node {
+    #[cfg_attr(feature = "strip-table", allow(dead_code))]
+    pub(crate) fn step_quad_leaf(&self, nw: Leaf, ne: Leaf, sw: Leaf, se: Leaf, max: bool) -> Node {
         let t00 = self.step_leaf(nw);
         let t01 = self.step_leaf((nw & 0x3333) << 2 | (ne & 0xcccc) >> 2);
         let t02 = self.step_leaf(ne);
@@ -76,47 +373,71 @@ impl World {
     }
 
     fn central_node(&mut self, id: NodeId) -> Node {
-        match self[id].children {
-            QuadChildren::Leaf { nw, ne, sw, se } => Node::Leaf(
+        let data = &self[id];
+        self.center([data.nw(), data.ne(), data.sw(), data.se()])
+    }
+
+    // The step of the node with these children, which is built, so that the
+    // step is cached.
+    fn step_children(&mut self, [nw, ne, sw, se]: [Node; 4]) -> Node {
+        let id = self.find_node(nw, ne, sw, se);
+        self.step_id(id)
+    }
+
+    // The central node of the node with these children, without building
+    // that node.
+    fn center(&mut self, children: [Node; 4]) -> Node {
+        match children {
+            [Node::Leaf(nw), Node::Leaf(ne), Node::Leaf(sw), Node::Leaf(se)] => Node::Leaf(
                 (nw & 0x0033) << 10 | (ne & 0x00cc) << 6 | (sw & 0x3300) >> 6 | (se & 0xcc00) >> 10,
             ),
-            QuadChildren::NodeId { nw, ne, sw, se } => {
+            [Node::NodeId(nw), Node::NodeId(ne), Node::NodeId(sw), Node::NodeId(se)] => {
                 let new_nw = self[nw].se();
                 let new_ne = self[ne].sw();
                 let new_sw = self[sw].ne();
                 let new_se = self[se].nw();
                 Node::NodeId(self.find_node(new_nw, new_ne, new_sw, new_se))
             }
+            _ => unreachable!("All children must have the same level."),
         }
     }
 
     fn step_quad(&mut self, nw: NodeId, ne: NodeId, sw: NodeId, se: NodeId, max: bool) -> Node {
-        let n01 = self.find_node(self[nw].ne(), self[ne].nw(), self[nw].se(), self[ne].sw());
-        let n10 = self.find_node(self[nw].sw(), self[nw].se(), self[sw].nw(), self[sw].ne());
-        let n11 = self.find_node(self[nw].se(), self[ne].sw(), self[sw].ne(), self[se].nw());
-        let n12 = self.find_node(self[ne].sw(), self[ne].se(), self[se].nw(), self[se].ne());
-        let n21 = self.find_node(self[sw].ne(), self[se].nw(), self[sw].se(), self[se].sw());
+        // The children of the overlapping nodes between the four children.
+        let o01 = [self[nw].ne(), self[ne].nw(), self[nw].se(), self[ne].sw()];
+        let o10 = [self[nw].sw(), self[nw].se(), self[sw].nw(), self[sw].ne()];
+        let o11 = [self[nw].se(), self[ne].sw(), self[sw].ne(), self[se].nw()];
+        let o12 = [self[ne].sw(), self[ne].se(), self[se].nw(), self[se].ne()];
+        let o21 = [self[sw].ne(), self[se].nw(), self[sw].se(), self[se].sw()];
         let (t00, t01, t02, t10, t11, t12, t20, t21, t22);
         if max {
             t00 = self.step_id(nw);
-            t01 = self.step_id(n01);
+            t01 = self.step_children(o01);
             t02 = self.step_id(ne);
-            t10 = self.step_id(n10);
-            t11 = self.step_id(n11);
-            t12 = self.step_id(n12);
+            t10 = self.step_children(o10);
+            t11 = self.step_children(o11);
+            t12 = self.step_children(o12);
             t20 = self.step_id(sw);
-            t21 = self.step_id(n21);
+            t21 = self.step_children(o21);
             t22 = self.step_id(se);
         } else {
+            // Only the centers of the overlapping nodes are needed, so the
+            // nodes themselves are not built.
             t00 = self.central_node(nw);
-            t01 = self.central_node(n01);
+            t01 = self.center(o01);
             t02 = self.central_node(ne);
-            t10 = self.central_node(n10);
-            t11 = self.central_node(n11);
-            t12 = self.central_node(n12);
+            t10 = self.center(o10);
+            t11 = self.center(o11);
+            t12 = self.center(o12);
             t20 = self.central_node(sw);
-            t21 = self.central_node(n21);
+            t21 = self.center(o21);
             t22 = self.central_node(se);
+            #[cfg(test)]
+            if self.build_overlapping {
+                for [nw, ne, sw, se] in [o01, o10, o11, o12, o21] {
+                    self.find_node(nw, ne, sw, se);
+                }
+            }
         }
         let pre_new_nw = self.find_node(t00, t01, t10, t11);
         let pre_new_ne = self.find_node(t01, t02, t11, t12);
@@ -222,4 +543,131 @@ mod tests {
             assert_eq!(world.population(), n);
         }
     }
+
+    #[test]
+    fn test_step_by() {
+        let mut world = World::default();
+        world.set_step(3);
+        world.root = Node::Leaf(0b_0000_0011_0110_0010);
+        world.step_by(256 + 5);
+        assert_eq!(world.get_generation(), 261);
+        assert_eq!(world.get_step(), 3);
+        let mut expected = World::default();
+        expected.root = Node::Leaf(0b_0000_0011_0110_0010);
+        for _ in 0..261 {
+            expected.step();
+        }
+        assert_eq!(world.population(), expected.population());
+        assert!(world.step_to(1103));
+        assert_eq!((world.get_generation(), world.population()), (1103, 116));
+        assert!(!world.step_to(1000));
+        assert_eq!(world.get_generation(), 1103);
+    }
+
+    #[test]
+    fn test_step_many() {
+        for (step, populations) in [
+            (0, [6, 7, 9, 8, 9, 12, 11, 18]),
+            (8, [141, 188, 204, 162, 116, 116, 116, 116]),
+        ] {
+            let mut world = World::default();
+            world.set_step(step);
+            world.root = Node::Leaf(0b_0000_0011_0110_0010);
+            let mut steps = 0;
+            for k in [1, 2, 3, 2] {
+                let summary = world.step_many(k);
+                assert_eq!((summary.steps, summary.interrupted), (k, None));
+                assert_eq!(summary.generations, (k as u64) << step);
+                steps += k as usize;
+                assert_eq!(world.population(), populations[steps - 1]);
+            }
+            assert_eq!(world.get_generation(), 8 << step);
+        }
+
+        let mut world = World::from_file("patterns/switch-engine-breeder.rle").unwrap();
+        world.set_step(3).set_gc_threshold(10000);
+        let mut expected = world.clone();
+        let summary = world.step_many(200);
+        for _ in 0..200 {
+            expected.step();
+        }
+        assert_eq!(summary.generations, 1600);
+        assert!(summary.nodes_created > 0 && summary.garbage_collections > 0);
+        assert_eq!(world.get_generation(), expected.get_generation());
+        assert_eq!(world.bound(), expected.bound());
+        assert_eq!(world.population(), expected.population());
+        let cells = |world: &World| {
+            let mut cells = world.living_cells_all().collect::<Vec<_>>();
+            cells.sort_unstable();
+            cells
+        };
+        assert_eq!(cells(&world), cells(&expected));
+    }
+
+    #[test]
+    fn test_overlapping_nodes() {
+        // With steps of one generation, the overlapping nodes are never
+        // stepped, and only their centers are built.
+        let mut world = World::from_bytes(b"b2o$2o$bo!").unwrap();
+        // Stepped as nodes rather than as a bitboard.
+        world.life_like = Some(None);
+        let mut building = world.clone();
+        building.build_overlapping = true;
+        let summary = world.step_many(100);
+        let building_summary = building.step_many(100);
+        assert_eq!(world.population(), 121);
+        assert_eq!(world.cells_vec(), building.cells_vec());
+        assert_eq!(summary.garbage_collections, 0);
+        assert_eq!(building_summary.garbage_collections, 0);
+        assert!(summary.nodes_created < building_summary.nodes_created);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut world = World::default();
+        world.root = Node::Leaf(0b_0000_0011_0110_0010);
+        world.set_step(4);
+        world.step();
+        let flag = Arc::new(AtomicBool::new(true));
+        world.set_cancel_flag(Some(flag.clone())).set_step(10);
+        let population = world.population();
+        assert_eq!(world.try_step(), Err(StepError::Cancelled));
+        let summary = world.step_many(3);
+        assert_eq!(
+            (summary.steps, summary.interrupted),
+            (0, Some(StepError::Cancelled))
+        );
+        assert!(!world.step_to(1000));
+        assert_eq!(
+            (world.get_generation(), world.population()),
+            (16, population)
+        );
+        flag.store(false, Ordering::Relaxed);
+        assert!(world.step_to(1103));
+        assert_eq!(world.population(), 116);
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let mut world = World::from_file("patterns/switch-engine-breeder.rle").unwrap();
+        world.set_step(6);
+        world.step();
+        let (generation, population) = (world.get_generation(), world.population());
+        world.set_node_limit(world.stats().nodes + 100).set_step(12);
+        assert_eq!(world.try_step(), Err(StepError::NodeLimit));
+        assert_eq!(world.get_generation(), generation);
+        assert_eq!(world.population(), population);
+        assert!(!world.step_by(1 << 12));
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| world.step())).is_err());
+
+        // The garbage is collected before giving up.
+        world.set_node_limit(usize::MAX).set_step(0);
+        for _ in 0..100 {
+            world.step();
+        }
+        let nodes = world.stats().nodes;
+        world.set_node_limit(nodes);
+        assert_eq!(world.try_step(), Ok(()));
+        assert!(world.stats().nodes < nodes);
+    }
 }
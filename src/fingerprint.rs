@@ -0,0 +1,109 @@
+use crate::world::{expand_leaf, Node, NodeId, Nodes, World};
+use rustc_hash::{FxHashMap, FxHasher};
+use std::hash::{Hash, Hasher};
+
+// The hashes of the nodes, computed from the hashes of their children, so
+// that they do not depend on the ids of the nodes.
+struct Hashes<'a> {
+    nodes: &'a Nodes,
+    memo: FxHashMap<NodeId, u64>,
+}
+
+impl Hashes<'_> {
+    fn hash(&mut self, node: Node) -> u64 {
+        let id = match node {
+            Node::Leaf(leaf) => return hash_quad(2, [u64::from(leaf); 4]),
+            Node::NodeId(id) => id,
+        };
+        if let Some(&hash) = self.memo.get(&id) {
+            return hash;
+        }
+        let data = &self.nodes[id];
+        let children = [data.nw(), data.ne(), data.sw(), data.se()];
+        let hash = hash_quad(data.level(), children.map(|child| self.hash(child)));
+        self.memo.insert(id, hash);
+        hash
+    }
+}
+
+fn hash_quad(level: u8, children: [u64; 4]) -> u64 {
+    let mut hasher = FxHasher::default();
+    (level, children).hash(&mut hasher);
+    hasher.finish()
+}
+
+impl World {
+    // A hash of the current pattern, i.e., of the cells and their positions.
+    // Equal patterns have equal fingerprints, whatever the size of the root,
+    // and whether the worlds share a store or not. The generation and the
+    // rule are not included.
+    //
+    // The root is hashed as the smallest centered node of level at least 3
+    // that contains all the cells, as in `canonicalize_root`, but without
+    // building it: only its quarters are looked up.
+    pub fn fingerprint(&self) -> u64 {
+        let nodes = self.nodes();
+        let (mut level, mut quarters) = match self.root {
+            Node::Leaf(leaf) => (3, expand_leaf(leaf).map(Node::Leaf)),
+            Node::NodeId(id) => {
+                let data = &nodes[id];
+                (data.level(), [data.nw(), data.ne(), data.sw(), data.se()])
+            }
+        };
+        let population = nodes.node_population(self.root);
+        while level > 3 {
+            let [nw, ne, sw, se] = quarters.map(|quarter| match quarter {
+                Node::NodeId(id) => &nodes[id],
+                Node::Leaf(_) => unreachable!(),
+            });
+            let center = [nw.se(), ne.sw(), sw.ne(), se.nw()];
+            let inner: u64 = center.iter().map(|&node| nodes.node_population(node)).sum();
+            if inner != population {
+                break;
+            }
+            level -= 1;
+            quarters = center;
+        }
+        let mut hashes = Hashes {
+            nodes: &nodes,
+            memo: FxHashMap::default(),
+        };
+        hash_quad(level, quarters.map(|quarter| hashes.hash(quarter)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::NodeStore;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_fingerprint() {
+        let glider = World::from_bytes(b"bo$2bo$3o!").unwrap();
+        let mut expanded = glider.clone();
+        expanded.expand_to_level(10);
+        assert_eq!(glider.fingerprint(), expanded.fingerprint());
+
+        let store = Arc::new(NodeStore::new("B3/S23".parse().unwrap()));
+        let mut shared = World::new_in(&store);
+        shared.paste(&glider, (0, 0));
+        assert_eq!(glider.fingerprint(), shared.fingerprint());
+
+        let mut moved = glider.clone();
+        moved.translate((1, 0));
+        assert_ne!(glider.fingerprint(), moved.fingerprint());
+        let mut stepped = glider.clone();
+        stepped.set_step(2);
+        stepped.step();
+        moved.translate((0, 1));
+        assert_eq!(stepped.fingerprint(), moved.fingerprint());
+
+        let mut far = World::default();
+        far.set_cell(1 << 40, 0, true);
+        let mut near = World::default();
+        near.set_cell(1, 0, true);
+        assert_ne!(far.fingerprint(), near.fingerprint());
+        assert_ne!(World::default().fingerprint(), near.fingerprint());
+    }
+}
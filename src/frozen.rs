@@ -0,0 +1,109 @@
+use crate::{store::NodeStore, world::World};
+use std::{ops::Deref, sync::Arc};
+
+// An immutable view of a world at one generation, which can be sent to other
+// threads and shared between them, e.g. to render or analyze a pattern while
+// the world goes on stepping. The read-only methods of `World` are available
+// through `Deref`.
+//
+// The view shares the nodes of the world through a `NodeStore`, where its
+// root is pinned until it is dropped, so that GC keeps its nodes. Like any
+// world of a store, it locks the store for every read: a read waits while
+// another world of the store steps, and a callback of the view must not use
+// a world of the same store.
+#[derive(Debug)]
+pub struct FrozenWorld {
+    world: World,
+}
+
+impl Deref for FrozenWorld {
+    type Target = World;
+
+    fn deref(&self) -> &World {
+        &self.world
+    }
+}
+
+impl World {
+    // A frozen view of the current pattern, which costs a copy of the rule.
+    // The world itself is not changed. A world in a store shares it with the
+    // view, so only the root is pinned. The nodes of a world with its own
+    // nodes that are reachable from the root are copied into a new store
+    // instead, so that the world keeps using them without a lock.
+    pub fn freeze(&self) -> FrozenWorld {
+        let store = match self.store() {
+            Some(store) => store.clone(),
+            None => Arc::new(NodeStore::new(self.rule.clone())),
+        };
+        let mut world = World::new_in(&store);
+        world.rule = self.rule.clone();
+        world.step = self.step;
+        world.generation = self.generation;
+        world.root = world.import(self);
+        // Pins the root.
+        world.with_nodes(|_| ());
+        FrozenWorld { world }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_freeze() {
+        let mut world = World::from_bytes(b"bo$2bo$3o!").unwrap();
+        let cells = world.cells_vec();
+        let fingerprint = world.fingerprint();
+        let frozen = Arc::new(world.freeze());
+        // The world keeps its own nodes.
+        assert!(world.store().is_none());
+        world.set_gc_threshold(0);
+        world.set_step(4);
+        for _ in 0..16 {
+            world.step();
+        }
+        world.garbage_collect();
+        assert_eq!(world.get_generation(), 256);
+
+        // Read from other threads, the frozen pattern is unchanged.
+        let readers: Vec<_> = (0..2)
+            .map(|_| {
+                let frozen = frozen.clone();
+                thread::spawn(move || (frozen.get_generation(), frozen.cells_vec()))
+            })
+            .collect();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), (0, cells.clone()));
+        }
+        assert_eq!(frozen.population(), 5);
+        assert!(frozen.get_cell(1, 0));
+        assert_eq!(frozen.fingerprint(), fingerprint);
+        assert_ne!(world.fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn test_freeze_in_store() {
+        let store = Arc::new(NodeStore::new("B3/S23".parse().unwrap()));
+        let mut world = World::new_in(&store);
+        world.paste(&World::from_bytes(b"bo$2bo$3o!").unwrap(), (0, 0));
+        let frozen = world.freeze();
+        assert!(Arc::ptr_eq(frozen.store().unwrap(), &store));
+        assert_eq!(frozen.root, world.root);
+        world.set_gc_threshold(0);
+        world.set_step(4);
+        for _ in 0..16 {
+            world.step();
+        }
+        world.garbage_collect();
+
+        // Dropping the view lets GC remove its nodes.
+        let pinned = world.stats().nodes;
+        assert_eq!(frozen.population(), 5);
+        drop(frozen);
+        world.garbage_collect();
+        assert!(world.stats().nodes < pinned);
+        assert_eq!(world.population(), 5);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::world::World;
+
+// The number of intervals the window is split into. The population is
+// sampled at both ends of each.
+const SAMPLES: u64 = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Growth {
+    // E.g. still lifes and oscillators.
+    Bounded,
+    // E.g. guns, puffers and spaceships that leave debris behind.
+    Linear,
+    // E.g. breeders.
+    Quadratic,
+}
+
+// See `World::growth_rate`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrowthEstimate {
+    pub growth: Growth,
+    // The mean population for bounded growth, the cells per generation for
+    // linear growth, and the cells per generation squared for quadratic
+    // growth.
+    pub coefficient: f64,
+    // The root mean square difference between the samples and the fitted
+    // polynomial, in cells.
+    pub residual: f64,
+}
+
+// The least squares polynomial of the given degree (at most 2), as its
+// coefficients from the constant one, and its root mean square residual.
+fn fit(samples: &[(f64, f64)], degree: usize) -> ([f64; 3], f64) {
+    let n = degree + 1;
+    // The normal equations, as an augmented matrix.
+    let mut matrix = [[0.0; 4]; 3];
+    for &(x, y) in samples {
+        let powers = [1.0, x, x * x];
+        for i in 0..n {
+            for j in 0..n {
+                matrix[i][j] += powers[i] * powers[j];
+            }
+            matrix[i][3] += powers[i] * y;
+        }
+    }
+    for i in 0..n {
+        let pivot = (i..n)
+            .max_by(|&a, &b| matrix[a][i].abs().total_cmp(&matrix[b][i].abs()))
+            .unwrap();
+        matrix.swap(i, pivot);
+        if matrix[i][i] == 0.0 {
+            continue;
+        }
+        let row = matrix[i];
+        for (k, other) in matrix.iter_mut().enumerate().take(n) {
+            if k != i {
+                let factor = other[i] / row[i];
+                for (value, &subtrahend) in other.iter_mut().zip(&row).skip(i) {
+                    *value -= factor * subtrahend;
+                }
+            }
+        }
+    }
+    let mut coefficients = [0.0; 3];
+    for i in 0..n {
+        if matrix[i][i] != 0.0 {
+            coefficients[i] = matrix[i][3] / matrix[i][i];
+        }
+    }
+    let [a, b, c] = coefficients;
+    let squares: f64 = samples
+        .iter()
+        .map(|&(x, y)| (a + b * x + c * x * x - y).powi(2))
+        .sum();
+    (coefficients, (squares / samples.len() as f64).sqrt())
+}
+
+impl World {
+    // Runs the pattern for `window` generations from the current one, and
+    // fits its population with polynomials of degree 0, 1 and 2, which tells
+    // oscillators from guns and puffers from breeders. The pattern, the
+    // generation and the history are restored afterwards.
+    //
+    // The growth is linear if the linear fit grows by more than twice the
+    // residual of the constant fit over the window, and quadratic if in
+    // addition the quadratic term contributes more than a fifth of the
+    // growth, and more than four times the residual of the quadratic fit.
+    // Large residuals mean that the window is too short for the period of
+    // the pattern, or that it is still chaotic.
+    pub fn growth_rate(&mut self, window: u64) -> GrowthEstimate {
+        let start = self.generation;
+        let window = window.max(1);
+        let samples = self.with_trackers_suspended(|world| {
+            let mut samples = vec![(0.0, world.population() as f64)];
+            for i in 1..=SAMPLES.min(window) {
+                let generation =
+                    start + (window as u128 * i as u128 / SAMPLES.min(window) as u128) as u64;
+                if !world.step_to(generation) {
+                    break;
+                }
+                let x = (world.generation - start) as f64 / window as f64;
+                samples.push((x, world.population() as f64));
+            }
+            samples
+        });
+
+        let window = window as f64;
+        let (constant, constant_residual) = fit(&samples, 0);
+        let (linear, linear_residual) = fit(&samples, 1);
+        let (quadratic, quadratic_residual) = fit(&samples, 2);
+        let growth = quadratic[1] + quadratic[2];
+        if linear[1].abs() <= 2.0 * constant_residual {
+            GrowthEstimate {
+                growth: Growth::Bounded,
+                coefficient: constant[0],
+                residual: constant_residual,
+            }
+        } else if quadratic[2].abs() > 0.2 * growth.abs()
+            && quadratic[2].abs() > 4.0 * quadratic_residual
+        {
+            GrowthEstimate {
+                growth: Growth::Quadratic,
+                coefficient: quadratic[2] / (window * window),
+                residual: quadratic_residual,
+            }
+        } else {
+            GrowthEstimate {
+                growth: Growth::Linear,
+                coefficient: linear[1] / window,
+                residual: linear_residual,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimate_growth(world: World, window: u64) -> GrowthEstimate {
+        let mut world = world;
+        let generation = world.get_generation();
+        let population = world.population();
+        let estimate = world.growth_rate(window);
+        assert_eq!(world.get_generation(), generation);
+        assert_eq!(world.population(), population);
+        estimate
+    }
+
+    #[test]
+    fn test_growth_rate() {
+        let pulsar = World::from_apgcode(
+            "xp3_co9nas0san9oczgoldlo0oldlogz1047210127401",
+            "B3/S23".parse().unwrap(),
+        )
+        .unwrap();
+        let estimate = estimate_growth(pulsar, 1 << 10);
+        assert_eq!(estimate.growth, Growth::Bounded);
+        assert!((48.0..=72.0).contains(&estimate.coefficient));
+
+        // The Gosper glider gun, which emits 5 cells every 30 generations.
+        let rle = "24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$\
+                   2o8bo3bob2o4bobo$10bo5bo7bo$11bo3bo$12b2o!";
+        let estimate = estimate_growth(World::from_bytes(rle.as_bytes()).unwrap(), 1 << 12);
+        assert_eq!(estimate.growth, Growth::Linear);
+        assert!((estimate.coefficient - 5.0 / 30.0).abs() < 0.01);
+
+        let breeder = World::from_file("patterns/switch-engine-breeder.rle").unwrap();
+        let estimate = estimate_growth(breeder, 1 << 12);
+        assert_eq!(estimate.growth, Growth::Quadratic);
+        assert!(estimate.coefficient > 0.0);
+    }
+}
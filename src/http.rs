@@ -0,0 +1,94 @@
+use crate::{read::LoadError, world::World};
+use std::{io::Read, time::Duration};
+
+// Larger downloads are refused, so that a wrong URL cannot fill the memory.
+const MAX_SIZE: u64 = 64 << 20;
+// The time limit of the whole download, including the connection.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// Downloads a file over HTTP or HTTPS, as it is: gzipped patterns are not
+// decompressed. An error status of the server is a `LoadError::Http`.
+pub fn download(url: &str) -> Result<Vec<u8>, LoadError> {
+    download_with_limit(url, MAX_SIZE)
+}
+
+fn download_with_limit(url: &str, limit: u64) -> Result<Vec<u8>, LoadError> {
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    let response = agent
+        .get(url)
+        .call()
+        .map_err(|e| LoadError::Http(Box::new(e)))?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(limit + 1)
+        .read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > limit {
+        return Err(LoadError::TooLarge(limit));
+    }
+    Ok(bytes)
+}
+
+impl World {
+    // Same as `from_file`, but downloads the pattern from an HTTP or HTTPS
+    // URL. Blocks until the download is finished, for at most 30 seconds,
+    // and refuses files larger than 64 MiB.
+    pub fn from_url(url: &str) -> Result<Self, LoadError> {
+        Self::from_bytes(&download(url)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    const GLIDER: &str = "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+
+    // Serves the responses, one per connection, and returns the URL of the
+    // server.
+    fn serve(responses: Vec<(&'static str, Vec<u8>)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let mut stream = listener.accept().unwrap().0;
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_from_url() {
+        let url = serve(vec![
+            ("200 OK", GLIDER.as_bytes().to_vec()),
+            ("404 Not Found", b"Not found".to_vec()),
+            ("200 OK", vec![b'o'; 100]),
+        ]);
+        let world = World::from_url(&format!("{}/glider.rle", url)).unwrap();
+        assert_eq!(world.population(), 5);
+
+        let error = World::from_url(&format!("{}/missing.rle", url)).unwrap_err();
+        assert!(matches!(&error, LoadError::Http(e) if e.kind() == ureq::ErrorKind::HTTP));
+
+        let error = download_with_limit(&format!("{}/large.rle", url), 10).unwrap_err();
+        assert!(matches!(error, LoadError::TooLarge(10)));
+    }
+}
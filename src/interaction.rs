@@ -0,0 +1,96 @@
+use crate::{boolean::BoolOp, store::NodeStore, world::World};
+use rustc_hash::FxHashMap;
+use std::sync::Arc;
+
+impl World {
+    // The first generation at which the pattern of `self` and the pattern of
+    // `other` moved by `offset`, run together, differ from the union of the
+    // two run apart, i.e. at which they interact, within `max_generations`
+    // generations from now. The generation is counted like that of `self`,
+    // and the three runs use its rule.
+    //
+    // The runs share a store, so the parts of the patterns that do not
+    // interact are only stepped once, and each comparison only builds the
+    // nodes of the union where both parts have living cells.
+    pub fn first_interaction(
+        &self,
+        other: &World,
+        offset: (i64, i64),
+        max_generations: u64,
+    ) -> Option<u64> {
+        let (dx, dy) = offset;
+        let store = Arc::new(NodeStore::new(self.rule.clone()));
+        let mut own = World::new_in(&store);
+        own.set_cells_unrecorded(self.cells_vec(), true);
+        let mut moved = World::new_in(&store);
+        let cells = other.living_cells_all().map(|(x, y)| (x + dx, y + dy));
+        moved.set_cells_unrecorded(cells.collect(), true);
+        if own.population() == 0 || moved.population() == 0 {
+            return None;
+        }
+        let mut combined = own.clone();
+        combined.set_cells_unrecorded(moved.cells_vec(), true);
+        for generation in 1..=max_generations {
+            for world in [&mut own, &mut moved, &mut combined] {
+                world.step();
+            }
+            if !combined.is_union_of(&own, &moved) {
+                return Some(self.generation + generation);
+            }
+        }
+        None
+    }
+
+    // Whether the pattern is the union of the patterns of `a` and `b`, all
+    // three in the same store.
+    fn is_union_of(&mut self, a: &World, b: &World) -> bool {
+        let (a, b) = (a.root, b.root);
+        self.with_nodes(|world| {
+            let [root, a, b] = world.align_levels([world.root, a, b]);
+            world.combine_rec(a, b, BoolOp::Or, &mut FxHashMap::default()) == root
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_interaction() {
+        // Going down and right, and down and left.
+        let glider = World::from_bytes(b"bo$2bo$3o!").unwrap();
+        let mirrored = World::from_bytes(b"bo$o$3o!").unwrap();
+
+        // Side by side, and going apart.
+        assert_eq!(glider.first_interaction(&glider, (0, 6), 200), None);
+        assert_eq!(glider.first_interaction(&mirrored, (-6, 0), 200), None);
+        // Going towards each other, they collide later when further apart.
+        let generation = glider.first_interaction(&mirrored, (12, 0), 200).unwrap();
+        let later = glider.first_interaction(&mirrored, (30, 0), 200).unwrap();
+        assert!(0 < generation && generation < later);
+
+        // The interaction is checked against stepping by hand.
+        let mut together = glider.clone();
+        together.paste(&mirrored, (12, 0)).set_step(0);
+        let (mut apart, mut moved) = (glider.clone(), mirrored.clone());
+        moved.translate((12, 0)).set_step(0);
+        apart.set_step(0);
+        for g in 1..=generation {
+            together.step();
+            apart.step();
+            moved.step();
+            let mut union = apart.cells_vec();
+            union.extend(moved.cells_vec());
+            union.sort_unstable();
+            union.dedup();
+            let mut cells = together.cells_vec();
+            cells.sort_unstable();
+            assert_eq!(cells == union, g < generation);
+        }
+
+        // An empty pattern never interacts.
+        let empty = World::default();
+        assert_eq!(glider.first_interaction(&empty, (0, 0), 10), None);
+    }
+}
@@ -1,4 +1,105 @@
-use crate::world::{Node, World};
+use crate::store::NodesRef;
+use crate::world::{Leaf, Node, Nodes, World};
+use smallvec::{smallvec, SmallVec};
+
+// The nodes still to visit in a traversal. Each node is replaced by at most
+// 4 children, one level lower, so there are at most `3 * level + 1` of them
+// for a root of that level, and the stack never allocates for any root
+// whose coordinates fit in `i64`.
+type Stack<T> = SmallVec<[T; 3 * 64 + 1]>;
+
+pub struct LivingCells<'a> {
+    nodes: NodesRef<'a>,
+    stack: Stack<(Node, i64, i64)>,
+    leaf: (Leaf, i64, i64),
+}
+
+impl<'a> Iterator for LivingCells<'a> {
+    type Item = (i64, i64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.leaf.0 == 0 {
+            let (node, x, y) = self.stack.pop()?;
+            if self.nodes.node_population(node) == 0 {
+                continue;
+            }
+            match node {
+                Node::Leaf(leaf) => self.leaf = (leaf, x, y),
+                Node::NodeId(id) => {
+                    let node_size = 1 << (self.nodes.node_level(node) - 2);
+                    let data = &self.nodes[id];
+                    self.stack.push((data.se(), x + node_size, y + node_size));
+                    self.stack.push((data.sw(), x - node_size, y + node_size));
+                    self.stack.push((data.ne(), x + node_size, y - node_size));
+                    self.stack.push((data.nw(), x - node_size, y - node_size));
+                }
+            }
+        }
+        let (leaf, x, y) = self.leaf;
+        let bit = 15 - leaf.leading_zeros() as i64;
+        self.leaf.0 = leaf & !(1 << bit);
+        Some((x + 1 - bit % 4, y + 1 - bit / 4))
+    }
+}
+
+// Each leaf is a 4x4 block, in row-major order from the most significant
+// bit: bit `15 - (4 * row + column)` is the cell at `(x + column, y + row)`.
+pub struct Leaves<'a> {
+    nodes: NodesRef<'a>,
+    stack: Stack<(Node, i64, i64)>,
+    bound: (i64, i64, i64, i64),
+}
+
+impl<'a> Iterator for Leaves<'a> {
+    type Item = (i64, i64, u16);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (left, right, top, bottom) = self.bound;
+        while let Some((node, x, y)) = self.stack.pop() {
+            if self.nodes.node_population(node) == 0 {
+                continue;
+            }
+            // The edges of a root of level 64 do not fit in `i64`.
+            let level = self.nodes.node_level(node);
+            let half = 1i128 << (level - 1);
+            let (cx, cy) = (x as i128, y as i128);
+            if cx + half <= left as i128
+                || cx - half >= right as i128
+                || cy + half <= top as i128
+                || cy - half >= bottom as i128
+            {
+                continue;
+            }
+            let node_size: i64 = 1 << (level - 2);
+            match node {
+                Node::Leaf(leaf) => {
+                    let mut mask = 0;
+                    for i in 0..4 {
+                        if x - 2 + i >= left && x - 2 + i < right {
+                            mask |= 0x8888 >> i;
+                        }
+                    }
+                    for i in 0..4 {
+                        if y - 2 + i < top || y - 2 + i >= bottom {
+                            mask &= !(0xf000 >> (4 * i));
+                        }
+                    }
+                    if leaf & mask != 0 {
+                        return Some((x - 2, y - 2, leaf & mask));
+                    }
+                }
+                Node::NodeId(id) => {
+                    let data = &self.nodes[id];
+                    self.stack.push((data.se(), x + node_size, y + node_size));
+                    self.stack.push((data.sw(), x - node_size, y + node_size));
+                    self.stack.push((data.ne(), x + node_size, y - node_size));
+                    self.stack.push((data.nw(), x - node_size, y - node_size));
+                }
+            }
+        }
+        None
+    }
+}
 
 impl World {
     // Bound: (left, right, top, bottom).
@@ -7,7 +108,18 @@ impl World {
         F: FnMut(i64, i64),
     {
         let mut f = f;
-        self.for_nodes_rec(self.root, level, bound, (0, 0), &mut f)
+        self.nodes()
+            .for_nodes_in(self.root, level, bound, |x, y, _| f(x, y))
+    }
+
+    // Bound: (left, right, top, bottom).
+    // Same as `for_nodes`, but also gives the population of each node, which
+    // is at most `4^level`.
+    pub fn for_nodes_with_population<F>(&self, level: u8, bound: (i64, i64, i64, i64), f: F)
+    where
+        F: FnMut(i64, i64, u64),
+    {
+        self.nodes().for_nodes_in(self.root, level, bound, f)
     }
 
     // Bound: (left, right, top, bottom).
@@ -18,153 +130,280 @@ impl World {
         self.for_nodes(0, bound, f)
     }
 
-    fn for_nodes_rec<F>(
-        &self,
-        node: Node,
-        level: u8,
-        bound: (i64, i64, i64, i64),
-        offset: (i64, i64),
-        f: &mut F,
-    ) where
+    pub fn for_all_living_cells<F>(&self, f: F)
+    where
+        F: FnMut(i64, i64),
+    {
+        self.for_living_cells((i64::MIN, i64::MAX, i64::MIN, i64::MAX), f)
+    }
+
+    // Cells are visited in an unspecified order.
+    pub fn living_cells_all(&self) -> LivingCells<'_> {
+        LivingCells {
+            nodes: self.nodes(),
+            stack: smallvec![(self.root, 0, 0)],
+            leaf: (0, 0, 0),
+        }
+    }
+
+    // Beware that this allocates 16 bytes per living cell; use the callback
+    // or the iterator for large patterns.
+    pub fn cells_vec(&self) -> Vec<(i64, i64)> {
+        let mut cells = Vec::with_capacity(self.population() as usize);
+        self.for_all_living_cells(|x, y| cells.push((x, y)));
+        cells
+    }
+
+    // Bound: (left, right, top, bottom).
+    // Iterates over non-empty 4x4 leaves, as `(left, top, leaf)`. Cells
+    // outside of the bound are masked out.
+    pub fn leaves(&self, bound: (i64, i64, i64, i64)) -> Leaves<'_> {
+        Leaves {
+            nodes: self.nodes(),
+            stack: smallvec![(self.root, 0, 0)],
+            bound,
+        }
+    }
+
+    // Bound: (left, right, top, bottom).
+    pub fn for_leaves<F>(&self, bound: (i64, i64, i64, i64), f: F)
+    where
+        F: FnMut(i64, i64, u16),
+    {
+        let mut f = f;
+        self.leaves(bound).for_each(|(x, y, leaf)| f(x, y, leaf))
+    }
+
+    // Bound: (left, right, top, bottom), in pixels.
+    // Each cell is magnified to a square of `2^magnification` pixels. Calls
+    // `f` with the top-left pixel of every living cell whose square intersects
+    // the bound; the square may extend beyond the bound.
+    pub fn for_cells_magnified<F>(&self, magnification: u8, bound: (i64, i64, i64, i64), f: F)
+    where
         F: FnMut(i64, i64),
     {
-        if self.node_population(node) != 0 {
+        let mut f = f;
+        let (left, right, top, bottom) = bound;
+        if left >= right || top >= bottom {
+            return;
+        }
+        let cell_bound = (
+            left >> magnification,
+            ((right - 1) >> magnification) + 1,
+            top >> magnification,
+            ((bottom - 1) >> magnification) + 1,
+        );
+        self.for_living_cells(cell_bound, |x, y| f(x << magnification, y << magnification))
+    }
+
+    // Bound: (left, right, top, bottom).
+    // Cells are visited in row-major order: by y, then by x, both ascending.
+    pub fn for_living_cells_ordered<F>(&self, bound: (i64, i64, i64, i64), f: F)
+    where
+        F: FnMut(i64, i64),
+    {
+        let mut f = f;
+        self.nodes().for_cells_in_rows(self.root, bound, &mut f);
+    }
+}
+
+impl Nodes {
+    // Bound: (left, right, top, bottom), relative to the center of `root`.
+    // The nodes are visited a row at a time: a row is a run of nonempty
+    // nodes of the same level, from left to right, as `(node, x)` with `x`
+    // the center of the node, and their common center `y`. The northern
+    // children of all the nodes of a row form the next row, which is
+    // visited before the one of the southern children, so that the cells
+    // come row by row, and empty children are dropped, so that empty rows
+    // are skipped in one go whatever their height.
+    //
+    // The rows are kept one after the other in a single buffer, with the
+    // row of the children after the row of their parents, and a frame on
+    // the stack for each row, as `(start, end, y, north)`, where `north`
+    // tells whether the northern children are still to visit. There are at
+    // most two frames per level.
+    fn for_cells_in_rows<F>(&self, root: Node, bound: (i64, i64, i64, i64), f: &mut F)
+    where
+        F: FnMut(i64, i64),
+    {
+        if self.node_population(root) == 0 {
+            return;
+        }
+        let (left, right, top, bottom) = bound;
+        let mut rows = vec![(root, 0)];
+        let mut stack: Stack<(usize, usize, i64, bool)> = smallvec![(0, 1, 0, true)];
+        while let Some((start, end, y, north)) = stack.pop() {
+            // Drops the rows of the children that are already visited.
+            rows.truncate(end);
+            let level = self.node_level(rows[start].0);
+            if level == 2 {
+                for dy in top.saturating_sub(y).max(-2)..bottom.saturating_sub(y).min(2) {
+                    for &(node, x) in &rows[start..end] {
+                        let leaf = match node {
+                            Node::Leaf(leaf) => leaf,
+                            Node::NodeId(_) => unreachable!(),
+                        };
+                        for dx in left.saturating_sub(x).max(-2)..right.saturating_sub(x).min(2) {
+                            if leaf & 1 << ((1 - dy) * 4 + (1 - dx)) != 0 {
+                                f(x + dx, y + dy);
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
+            if north {
+                stack.push((start, end, y, false));
+            }
+            // The children are twice this size, and computed in `i128` since
+            // the edges of the root may not fit in `i64`.
+            let quarter = 1i64 << (level - 2);
+            let overlaps = |center: i64, low: i64, high: i64| {
+                let center = center as i128;
+                let size = quarter as i128;
+                center - size < high as i128 && center + size > low as i128
+            };
+            let y = if north { y - quarter } else { y + quarter };
+            if !overlaps(y, top, bottom) {
+                continue;
+            }
+            for i in start..end {
+                let (node, x) = rows[i];
+                let data = match node {
+                    Node::NodeId(id) => &self[id],
+                    Node::Leaf(_) => unreachable!(),
+                };
+                let (west, east) = if north {
+                    (data.nw(), data.ne())
+                } else {
+                    (data.sw(), data.se())
+                };
+                for (child, x) in [(west, x - quarter), (east, x + quarter)] {
+                    if self.node_population(child) > 0 && overlaps(x, left, right) {
+                        rows.push((child, x));
+                    }
+                }
+            }
+            if rows.len() > end {
+                stack.push((end, rows.len(), y, true));
+            }
+        }
+    }
+
+    // Bound: (left, right, top, bottom), relative to the center of `root`.
+    // Calls `f(x, y, population)` for every nonempty node of the level, or
+    // every cell or block of 2x2 cells if the level is 0 or 1, whose
+    // coordinates are in the bound, row by row within each node.
+    pub(crate) fn for_nodes_in<F>(&self, root: Node, level: u8, bound: (i64, i64, i64, i64), f: F)
+    where
+        F: FnMut(i64, i64, u64),
+    {
+        let mut f = f;
+        let mut stack: Stack<_> = smallvec![(root, bound, (0, 0))];
+        while let Some((node, bound, offset)) = stack.pop() {
+            let population = self.node_population(node);
+            if population == 0 {
+                continue;
+            }
             let node_level = self.node_level(node);
             let (left, right, top, bottom) = bound;
-
             if node_level <= level {
                 if left <= 0 && right > 0 && top <= 0 && bottom > 0 {
-                    f(offset.0, offset.1);
+                    f(offset.0, offset.1, population);
                 }
-            } else {
-                match node {
-                    Node::Leaf(leaf) => match level {
-                        0 => {
-                            let left = left.max(-2);
-                            let right = right.min(2);
-                            let top = top.max(-2);
-                            let bottom = bottom.min(2);
-                            for y in top..bottom {
-                                for x in left..right {
-                                    if leaf & 1 << ((1 - y) * 4 + (1 - x)) != 0 {
-                                        f(x + offset.0, y + offset.1);
-                                    }
+                continue;
+            }
+            match node {
+                Node::Leaf(leaf) => match level {
+                    0 => {
+                        let left = left.max(-2);
+                        let right = right.min(2);
+                        let top = top.max(-2);
+                        let bottom = bottom.min(2);
+                        for y in top..bottom {
+                            for x in left..right {
+                                if leaf & 1 << ((1 - y) * 4 + (1 - x)) != 0 {
+                                    f(x + offset.0, y + offset.1, 1);
                                 }
                             }
                         }
-                        1 => {
-                            let left = left.max(-1);
-                            let right = right.min(1);
-                            let top = top.max(-1);
-                            let bottom = bottom.min(1);
-                            for y in top..bottom {
-                                for x in left..right {
-                                    if leaf & 0x0033 << (-8 * y - 2 * x) != 0 {
-                                        f(x + offset.0, y + offset.1);
-                                    }
+                    }
+                    1 => {
+                        let left = left.max(-1);
+                        let right = right.min(1);
+                        let top = top.max(-1);
+                        let bottom = bottom.min(1);
+                        for y in top..bottom {
+                            for x in left..right {
+                                let block = leaf & 0x0033 << (-8 * y - 2 * x);
+                                if block != 0 {
+                                    f(x + offset.0, y + offset.1, block.count_ones() as u64);
                                 }
                             }
                         }
-                        _ => unreachable!(),
-                    },
-                    Node::NodeId(id) => {
-                        let data = &self[id];
-                        if node_level >= level + 2 {
-                            let node_size = 1 << (node_level - level - 2);
-                            if left < 0 && top < 0 {
-                                self.for_nodes_rec(
-                                    data.nw(),
-                                    level,
-                                    (
-                                        left + node_size,
-                                        right.min(0) + node_size,
-                                        top + node_size,
-                                        bottom.min(0) + node_size,
-                                    ),
-                                    (offset.0 - node_size, offset.1 - node_size),
-                                    f,
-                                );
-                            }
-                            if right > 0 && top < 0 {
-                                self.for_nodes_rec(
-                                    data.ne(),
-                                    level,
-                                    (
-                                        left.max(0) - node_size,
-                                        right - node_size,
-                                        top + node_size,
-                                        bottom.min(0) + node_size,
-                                    ),
-                                    (offset.0 + node_size, offset.1 - node_size),
-                                    f,
-                                );
-                            }
-                            if left < 0 && bottom > 0 {
-                                self.for_nodes_rec(
-                                    data.sw(),
-                                    level,
-                                    (
-                                        left + node_size,
-                                        right.min(0) + node_size,
-                                        top.max(0) - node_size,
-                                        bottom - node_size,
-                                    ),
-                                    (offset.0 - node_size, offset.1 + node_size),
-                                    f,
-                                );
-                            }
-                            if right > 0 && bottom > 0 {
-                                self.for_nodes_rec(
-                                    data.se(),
-                                    level,
-                                    (
-                                        left.max(0) - node_size,
-                                        right - node_size,
-                                        top.max(0) - node_size,
-                                        bottom - node_size,
-                                    ),
-                                    (offset.0 + node_size, offset.1 + node_size),
-                                    f,
-                                );
-                            }
-                        } else {
-                            if left < 0 && top < 0 {
-                                self.for_nodes_rec(
-                                    data.nw(),
-                                    level,
-                                    (left + 1, right.min(0) + 1, top + 1, bottom.min(0) + 1),
-                                    (offset.0 - 1, offset.1 - 1),
-                                    f,
-                                );
-                            }
-                            if right > 0 && top < 0 {
-                                self.for_nodes_rec(
-                                    data.ne(),
-                                    level,
-                                    (left.max(0), right, top + 1, bottom.min(0) + 1),
-                                    (offset.0, offset.1 - 1),
-                                    f,
-                                );
-                            }
-                            if left < 0 && bottom > 0 {
-                                self.for_nodes_rec(
-                                    data.sw(),
-                                    level,
-                                    (left + 1, right.min(0) + 1, top.max(0), bottom),
-                                    (offset.0 - 1, offset.1),
-                                    f,
-                                );
-                            }
-                            if right > 0 && bottom > 0 {
-                                self.for_nodes_rec(
-                                    data.se(),
-                                    level,
-                                    (left.max(0), right, top.max(0), bottom),
-                                    (offset.0, offset.1),
-                                    f,
-                                );
-                            }
-                        };
+                    }
+                    _ => unreachable!(),
+                },
+                Node::NodeId(id) => {
+                    let data = &self[id];
+                    // How far the centers of the children on the negative
+                    // and on the positive side are from the center, in units
+                    // of the level: a child of the level itself is the unit
+                    // at its top-left.
+                    let (near, far) = if node_level >= level + 2 {
+                        let node_size = 1 << (node_level - level - 2);
+                        (node_size, node_size)
+                    } else {
+                        (1, 0)
+                    };
+                    // From the last, so that the first is visited first.
+                    if right > 0 && bottom > 0 {
+                        stack.push((
+                            data.se(),
+                            (
+                                left.max(0) - far,
+                                right - far,
+                                top.max(0) - far,
+                                bottom - far,
+                            ),
+                            (offset.0 + far, offset.1 + far),
+                        ));
+                    }
+                    if left < 0 && bottom > 0 {
+                        stack.push((
+                            data.sw(),
+                            (
+                                left + near,
+                                right.min(0) + near,
+                                top.max(0) - far,
+                                bottom - far,
+                            ),
+                            (offset.0 - near, offset.1 + far),
+                        ));
+                    }
+                    if right > 0 && top < 0 {
+                        stack.push((
+                            data.ne(),
+                            (
+                                left.max(0) - far,
+                                right - far,
+                                top + near,
+                                bottom.min(0) + near,
+                            ),
+                            (offset.0 + far, offset.1 - near),
+                        ));
+                    }
+                    if left < 0 && top < 0 {
+                        stack.push((
+                            data.nw(),
+                            (
+                                left + near,
+                                right.min(0) + near,
+                                top + near,
+                                bottom.min(0) + near,
+                            ),
+                            (offset.0 - near, offset.1 - near),
+                        ));
                     }
                 }
             }
@@ -198,4 +437,175 @@ mod tests {
         world.for_nodes(2, (-2, 2, -2, 2), |x, y| cells.push((x, y)));
         assert_eq!(cells, vec![(-1, -1), (0, -1), (-1, 0), (0, 0)]);
     }
+
+    #[test]
+    fn test_for_nodes_with_population() {
+        let mut world = World::default();
+        world.draw_rect((0, 10, 0, 10), false, true);
+        for level in 0..5 {
+            let mut total = 0;
+            world.for_nodes_with_population(level, (-100, 100, -100, 100), |_, _, population| {
+                assert!(population > 0 && population <= 1 << (2 * level));
+                total += population;
+            });
+            assert_eq!(total, 36);
+        }
+        let mut blocks = Vec::new();
+        world.for_nodes_with_population(1, (0, 2, 0, 2), |x, y, population| {
+            blocks.push((x, y, population))
+        });
+        blocks.sort_unstable();
+        assert_eq!(blocks, vec![(0, 0, 3), (0, 1, 2), (1, 0, 2)]);
+    }
+
+    #[test]
+    fn test_for_living_cells_ordered() {
+        let mut world = World::default();
+        let cells = [
+            (-9, -7),
+            (5, -7),
+            (-3, -7),
+            (8, 2),
+            (-6, 2),
+            (0, 0),
+            (-1, 0),
+            (3, -1),
+        ];
+        for &(x, y) in cells.iter() {
+            world.set_cell(x, y, true);
+        }
+        let mut visited = Vec::new();
+        world.for_living_cells_ordered((-100, 100, -100, 100), |x, y| visited.push((x, y)));
+        let mut expected = cells.to_vec();
+        expected.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(visited, expected);
+        visited.clear();
+        world.for_living_cells_ordered((-4, 6, -7, 1), |x, y| visited.push((x, y)));
+        assert_eq!(visited, vec![(-3, -7), (5, -7), (3, -1), (-1, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn test_for_living_cells_ordered_sparse() {
+        // Rows far apart are not visited one by one.
+        let mut world = World::default();
+        let far = 1 << 34;
+        let cells = [(3, far), (-far, far), (0, 0), (far, -far), (-1, 0)];
+        for &(x, y) in cells.iter() {
+            world.set_cell(x, y, true);
+        }
+        let mut visited = Vec::new();
+        world.for_living_cells_ordered((i64::MIN, i64::MAX, i64::MIN, i64::MAX), |x, y| {
+            visited.push((x, y))
+        });
+        let mut expected = cells.to_vec();
+        expected.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(visited, expected);
+        visited.clear();
+        world.for_living_cells_ordered((-far, 1, 0, far + 1), |x, y| visited.push((x, y)));
+        assert_eq!(visited, vec![(-1, 0), (0, 0), (-far, far)]);
+    }
+
+    #[test]
+    fn test_for_cells_magnified() {
+        let mut world = World::default();
+        let cells = [(0, 0), (1, 0), (-1, -3), (5, 2), (-7, 6), (3, -8)];
+        for &(x, y) in cells.iter() {
+            world.set_cell(x, y, true);
+        }
+        for magnification in 1..=4 {
+            let size = 1 << magnification;
+            for &bound in &[
+                (-37, 29, -41, 33),
+                (1, 2, -1, 1),
+                (-size - 1, size + 1, 5, 200),
+            ] {
+                let (left, right, top, bottom) = bound;
+                let width = (right - left) as usize;
+                let mut pixels = vec![false; width * (bottom - top) as usize];
+                world.for_cells_magnified(magnification, bound, |x, y| {
+                    for py in y.max(top)..(y + size).min(bottom) {
+                        for px in x.max(left)..(x + size).min(right) {
+                            pixels[(py - top) as usize * width + (px - left) as usize] = true;
+                        }
+                    }
+                });
+                for py in top..bottom {
+                    for px in left..right {
+                        let expected = cells.contains(&(px >> magnification, py >> magnification));
+                        let pixel = pixels[(py - top) as usize * width + (px - left) as usize];
+                        assert_eq!(
+                            pixel, expected,
+                            "pixel ({}, {}) at 2^{}",
+                            px, py, magnification
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_living_cells_all() {
+        let mut world = World::default();
+        assert_eq!(world.cells_vec(), vec![]);
+        assert_eq!(world.living_cells_all().next(), None);
+        world.set_step(8);
+        world.root = Node::Leaf(0b_0000_0011_0110_0010);
+        world.step();
+        let mut cells = world.cells_vec();
+        assert_eq!(cells.len() as u64, world.population());
+        let mut expected = Vec::new();
+        world.for_living_cells(world.bound().unwrap(), |x, y| expected.push((x, y)));
+        assert_eq!(cells, expected);
+        let mut iterated = world.living_cells_all().collect::<Vec<_>>();
+        iterated.sort_unstable();
+        cells.sort_unstable();
+        assert_eq!(iterated, cells);
+    }
+
+    #[test]
+    fn test_for_leaves() {
+        let mut world = World::default();
+        world.set_step(8);
+        world.root = Node::Leaf(0b_0000_0011_0110_0010);
+        world.step();
+        for &bound in &[(-100, 100, -100, 100), (-13, 7, -30, 3), (2, 3, -50, 50)] {
+            let mut cells = Vec::new();
+            world.for_leaves(bound, |x, y, leaf| {
+                assert_ne!(leaf, 0);
+                for row in 0..4 {
+                    for column in 0..4 {
+                        if leaf & 1 << (15 - (4 * row + column)) != 0 {
+                            cells.push((x + column, y + row));
+                        }
+                    }
+                }
+            });
+            let mut expected = Vec::new();
+            world.for_living_cells(bound, |x, y| expected.push((x, y)));
+            cells.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(cells, expected);
+        }
+    }
+
+    #[test]
+    fn test_for_leaves_far() {
+        // A root of level 64, whose edges do not fit in `i64`.
+        let mut world = World::default();
+        world.set_cells(vec![(i64::MAX - 3, 0), (i64::MIN + 3, 0)], true);
+        assert_eq!(world.node_level(world.root), 64);
+        let all = (i64::MIN, i64::MAX, i64::MIN, i64::MAX);
+        let mut cells = Vec::new();
+        world.for_leaves(all, |x, y, leaf| {
+            for bit in 0..16 {
+                if leaf & 1 << (15 - bit) != 0 {
+                    cells.push((x + bit % 4, y + bit / 4));
+                }
+            }
+        });
+        cells.sort_unstable();
+        assert_eq!(cells, vec![(i64::MIN + 3, 0), (i64::MAX - 3, 0)]);
+        assert_eq!(world.leaves((0, i64::MAX, -1, 1)).count(), 1);
+    }
 }
@@ -0,0 +1,182 @@
+use crate::{rule::Rule, world::World};
+use serde_json::{Error as SerdeError, Value};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult, Write},
+};
+
+// `to_json` refuses to write more cells than this. JSON is only meant for
+// small patterns; use `to_json_unlimited` to write more anyway.
+pub const JSON_MAX_CELLS: u64 = 1 << 20;
+
+#[derive(Debug)]
+pub enum JsonError {
+    Json(SerdeError),
+    // The JSON is valid, but not an object with the expected fields. The
+    // message tells which field is wrong.
+    Invalid(&'static str),
+    // The rulestring is not supported.
+    Rule(String),
+    // The number of cells to write, which is more than `JSON_MAX_CELLS`.
+    TooManyCells(u64),
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            JsonError::Json(e) => write!(f, "Invalid JSON: {}", e),
+            JsonError::Invalid(message) => write!(f, "Invalid JSON pattern: {}", message),
+            JsonError::Rule(rule) => write!(f, "Unsupported rule {}", rule),
+            JsonError::TooManyCells(cells) => write!(
+                f,
+                "Too many cells for JSON: {} (at most {})",
+                cells, JSON_MAX_CELLS
+            ),
+        }
+    }
+}
+
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JsonError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SerdeError> for JsonError {
+    fn from(e: SerdeError) -> Self {
+        JsonError::Json(e)
+    }
+}
+
+impl World {
+    // Writes the living cells inside the bound `(left, right, top, bottom)`,
+    // or all of them, as
+    // `{"rule": "B3/S23", "generation": 0, "cells": [[x, y], ...]}`, with
+    // the cells in row-major order. A rule without a rulestring is `null`.
+    // Fails with `JsonError::TooManyCells` if there are more than
+    // `JSON_MAX_CELLS` cells.
+    pub fn to_json(&self, bound: Option<(i64, i64, i64, i64)>) -> Result<String, JsonError> {
+        let cells = match bound {
+            Some(bound) => self.population_in(bound),
+            None => self.population(),
+        };
+        if cells > JSON_MAX_CELLS {
+            return Err(JsonError::TooManyCells(cells));
+        }
+        Ok(self.to_json_unlimited(bound))
+    }
+
+    // Same as `to_json`, without the limit on the number of cells.
+    pub fn to_json_unlimited(&self, bound: Option<(i64, i64, i64, i64)>) -> String {
+        let rule = match self.rule.rulestring() {
+            Some(rulestring) => Value::from(rulestring).to_string(),
+            None => "null".to_string(),
+        };
+        let mut json = format!(
+            "{{\"rule\": {}, \"generation\": {}, \"cells\": [",
+            rule, self.generation
+        );
+        if let Some(bound) = bound.or_else(|| self.bound()) {
+            let mut first = true;
+            self.for_living_cells_ordered(bound, |x, y| {
+                if !first {
+                    json.push_str(", ");
+                }
+                first = false;
+                write!(json, "[{}, {}]", x, y).unwrap();
+            });
+        }
+        json.push_str("]}");
+        json
+    }
+
+    // Reads a pattern written by `to_json`. The rule and the generation may
+    // be missing or `null`, and are then B3/S23 and 0.
+    pub fn from_json(json: &str) -> Result<Self, JsonError> {
+        let value: Value = serde_json::from_str(json)?;
+        let object = value
+            .as_object()
+            .ok_or(JsonError::Invalid("not an object"))?;
+        let rule: Rule = match object.get("rule").unwrap_or(&Value::Null) {
+            Value::Null => "B3/S23".parse().unwrap(),
+            Value::String(rulestring) => rulestring
+                .parse()
+                .map_err(|_| JsonError::Rule(rulestring.clone()))?,
+            _ => return Err(JsonError::Invalid("rule")),
+        };
+        let generation = match object.get("generation").unwrap_or(&Value::Null) {
+            Value::Null => 0,
+            generation => generation
+                .as_u64()
+                .ok_or(JsonError::Invalid("generation"))?,
+        };
+        let cells = object
+            .get("cells")
+            .and_then(Value::as_array)
+            .ok_or(JsonError::Invalid("cells"))?
+            .iter()
+            .map(|cell| match cell.as_array().map(Vec::as_slice) {
+                Some([x, y]) => Some((x.as_i64()?, y.as_i64()?)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(JsonError::Invalid("cells"))?;
+        let mut world = World::new(rule);
+        world.generation = generation;
+        world.set_cells(cells, true);
+        Ok(world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let mut world = World::from_bytes(b"x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!").unwrap();
+        world.set_generation(123);
+        let json = world.to_json(None).unwrap();
+        assert_eq!(
+            json,
+            r#"{"rule": "B3/S23", "generation": 123, "cells": [[1, 0], [2, 1], [0, 2], [1, 2], [2, 2]]}"#
+        );
+        let loaded = World::from_json(&json).unwrap();
+        assert_eq!(loaded.get_generation(), 123);
+        assert_eq!(loaded.cells_vec(), world.cells_vec());
+        assert_eq!(
+            World::default().to_json(None).unwrap(),
+            r#"{"rule": "B3/S23", "generation": 0, "cells": []}"#
+        );
+        assert_eq!(
+            world.to_json(Some((0, 3, 0, 2))).unwrap(),
+            r#"{"rule": "B3/S23", "generation": 123, "cells": [[1, 0], [2, 1]]}"#
+        );
+        let json = r#"{"rule": "B36/S23", "cells": [[1, 0], [2, 1], [0, 2], [1, 2], [2, 2]]}"#;
+        let loaded = World::from_json(json).unwrap();
+        assert_eq!(loaded.population(), 5);
+        assert_eq!(loaded.get_rule().rulestring(), Some("B36/S23"));
+        assert_eq!(loaded.get_generation(), 0);
+
+        world.fill_region((0, 1025, 0, 1025), true);
+        assert!(matches!(
+            world.to_json(None),
+            Err(JsonError::TooManyCells(1050625))
+        ));
+        assert!(world.to_json(Some((0, 10, 0, 10))).is_ok());
+
+        assert!(matches!(World::from_json("[]"), Err(JsonError::Invalid(_))));
+        assert!(matches!(
+            World::from_json(r#"{"cells": [[0, 0, 0]]}"#),
+            Err(JsonError::Invalid("cells"))
+        ));
+        assert!(matches!(
+            World::from_json(r#"{"rule": "B9", "cells": []}"#),
+            Err(JsonError::Rule(_))
+        ));
+        assert!(matches!(World::from_json("{"), Err(JsonError::Json(_))));
+    }
+}
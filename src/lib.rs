@@ -1,9 +1,71 @@
+mod activity;
+mod age;
+mod apgcode;
+mod arena;
+#[cfg(feature = "ndarray")]
+mod array;
+#[cfg(feature = "background-gc")]
+mod background_gc;
+mod block_stats;
+mod boolean;
+mod census;
+mod components;
+mod cycle;
+mod draw;
+mod envelope;
 mod evolve;
+mod fingerprint;
+mod frozen;
+mod growth;
+#[cfg(feature = "http")]
+pub mod http;
+mod interaction;
 mod iter;
+#[cfg(feature = "serde_json")]
+mod json;
+mod matching;
+mod metadata;
+mod population;
+mod profile;
 mod read;
+mod recording;
 mod rule;
+mod schedule;
+pub mod search;
+mod small;
+mod snapshot;
+mod store;
+#[cfg(feature = "strip-table")]
+mod strip;
+#[cfg(feature = "experimental-symmetry")]
+mod symmetry;
+mod time_index;
+mod trajectory;
+pub mod view;
 mod world;
+mod write;
 
+pub use apgcode::InvalidApgcode;
+pub use block_stats::{BlockStats, DENSITY_BUCKETS};
 pub use ca_formats;
-pub use rule::Rule;
-pub use world::World;
+pub use census::{object_name, Census};
+pub use components::Connectivity;
+pub use evolve::{StepError, StepSummary};
+#[cfg(feature = "gz")]
+pub use flate2::Compression;
+pub use frozen::FrozenWorld;
+pub use growth::{Growth, GrowthEstimate};
+pub use iter::{Leaves, LivingCells};
+#[cfg(feature = "serde_json")]
+pub use json::{JsonError, JSON_MAX_CELLS};
+pub use matching::{Match, Transform};
+pub use metadata::PatternMetadata;
+pub use population::RunExtremes;
+pub use read::{Format, LoadError, LoadOptions, LoadProgress, MacrocellOrigin};
+pub use recording::{InvalidRecording, Op, Recording};
+pub use rule::{InvalidRule, InvalidTable, Rule};
+pub use schedule::{Edit, EditId, InvalidEdit};
+pub use small::SmallWorld;
+pub use snapshot::Snapshot;
+pub use store::NodeStore;
+pub use world::{Cells, Stats, World};
@@ -0,0 +1,229 @@
+use crate::world::World;
+use rustc_hash::FxHashSet;
+
+type Cell = (i64, i64);
+type Bound = (i64, i64, i64, i64);
+
+// The eight rotations and reflections of the plane, with `y` going down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Transform {
+    Identity,
+    // Clockwise.
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    // Mirrored left to right.
+    FlipX,
+    // Mirrored top to bottom.
+    FlipY,
+    // Mirrored along the diagonal from the top-left.
+    Transpose,
+    // Mirrored along the diagonal from the top-right.
+    AntiTranspose,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 8] = [
+        Transform::Identity,
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::FlipX,
+        Transform::FlipY,
+        Transform::Transpose,
+        Transform::AntiTranspose,
+    ];
+
+    // Where the cell goes, around the origin.
+    pub fn apply(self, (x, y): Cell) -> Cell {
+        match self {
+            Transform::Identity => (x, y),
+            Transform::Rotate90 => (-y, x),
+            Transform::Rotate180 => (-x, -y),
+            Transform::Rotate270 => (y, -x),
+            Transform::FlipX => (-x, y),
+            Transform::FlipY => (x, -y),
+            Transform::Transpose => (y, x),
+            Transform::AntiTranspose => (-y, -x),
+        }
+    }
+}
+
+// A match of `World::find_pattern` or `World::find_pattern_d8`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Match {
+    // The top-left corner of the bounding box of the match.
+    pub offset: Cell,
+    // How the needle is transformed in this match.
+    pub transform: Transform,
+}
+
+// A needle in one orientation: its cells, with the top-left corner of their
+// bounding box at the origin, in row-major order, and the size of the box.
+struct Orientation {
+    transform: Transform,
+    cells: Vec<Cell>,
+    size: Cell,
+}
+
+impl Orientation {
+    fn new(needle: &World, transform: Transform) -> Option<Self> {
+        let mut cells = needle
+            .cells_vec()
+            .into_iter()
+            .map(|cell| transform.apply(cell))
+            .collect::<Vec<_>>();
+        let left = cells.iter().map(|&(x, _)| x).min()?;
+        let top = cells.iter().map(|&(_, y)| y).min()?;
+        for (x, y) in &mut cells {
+            *x -= left;
+            *y -= top;
+        }
+        cells.sort_unstable_by_key(|&(x, y)| (y, x));
+        let width = cells.iter().map(|&(x, _)| x + 1).max()?;
+        let height = cells.last()?.1 + 1;
+        Some(Orientation {
+            transform,
+            cells,
+            size: (width, height),
+        })
+    }
+}
+
+impl World {
+    // A copy of the pattern, transformed around the origin.
+    pub fn transformed(&self, transform: Transform) -> World {
+        let mut world = World::new_with_step(self.rule.clone(), self.step);
+        world.generation = self.generation;
+        world.set_cells(
+            self.cells_vec()
+                .into_iter()
+                .map(|cell| transform.apply(cell)),
+            true,
+        );
+        world
+    }
+
+    // The positions where the pattern is exactly the needle, inside the
+    // bounding box of the needle, i.e., the living cells of the needle are
+    // alive, and the other cells of its box are dead. Only matches entirely
+    // inside the bound `(left, right, top, bottom)`, or the whole pattern,
+    // are found. The matches are in row-major order.
+    pub fn find_pattern(&self, needle: &World, bound: Option<Bound>) -> Vec<Match> {
+        let orientations = Orientation::new(needle, Transform::Identity);
+        self.find_orientations(orientations.as_slice(), bound)
+    }
+
+    // Same as `find_pattern`, but also finds the needle rotated and
+    // reflected. Orientations that are the same as an earlier one in
+    // `Transform::ALL` are skipped, so that a symmetric needle is only
+    // matched once at each position, e.g. a block with `Identity`.
+    pub fn find_pattern_d8(&self, needle: &World, bound: Option<Bound>) -> Vec<Match> {
+        self.find_orientations(&distinct_orientations(needle), bound)
+    }
+
+    fn find_orientations(&self, orientations: &[Orientation], bound: Option<Bound>) -> Vec<Match> {
+        let (left, right, top, bottom) = match bound.or_else(|| self.bound()) {
+            Some(bound) if !orientations.is_empty() => bound,
+            _ => return Vec::new(),
+        };
+        let mut living = FxHashSet::default();
+        self.for_living_cells((left, right, top, bottom), |x, y| {
+            living.insert((x, y));
+        });
+        // Every match has its first cell on a living cell, whatever the
+        // orientation.
+        let mut matches = Vec::new();
+        for &(x, y) in &living {
+            for orientation in orientations {
+                let (first_x, first_y) = orientation.cells[0];
+                let offset = (x - first_x, y - first_y);
+                let (width, height) = orientation.size;
+                let area = (offset.0, offset.0 + width, offset.1, offset.1 + height);
+                if area.0 < left || area.1 > right || area.2 < top || area.3 > bottom {
+                    continue;
+                }
+                let found = orientation
+                    .cells
+                    .iter()
+                    .all(|&(dx, dy)| living.contains(&(offset.0 + dx, offset.1 + dy)))
+                    && self.population_in(area) == orientation.cells.len() as u64;
+                if found {
+                    matches.push(Match {
+                        offset,
+                        transform: orientation.transform,
+                    });
+                }
+            }
+        }
+        matches.sort_unstable_by_key(|m| ((m.offset.1, m.offset.0), m.transform));
+        matches
+    }
+}
+
+fn distinct_orientations(needle: &World) -> Vec<Orientation> {
+    let mut orientations: Vec<Orientation> = Vec::new();
+    for &transform in &Transform::ALL {
+        if let Some(orientation) = Orientation::new(needle, transform) {
+            if orientations
+                .iter()
+                .all(|other| other.cells != orientation.cells)
+            {
+                orientations.push(orientation);
+            }
+        }
+    }
+    orientations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orientations(rle: &str) -> usize {
+        distinct_orientations(&World::from_bytes(rle.as_bytes()).unwrap()).len()
+    }
+
+    #[test]
+    fn test_distinct_orientations() {
+        assert_eq!(orientations("2o$2o!"), 1);
+        assert_eq!(orientations("3o!"), 2);
+        // A boat is symmetric along a diagonal.
+        assert_eq!(orientations("2o$obo$bo!"), 4);
+        // No phase of the glider is symmetric: its mirror images are gliders
+        // in another phase.
+        assert_eq!(orientations("bo$2bo$3o!"), 8);
+        assert_eq!(orientations("obo$b2o$bo!"), 8);
+        assert_eq!(orientations("!"), 0);
+    }
+
+    #[test]
+    fn test_find_pattern_d8() {
+        let glider = World::from_bytes(b"obo$b2o$bo!").unwrap();
+        let mut world = World::default();
+        for (i, &transform) in [Transform::Identity, Transform::Rotate90, Transform::FlipY]
+            .iter()
+            .enumerate()
+        {
+            world.paste(&glider.transformed(transform), (20 * i as i64, 0));
+        }
+        // A glider with a block in its bounding box is not an exact match.
+        world
+            .paste(&glider, (0, 20))
+            .set_cells([(2, 22), (3, 22), (2, 23), (3, 23)], true);
+
+        let matches = world.find_pattern_d8(&glider, None);
+        let offsets = matches.iter().map(|m| m.offset).collect::<Vec<_>>();
+        assert_eq!(offsets, [(40, -2), (0, 0), (18, 0)]);
+        assert_eq!(matches[0].transform, Transform::FlipY);
+        assert_eq!(matches[1].transform, Transform::Identity);
+        assert_eq!(world.find_pattern(&glider, None).len(), 1);
+        assert_eq!(
+            world
+                .find_pattern_d8(&glider, Some((10, 100, -10, 10)))
+                .len(),
+            2
+        );
+        assert!(world.find_pattern_d8(&World::default(), None).is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+use crate::world::World;
+use std::io::{Result as IoResult, Write};
+
+// The comment that the writers append, with the generation after it. Older
+// ones are not written again, so that they do not pile up.
+const SAVED_AT: &str = "Saved by hashlife at generation ";
+
+// The `#N`, `#O` and `#C` lines of an RLE or Macrocell pattern, kept by the
+// loaders that read the raw lines, e.g. `from_bytes` and `from_file`, and
+// written back by `write_rle` and `write_macrocell`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatternMetadata {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    // In the order of the lines, including `#c` ones.
+    pub comments: Vec<String>,
+}
+
+impl PatternMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.author.is_none() && self.comments.is_empty()
+    }
+
+    // Keeps the line if it is a name, an author or a comment. Other lines
+    // are ignored, including `#CXRLE`. A repeated name or author replaces
+    // the previous one.
+    pub(crate) fn read_line(&mut self, line: &[u8]) {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let text = |rest: &[u8]| String::from_utf8_lossy(rest).trim().to_string();
+        if let Some(rest) = line.strip_prefix(b"#N") {
+            self.name = Some(text(rest));
+        } else if let Some(rest) = line.strip_prefix(b"#O") {
+            self.author = Some(text(rest));
+        } else if let Some(rest) = line
+            .strip_prefix(b"#C")
+            .or_else(|| line.strip_prefix(b"#c"))
+        {
+            if !line.starts_with(b"#CXRLE") {
+                self.comments.push(text(rest));
+            }
+        }
+    }
+
+    // Nothing is written for a world without metadata.
+    pub(crate) fn write<W: Write>(&self, writer: &mut W, generation: u64) -> IoResult<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        if let Some(name) = &self.name {
+            writeln!(writer, "#N {}", name)?;
+        }
+        if let Some(author) = &self.author {
+            writeln!(writer, "#O {}", author)?;
+        }
+        for comment in &self.comments {
+            if !comment.starts_with(SAVED_AT) {
+                writeln!(writer, "#C {}", comment)?;
+            }
+        }
+        writeln!(writer, "#C {}{}", SAVED_AT, generation)
+    }
+}
+
+impl World {
+    pub fn get_metadata(&self) -> &PatternMetadata {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: PatternMetadata) -> &mut Self {
+        self.metadata = metadata;
+        self
+    }
+}
@@ -0,0 +1,194 @@
+use crate::world::World;
+
+// The smallest and the largest population since tracking started, see
+// `World::track_extremes`, at their first generations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunExtremes {
+    pub min_pop: u64,
+    pub min_gen: u64,
+    pub max_pop: u64,
+    pub max_gen: u64,
+}
+
+impl World {
+    // Records `(generation, population)` whenever a step crosses a multiple
+    // of `every` generations, and now if the generation is one. A step of
+    // `2^k` generations is only sampled at its end, but `step_by` and
+    // `step_to` split their steps so that they land on every multiple. An
+    // `every` of `0` stops recording; the samples are kept until
+    // `clear_population_history`.
+    pub fn record_population(&mut self, every: u64) -> &mut Self {
+        self.population_every = every;
+        if every > 0 && self.generation.is_multiple_of(every) {
+            self.push_population();
+        }
+        self
+    }
+
+    // The recorded samples, oldest first. Rewinding does not remove them.
+    pub fn population_history(&self) -> &[(u64, u64)] {
+        &self.population_history
+    }
+
+    pub fn clear_population_history(&mut self) {
+        self.population_history.clear();
+    }
+
+    fn push_population(&mut self) {
+        let sample = (self.generation, self.population());
+        if self.population_history.last() != Some(&sample) {
+            self.population_history.push(sample);
+        }
+    }
+
+    // Called after each step from `previous` to the current generation.
+    pub(crate) fn sample_population(&mut self, previous: u64) {
+        let every = self.population_every;
+        if every > 0 && self.generation / every > previous / every {
+            self.push_population();
+        }
+        let (population, generation) = (self.population(), self.generation);
+        if let Some(extremes) = &mut self.extremes {
+            if population < extremes.min_pop {
+                extremes.min_pop = population;
+                extremes.min_gen = generation;
+            }
+            if population > extremes.max_pop {
+                extremes.max_pop = population;
+                extremes.max_gen = generation;
+            }
+        }
+    }
+
+    // How many of the `generations` to advance before the next multiple of
+    // `every`, so that `step_by` can sample there, or before the next
+    // scheduled edit, or only one generation if every generation must be
+    // stepped.
+    pub(crate) fn generations_to_sample(&self, generations: u64) -> u64 {
+        let generations = match self.population_every {
+            0 => generations,
+            every => generations.min(every - self.generation % every),
+        };
+        let generations = match self.generations_to_edit() {
+            Some(until) if until > 0 => generations.min(until),
+            _ => generations,
+        };
+        if self.single_generations() {
+            generations.min(1)
+        } else {
+            generations
+        }
+    }
+
+    // Tracks the smallest and the largest population from now on, starting
+    // with the current one, after each step, including the steps that
+    // `step_by` and `step_to` are split into.
+    //
+    // A step of `2^k` generations is only sampled at its end, so a peak or
+    // a dip inside it is missed. With `exact`, every generation is sampled:
+    // `step_by` steps one generation at a time, and so does `step` with a
+    // larger step size, which also clears the cache of the larger steps
+    // each time. This is much slower for long runs, but methuselah metrics,
+    // e.g. the peak population, need it.
+    pub fn track_extremes(&mut self, exact: bool) -> &mut Self {
+        self.exact_extremes = exact;
+        self.extremes = Some(self.current_extremes());
+        self
+    }
+
+    pub fn stop_tracking_extremes(&mut self) {
+        self.extremes = None;
+        self.exact_extremes = false;
+    }
+
+    // `None` unless tracking.
+    pub fn extremes(&self) -> Option<RunExtremes> {
+        self.extremes
+    }
+
+    // Starts again from the current population, if tracking.
+    pub fn reset_extremes(&mut self) {
+        if self.extremes.is_some() {
+            self.extremes = Some(self.current_extremes());
+        }
+    }
+
+    fn current_extremes(&self) -> RunExtremes {
+        let (population, generation) = (self.population(), self.generation);
+        RunExtremes {
+            min_pop: population,
+            min_gen: generation,
+            max_pop: population,
+            max_gen: generation,
+        }
+    }
+
+    pub(crate) fn exact_extremes(&self) -> bool {
+        self.exact_extremes && self.extremes.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_population() {
+        let mut world = World::from_bytes(b"bo$2bo$3o!").unwrap();
+        world.record_population(10);
+        world.step_by(35);
+        assert_eq!(
+            world.population_history(),
+            [(0, 5), (10, 5), (20, 5), (30, 5)]
+        );
+        // Steps of 16 generations are sampled at their ends.
+        world.set_step(4).step();
+        world.step();
+        assert_eq!(world.population_history()[4..], [(51, 5), (67, 5)]);
+
+        world.clear_population_history();
+        world.record_population(0).step_by(100);
+        assert!(world.population_history().is_empty());
+    }
+
+    // The R-pentomino, in steps of 16 generations.
+    fn r_pentomino_extremes(exact: bool) -> RunExtremes {
+        let mut world = World::from_bytes(b"b2o$2o$bo!").unwrap();
+        world.track_extremes(exact).set_step(4);
+        for _ in 0..70 {
+            world.step();
+        }
+        assert_eq!(world.get_generation(), 1120);
+        assert_eq!(world.get_step(), 4);
+        world.extremes().unwrap()
+    }
+
+    #[test]
+    fn test_extremes() {
+        let extremes = r_pentomino_extremes(true);
+        assert_eq!((extremes.min_pop, extremes.min_gen), (5, 0));
+        assert_eq!((extremes.max_pop, extremes.max_gen), (319, 821));
+        let extremes = r_pentomino_extremes(false);
+        assert!(extremes.max_pop < 319);
+        assert_eq!(extremes.max_gen % 16, 0);
+
+        let mut world = World::from_bytes(b"3o!").unwrap();
+        assert_eq!(world.extremes(), None);
+        world.track_extremes(true).step_by(3);
+        assert_eq!(
+            world.extremes(),
+            Some(RunExtremes {
+                min_pop: 3,
+                min_gen: 0,
+                max_pop: 3,
+                max_gen: 0
+            })
+        );
+        world.set_cell(5, 5, true).reset_extremes();
+        assert_eq!(world.extremes().unwrap().max_pop, 4);
+        world.step();
+        assert_eq!(world.extremes().unwrap().min_gen, 4);
+        world.stop_tracking_extremes();
+        assert_eq!(world.extremes(), None);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::world::{Leaf, Node, NodeId, Nodes, World};
+use rustc_hash::FxHashMap;
+use std::collections::BTreeMap;
+
+// The populations of the nonempty rows or columns, sorted.
+type Profile = Vec<(i64, u64)>;
+
+#[derive(Clone, Copy)]
+enum Axis {
+    Row,
+    Column,
+}
+
+// Counts the cells of a leaf in each row or column, from -2 to 1.
+fn leaf_profile(leaf: Leaf, axis: Axis) -> Profile {
+    (-2..2)
+        .filter_map(|i| {
+            let mask = match axis {
+                Axis::Row => 0xf << ((1 - i) * 4),
+                Axis::Column => 0x1111 << (1 - i),
+            };
+            let count = (leaf & mask).count_ones() as u64;
+            (count > 0).then_some((i, count))
+        })
+        .collect()
+}
+
+// Adds two profiles, each moved by the offset.
+fn merge(a: &[(i64, u64)], b: &[(i64, u64)], offset: i64, profile: &mut Profile) {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let next = match (a.get(i), b.get(j)) {
+            (Some(&(x, m)), Some(&(y, n))) if x == y => {
+                i += 1;
+                j += 1;
+                (x, m + n)
+            }
+            (Some(&(x, m)), Some(&(y, _))) if x < y => {
+                i += 1;
+                (x, m)
+            }
+            (Some(&(x, m)), None) => {
+                i += 1;
+                (x, m)
+            }
+            (_, Some(&(y, n))) => {
+                j += 1;
+                (y, n)
+            }
+            (None, None) => unreachable!(),
+        };
+        profile.push((next.0 + offset, next.1));
+    }
+}
+
+struct Profiler<'a> {
+    nodes: &'a Nodes,
+    axis: Axis,
+    // The profiles of the nodes that are entirely inside the bound.
+    cache: FxHashMap<NodeId, Profile>,
+    counts: BTreeMap<i64, u64>,
+}
+
+impl Profiler<'_> {
+    // The profile of a whole node, relative to its center.
+    fn whole(&mut self, id: NodeId) -> &Profile {
+        if !self.cache.contains_key(&id) {
+            let nodes = self.nodes;
+            let data = &nodes[id];
+            let half = 1 << (data.level() - 2);
+            let (first, second) = match self.axis {
+                Axis::Row => ((data.nw(), data.ne()), (data.sw(), data.se())),
+                Axis::Column => ((data.nw(), data.sw()), (data.ne(), data.se())),
+            };
+            let mut profile = Vec::new();
+            for ((a, b), offset) in [(first, -half), (second, half)] {
+                let (a, b) = (self.child(a), self.child(b));
+                merge(&a, &b, offset, &mut profile);
+            }
+            self.cache.insert(id, profile);
+        }
+        &self.cache[&id]
+    }
+
+    fn child(&mut self, node: Node) -> Profile {
+        match node {
+            Node::Leaf(leaf) => leaf_profile(leaf, self.axis),
+            Node::NodeId(_) if self.nodes.node_population(node) == 0 => Vec::new(),
+            Node::NodeId(id) => self.whole(id).clone(),
+        }
+    }
+
+    // Bound: (left, right, top, bottom), relative to the center of the node,
+    // which is at `origin` along the axis.
+    fn add(&mut self, node: Node, bound: (i64, i64, i64, i64), origin: i64) {
+        if self.nodes.node_population(node) == 0 {
+            return;
+        }
+        let node_size = 1 << (self.nodes.node_level(node) - 2);
+        let (left, right, top, bottom) = bound;
+        let left = left.max(-2 * node_size);
+        let right = right.min(2 * node_size);
+        let top = top.max(-2 * node_size);
+        let bottom = bottom.min(2 * node_size);
+        if left >= right || top >= bottom {
+            return;
+        }
+        let whole = left == -2 * node_size
+            && right == 2 * node_size
+            && top == -2 * node_size
+            && bottom == 2 * node_size;
+        let profile = match node {
+            Node::Leaf(leaf) => {
+                let mut mask: Leaf = 0;
+                for y in top..bottom {
+                    for x in left..right {
+                        mask |= 1 << ((1 - y) * 4 + (1 - x));
+                    }
+                }
+                leaf_profile(leaf & mask, self.axis)
+            }
+            Node::NodeId(id) if whole => self.whole(id).clone(),
+            Node::NodeId(id) => {
+                let nodes = self.nodes;
+                let data = &nodes[id];
+                let (dx, dy) = match self.axis {
+                    Axis::Row => (0, 1),
+                    Axis::Column => (1, 0),
+                };
+                for (child, sx, sy) in [
+                    (data.nw(), -1, -1),
+                    (data.ne(), 1, -1),
+                    (data.sw(), -1, 1),
+                    (data.se(), 1, 1),
+                ] {
+                    let bound = (
+                        left - sx * node_size,
+                        right - sx * node_size,
+                        top - sy * node_size,
+                        bottom - sy * node_size,
+                    );
+                    self.add(child, bound, origin + (dx * sx + dy * sy) * node_size);
+                }
+                return;
+            }
+        };
+        for (i, count) in profile {
+            *self.counts.entry(origin + i).or_insert(0) += count;
+        }
+    }
+}
+
+impl World {
+    // Bound: (left, right, top, bottom).
+    // The population of each row of the bound that has living cells, as
+    // `(y, population)`, from top to bottom, e.g. to find the active front of
+    // a wickstretcher, or to crop a render.
+    //
+    // Nodes that cross the edges of the bound are descended, like in
+    // `population_in`. The profile of a node that is entirely inside is
+    // built from those of its children, once for each distinct node, in a
+    // cache that only lasts for the call. So the time is about the number of
+    // distinct nodes inside the bound times their nonempty rows, rather than
+    // the number of cells: repetitive patterns are fast, chaotic ones take
+    // about as long as visiting every leaf.
+    pub fn row_populations(&self, bound: (i64, i64, i64, i64)) -> Vec<(i64, u64)> {
+        self.profile(bound, Axis::Row)
+    }
+
+    // Bound: (left, right, top, bottom).
+    // Same as `row_populations`, for each column, as `(x, population)`, from
+    // left to right.
+    pub fn column_populations(&self, bound: (i64, i64, i64, i64)) -> Vec<(i64, u64)> {
+        self.profile(bound, Axis::Column)
+    }
+
+    fn profile(&self, bound: (i64, i64, i64, i64), axis: Axis) -> Profile {
+        let nodes = self.nodes();
+        let mut profiler = Profiler {
+            nodes: &nodes,
+            axis,
+            cache: FxHashMap::default(),
+            counts: BTreeMap::new(),
+        };
+        profiler.add(self.root, bound, 0);
+        profiler.counts.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_force(world: &World, bound: (i64, i64, i64, i64), axis: Axis) -> Profile {
+        let mut counts = BTreeMap::new();
+        for (x, y) in world.cells_vec() {
+            if x >= bound.0 && x < bound.1 && y >= bound.2 && y < bound.3 {
+                let i = match axis {
+                    Axis::Row => y,
+                    Axis::Column => x,
+                };
+                *counts.entry(i).or_insert(0) += 1;
+            }
+        }
+        counts.into_iter().collect()
+    }
+
+    #[test]
+    fn test_profile() {
+        // A Gosper glider gun after 300 generations, and a filled square.
+        let mut world = World::from_bytes(
+            b"24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$2o8bo3bob2o4b\
+              obo$10bo5bo7bo$11bo3bo$12b2o!",
+        )
+        .unwrap();
+        world.step_by(300);
+        world.fill_region((-100, -60, 30, 50), true);
+        for bound in [
+            (-1000, 1000, -1000, 1000),
+            (-80, 20, 0, 40),
+            (3, 17, -5, 9),
+            (0, 0, 0, 10),
+        ] {
+            assert_eq!(
+                world.row_populations(bound),
+                brute_force(&world, bound, Axis::Row)
+            );
+            assert_eq!(
+                world.column_populations(bound),
+                brute_force(&world, bound, Axis::Column)
+            );
+        }
+        assert_eq!(world.row_populations((-100, -60, 30, 31)), [(30, 40)]);
+        assert!(World::default()
+            .row_populations((-10, 10, -10, 10))
+            .is_empty());
+    }
+}
@@ -1,32 +1,616 @@
-use crate::world::{Leaf, Node, World};
+use crate::{
+    metadata::PatternMetadata,
+    rule::{InvalidRule, Rule},
+    world::{Leaf, Node, NodeId, World},
+};
 use ca_formats::{
     macrocell::{Error as MacrocellError, Macrocell, NodeData},
+    plaintext::{Error as PlaintextError, Plaintext},
     rle::{Error as RleError, Rle},
     Input,
 };
+#[cfg(feature = "gz")]
+use flate2::bufread::GzDecoder;
+use std::{
+    cell::Cell,
+    convert::TryFrom,
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::{BufRead, BufReader, Cursor, Error as IoError, Read, Result as IoResult},
+    ops::ControlFlow,
+    rc::Rc,
+};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::{fs::File, path::Path};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+// The progress callback is called every this many cells or nodes.
+const PROGRESS_INTERVAL: u64 = 1 << 12;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(IoError),
+    Rle(RleError),
+    Macrocell(MacrocellError),
+    Plaintext(PlaintextError),
+    // The rule of the pattern cannot be used, see `LoadOptions::strict_rule`.
+    Rule(InvalidRule),
+    // The line, counting from 1, is not a pair of coordinates.
+    Life106(usize),
+    // The input is gzipped, but the `gz` feature is disabled.
+    GzipNotSupported,
+    // The input contains no data at all.
+    Empty,
+    // The progress callback asked to stop.
+    Cancelled,
+    // The download failed, or the server answered with an error status.
+    #[cfg(feature = "http")]
+    Http(Box<ureq::Error>),
+    // The download is larger than the limit, in bytes.
+    #[cfg(feature = "http")]
+    TooLarge(u64),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Rle(e) => write!(f, "Invalid RLE: {}", e),
+            LoadError::Macrocell(e) => write!(f, "Invalid Macrocell: {}", e),
+            LoadError::Plaintext(e) => write!(f, "Invalid Plaintext: {}", e),
+            LoadError::Rule(e) => write!(f, "Invalid rule: {}", e),
+            LoadError::Life106(line) => write!(f, "Invalid Life 1.06 at line {}", line),
+            LoadError::GzipNotSupported => write!(f, "Gzipped patterns are not supported"),
+            LoadError::Empty => write!(f, "The input is empty"),
+            LoadError::Cancelled => write!(f, "Loading was cancelled"),
+            #[cfg(feature = "http")]
+            LoadError::Http(e) => write!(f, "{}", e),
+            #[cfg(feature = "http")]
+            LoadError::TooLarge(limit) => write!(f, "The download exceeds {} bytes", limit),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            LoadError::Rle(e) => Some(e),
+            LoadError::Macrocell(e) => Some(e),
+            LoadError::Plaintext(e) => Some(e),
+            LoadError::Rule(e) => Some(e),
+            #[cfg(feature = "http")]
+            LoadError::Http(e) => Some(e),
+            #[cfg(feature = "http")]
+            LoadError::TooLarge(_) => None,
+            LoadError::Life106(_)
+            | LoadError::GzipNotSupported
+            | LoadError::Empty
+            | LoadError::Cancelled => None,
+        }
+    }
+}
+
+impl From<IoError> for LoadError {
+    fn from(e: IoError) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<RleError> for LoadError {
+    fn from(e: RleError) -> Self {
+        LoadError::Rle(e)
+    }
+}
+
+impl From<MacrocellError> for LoadError {
+    fn from(e: MacrocellError) -> Self {
+        LoadError::Macrocell(e)
+    }
+}
+
+impl From<PlaintextError> for LoadError {
+    fn from(e: PlaintextError) -> Self {
+        LoadError::Plaintext(e)
+    }
+}
+
+impl From<InvalidRule> for LoadError {
+    fn from(e: InvalidRule) -> Self {
+        LoadError::Rule(e)
+    }
+}
+
+// How far loading has come, given to the callbacks of the `*_with_progress`
+// functions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    // Cells of an RLE, or nodes of a Macrocell, read so far.
+    pub items: u64,
+    // Bytes of the input read so far, including the ones that are buffered.
+    // Only known to `from_reader_with_progress`; for a gzipped input, these
+    // are compressed bytes.
+    pub bytes: Option<u64>,
+}
+
+// Options of `from_reader_with_options`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadOptions {
+    // Centers RLE patterns at the origin, as in `from_rle_centered`.
+    pub center_rle: bool,
+    // Where Macrocell patterns are placed.
+    pub macrocell_origin: MacrocellOrigin,
+    // Fails with `LoadError::Rule` if the rule of an RLE or Macrocell
+    // pattern cannot be parsed or is not supported, e.g. B0, instead of
+    // falling back to B3/S23.
+    pub strict_rule: bool,
+}
+
+// Where the root node of a Macrocell pattern is placed. Writing a pattern with
+// `write_macrocell_with_origin` and loading it with the same origin keeps its
+// position.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MacrocellOrigin {
+    // The center of the root node is at the origin: a root of size `2^n`
+    // covers `-2^(n-1) <= x, y < 2^(n-1)`.
+    #[default]
+    Center,
+    // Golly's placement, one row lower than `Center`, since Golly's quadtree
+    // counts `y` upwards, and then negates it. The pattern gets the same
+    // coordinates as in Golly, and as in the `#CXRLE` line of an RLE that
+    // Golly writes for it.
+    Golly,
+}
+
+// Calls the callback every `PROGRESS_INTERVAL` items.
+struct Progress<F> {
+    items: u64,
+    bytes: Option<Rc<Cell<u64>>>,
+    callback: F,
+    // The callback asked to stop. The partial world is then discarded by
+    // `run`.
+    cancelled: bool,
+}
+
+impl<F: FnMut(LoadProgress) -> ControlFlow<()>> Progress<F> {
+    fn new(callback: F) -> Self {
+        Progress {
+            items: 0,
+            bytes: None,
+            callback,
+            cancelled: false,
+        }
+    }
+
+    // Returns `false` if the callback asks to stop.
+    fn tick(&mut self) -> bool {
+        self.items += 1;
+        if !self.items.is_multiple_of(PROGRESS_INTERVAL) {
+            return true;
+        }
+        let progress = LoadProgress {
+            items: self.items,
+            bytes: self.bytes.as_ref().map(|bytes| bytes.get()),
+        };
+        self.cancelled = (self.callback)(progress).is_break();
+        !self.cancelled
+    }
+
+    // Runs `read`, and discards its world if it was cancelled.
+    fn run<E, R>(&mut self, read: R) -> Result<World, LoadError>
+    where
+        E: Into<LoadError>,
+        R: FnOnce(&mut Self) -> Result<World, E>,
+    {
+        let result = read(self);
+        if self.cancelled {
+            return Err(LoadError::Cancelled);
+        }
+        result.map_err(Into::into)
+    }
+}
+
+// Parses a level 3 node of Macrocell, e.g. `$$..*$...*$.***$$$$`, where `$`
+// ends a row, and missing cells at the ends of the rows and at the bottom are
+// dead.
+fn parse_level3(line: &[u8]) -> Option<u64> {
+    let mut node = 0;
+    let (mut x, mut y) = (0, 0);
+    for &c in line {
+        match c {
+            b'.' => x += 1,
+            b'*' => {
+                if x >= 8 || y >= 8 {
+                    return None;
+                }
+                node |= 1 << ((7 - y) * 8 + (7 - x));
+                x += 1;
+            }
+            b'$' => {
+                x = 0;
+                y += 1;
+            }
+            c if c.is_ascii_whitespace() => {}
+            _ => return None,
+        }
+    }
+    Some(node)
+}
+
+// Parses a decimal number, without allocating.
+fn parse_number<T: TryFrom<u64>>(bytes: &[u8]) -> Option<T> {
+    if bytes.is_empty() {
+        return None;
+    }
+    let mut n = 0u64;
+    for &c in bytes {
+        if !c.is_ascii_digit() {
+            return None;
+        }
+        n = n.checked_mul(10)?.checked_add((c - b'0') as u64)?;
+    }
+    T::try_from(n).ok()
+}
+
+// Parses `level nw ne sw se` of a node of Macrocell. Anything after them is
+// ignored.
+fn parse_node_line(line: &[u8]) -> Option<[usize; 5]> {
+    let mut words = line
+        .split(|c| c.is_ascii_whitespace())
+        .filter(|word| !word.is_empty());
+    let mut numbers = [0; 5];
+    for number in &mut numbers {
+        *number = parse_number(words.next()?)?;
+    }
+    Some(numbers)
+}
+
+// Fails if the rulestring in the header of a pattern, if any, cannot be
+// parsed, see `LoadOptions::strict_rule`.
+fn check_rule(rulestring: Option<&str>) -> Result<(), InvalidRule> {
+    match rulestring {
+        Some(rulestring) => rulestring.parse::<Rule>().map(drop),
+        None => Ok(()),
+    }
+}
+
+// Reads the lines at the beginning of an RLE or Macrocell pattern that
+// start with `#`, which the parsers of `ca_formats` skip, for their metadata.
+// The lines are returned, to be given to the parsers before the rest.
+fn read_metadata<B: BufRead>(reader: &mut B) -> IoResult<(Cursor<Vec<u8>>, PatternMetadata)> {
+    let mut head = Vec::new();
+    let mut metadata = PatternMetadata::default();
+    loop {
+        let buffer = reader.fill_buf()?;
+        if !(buffer.starts_with(b"#") || buffer.starts_with(b"[M2]")) {
+            break;
+        }
+        let start = head.len();
+        reader.read_until(b'\n', &mut head)?;
+        let line = &head[start..];
+        metadata.read_line(line.strip_suffix(b"\n").unwrap_or(line));
+    }
+    Ok((Cursor::new(head), metadata))
+}
+
+// Counts the bytes read, for `LoadProgress::bytes`.
+struct CountingReader<R> {
+    reader: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let n = self.reader.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+// The pattern formats that can be read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Rle,
+    Macrocell,
+    Plaintext,
+    Life106,
+}
+
+impl Format {
+    // Detects the format from the beginning of an uncompressed pattern.
+    // Plaintext starts with a `!` comment, or with a row of `.` and `O`;
+    // anything that is not recognized is taken as RLE.
+    pub fn detect(buffer: &[u8]) -> Self {
+        let first_line = buffer.split(|&c| c == b'\n').next().unwrap_or_default();
+        let first_line = first_line.strip_suffix(b"\r").unwrap_or(first_line);
+        if buffer.starts_with(b"[M2]") {
+            Format::Macrocell
+        } else if buffer.starts_with(b"#Life 1.06") {
+            Format::Life106
+        } else if buffer.starts_with(b"!")
+            || (!first_line.is_empty() && first_line.iter().all(|c| b".O*".contains(c)))
+        {
+            Format::Plaintext
+        } else {
+            Format::Rle
+        }
+    }
+}
 
 impl World {
+    // Loads an RLE, Macrocell, Plaintext or Life 1.06 pattern, possibly
+    // gzipped. The format is detected from the content, not from the file
+    // name.
+    //
+    // Not available on wasm32-unknown-unknown, which has no file system; use
+    // `from_bytes` or `from_reader` there.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    // Same as `from_file`, but reads from any reader.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, LoadError> {
+        Self::from_reader_with_progress(reader, |_| ControlFlow::Continue(()))
+    }
+
+    // Same as `from_reader`, but calls `callback` every few thousand cells of
+    // an RLE or nodes of a Macrocell, with the number of bytes read so far,
+    // and stops with `LoadError::Cancelled` if it returns `Break`. Plaintext
+    // and Life 1.06 are loaded without progress.
+    pub fn from_reader_with_progress<R, F>(reader: R, callback: F) -> Result<Self, LoadError>
+    where
+        R: Read,
+        F: FnMut(LoadProgress) -> ControlFlow<()>,
+    {
+        Self::from_reader_with_options(reader, LoadOptions::default(), callback)
+    }
+
+    // Same as `from_reader_with_progress`, with options.
+    pub fn from_reader_with_options<R, F>(
+        reader: R,
+        options: LoadOptions,
+        callback: F,
+    ) -> Result<Self, LoadError>
+    where
+        R: Read,
+        F: FnMut(LoadProgress) -> ControlFlow<()>,
+    {
+        let count = Rc::new(Cell::new(0));
+        let reader = CountingReader {
+            reader,
+            count: count.clone(),
+        };
+        let mut progress = Progress::new(callback);
+        progress.bytes = Some(count);
+        let mut reader = BufReader::new(reader);
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            return Err(LoadError::Empty);
+        }
+        if buffer.starts_with(GZIP_MAGIC) {
+            #[cfg(feature = "gz")]
+            return Self::from_buf_read(
+                &mut BufReader::new(GzDecoder::new(reader)),
+                options,
+                &mut progress,
+            );
+            #[cfg(not(feature = "gz"))]
+            return Err(LoadError::GzipNotSupported);
+        }
+        Self::from_buf_read(&mut reader, options, &mut progress)
+    }
+
+    // Same as `from_file`, but reads from a buffer in memory. An uncompressed
+    // Macrocell pattern is read with `from_macrocell_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LoadError> {
+        Self::from_bytes_with_progress(bytes, |_| ControlFlow::Continue(()))
+    }
+
+    // Same as `from_bytes`, but with a callback as in
+    // `from_reader_with_progress`.
+    pub fn from_bytes_with_progress<F>(bytes: &[u8], callback: F) -> Result<Self, LoadError>
+    where
+        F: FnMut(LoadProgress) -> ControlFlow<()>,
+    {
+        if Format::detect(bytes) != Format::Macrocell {
+            return Self::from_reader_with_progress(bytes, callback);
+        }
+        Progress::new(callback).run(|progress| World::read_macrocell_bytes(bytes, progress))
+    }
+
+    // Same as `from_macrocell`, but much faster for a huge pattern, e.g. a
+    // memory-mapped file: the lines are parsed in place, without allocating,
+    // and the space for the nodes is reserved in advance. The input must not
+    // be compressed.
+    pub fn from_macrocell_bytes(bytes: &[u8]) -> Result<Self, MacrocellError> {
+        World::read_macrocell_bytes(bytes, &mut Progress::new(|_| ControlFlow::Continue(())))
+    }
+
+    // Each branch returns the result directly: a `World` is large, and in
+    // debug builds every temporary one takes its own space on the stack.
+    fn from_buf_read<B, F>(
+        reader: &mut B,
+        options: LoadOptions,
+        progress: &mut Progress<F>,
+    ) -> Result<Self, LoadError>
+    where
+        B: BufRead,
+        F: FnMut(LoadProgress) -> ControlFlow<()>,
+    {
+        match Format::detect(reader.fill_buf()?) {
+            Format::Macrocell => {
+                let (head, metadata) = read_metadata(reader)?;
+                let mut reader = head.chain(reader);
+                let macrocell = Macrocell::new(&mut reader)?;
+                if options.strict_rule {
+                    check_rule(macrocell.rule())?;
+                }
+                let origin = options.macrocell_origin;
+                progress
+                    .run(|progress| World::read_macrocell(macrocell, origin, metadata, progress))
+            }
+            Format::Life106 => World::from_life106(reader),
+            Format::Plaintext => {
+                World::from_plaintext(Plaintext::new(reader)?).map_err(LoadError::from)
+            }
+            Format::Rle => {
+                let (head, metadata) = read_metadata(reader)?;
+                let mut reader = head.chain(reader);
+                let rle = Rle::new(&mut reader)?;
+                if options.strict_rule {
+                    check_rule(rle.header_data().and_then(|header| header.rule.as_deref()))?;
+                }
+                let center = options.center_rle;
+                progress.run(|progress| World::read_rle(rle, center, metadata, progress))
+            }
+        }
+    }
+
     pub fn from_rle<I: Input>(rle: Rle<I>) -> Result<Self, RleError> {
-        let rule = rle
-            .header_data()
+        let mut progress = Progress::new(|_| ControlFlow::Continue(()));
+        World::read_rle(rle, false, PatternMetadata::default(), &mut progress)
+    }
+
+    // Same as `from_rle`, but moves the pattern by half the size in its
+    // header, so that it is centered at the origin, and the quadtree is no
+    // deeper than needed. A position given by a `#CXRLE` line is kept.
+    pub fn from_rle_centered<I: Input>(rle: Rle<I>) -> Result<Self, RleError> {
+        let mut progress = Progress::new(|_| ControlFlow::Continue(()));
+        World::read_rle(rle, true, PatternMetadata::default(), &mut progress)
+    }
+
+    // Same as `from_rle`, but calls `callback` every few thousand cells, and
+    // stops with `LoadError::Cancelled` if it returns `Break`.
+    pub fn from_rle_with_progress<I, F>(rle: Rle<I>, callback: F) -> Result<Self, LoadError>
+    where
+        I: Input,
+        F: FnMut(LoadProgress) -> ControlFlow<()>,
+    {
+        Progress::new(callback)
+            .run(|progress| World::read_rle(rle, false, PatternMetadata::default(), progress))
+    }
+
+    // Stops early if the callback asks to.
+    fn read_rle<I, F>(
+        rle: Rle<I>,
+        center: bool,
+        metadata: PatternMetadata,
+        progress: &mut Progress<F>,
+    ) -> Result<Self, RleError>
+    where
+        I: Input,
+        F: FnMut(LoadProgress) -> ControlFlow<()>,
+    {
+        let header = rle.header_data();
+        let rule = header
             .and_then(|header| header.rule.as_deref())
             .and_then(|rulestring| rulestring.parse().ok())
             .unwrap_or_else(|| "B3/S23".parse().unwrap());
+        let has_position = rle.cxrle_data().is_some_and(|cxrle| cxrle.pos.is_some());
+        let (dx, dy) = match header {
+            Some(header) if center && !has_position => {
+                (-(header.x as i64 / 2), -(header.y as i64 / 2))
+            }
+            _ => (0, 0),
+        };
         let mut world = World::new(rule);
+        world.metadata = metadata;
         for cell in rle {
             let (x, y) = cell?.position;
+            world.set_cell(x + dx, y + dy, true);
+            if !progress.tick() {
+                break;
+            }
+        }
+        Ok(world)
+    }
+
+    // Plaintext has no rule, so the rule is B3/S23.
+    pub fn from_plaintext<I: Input>(plaintext: Plaintext<I>) -> Result<Self, PlaintextError> {
+        let mut world = World::default();
+        for cell in plaintext {
+            let (x, y) = cell?;
             world.set_cell(x, y, true);
         }
         Ok(world)
     }
 
+    // Life 1.06 is a list of living cells, one `x y` pair per line, after a
+    // `#Life 1.06` header. Other lines starting with `#` are ignored. It has
+    // no rule, so the rule is B3/S23.
+    pub fn from_life106<B: BufRead>(reader: B) -> Result<Self, LoadError> {
+        let mut world = World::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut coordinates = line.split_whitespace().map(str::parse::<i64>);
+            match (coordinates.next(), coordinates.next(), coordinates.next()) {
+                (Some(Ok(x)), Some(Ok(y)), None) => {
+                    world.set_cell(x, y, true);
+                }
+                _ => return Err(LoadError::Life106(i + 1)),
+            }
+        }
+        Ok(world)
+    }
+
+    // The center of the root node is placed at the origin, see
+    // `MacrocellOrigin::Center`.
     pub fn from_macrocell<I: Input>(macrocell: Macrocell<I>) -> Result<Self, MacrocellError> {
+        Self::from_macrocell_with_origin(macrocell, MacrocellOrigin::Center)
+    }
+
+    pub fn from_macrocell_with_origin<I: Input>(
+        macrocell: Macrocell<I>,
+        origin: MacrocellOrigin,
+    ) -> Result<Self, MacrocellError> {
+        let mut progress = Progress::new(|_| ControlFlow::Continue(()));
+        World::read_macrocell(macrocell, origin, PatternMetadata::default(), &mut progress)
+    }
+
+    // Same as `from_macrocell`, but calls `callback` every few thousand
+    // nodes, and stops with `LoadError::Cancelled` if it returns `Break`.
+    pub fn from_macrocell_with_progress<I, F>(
+        macrocell: Macrocell<I>,
+        callback: F,
+    ) -> Result<Self, LoadError>
+    where
+        I: Input,
+        F: FnMut(LoadProgress) -> ControlFlow<()>,
+    {
+        Progress::new(callback).run(|progress| {
+            let origin = MacrocellOrigin::Center;
+            World::read_macrocell(macrocell, origin, PatternMetadata::default(), progress)
+        })
+    }
+
+    // Stops early, with an empty world, if the callback asks to.
+    fn read_macrocell<I, F>(
+        macrocell: Macrocell<I>,
+        origin: MacrocellOrigin,
+        metadata: PatternMetadata,
+        progress: &mut Progress<F>,
+    ) -> Result<Self, MacrocellError>
+    where
+        I: Input,
+        F: FnMut(LoadProgress) -> ControlFlow<()>,
+    {
         let rule = macrocell
             .rule()
             .and_then(|rulestring| rulestring.parse().ok())
             .unwrap_or_else(|| "B3/S23".parse().unwrap());
+        let generation = macrocell.gen().unwrap_or(0);
         let mut world = World::new(rule);
+        world.generation = generation;
+        world.metadata = metadata;
         let mut nodes = Vec::new();
+        // The levels of the nodes only grow by one at a time, so this grows
+        // as needed.
+        let mut empty = Vec::new();
         for node in macrocell {
             let node = node?;
             debug_assert_eq!(nodes.len() + 1, node.id);
@@ -36,33 +620,7 @@ impl World {
                         "Rules with more than 2 states are not supported".to_string(),
                     ))
                 }
-                NodeData::Level3(n) => {
-                    let nw = Node::Leaf(
-                        ((n & 0x_f000_0000_0000_0000) >> 48
-                            | (n & 0x_00f0_0000_0000_0000) >> 44
-                            | (n & 0x_0000_f000_0000_0000) >> 40
-                            | (n & 0x_0000_00f0_0000_0000) >> 36) as Leaf,
-                    );
-                    let ne = Node::Leaf(
-                        ((n & 0x_0f00_0000_0000_0000) >> 44
-                            | (n & 0x_000f_0000_0000_0000) >> 40
-                            | (n & 0x_0000_0f00_0000_0000) >> 36
-                            | (n & 0x_0000_000f_0000_0000) >> 32) as Leaf,
-                    );
-                    let sw = Node::Leaf(
-                        ((n & 0x_0000_0000_f000_0000) >> 16
-                            | (n & 0x_0000_0000_00f0_0000) >> 12
-                            | (n & 0x_0000_0000_0000_f000) >> 8
-                            | (n & 0x_0000_0000_0000_00f0) >> 4) as Leaf,
-                    );
-                    let se = Node::Leaf(
-                        ((n & 0x_0000_0000_0f00_0000) >> 12
-                            | (n & 0x_0000_0000_000f_0000) >> 8
-                            | (n & 0x_0000_0000_0000_0f00) >> 4
-                            | (n & 0x_0000_0000_0000_000f)) as Leaf,
-                    );
-                    world.find_node(nw, ne, sw, se)
-                }
+                NodeData::Level3(n) => world.find_level3(n),
                 NodeData::Node {
                     level,
                     nw,
@@ -70,34 +628,163 @@ impl World {
                     sw,
                     se,
                 } => {
-                    let nw = if nw == 0 {
-                        world.empty_node(level - 1)
-                    } else {
-                        Node::NodeId(nodes[nw - 1])
-                    };
-                    let ne = if ne == 0 {
-                        world.empty_node(level - 1)
-                    } else {
-                        Node::NodeId(nodes[ne - 1])
-                    };
-                    let sw = if sw == 0 {
-                        world.empty_node(level - 1)
-                    } else {
-                        Node::NodeId(nodes[sw - 1])
-                    };
-                    let se = if se == 0 {
-                        world.empty_node(level - 1)
-                    } else {
-                        Node::NodeId(nodes[se - 1])
-                    };
+                    if empty.len() < level as usize - 2 {
+                        empty = world.empty_nodes_below(level);
+                    }
+                    let nw = world.macrocell_child(&nodes, &empty, level, nw);
+                    let ne = world.macrocell_child(&nodes, &empty, level, ne);
+                    let sw = world.macrocell_child(&nodes, &empty, level, sw);
+                    let se = world.macrocell_child(&nodes, &empty, level, se);
                     world.find_node(nw, ne, sw, se)
                 }
             };
             nodes.push(node);
+            if !progress.tick() {
+                return Ok(world);
+            }
         }
         world.root = Node::NodeId(nodes.pop().unwrap());
+        if origin == MacrocellOrigin::Golly {
+            world.shift_row(true);
+        }
+        Ok(world)
+    }
+
+    // Accepts the same files as `Macrocell`, except that empty lines are
+    // skipped, and that references to missing nodes are errors.
+    fn read_macrocell_bytes<F>(
+        bytes: &[u8],
+        progress: &mut Progress<F>,
+    ) -> Result<Self, MacrocellError>
+    where
+        F: FnMut(LoadProgress) -> ControlFlow<()>,
+    {
+        let invalid_node = |line: &[u8]| {
+            MacrocellError::InvalidNodeLine(String::from_utf8_lossy(line).into_owned())
+        };
+        let mut lines = bytes
+            .split(|&c| c == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .peekable();
+        let mut rulestring = None;
+        let mut generation = 0;
+        let mut metadata = PatternMetadata::default();
+        while let Some(line) = lines.next_if(|line| line.is_empty() || b"[#".contains(&line[0])) {
+            metadata.read_line(line);
+            if let Some(rest) = line.strip_prefix(b"#R") {
+                rulestring = std::str::from_utf8(rest).ok().map(str::trim);
+            } else if let Some(rest) = line.strip_prefix(b"#G") {
+                generation = parse_number(rest.trim_ascii()).ok_or_else(|| {
+                    MacrocellError::InvalidHeaderLine(String::from_utf8_lossy(line).into_owned())
+                })?;
+            }
+        }
+        let rule = rulestring
+            .and_then(|rulestring| rulestring.parse().ok())
+            .unwrap_or_else(|| "B3/S23".parse().unwrap());
+        // At most one node per line.
+        let capacity = bytes.iter().filter(|&&c| c == b'\n').count() + 1;
+        let mut world = World::with_capacity(rule, capacity);
+        world.generation = generation;
+        world.metadata = metadata;
+        let mut nodes = Vec::with_capacity(capacity);
+        // The root is the last node, and no node is above it, so the empty
+        // nodes below it are made once, before the others.
+        let root_level = lines
+            .clone()
+            .rev()
+            .find(|line| !line.is_empty() && !b"#.*$".contains(&line[0]))
+            .and_then(parse_node_line)
+            .map_or(0, |[level, ..]| level.min(255) as u8);
+        let empty = world.empty_nodes_below(root_level);
+        let bytes_start = bytes.as_ptr() as usize;
+        let bytes_read = Rc::new(Cell::new(0));
+        progress.bytes = Some(bytes_read.clone());
+        for line in lines {
+            if line.is_empty() || line[0] == b'#' {
+                continue;
+            }
+            let node = if b".*$".contains(&line[0]) {
+                world.find_level3(parse_level3(line).ok_or_else(|| invalid_node(line))?)
+            } else {
+                let numbers = parse_node_line(line).ok_or_else(|| invalid_node(line))?;
+                let [level, nw, ne, sw, se] = numbers;
+                if level == 1 {
+                    return Err(MacrocellError::InvalidNodeLine(
+                        "Rules with more than 2 states are not supported".to_string(),
+                    ));
+                }
+                if !(4..=255).contains(&level) || numbers[1..].iter().any(|&id| id > nodes.len()) {
+                    return Err(invalid_node(line));
+                }
+                let level = level as u8;
+                let nw = world.macrocell_child(&nodes, &empty, level, nw);
+                let ne = world.macrocell_child(&nodes, &empty, level, ne);
+                let sw = world.macrocell_child(&nodes, &empty, level, sw);
+                let se = world.macrocell_child(&nodes, &empty, level, se);
+                world.find_node(nw, ne, sw, se)
+            };
+            nodes.push(node);
+            // The offset of the end of the line.
+            bytes_read.set((line.as_ptr() as usize - bytes_start + line.len()) as u64);
+            if !progress.tick() {
+                return Ok(world);
+            }
+        }
+        if let Some(root) = nodes.pop() {
+            world.root = Node::NodeId(root);
+        }
         Ok(world)
     }
+
+    // A level 3 node of Macrocell, as 64 bits in rows from the top, where the
+    // highest bit of each row is its leftmost cell.
+    fn find_level3(&mut self, n: u64) -> NodeId {
+        let nw = Node::Leaf(
+            ((n & 0x_f000_0000_0000_0000) >> 48
+                | (n & 0x_00f0_0000_0000_0000) >> 44
+                | (n & 0x_0000_f000_0000_0000) >> 40
+                | (n & 0x_0000_00f0_0000_0000) >> 36) as Leaf,
+        );
+        let ne = Node::Leaf(
+            ((n & 0x_0f00_0000_0000_0000) >> 44
+                | (n & 0x_000f_0000_0000_0000) >> 40
+                | (n & 0x_0000_0f00_0000_0000) >> 36
+                | (n & 0x_0000_000f_0000_0000) >> 32) as Leaf,
+        );
+        let sw = Node::Leaf(
+            ((n & 0x_0000_0000_f000_0000) >> 16
+                | (n & 0x_0000_0000_00f0_0000) >> 12
+                | (n & 0x_0000_0000_0000_f000) >> 8
+                | (n & 0x_0000_0000_0000_00f0) >> 4) as Leaf,
+        );
+        let se = Node::Leaf(
+            ((n & 0x_0000_0000_0f00_0000) >> 12
+                | (n & 0x_0000_0000_000f_0000) >> 8
+                | (n & 0x_0000_0000_0000_0f00) >> 4
+                | (n & 0x_0000_0000_0000_000f)) as Leaf,
+        );
+        self.find_node(nw, ne, sw, se)
+    }
+
+    // The empty nodes of levels 2 to `level - 1`, or none if `level` is
+    // lower than 3.
+    fn empty_nodes_below(&mut self, level: u8) -> Vec<Node> {
+        (2..level).map(|level| self.empty_node(level)).collect()
+    }
+
+    // A child of a Macrocell node of the given level: `0` is the empty node,
+    // found in `empty` from `empty_nodes_below`, and other numbers count the
+    // nodes from `1`.
+    fn macrocell_child(&mut self, nodes: &[NodeId], empty: &[Node], level: u8, id: usize) -> Node {
+        match id {
+            0 => empty
+                .get(level as usize - 3)
+                .copied()
+                .unwrap_or_else(|| self.empty_node(level - 1)),
+            _ => Node::NodeId(nodes[id - 1]),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -110,4 +797,212 @@ mod tests {
         let world = World::from_macrocell(macrocell).unwrap();
         assert_eq!(world.population(), 196);
     }
+
+    #[test]
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn test_from_file() {
+        let world = World::from_file("patterns/totalperiodic.mc").unwrap();
+        assert_eq!(world.population(), 196);
+        let world = World::from_reader(&b"x = 3, y = 3\nbo$2bo$3o!"[..]).unwrap();
+        assert_eq!(world.population(), 5);
+        #[cfg(feature = "gz")]
+        {
+            let world = World::from_file("patterns/demonoid-c512-hashlife-friendly.mc.gz").unwrap();
+            assert!(world.population() > 0);
+        }
+        assert!(matches!(
+            World::from_file("patterns/nonexistent.rle"),
+            Err(LoadError::Io(_))
+        ));
+        let macrocell = include_bytes!("../patterns/totalperiodic.mc");
+        assert_eq!(World::from_bytes(macrocell).unwrap().population(), 196);
+        assert!(matches!(World::from_bytes(b""), Err(LoadError::Empty)));
+    }
+
+    #[test]
+    fn test_from_macrocell_bytes() {
+        let mut patterns = vec![
+            include_bytes!("../patterns/totalperiodic.mc").to_vec(),
+            include_bytes!("../patterns/metapixel-galaxy.mc").to_vec(),
+        ];
+        #[cfg(feature = "gz")]
+        {
+            let gz = &include_bytes!("../patterns/demonoid-c512-hashlife-friendly.mc.gz")[..];
+            let mut bytes = Vec::new();
+            GzDecoder::new(gz).read_to_end(&mut bytes).unwrap();
+            patterns.push(bytes);
+        }
+        for bytes in patterns {
+            let expected = World::from_macrocell(Macrocell::new(&bytes[..]).unwrap()).unwrap();
+            let world = World::from_macrocell_bytes(&bytes).unwrap();
+            assert_eq!(world.root, expected.root);
+            assert_eq!(world.stats().nodes, expected.stats().nodes);
+            assert_eq!(world.population(), expected.population());
+            assert_eq!(world.get_generation(), expected.get_generation());
+            assert_eq!(
+                world.get_rule().rulestring(),
+                expected.get_rule().rulestring()
+            );
+        }
+        assert!(matches!(
+            World::from_macrocell_bytes(b"[M2]\n#G x\n"),
+            Err(MacrocellError::InvalidHeaderLine(_))
+        ));
+        assert!(matches!(
+            World::from_macrocell_bytes(b"[M2]\n$*$\n4 1 0 0 2\n"),
+            Err(MacrocellError::InvalidNodeLine(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_rle_centered() {
+        let corners = "x = 4000, y = 3000\no3998bo2999$3999bo!";
+        let world = World::from_rle(Rle::new(corners).unwrap()).unwrap();
+        assert_eq!(world.bound(), Some((0, 4000, 0, 3000)));
+        let world = World::from_rle_centered(Rle::new(corners).unwrap()).unwrap();
+        assert_eq!(world.bound(), Some((-2000, 2000, -1500, 1500)));
+        let options = LoadOptions {
+            center_rle: true,
+            ..LoadOptions::default()
+        };
+        let world = World::from_reader_with_options(corners.as_bytes(), options, |_| {
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(world.bound(), Some((-2000, 2000, -1500, 1500)));
+
+        let cxrle = "#CXRLE Pos=-1,-1\nx = 3, y = 3\nbo$2bo$3o!";
+        let world = World::from_rle_centered(Rle::new(cxrle).unwrap()).unwrap();
+        assert_eq!(world.bound(), Some((-1, 2, -1, 2)));
+    }
+
+    #[test]
+    fn test_macrocell_origin() {
+        // A glider in the north-west quadrant of a level 4 root, and the RLE
+        // that Golly writes for it.
+        let mc = "[M2] (golly 4.0)\n#R B3/S23\n$$..*$...*$.***$$$$\n4 1 0 0 0\n";
+        let rle = "#CXRLE Pos=-7,-5\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+        let from_rle = World::from_rle(Rle::new(rle).unwrap()).unwrap();
+        let world = World::from_macrocell(Macrocell::new(mc).unwrap()).unwrap();
+        assert_eq!(world.bound(), Some((-7, -4, -6, -3)));
+        let world =
+            World::from_macrocell_with_origin(Macrocell::new(mc).unwrap(), MacrocellOrigin::Golly)
+                .unwrap();
+        assert_eq!(world.bound(), from_rle.bound());
+        assert_eq!(world.cells_vec(), from_rle.cells_vec());
+        let options = LoadOptions {
+            macrocell_origin: MacrocellOrigin::Golly,
+            ..LoadOptions::default()
+        };
+        let world =
+            World::from_reader_with_options(mc.as_bytes(), options, |_| ControlFlow::Continue(()))
+                .unwrap();
+        assert_eq!(world.bound(), from_rle.bound());
+    }
+
+    #[test]
+    fn test_b0_rule() {
+        let rle = "x = 3, y = 3, rule = B0/S8\nbo$2bo$3o!\n";
+        let mc = "[M2] (golly 4.0)\n#R B0123478/S01234678\n$$..*$...*$.***$$$$\n4 1 0 0 0\n";
+        // Worlds are large, so they are checked one at a time.
+        let fallback = |world: Result<World, LoadError>| {
+            let world = world.unwrap();
+            assert_eq!(world.get_rule().rulestring(), Some("B3/S23"));
+            assert_eq!(world.population(), 5);
+        };
+        fallback(World::from_rle(Rle::new(rle).unwrap()).map_err(LoadError::from));
+        fallback(World::from_bytes(rle.as_bytes()));
+        fallback(World::from_macrocell(Macrocell::new(mc).unwrap()).map_err(LoadError::from));
+        fallback(World::from_macrocell_bytes(mc.as_bytes()).map_err(LoadError::from));
+        let options = LoadOptions {
+            strict_rule: true,
+            ..LoadOptions::default()
+        };
+        for pattern in [rle, mc] {
+            let result = World::from_reader_with_options(pattern.as_bytes(), options, |_| {
+                ControlFlow::Continue(())
+            });
+            assert!(matches!(result, Err(LoadError::Rule(InvalidRule::B0))));
+        }
+        let life = rle.replace("B0/S8", "B36/S23");
+        let world =
+            World::from_reader_with_options(
+                life.as_bytes(),
+                options,
+                |_| ControlFlow::Continue(()),
+            )
+            .unwrap();
+        assert_eq!(world.get_rule().rulestring(), Some("B36/S23"));
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(Format::detect(b"[M2] (golly 4.0)\n"), Format::Macrocell);
+        assert_eq!(Format::detect(b"#Life 1.06\n0 0\n"), Format::Life106);
+        assert_eq!(Format::detect(b"!Name: Glider\n"), Format::Plaintext);
+        assert_eq!(Format::detect(b"..O\r\n"), Format::Plaintext);
+        assert_eq!(Format::detect(b"#N Glider\nx = 3, y = 3\n"), Format::Rle);
+        assert_eq!(Format::detect(b"bo$2bo$3o!"), Format::Rle);
+    }
+
+    #[test]
+    fn test_read_plaintext_and_life106() {
+        let glider = [(0, 2), (1, 0), (1, 2), (2, 1), (2, 2)];
+        for input in [
+            &b"!Name: Glider\n!\n.O.\n..O\nOOO\n"[..],
+            &b".O\r\n..O\r\nOOO\r\n"[..],
+            &b"#Life 1.06\n#N Glider\n1 0\n2 1\n0 2\n1 2\n2 2\n"[..],
+        ] {
+            let mut cells = World::from_bytes(input).unwrap().cells_vec();
+            cells.sort_unstable();
+            assert_eq!(cells, glider);
+        }
+        assert!(matches!(
+            World::from_bytes(b"#Life 1.06\n0 0\n1 x\n"),
+            Err(LoadError::Life106(3))
+        ));
+    }
+
+    #[test]
+    fn test_load_progress() {
+        let row = "x = 10000, y = 1\n10000o!";
+        let mut calls = Vec::new();
+        let world = World::from_rle_with_progress(Rle::new(row).unwrap(), |progress| {
+            calls.push(progress);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(world.population(), 10000);
+        assert_eq!(
+            calls.iter().map(|p| p.items).collect::<Vec<_>>(),
+            [4096, 8192]
+        );
+        assert_eq!(calls[0].bytes, None);
+        let cancelled =
+            World::from_rle_with_progress(Rle::new(row).unwrap(), |_| ControlFlow::Break(()));
+        assert!(matches!(cancelled, Err(LoadError::Cancelled)));
+
+        // Random cells give a Macrocell file with many distinct nodes.
+        let mut world = World::default();
+        let mut seed = 1u64;
+        for _ in 0..20000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            world.set_cell((seed % 1024) as i64, (seed >> 32) as i64 % 1024, true);
+        }
+        let mut bytes = Vec::new();
+        world.write_macrocell(&mut bytes).unwrap();
+        let mut last = LoadProgress::default();
+        let loaded = World::from_reader_with_progress(&bytes[..], |progress| {
+            last = progress;
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(loaded.population(), world.population());
+        assert!(last.items >= 4096);
+        assert!(last.bytes.unwrap() <= bytes.len() as u64);
+        let cancelled = World::from_reader_with_progress(&bytes[..], |_| ControlFlow::Break(()));
+        assert!(matches!(cancelled, Err(LoadError::Cancelled)));
+    }
 }
@@ -0,0 +1,393 @@
+use crate::{rule::Rule, world::World};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+type Cell = (i64, i64);
+
+// An edit or a step, as recorded by `World::start_recording`.
+#[derive(Clone, Debug)]
+pub enum Op {
+    SetCell(Cell, bool),
+    SetCells(Vec<Cell>, bool),
+    // The living cells of the pasted world, and the offset.
+    Paste(Vec<Cell>, Cell),
+    // The offset, as `World::translate`.
+    Translate(Cell),
+    // Bound: (left, right, top, bottom).
+    ClearRegion((i64, i64, i64, i64)),
+    // Bounds as for `ClearRegion`, filled in order, as `World::draw_rect`.
+    FillRegions(Vec<(i64, i64, i64, i64)>, bool),
+    SetStep(u8),
+    SetRule(Box<Rule>),
+    Step,
+    // The generations that were actually advanced, which are fewer than
+    // requested if the steps were interrupted by the cancel flag.
+    StepBy(u64),
+}
+
+// A log of ops, shared by the world that records it and the handles that
+// `World::start_recording` returns, so it can be read while it grows.
+//
+// As text, each op is a line: `set-cell x y 0|1`, `set-cells 0|1 x,y ...`,
+// `paste dx,dy x,y ...`, `translate dx,dy`,
+// `clear-region left right top bottom`,
+// `fill-regions 0|1 left,right,top,bottom ...`, `set-step k`, `rule B3/S23`, `rule-table <hex of Rule::to_bytes>`,
+// `step` and `step-by n`. Empty lines and lines starting with `#` are
+// skipped when parsing.
+#[derive(Clone, Debug, Default)]
+pub struct Recording {
+    ops: Arc<Mutex<Vec<Op>>>,
+}
+
+// The state of a world that records its ops.
+#[derive(Debug, Default)]
+pub(crate) struct Recorder {
+    recording: Option<Recording>,
+    // Set while an op is applied, so that the public methods it calls, e.g.
+    // `set_step` during `step_by`, are not recorded again.
+    applying: bool,
+}
+
+// A copy of a world does not record into the same recording.
+impl Clone for Recorder {
+    fn clone(&self) -> Self {
+        Recorder::default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidRecording {
+    // The line that is not a valid op, from 1.
+    pub line: usize,
+}
+
+impl Display for InvalidRecording {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "Invalid op on line {} of the recording", self.line)
+    }
+}
+
+impl Error for InvalidRecording {}
+
+impl Recording {
+    pub fn ops(&self) -> Vec<Op> {
+        self.ops.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&self, op: Op) {
+        self.ops.lock().unwrap().push(op);
+    }
+
+    // Applies the ops to the world, which gives the same pattern as the
+    // recorded session if it starts from the same pattern, rule and step
+    // size. The ops are also recorded if the world is recording.
+    pub fn replay(&self, base: &mut World) {
+        for op in self.ops() {
+            base.apply(op);
+        }
+    }
+}
+
+fn write_cells(f: &mut Formatter<'_>, cells: &[Cell]) -> FmtResult {
+    for &(x, y) in cells {
+        write!(f, " {},{}", x, y)?;
+    }
+    Ok(())
+}
+
+impl Display for Recording {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        for op in self.ops.lock().unwrap().iter() {
+            match op {
+                Op::SetCell((x, y), state) => write!(f, "set-cell {} {} {}", x, y, *state as u8)?,
+                Op::SetCells(cells, state) => {
+                    write!(f, "set-cells {}", *state as u8)?;
+                    write_cells(f, cells)?;
+                }
+                Op::Paste(cells, (dx, dy)) => {
+                    write!(f, "paste {},{}", dx, dy)?;
+                    write_cells(f, cells)?;
+                }
+                Op::Translate((dx, dy)) => write!(f, "translate {},{}", dx, dy)?,
+                Op::ClearRegion((left, right, top, bottom)) => {
+                    write!(f, "clear-region {} {} {} {}", left, right, top, bottom)?
+                }
+                Op::FillRegions(bounds, state) => {
+                    write!(f, "fill-regions {}", *state as u8)?;
+                    for (left, right, top, bottom) in bounds {
+                        write!(f, " {},{},{},{}", left, right, top, bottom)?;
+                    }
+                }
+                Op::SetStep(step) => write!(f, "set-step {}", step)?,
+                Op::SetRule(rule) => match rule.rulestring() {
+                    Some(rulestring) => write!(f, "rule {}", rulestring)?,
+                    None => {
+                        write!(f, "rule-table ")?;
+                        for byte in rule.to_bytes() {
+                            write!(f, "{:02x}", byte)?;
+                        }
+                    }
+                },
+                Op::Step => write!(f, "step")?,
+                Op::StepBy(generations) => write!(f, "step-by {}", generations)?,
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_state(word: &str) -> Option<bool> {
+    match word {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None,
+    }
+}
+
+fn parse_cell(word: &str) -> Option<Cell> {
+    let (x, y) = word.split_once(',')?;
+    Some((x.parse().ok()?, y.parse().ok()?))
+}
+
+fn parse_cells(words: &[&str]) -> Option<Vec<Cell>> {
+    words.iter().map(|word| parse_cell(word)).collect()
+}
+
+fn parse_bound(word: &str) -> Option<(i64, i64, i64, i64)> {
+    let mut numbers = word.split(',').map(|number| number.parse().ok());
+    let bound = (
+        numbers.next()??,
+        numbers.next()??,
+        numbers.next()??,
+        numbers.next()??,
+    );
+    match numbers.next() {
+        Some(_) => None,
+        None => Some(bound),
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn parse_op(line: &str) -> Option<Op> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    let op = match words.as_slice() {
+        ["set-cell", x, y, state] => {
+            Op::SetCell((x.parse().ok()?, y.parse().ok()?), parse_state(state)?)
+        }
+        ["set-cells", state, cells @ ..] => Op::SetCells(parse_cells(cells)?, parse_state(state)?),
+        ["paste", offset, cells @ ..] => Op::Paste(parse_cells(cells)?, parse_cell(offset)?),
+        ["translate", offset] => Op::Translate(parse_cell(offset)?),
+        ["clear-region", left, right, top, bottom] => Op::ClearRegion((
+            left.parse().ok()?,
+            right.parse().ok()?,
+            top.parse().ok()?,
+            bottom.parse().ok()?,
+        )),
+        ["fill-regions", state, bounds @ ..] => Op::FillRegions(
+            bounds
+                .iter()
+                .map(|word| parse_bound(word))
+                .collect::<Option<_>>()?,
+            parse_state(state)?,
+        ),
+        ["set-step", step] => Op::SetStep(step.parse().ok()?),
+        ["rule", rulestring] => Op::SetRule(Box::new(rulestring.parse().ok()?)),
+        ["rule-table", hex] => Op::SetRule(Box::new(Rule::from_bytes(&parse_hex(hex)?).ok()?)),
+        ["step"] => Op::Step,
+        ["step-by", generations] => Op::StepBy(generations.parse().ok()?),
+        _ => return None,
+    };
+    Some(op)
+}
+
+impl FromStr for Recording {
+    type Err = InvalidRecording;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let recording = Recording::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            recording.push(parse_op(line).ok_or(InvalidRecording { line: i + 1 })?);
+        }
+        Ok(recording)
+    }
+}
+
+impl World {
+    // Records the edits and the steps from now on, until `stop_recording`,
+    // and returns the recording, which replaces any previous one.
+    //
+    // Recorded are `set_cell`, `set_cells`, `paste`, `translate`,
+    // `clear_region`, `draw_rect`, `draw_circle`, `set_from_array2`,
+    // `set_step`, `set_rule`, `step`, `try_step` and `step_by`, and whatever
+    // calls them, e.g. `draw_line` and `step_to`. `clear`,
+    // `set_row_bits`, `set_generation` and restoring snapshots are not, and
+    // neither are the steps that `growth_rate` and `detect_global_cycle`
+    // take back. A step interrupted by the cancel flag is not recorded, and
+    // neither are the edits that steps apply, see `schedule_edit`.
+    pub fn start_recording(&mut self) -> Recording {
+        let recording = Recording::default();
+        self.recorder.recording = Some(recording.clone());
+        recording
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recorder.recording.take()
+    }
+
+    // Every recorded method goes through here, except `step_many`, see
+    // `record_steps`. Returns `false` if a step is interrupted by the cancel
+    // flag.
+    pub(crate) fn apply(&mut self, op: Op) -> bool {
+        let recording = match &self.recorder.recording {
+            Some(recording) if !self.recorder.applying => Some((recording.clone(), op.clone())),
+            _ => None,
+        };
+        let applying = std::mem::replace(&mut self.recorder.applying, true);
+        let generation = self.generation;
+        let done = self.perform(op);
+        self.recorder.applying = applying;
+        match recording {
+            Some((_, Op::Step)) if !done => {}
+            Some((recording, Op::StepBy(_))) => {
+                recording.push(Op::StepBy(self.generation - generation))
+            }
+            Some((recording, op)) => recording.push(op),
+            None => {}
+        }
+        done
+    }
+
+    // Runs steps that are not an op of their own, and records the
+    // generations that were advanced as `Op::StepBy`.
+    pub(crate) fn record_steps<R>(&mut self, f: impl FnOnce(&mut World) -> R) -> R {
+        let recording = match &self.recorder.recording {
+            Some(recording) if !self.recorder.applying => Some(recording.clone()),
+            _ => None,
+        };
+        let applying = std::mem::replace(&mut self.recorder.applying, true);
+        let generation = self.generation;
+        let result = f(self);
+        self.recorder.applying = applying;
+        if let Some(recording) = recording {
+            if self.generation > generation {
+                recording.push(Op::StepBy(self.generation - generation));
+            }
+        }
+        result
+    }
+
+    fn perform(&mut self, op: Op) -> bool {
+        match op {
+            Op::SetCell((x, y), state) => self.set_cell_unrecorded(x, y, state),
+            Op::SetCells(cells, state) => self.set_cells_unrecorded(cells, state),
+            Op::Paste(cells, (dx, dy)) => self.set_cells_unrecorded(
+                cells.into_iter().map(|(x, y)| (x + dx, y + dy)).collect(),
+                true,
+            ),
+            Op::Translate(offset) => self.translate_unrecorded(offset),
+            Op::ClearRegion(bound) => self.clear_region_unrecorded(bound),
+            Op::FillRegions(bounds, state) => {
+                for bound in bounds {
+                    self.fill_region(bound, state);
+                }
+            }
+            Op::SetStep(step) => self.set_step_unrecorded(step),
+            Op::SetRule(rule) => self.set_rule_unrecorded(*rule),
+            Op::Step => return self.step_unrecorded(),
+            Op::StepBy(generations) => return self.step_by_unrecorded(generations),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(world: &mut World) {
+        world
+            .set_cell(0, 0, true)
+            .set_cells(vec![(1, 0), (2, 0)], true);
+        world.paste(&World::from_bytes(b"bo$2bo$3o!").unwrap(), (10, -10));
+        world.draw_line((-20, 5), (-10, 8), true);
+        world
+            .clear_region((-15, -12, 0, 10))
+            .translate((3, -7))
+            .set_step(3);
+        world
+            .draw_rect((30, 40, 30, 36), false, true)
+            .draw_circle((-30, -30), 6, true, true)
+            .draw_circle((-30, -30), 3, false, false);
+        world.step();
+        world.set_rule("B36/S23".parse().unwrap());
+        world.step_by(100);
+        world.set_cell(0, 0, false);
+    }
+
+    fn replayed(recording: &Recording) -> Vec<Cell> {
+        let mut world = World::default();
+        recording.replay(&mut world);
+        assert_eq!(world.get_generation(), 108);
+        let mut cells = world.cells_vec();
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn test_recording() {
+        let mut world = World::default();
+        let recording = world.start_recording();
+        session(&mut world);
+        assert!(world.stop_recording().is_some());
+        world.set_cell(60, 60, true).set_cell(60, 60, false);
+        // `step_by` changes the step size, but only the call is recorded.
+        assert_eq!(recording.len(), 14);
+        let mut cells = world.cells_vec();
+        cells.sort_unstable();
+
+        let text = recording.to_string();
+        assert!(text.starts_with(
+            "set-cell 0 0 1\nset-cells 1 1,0 2,0\npaste 10,-10 1,0 2,1 0,2 1,2 2,2\n"
+        ));
+        assert!(text.contains("\ntranslate 3,-7\nset-step 3\n"));
+        assert!(text.contains("\nfill-regions 1 30,40,30,31 30,40,35,36 30,31,31,35 39,40,31,35\n"));
+        assert!(text.ends_with("step\nrule B36/S23\nstep-by 100\nset-cell 0 0 0\n"));
+        let parsed: Recording = format!("# a session\n\n{}", text).parse().unwrap();
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(replayed(&recording), cells);
+        assert_eq!(replayed(&parsed), cells);
+
+        assert_eq!(
+            "step\nset-cell 0 0 2\n".parse::<Recording>().unwrap_err(),
+            InvalidRecording { line: 2 }
+        );
+        assert!("rule B9\n".parse::<Recording>().is_err());
+        assert!("fill-regions 1 0,1,2\n".parse::<Recording>().is_err());
+    }
+}
@@ -1,18 +1,22 @@
 use ca_rules::{ParseNtLife, ParseRuleError};
 use std::{
-    fmt::{Debug, Formatter, Result as DebugResult},
+    convert::{TryFrom, TryInto},
+    error::Error,
+    fmt::{Debug, Display, Formatter, Result as DebugResult},
     str::FromStr,
 };
 
+const TABLE_MAGIC: &[u8; 4] = b"HLRT";
+const TABLE_VERSION: u8 = 1;
+const NEIGHBORHOOD_MOORE: u8 = 0;
+
 struct Rule3x3 {
     rule_table: [bool; 1 << 9],
 }
 
+// B0 rules are parsed, and rejected by `Rule::from_str`.
 impl ParseNtLife for Rule3x3 {
     fn from_bs(b: Vec<u8>, s: Vec<u8>) -> Self {
-        if b.contains(&0x00) {
-            unimplemented!("B0 rules are not yet supported.")
-        }
         let mut rule_table = [false; 1 << 9];
         b.into_iter()
             .map(|n| ((n & 0xf0) as usize) << 1 | (n & 0x0f) as usize)
@@ -28,50 +32,403 @@ impl ParseNtLife for Rule3x3 {
 #[derive(Clone)]
 pub struct Rule {
     pub rule_table: [u8; 1 << 16],
+    pub(crate) rulestring: Option<String>,
 }
 
 impl Debug for Rule {
     fn fmt(&self, f: &mut Formatter<'_>) -> DebugResult {
-        f.debug_struct("Rule").finish()
+        f.debug_struct("Rule")
+            .field("rulestring", &self.rulestring)
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidTable {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnsupportedNeighborhood(u8),
+    Truncated,
+    ChecksumMismatch,
+    // The rulestring is not valid UTF-8, or not a valid rule.
+    InvalidRulestring,
+    // The rulestring gives another table.
+    RulestringMismatch,
+    // The table gives birth to cells with no living neighbors, which the
+    // engine cannot evolve, as for `InvalidRule::B0`.
+    B0,
+}
+
+impl Display for InvalidTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> DebugResult {
+        match self {
+            InvalidTable::BadMagic => write!(f, "Not a compiled rule table"),
+            InvalidTable::UnsupportedVersion(v) => {
+                write!(f, "Unsupported rule table version {}", v)
+            }
+            InvalidTable::UnsupportedNeighborhood(n) => {
+                write!(f, "Unsupported neighborhood kind {}", n)
+            }
+            InvalidTable::Truncated => write!(f, "Rule table is truncated"),
+            InvalidTable::ChecksumMismatch => write!(f, "Rule table checksum mismatch"),
+            InvalidTable::InvalidRulestring => write!(f, "Invalid rulestring in the rule table"),
+            InvalidTable::RulestringMismatch => {
+                write!(f, "Rulestring does not match the rule table")
+            }
+            InvalidTable::B0 => write!(f, "B0 rules are not supported"),
+        }
+    }
+}
+
+impl Error for InvalidTable {}
+
+// Why a rulestring cannot be used, see `Rule::from_str`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidRule {
+    // `found` is what is left of the rulestring from the byte `offset`
+    // where parsing stopped, up to the next `/` after it. The offset is in the
+    // rulestring as given, before it is normalized. `hint` names the family
+    // of rules that the rulestring looks like, if it is one that is not
+    // supported yet.
+    Parse {
+        error: ParseRuleError,
+        offset: usize,
+        found: String,
+        hint: Option<&'static str>,
+    },
+    // Rules where a cell with no living neighbors is born, which would fill
+    // the infinite empty background.
+    B0,
+    // The normalized rulestring is longer than `Rule::to_bytes` can store,
+    // which is `u16::MAX` bytes.
+    TooLong(usize),
+}
+
+impl Display for InvalidRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> DebugResult {
+        match self {
+            InvalidRule::Parse {
+                error,
+                offset,
+                found,
+                hint,
+            } => {
+                if found.is_empty() {
+                    write!(f, "{} at the end of the rule", error)?;
+                } else {
+                    write!(f, "{} at byte {}, {:?}", error, offset, found)?;
+                }
+                match hint {
+                    Some(hint) => write!(f, " ({})", hint),
+                    None => Ok(()),
+                }
+            }
+            InvalidRule::B0 => write!(f, "B0 rules are not supported"),
+            InvalidRule::TooLong(len) => write!(
+                f,
+                "The rulestring is {} bytes long, at most {} are supported",
+                len,
+                u16::MAX
+            ),
+        }
+    }
+}
+
+impl Error for InvalidRule {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InvalidRule::Parse { error, .. } => Some(error),
+            InvalidRule::B0 | InvalidRule::TooLong(_) => None,
+        }
+    }
+}
+
+// The letters of Hensel notation that can follow each number of neighbors.
+const HENSEL_LETTERS: [&str; 9] = [
+    "c",
+    "ce",
+    "cekain",
+    "cekainyqjr",
+    "cekainyqjrtwz",
+    "cekainyqjr",
+    "cekain",
+    "ce",
+    "c",
+];
+
+// Skips the conditions of one half of a B/S rule, e.g. `2-a3i`, as
+// `ca_rules` does, and returns the byte after them.
+fn skip_conditions(bytes: &[u8], mut i: usize) -> usize {
+    while let Some(&c @ b'0'..=b'8') = bytes.get(i) {
+        let letters = HENSEL_LETTERS[(c - b'0') as usize].as_bytes();
+        i += 1;
+        if bytes.get(i) == Some(&b'-') {
+            i += 1;
+        }
+        while bytes.get(i).is_some_and(|c| letters.contains(c)) {
+            i += 1;
+        }
     }
+    i
 }
 
+// The byte where a B/S rule, in either order, stops being valid, if it does.
+fn invalid_offset(bytes: &[u8]) -> Option<usize> {
+    let i = if bytes.first() == Some(&b'B') {
+        let mut i = skip_conditions(bytes, 1);
+        if bytes.get(i) == Some(&b'/') {
+            i += 1;
+        }
+        if bytes.get(i) != Some(&b'S') {
+            return Some(i);
+        }
+        skip_conditions(bytes, i + 1)
+    } else {
+        let i = skip_conditions(bytes, 0);
+        if bytes.get(i) != Some(&b'/') {
+            return Some(i);
+        }
+        skip_conditions(bytes, i + 1)
+    };
+    (i < bytes.len()).then_some(i)
+}
+
+// The families of rules that `ca_rules` can parse, or that Golly supports,
+// but that are not supported here yet.
+fn hint(rulestring: &str) -> Option<&'static str> {
+    let upper = rulestring.to_uppercase();
+    if rulestring.starts_with("MAP") {
+        Some("only MAP rules of the Moore neighborhood are supported")
+    } else if upper.starts_with('R') && rulestring.contains(',') {
+        Some("Larger than Life rules are not supported yet")
+    } else if rulestring.matches('/').count() >= 2 || upper.contains("/G") {
+        Some("Generations rules are not supported yet")
+    } else {
+        None
+    }
+}
+
+// Whether a string is the conditions of one half of a B/S rule, which may
+// be empty.
+fn is_conditions(s: &str) -> bool {
+    skip_conditions(s.as_bytes(), 0) == s.len()
+}
+
+// Removes the whitespace, and writes the letters `B` and `S` of B/S rules in
+// uppercase. A B/S rule that is otherwise valid is written as `B3/S23`,
+// including the legacy S/B order, e.g. `23/3`. MAP rules are only trimmed,
+// since their base64 is case-sensitive.
+fn normalize(rulestring: &str) -> String {
+    let compact: String = rulestring.chars().filter(|c| !c.is_whitespace()).collect();
+    if compact.starts_with("MAP") {
+        return compact;
+    }
+    let upper = compact.replace('b', "B").replace('s', "S");
+    let halves = match upper.strip_prefix('B') {
+        Some(rest) => rest
+            .split_once('S')
+            .map(|(b, s)| (b.strip_suffix('/').unwrap_or(b), s)),
+        None => upper.split_once('/').map(|(s, b)| (b, s)),
+    };
+    match halves {
+        Some((b, s)) if is_conditions(b) && is_conditions(s) => format!("B{}/S{}", b, s),
+        _ => upper,
+    }
+}
+
+// Finds where a rulestring that `ca_rules` cannot parse goes wrong. The
+// offset is found in the rulestring without whitespace, and then moved to
+// the rulestring as given.
+fn parse_error(rulestring: &str, error: ParseRuleError) -> InvalidRule {
+    let (positions, compact): (Vec<usize>, String) = rulestring
+        .char_indices()
+        .filter(|(_, c)| !c.is_whitespace())
+        .unzip();
+    let start = if compact.starts_with("MAP") {
+        3
+    } else {
+        let upper = compact.replace('b', "B").replace('s', "S");
+        invalid_offset(upper.as_bytes()).unwrap_or(0)
+    };
+    let rest = &compact[start..];
+    let end = rest
+        .char_indices()
+        .skip(1)
+        .find(|&(_, c)| c == '/')
+        .map_or(rest.len(), |(i, _)| i);
+    let found = &rest[..end];
+    InvalidRule::Parse {
+        error,
+        offset: positions.get(start).copied().unwrap_or(rulestring.len()),
+        found: found.to_string(),
+        hint: hint(&compact),
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// The table of the 2x2 centers of the leaves, from the table of the 3x3
+// neighborhoods. A `const fn`, so that the table of Life is built at compile
+// time.
+const fn rule_table(rule_3x3: &[bool; 1 << 9]) -> [u8; 1 << 16] {
+    let mut rule_table = [0; 1 << 16];
+    let mut i = 0;
+    while i < 1 << 16 {
+        let nw_3x3 = (i & 0xe000) >> 7 | (i & 0x0e00) >> 6 | (i & 0x00e0) >> 5;
+        let ne_3x3 = (i & 0x7000) >> 6 | (i & 0x0700) >> 5 | (i & 0x0070) >> 4;
+        let sw_3x3 = (i & 0x0e00) >> 3 | (i & 0x00e0) >> 2 | (i & 0x000e) >> 1;
+        let se_3x3 = (i & 0x0700) >> 2 | (i & 0x0070) >> 1 | (i & 0x0007);
+        rule_table[i] = (rule_3x3[nw_3x3] as u8) << 5
+            | (rule_3x3[ne_3x3] as u8) << 4
+            | (rule_3x3[sw_3x3] as u8) << 1
+            | (rule_3x3[se_3x3] as u8);
+        i += 1;
+    }
+    rule_table
+}
+
+// The 3x3 neighborhoods of B3/S23, the center at `0x10`.
+const fn life_3x3() -> [bool; 1 << 9] {
+    let mut rule_table = [false; 1 << 9];
+    let mut i: usize = 0;
+    while i < 1 << 9 {
+        let neighbors = (i & !0x10).count_ones();
+        rule_table[i] = neighbors == 3 || neighbors == 2 && i & 0x10 != 0;
+        i += 1;
+    }
+    rule_table
+}
+
+// Shared by the default rule and every rule parsed from "B3/S23", which
+// only copy it.
+static LIFE_TABLE: [u8; 1 << 16] = rule_table(&life_3x3());
+
 impl From<Rule3x3> for Rule {
     fn from(rule_3x3: Rule3x3) -> Self {
-        let mut rule_table = [0; 1 << 16];
-        rule_table.iter_mut().enumerate().for_each(|(i, n)| {
-            let nw_3x3 = (i & 0xe000) >> 7 | (i & 0x0e00) >> 6 | (i & 0x00e0) >> 5;
-            let ne_3x3 = (i & 0x7000) >> 6 | (i & 0x0700) >> 5 | (i & 0x0070) >> 4;
-            let sw_3x3 = (i & 0x0e00) >> 3 | (i & 0x00e0) >> 2 | (i & 0x000e) >> 1;
-            let se_3x3 = (i & 0x0700) >> 2 | (i & 0x0070) >> 1 | (i & 0x0007);
-            *n = (rule_3x3.rule_table[nw_3x3] as u8) << 5
-                | (rule_3x3.rule_table[ne_3x3] as u8) << 4
-                | (rule_3x3.rule_table[sw_3x3] as u8) << 1
-                | (rule_3x3.rule_table[se_3x3] as u8);
-        });
-        Rule { rule_table }
+        Rule {
+            rule_table: rule_table(&rule_3x3.rule_table),
+            rulestring: None,
+        }
     }
 }
 
-impl ParseNtLife for Rule {
-    fn from_bs(b: Vec<u8>, s: Vec<u8>) -> Self {
-        Rule3x3::from_bs(b, s).into()
+// B3/S23, Conway's Game of Life, whose table is built at compile time.
+impl Default for Rule {
+    fn default() -> Self {
+        Rule {
+            rule_table: LIFE_TABLE,
+            rulestring: Some("B3/S23".to_string()),
+        }
     }
 }
 
+impl Rule {
+    // Serializes the compiled rule table, so that it can be cached on disk:
+    // a magic number, a version byte, a neighborhood byte, the rulestring,
+    // if known, the 65536-entry table, and a checksum.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let rulestring = self.rulestring.as_deref().unwrap_or("").as_bytes();
+        let mut bytes = Vec::with_capacity(16 + rulestring.len() + self.rule_table.len());
+        bytes.extend_from_slice(TABLE_MAGIC);
+        bytes.push(TABLE_VERSION);
+        bytes.push(NEIGHBORHOOD_MOORE);
+        // Longer rulestrings are rejected by `from_str`.
+        let len = u16::try_from(rulestring.len()).expect("The rulestring is too long.");
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(rulestring);
+        bytes.extend_from_slice(&self.rule_table);
+        let sum = checksum(&bytes);
+        bytes.extend_from_slice(&sum.to_le_bytes());
+        bytes
+    }
+
+    // Reads a rule table written by `Rule::to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidTable> {
+        if bytes.len() < 8 {
+            return Err(InvalidTable::Truncated);
+        }
+        if &bytes[0..4] != TABLE_MAGIC {
+            return Err(InvalidTable::BadMagic);
+        }
+        if bytes[4] != TABLE_VERSION {
+            return Err(InvalidTable::UnsupportedVersion(bytes[4]));
+        }
+        if bytes[5] != NEIGHBORHOOD_MOORE {
+            return Err(InvalidTable::UnsupportedNeighborhood(bytes[5]));
+        }
+        let len = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+        let table_start = 8 + len;
+        let table_end = table_start + (1 << 16);
+        if bytes.len() != table_end + 8 {
+            return Err(InvalidTable::Truncated);
+        }
+        let sum = u64::from_le_bytes(bytes[table_end..].try_into().unwrap());
+        if checksum(&bytes[..table_end]) != sum {
+            return Err(InvalidTable::ChecksumMismatch);
+        }
+        let table = &bytes[table_start..table_end];
+        // The empty leaf.
+        if table[0] != 0 {
+            return Err(InvalidTable::B0);
+        }
+        let rulestring = if len == 0 {
+            None
+        } else {
+            let s = std::str::from_utf8(&bytes[8..table_start])
+                .map_err(|_| InvalidTable::InvalidRulestring)?;
+            let rule: Rule = s.parse().map_err(|_| InvalidTable::InvalidRulestring)?;
+            if rule.rule_table[..] != *table {
+                return Err(InvalidTable::RulestringMismatch);
+            }
+            Some(s.to_string())
+        };
+        let mut rule_table = [0; 1 << 16];
+        rule_table.copy_from_slice(table);
+        Ok(Rule {
+            rule_table,
+            rulestring,
+        })
+    }
+
+    pub fn rulestring(&self) -> Option<&str> {
+        self.rulestring.as_deref()
+    }
+}
+
+// Whitespace is ignored, as is the case of the letters `B` and `S`, and the
+// legacy S/B order is accepted, see `normalize`. The rulestring of the rule
+// is the normalized one.
 impl FromStr for Rule {
-    type Err = ParseRuleError;
+    type Err = InvalidRule;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Rule::parse_rule(s)
+        let rulestring = normalize(s);
+        if u16::try_from(rulestring.len()).is_err() {
+            return Err(InvalidRule::TooLong(rulestring.len()));
+        }
+        if rulestring == "B3/S23" {
+            return Ok(Rule::default());
+        }
+        let rule_3x3 = Rule3x3::parse_rule(&rulestring).map_err(|e| parse_error(s, e))?;
+        // The neighborhood with no living cells.
+        if rule_3x3.rule_table[0] {
+            return Err(InvalidRule::B0);
+        }
+        let mut rule = Rule::from(rule_3x3);
+        rule.rulestring = Some(rulestring);
+        Ok(rule)
     }
 }
 
 #[cfg(test)]
-#[allow(clippy::unusual_byte_groupings)]
+#[allow(clippy::unusual_byte_groupings, clippy::bool_assert_comparison)]
 mod tests {
-    use super::{ParseNtLife, Rule, Rule3x3};
-    use std::error::Error;
+    use super::{InvalidRule, InvalidTable, ParseNtLife, Rule, Rule3x3, LIFE_TABLE};
+    use std::{error::Error, fs, path::Path};
 
     #[test]
     fn parse_rule_3x3() -> Result<(), Box<dyn Error>> {
@@ -89,7 +446,7 @@ mod tests {
 
     #[test]
     fn parse_rule() -> Result<(), Box<dyn Error>> {
-        let rule = Rule::parse_rule("B3/S23")?;
+        let rule = Rule::from(Rule3x3::parse_rule("B3/S23")?);
         assert_eq!(rule.rule_table[0b_0000_0000_0000_0000], 0b_00_00_00);
         assert_eq!(rule.rule_table[0b_0011_1110_0101_1110], 0b_00_00_00);
         assert_eq!(rule.rule_table[0b_0111_0010_0010_1001], 0b_00_00_11);
@@ -98,4 +455,177 @@ mod tests {
         assert_eq!(rule.rule_table[0b_0011_1110_0000_0011], 0b_11_00_00);
         Ok(())
     }
+
+    #[test]
+    fn life_table() -> Result<(), Box<dyn Error>> {
+        let parsed = Rule::from(Rule3x3::parse_rule("B3/S23")?);
+        assert_eq!(&LIFE_TABLE[..], &parsed.rule_table[..]);
+        let rule = Rule::default();
+        assert_eq!(&rule.rule_table[..], &parsed.rule_table[..]);
+        assert_eq!(rule.rulestring(), Some("B3/S23"));
+        Ok(())
+    }
+
+    #[test]
+    fn b0_rules() {
+        for rulestring in ["B0/S8", "B0123478/S01234678"] {
+            assert_eq!(rulestring.parse::<Rule>().unwrap_err(), InvalidRule::B0);
+        }
+        assert!(matches!(
+            "B9/S23".parse::<Rule>(),
+            Err(InvalidRule::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn normalize_rule() -> Result<(), Box<dyn Error>> {
+        for rulestring in [
+            "B3/S23",
+            " B3/S23\n",
+            "B 3 / S 23",
+            "b3/s23",
+            "b3s23",
+            "23/3",
+        ] {
+            let rule: Rule = rulestring.parse()?;
+            assert_eq!(rule.rulestring(), Some("B3/S23"));
+            assert_eq!(&rule.rule_table[..], &LIFE_TABLE[..]);
+        }
+        let rule: Rule = "b2-a3i/s12".parse()?;
+        assert_eq!(rule.rulestring(), Some("B2-a3i/S12"));
+        let rule: Rule = "1357/1357".parse()?;
+        assert_eq!(rule.rulestring(), Some("B1357/S1357"));
+        Ok(())
+    }
+
+    #[test]
+    fn rule_errors() {
+        let error = |rulestring: &str| match rulestring.parse::<Rule>() {
+            Err(InvalidRule::Parse {
+                offset,
+                found,
+                hint,
+                ..
+            }) => (offset, found, hint),
+            result => panic!("{:?} for {}", result.map(|_| ()), rulestring),
+        };
+        assert_eq!(error("B3/D23"), (3, "D23".to_string(), None));
+        assert_eq!(error(" B3 / D23"), (6, "D23".to_string(), None));
+        assert_eq!(error("B3/S2x"), (5, "x".to_string(), None));
+        assert_eq!(error("B1k/S23"), (2, "k".to_string(), None));
+        assert_eq!(error("B3"), (2, "".to_string(), None));
+        assert_eq!(
+            "B3/D23".parse::<Rule>().unwrap_err().to_string(),
+            "Missing expected 'S' at byte 3, \"D23\""
+        );
+        assert_eq!(
+            error("B3/S23/G3"),
+            (
+                6,
+                "/G3".to_string(),
+                Some("Generations rules are not supported yet")
+            )
+        );
+        assert_eq!(
+            error("23/3/3").2,
+            Some("Generations rules are not supported yet")
+        );
+        assert_eq!(
+            error("R2,C0,M1,S2..3,B3..3,NM").2,
+            Some("Larger than Life rules are not supported yet")
+        );
+        let (offset, _, hint) = error("MAPARYXfhZofugWaH7oaIDogBZofuhogOiAaIDogIAAgAAWaH7oaIDog");
+        assert_eq!(offset, 3);
+        assert_eq!(
+            hint,
+            Some("only MAP rules of the Moore neighborhood are supported")
+        );
+    }
+
+    #[test]
+    fn table_round_trip() -> Result<(), Box<dyn Error>> {
+        let map = "MAPARYXfhZofugWaH7oaIDogBZofuhogOiAaIDogIAAgAAWaH7oaIDogGiA6ICAAIAAaIDogIAAgACAAIAAAAAAAA";
+        for rulestring in &["B36/S23", map] {
+            let rule: Rule = rulestring.parse()?;
+            let bytes = rule.to_bytes();
+            let loaded = Rule::from_bytes(&bytes)?;
+            assert_eq!(loaded.rulestring(), Some(*rulestring));
+            assert_eq!(&loaded.rule_table[..], &rule.rule_table[..]);
+        }
+        // `to_bytes` stores the length of the rulestring in two bytes.
+        let long = format!("B3{}/S23", "3".repeat(1 << 16));
+        assert_eq!(
+            long.parse::<Rule>().unwrap_err(),
+            InvalidRule::TooLong(long.len())
+        );
+        let bytes = "B3/S23".parse::<Rule>()?.to_bytes();
+        assert_eq!(
+            Rule::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            InvalidTable::Truncated
+        );
+        let mut corrupted = bytes.clone();
+        corrupted[100] ^= 1;
+        assert_eq!(
+            Rule::from_bytes(&corrupted).unwrap_err(),
+            InvalidTable::ChecksumMismatch
+        );
+        Ok(())
+    }
+
+    // Reads the rule from the cache if it is there and valid, and compiles
+    // it and writes it there otherwise. Returns whether it was cached.
+    fn load_cached(path: &Path, rulestring: &str) -> Result<bool, Box<dyn Error>> {
+        let cached = fs::read(path).ok().and_then(|b| Rule::from_bytes(&b).ok());
+        if let Some(rule) = cached {
+            assert_eq!(rule.rulestring(), Some(rulestring));
+            return Ok(true);
+        }
+        let rule: Rule = rulestring.parse()?;
+        fs::write(path, rule.to_bytes())?;
+        Ok(false)
+    }
+
+    #[test]
+    fn table_disk_cache() -> Result<(), Box<dyn Error>> {
+        let name = format!("hashlife-{}-b36s23.rule", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let _ = fs::remove_file(&path);
+        assert!(!load_cached(&path, "B36/S23")?);
+        assert!(load_cached(&path, "B36/S23")?);
+        // A corrupted cache is written again.
+        fs::write(&path, b"not a table")?;
+        assert!(!load_cached(&path, "B36/S23")?);
+        assert!(load_cached(&path, "B36/S23")?);
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_tables() -> Result<(), Box<dyn Error>> {
+        // Tables with a valid checksum are still checked.
+        let mut b0: Rule = "B3/S23".parse()?;
+        b0.rule_table[0] = 0x20;
+        b0.rulestring = None;
+        assert_eq!(
+            Rule::from_bytes(&b0.to_bytes()).unwrap_err(),
+            InvalidTable::B0
+        );
+        let mut mismatched: Rule = "B36/S23".parse()?;
+        mismatched.rulestring = Some("B3/S23".to_string());
+        assert_eq!(
+            Rule::from_bytes(&mismatched.to_bytes()).unwrap_err(),
+            InvalidTable::RulestringMismatch
+        );
+        mismatched.rulestring = Some("B3/S2x".to_string());
+        assert_eq!(
+            Rule::from_bytes(&mismatched.to_bytes()).unwrap_err(),
+            InvalidTable::InvalidRulestring
+        );
+        let mut anonymous: Rule = "B36/S23".parse()?;
+        anonymous.rulestring = None;
+        let loaded = Rule::from_bytes(&anonymous.to_bytes())?;
+        assert_eq!(loaded.rulestring(), None);
+        assert_eq!(&loaded.rule_table[..], &anonymous.rule_table[..]);
+        Ok(())
+    }
 }
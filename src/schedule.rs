@@ -0,0 +1,247 @@
+use crate::{recording::Op, world::World};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+type Cell = (i64, i64);
+
+// An edit that `World::schedule_edit` applies at a later generation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Edit {
+    // The cells, and the state they are set to, as `World::set_cells`.
+    SetCells(Vec<Cell>, bool),
+    // The living cells of another world, and the offset, as `World::paste`.
+    Paste(Vec<Cell>, Cell),
+}
+
+impl Edit {
+    // Pastes the living cells of `other` as they are now.
+    pub fn paste(other: &World, offset: Cell) -> Self {
+        Edit::Paste(other.living_cells_all().collect(), offset)
+    }
+
+    fn into_op(self) -> Op {
+        match self {
+            Edit::SetCells(cells, state) => Op::SetCells(cells, state),
+            Edit::Paste(cells, offset) => Op::Paste(cells, offset),
+        }
+    }
+}
+
+// A handle to a scheduled edit, only meaningful for the world that created
+// it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EditId(u64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidEdit {
+    pub generation: u64,
+    // The generation of the world when the edit was scheduled.
+    pub current: u64,
+}
+
+impl Display for InvalidEdit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "Cannot schedule an edit at generation {}, before the current generation {}",
+            self.generation, self.current
+        )
+    }
+}
+
+impl Error for InvalidEdit {}
+
+impl World {
+    // Applies the edit when the world reaches the generation. `step`,
+    // `step_by` and `step_many` split their steps there, so that it is never
+    // jumped over, and the larger steps are used again afterwards. Edits at
+    // the same generation are applied in the order they were scheduled, and
+    // an edit at the current generation is applied at once.
+    //
+    // The applied edits are not recorded, see `start_recording`: a replay
+    // only reproduces them if they are scheduled on the base world too. An
+    // edit whose generation is skipped by `set_generation` or `restore` is
+    // applied after the next step.
+    pub fn schedule_edit(&mut self, generation: u64, edit: Edit) -> Result<EditId, InvalidEdit> {
+        if generation < self.generation {
+            return Err(InvalidEdit {
+                generation,
+                current: self.generation,
+            });
+        }
+        let id = self.next_edit;
+        self.next_edit += 1;
+        if generation == self.generation {
+            self.apply(edit.into_op());
+        } else {
+            self.scheduled_edits.insert((generation, id), edit);
+        }
+        Ok(EditId(id))
+    }
+
+    // The edits that are not applied yet, with their generations, in the
+    // order they will be applied.
+    pub fn pending_edits(&self) -> impl Iterator<Item = (EditId, u64, &Edit)> + '_ {
+        self.scheduled_edits
+            .iter()
+            .map(|(&(generation, id), edit)| (EditId(id), generation, edit))
+    }
+
+    // Returns `false` if the edit is already applied or removed.
+    pub fn unschedule_edit(&mut self, id: EditId) -> bool {
+        let key = self
+            .scheduled_edits
+            .keys()
+            .find(|key| key.1 == id.0)
+            .copied();
+        key.and_then(|key| self.scheduled_edits.remove(&key))
+            .is_some()
+    }
+
+    // Whether an edit falls inside the next `generations` generations, so
+    // that a step over them has to be split.
+    pub(crate) fn edit_within(&self, generations: u64) -> bool {
+        self.generations_to_edit()
+            .is_some_and(|until| until < generations)
+    }
+
+    // How many generations to advance before the next edit.
+    pub(crate) fn generations_to_edit(&self) -> Option<u64> {
+        let &(generation, _) = self.scheduled_edits.keys().next()?;
+        Some(generation.saturating_sub(self.generation))
+    }
+
+    // Called after each step.
+    pub(crate) fn apply_due_edits(&mut self) {
+        while self
+            .scheduled_edits
+            .keys()
+            .next()
+            .is_some_and(|&(generation, _)| generation <= self.generation)
+        {
+            let (_, edit) = self.scheduled_edits.pop_first().unwrap();
+            self.apply(edit.into_op());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An R-pentomino, and a block in its way.
+    const PATTERN: &[u8] = b"b2o$2o$bo10$20b2o$20b2o!";
+    const BLOCK: [Cell; 4] = [(20, 12), (21, 12), (20, 13), (21, 13)];
+
+    #[test]
+    fn test_schedule_edit() {
+        let mut manual = World::from_bytes(PATTERN).unwrap();
+        manual.set_step(0);
+        for _ in 0..37 {
+            manual.step();
+        }
+        assert_eq!(manual.population_in((20, 22, 12, 14)), 4);
+        manual.set_cells(BLOCK.to_vec(), false);
+        for _ in 37..1000 {
+            manual.step();
+        }
+
+        let mut world = World::from_bytes(PATTERN).unwrap();
+        let mut untouched = world.clone();
+        let id = world
+            .schedule_edit(37, Edit::SetCells(BLOCK.to_vec(), false))
+            .unwrap();
+        assert_eq!(
+            world.pending_edits().collect::<Vec<_>>(),
+            [(id, 37, &Edit::SetCells(BLOCK.to_vec(), false))]
+        );
+        world.step_by(1000);
+        assert_eq!(world.get_generation(), 1000);
+        assert_eq!(world.pending_edits().count(), 0);
+        assert_eq!(world.get_step(), 0);
+        untouched.step_by(1000);
+        let cells = |world: &World| {
+            let mut cells = world.cells_vec();
+            cells.sort_unstable();
+            cells
+        };
+        assert_eq!(cells(&world), cells(&manual));
+        assert_ne!(cells(&world), cells(&untouched));
+
+        // A single large step is split too.
+        let mut world = World::from_bytes(PATTERN).unwrap();
+        world.set_step(10);
+        world
+            .schedule_edit(37, Edit::SetCells(BLOCK.to_vec(), false))
+            .unwrap();
+        world.step();
+        assert_eq!(world.get_generation(), 1024);
+        assert_eq!(world.get_step(), 10);
+        manual.set_step(0).step_by(24);
+        assert_eq!(cells(&world), cells(&manual));
+    }
+
+    #[test]
+    fn test_pending_edits() {
+        let mut world = World::from_bytes(b"2o$2o!").unwrap();
+        world.step_by(10);
+        assert_eq!(
+            world.schedule_edit(9, Edit::SetCells(vec![(5, 5)], true)),
+            Err(InvalidEdit {
+                generation: 9,
+                current: 10
+            })
+        );
+        let glider = World::from_bytes(b"bo$2bo$3o!").unwrap();
+        let first = world
+            .schedule_edit(20, Edit::paste(&glider, (10, 10)))
+            .unwrap();
+        let second = world
+            .schedule_edit(15, Edit::SetCells(vec![(0, 0)], false))
+            .unwrap();
+        world
+            .schedule_edit(10, Edit::SetCells(vec![(5, 5)], true))
+            .unwrap();
+        assert!(world.get_cell(5, 5));
+        let pending = world
+            .pending_edits()
+            .map(|(id, generation, _)| (id, generation));
+        assert_eq!(pending.collect::<Vec<_>>(), [(second, 15), (first, 20)]);
+
+        assert!(world.unschedule_edit(second));
+        assert!(!world.unschedule_edit(second));
+        world.step_many(16);
+        assert_eq!(world.get_generation(), 26);
+        assert_eq!(world.population(), 4 + 5);
+        assert!(world.get_cell(0, 0));
+        assert!(!world.unschedule_edit(first));
+    }
+
+    #[test]
+    fn test_pending_edits_after_look_ahead() {
+        // The analyses that step ahead and come back leave the edits alone.
+        let mut world = World::from_bytes(PATTERN).unwrap();
+        let id = world
+            .schedule_edit(50, Edit::SetCells(BLOCK.to_vec(), false))
+            .unwrap();
+        world.growth_rate(100);
+        assert_eq!(
+            world.pending_edits().map(|(id, ..)| id).collect::<Vec<_>>(),
+            [id]
+        );
+
+        let mut world = World::from_bytes(b"2o$2o!").unwrap();
+        let id = world
+            .schedule_edit(1, Edit::SetCells(vec![(5, 5)], true))
+            .unwrap();
+        assert_eq!(world.detect_global_cycle(100), Some((0, 1)));
+        assert_eq!(
+            world.pending_edits().map(|(id, ..)| id).collect::<Vec<_>>(),
+            [id]
+        );
+        world.step();
+        assert!(world.get_cell(5, 5));
+    }
+}
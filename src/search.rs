@@ -0,0 +1,289 @@
+// Running random soups to stability, and counting the objects they leave
+// behind, as apgsearch does.
+
+use crate::{census::Census, rule::Rule, world::World};
+use std::{sync::mpsc::channel, thread};
+
+// `lifespan` runs in steps of `2^SAMPLE_STEP` generations, and considers a
+// pattern stable once its population over the last `WINDOW` steps repeats
+// with a period of at most `MAX_SAMPLE_PERIOD` steps. Periods of the common
+// oscillators (2, 3, 4, 8, 14, 15, 30) are at most 15 steps.
+const SAMPLE_STEP: u8 = 3;
+const WINDOW: usize = 64;
+const MAX_SAMPLE_PERIOD: usize = 30;
+
+// The symmetry of a soup, as in apgsearch: every cell of the square is a
+// copy of a random cell of a part of it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    // No symmetry.
+    C1,
+    // Symmetric under a half turn.
+    C2,
+    // Symmetric under a quarter turn.
+    C4,
+    // Mirrored left to right.
+    D2,
+    // Mirrored left to right and top to bottom.
+    D4,
+    // Symmetric under all rotations and reflections of the square.
+    D8,
+}
+
+impl Symmetry {
+    // The images of a cell of the square of size `n`.
+    fn orbit(self, n: i64, (x, y): (i64, i64)) -> Vec<(i64, i64)> {
+        let (u, v) = (n - 1 - x, n - 1 - y);
+        match self {
+            Symmetry::C1 => vec![(x, y)],
+            Symmetry::C2 => vec![(x, y), (u, v)],
+            Symmetry::C4 => vec![(x, y), (v, x), (u, v), (y, u)],
+            Symmetry::D2 => vec![(x, y), (u, y)],
+            Symmetry::D4 => vec![(x, y), (u, y), (x, v), (u, v)],
+            Symmetry::D8 => vec![
+                (x, y),
+                (v, x),
+                (u, v),
+                (y, u),
+                (u, y),
+                (x, v),
+                (y, x),
+                (v, u),
+            ],
+        }
+    }
+}
+
+// What `run_soup_search` runs.
+#[derive(Clone, Debug)]
+pub struct SoupSearchConfig {
+    pub rule: Rule,
+    // The side of the square in which the soup is drawn.
+    pub size: u32,
+    // The probability that a cell is alive.
+    pub density: f64,
+    pub symmetry: Symmetry,
+    // Soups that are not stable after this many generations are reported
+    // with a `lifespan` of `None`, and censused as they are.
+    pub max_generations: u64,
+    // The number of soups; the soup `i` has the seed `seed + i`.
+    pub count: u64,
+    pub seed: u64,
+    // Objects with more cells are counted as unidentified, see
+    // `World::census`.
+    pub max_object_size: u64,
+    // The number of threads, each with its own world.
+    pub threads: usize,
+}
+
+impl Default for SoupSearchConfig {
+    // 16x16 soups of B3/S23 at density 1/2, as apgsearch's `C1`.
+    fn default() -> Self {
+        SoupSearchConfig {
+            rule: "B3/S23".parse().unwrap(),
+            size: 16,
+            density: 0.5,
+            symmetry: Symmetry::C1,
+            max_generations: 1 << 16,
+            count: 100,
+            seed: 0,
+            max_object_size: 256,
+            threads: 1,
+        }
+    }
+}
+
+// The outcome of one soup.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SoupResult {
+    // Gives the same soup again with `SoupSearchConfig::soup`.
+    pub seed: u64,
+    // The generation when the soup became stable, see `World::lifespan`.
+    pub lifespan: Option<u64>,
+    pub census: Census,
+}
+
+// SplitMix64, which is enough for soups, and keeps the search reproducible
+// without a dependency.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ z >> 30).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ z >> 27).wrapping_mul(0x94d049bb133111eb);
+        z ^ z >> 31
+    }
+
+    // Uniform in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl SoupSearchConfig {
+    // The soup with the given seed, in the square from `(0, 0)` to
+    // `(size, size)`.
+    pub fn soup(&self, seed: u64) -> World {
+        let n = self.size as i64;
+        let mut random = Random(seed);
+        let mut cells = Vec::new();
+        for y in 0..n {
+            for x in 0..n {
+                // Each orbit is decided by the first of its cells.
+                let alive = random.next_f64() < self.density;
+                let orbit = self.symmetry.orbit(n, (x, y));
+                if alive && orbit.iter().all(|&cell| (y, x) <= (cell.1, cell.0)) {
+                    cells.extend(orbit);
+                }
+            }
+        }
+        let mut world = World::new(self.rule.clone());
+        world.set_cells(cells, true);
+        world
+    }
+
+    fn run(&self, seed: u64) -> SoupResult {
+        let mut world = self.soup(seed);
+        let lifespan = world.lifespan(self.max_generations);
+        SoupResult {
+            seed,
+            lifespan,
+            census: world.census(self.max_object_size),
+        }
+    }
+}
+
+impl World {
+    // Runs the pattern until it is stable, i.e., until its population is
+    // periodic, and returns the number of generations it took, or `None`
+    // if it is not stable after `max_generations`. The pattern is left at
+    // the generation where this is noticed, several hundred generations
+    // later, and the step size is restored.
+    //
+    // The population is sampled every 8 generations, so the result is a
+    // multiple of 8, and escaping spaceships do not prevent stability.
+    pub fn lifespan(&mut self, max_generations: u64) -> Option<u64> {
+        let step = self.step;
+        self.set_step(SAMPLE_STEP);
+        let start = self.generation;
+        let mut populations = vec![self.population()];
+        let mut lifespan = None;
+        while self.generation - start < max_generations {
+            self.step();
+            populations.push(self.population());
+            if let Some(start) = stable_since(&populations) {
+                lifespan = Some((start as u64) << SAMPLE_STEP);
+                break;
+            }
+        }
+        self.set_step(step);
+        lifespan
+    }
+}
+
+// The first sample from which the populations are periodic, if they are
+// over the whole window.
+fn stable_since(populations: &[u64]) -> Option<usize> {
+    let len = populations.len();
+    if len < WINDOW {
+        return None;
+    }
+    let period = (1..=MAX_SAMPLE_PERIOD)
+        .find(|&p| (len - WINDOW + p..len).all(|i| populations[i] == populations[i - p]))?;
+    let mut start = len - WINDOW;
+    while start > 0 && populations[start - 1] == populations[start - 1 + period] {
+        start -= 1;
+    }
+    Some(start)
+}
+
+// Runs `config.count` soups to stability, and reports each of them with its
+// census. With more than one thread, the results come in no particular
+// order, but are still the same for the same seeds.
+pub fn run_soup_search<F: FnMut(SoupResult)>(config: SoupSearchConfig, on_result: F) {
+    let mut on_result = on_result;
+    let threads = config.threads.clamp(1, config.count.max(1) as usize) as u64;
+    if threads == 1 {
+        for i in 0..config.count {
+            on_result(config.run(config.seed.wrapping_add(i)));
+        }
+        return;
+    }
+    let (sender, receiver) = channel();
+    let config = &config;
+    thread::scope(|scope| {
+        for thread in 0..threads {
+            let sender = sender.clone();
+            scope.spawn(move || {
+                for i in (thread..config.count).step_by(threads as usize) {
+                    if sender
+                        .send(config.run(config.seed.wrapping_add(i)))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        for result in receiver {
+            on_result(result);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soup_search() {
+        let config = SoupSearchConfig {
+            size: 8,
+            count: 8,
+            seed: 1,
+            symmetry: Symmetry::D4,
+            ..SoupSearchConfig::default()
+        };
+        let mut results = Vec::new();
+        run_soup_search(config.clone(), |result| results.push(result));
+        assert_eq!(results.len(), 8);
+        assert_eq!(results[0].seed, 1);
+
+        let mut parallel = Vec::new();
+        let threads = SoupSearchConfig {
+            threads: 3,
+            ..config.clone()
+        };
+        run_soup_search(threads, |result| parallel.push(result));
+        parallel.sort_by_key(|result| result.seed);
+        assert_eq!(parallel, results);
+
+        // Some of the default soups give gliders.
+        let soups = SoupSearchConfig {
+            count: 100,
+            threads: 4,
+            ..SoupSearchConfig::default()
+        };
+        let mut gliders = 0;
+        run_soup_search(soups, |result| gliders += result.census.count("xq4_153"));
+        assert!(gliders > 0);
+
+        let mut cells = config.soup(1).cells_vec();
+        let mut mirrored = cells.iter().map(|&(x, y)| (7 - x, y)).collect::<Vec<_>>();
+        cells.sort_unstable();
+        mirrored.sort_unstable();
+        assert_eq!(cells, mirrored);
+
+        // A blinker and a block, stable from the start.
+        let mut world = World::from_bytes(b"3o5$2o$2o!").unwrap();
+        assert_eq!(world.lifespan(1 << 10), Some(0));
+        // A domino dies at once.
+        let mut world = World::from_bytes(b"2o!").unwrap();
+        assert_eq!(world.lifespan(1 << 10), Some(8));
+        let mut world = World::from_bytes(b"b2o$2o$bo!").unwrap();
+        assert_eq!(world.lifespan(100), None);
+    }
+}
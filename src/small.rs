@@ -0,0 +1,587 @@
+use crate::{
+    rule::Rule,
+    world::{Node, World},
+};
+
+// The side of the box of a `Bitboard`.
+const SIZE: i64 = 64;
+
+// The largest population that can fit in the box of a `Bitboard`, checked
+// before its bound, which is more expensive.
+const MAX_POPULATION: u64 = ((SIZE - 2) * (SIZE - 2)) as u64;
+
+// A pattern that fits in a box of 64x64 cells, stepped one generation at a
+// time with bitwise adders on whole rows, without any node or hash table.
+//
+// Only Life-like rules without B0 are supported. The box follows the
+// pattern: when a cell reaches its edge, the pattern is moved back to the
+// middle of the box. When it no longer fits, `step` fails.
+#[derive(Clone, Debug)]
+pub(crate) struct Bitboard {
+    // Bit `n` of `birth`: whether a dead cell with `n` living neighbors is
+    // born; of `survival`: whether a living one survives.
+    birth: u16,
+    survival: u16,
+    // The rows of the box from the top, the leftmost cell in the highest bit.
+    rows: [u64; SIZE as usize],
+    // The coordinates of the top-left cell of the box.
+    left: i64,
+    top: i64,
+}
+
+// A pattern that fits in a box of 64x64 cells, stepped as a `Bitboard`. For
+// small objects, e.g. while editing them, this is much faster than stepping
+// the nodes of a `World`, whose constant factors dominate at that size.
+// `World` does the same by itself for the steps of one generation, see
+// `World::step_small`, but still writes each generation into its nodes.
+//
+// When the pattern no longer fits, `step` fails, and `to_world` hands it
+// over to the full engine.
+#[derive(Clone, Debug)]
+pub struct SmallWorld {
+    rule: Box<Rule>,
+    board: Bitboard,
+    generation: u64,
+}
+
+// The bitboard of a `World` that is stepped one generation at a time, and
+// the root that its last generation was written to: it only stands for the
+// pattern while the root is unchanged and no garbage was collected since.
+#[derive(Clone, Debug)]
+pub(crate) struct SmallCache {
+    board: Bitboard,
+    root: Node,
+    collections: u64,
+}
+
+// The births and the survivals of a Life-like rule, read from its table.
+pub(crate) fn life_like(rule: &Rule) -> Option<(u16, u16)> {
+    let mut outcomes = [[None; 9]; 2];
+    for neighborhood in 0..1u16 << 9 {
+        // The 3x3 cells at the top-left of a leaf, the center at bit 4.
+        let leaf = (0..9)
+            .filter(|i| neighborhood & 1 << i != 0)
+            .fold(0u16, |leaf, i| leaf | 0x8000 >> (i / 3 * 4 + i % 3));
+        // The next state of the cell in the second row and column.
+        let next = rule.rule_table[leaf as usize] & 0x20 != 0;
+        let alive = neighborhood & 0x10 != 0;
+        let neighbors = (neighborhood & !0x10).count_ones() as usize;
+        let outcome = &mut outcomes[alive as usize][neighbors];
+        if outcome.replace(next).is_some_and(|other| other != next) {
+            return None;
+        }
+    }
+    let [birth, survival] = outcomes.map(|outcomes| {
+        (0..9)
+            .filter(|&n| outcomes[n] == Some(true))
+            .fold(0, |mask, n| mask | 1 << n)
+    });
+    (birth & 1 == 0).then_some((birth, survival))
+}
+
+impl Bitboard {
+    // Returns `None` if the pattern does not fit in the box with a margin of
+    // one cell.
+    fn new(world: &World, (birth, survival): (u16, u16)) -> Option<Self> {
+        let (left, top) = match world.bound() {
+            Some((left, right, top, bottom)) => {
+                // The width may not even fit in an `i64`.
+                let width = right.checked_sub(left)?;
+                let height = bottom.checked_sub(top)?;
+                if width > SIZE - 2 || height > SIZE - 2 {
+                    return None;
+                }
+                Bitboard::corner(left, top, width, height)?
+            }
+            None => (-SIZE / 2, -SIZE / 2),
+        };
+        let mut board = Bitboard {
+            birth,
+            survival,
+            rows: [0; SIZE as usize],
+            left,
+            top,
+        };
+        world.for_all_living_cells(|x, y| {
+            board.set_cell(x, y, true);
+        });
+        Some(board)
+    }
+
+    // The top-left cell of the box that has a pattern of that size and
+    // position in its middle, if the box fits in the universe.
+    fn corner(left: i64, top: i64, width: i64, height: i64) -> Option<(i64, i64)> {
+        let left = left.checked_sub((SIZE - width) / 2)?;
+        let top = top.checked_sub((SIZE - height) / 2)?;
+        left.checked_add(SIZE - 1)?;
+        top.checked_add(SIZE - 1)?;
+        Some((left, top))
+    }
+
+    fn population(&self) -> u64 {
+        self.rows.iter().map(|row| row.count_ones() as u64).sum()
+    }
+
+    // The bit of a cell in its row, if it is in the box.
+    fn locate(&self, x: i64, y: i64) -> Option<(usize, u64)> {
+        let (dx, dy) = (x.checked_sub(self.left)?, y.checked_sub(self.top)?);
+        ((0..SIZE).contains(&dx) && (0..SIZE).contains(&dy))
+            .then(|| (dy as usize, 1 << (SIZE - 1 - dx)))
+    }
+
+    fn get_cell(&self, x: i64, y: i64) -> bool {
+        self.locate(x, y)
+            .is_some_and(|(row, bit)| self.rows[row] & bit != 0)
+    }
+
+    fn set_cell(&mut self, x: i64, y: i64, state: bool) -> bool {
+        match self.locate(x, y) {
+            Some((row, bit)) => {
+                if state {
+                    self.rows[row] |= bit;
+                } else {
+                    self.rows[row] &= !bit;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn cells_vec(&self) -> Vec<(i64, i64)> {
+        let mut cells = Vec::new();
+        for (y, &row) in self.rows.iter().enumerate() {
+            let mut row = row;
+            while row != 0 {
+                let x = row.leading_zeros() as i64;
+                row &= !(1 << (SIZE - 1 - x));
+                cells.push((self.left + x, self.top + y as i64));
+            }
+        }
+        cells
+    }
+
+    // The first and the last nonempty rows.
+    fn row_range(&self) -> Option<(usize, usize)> {
+        let first = self.rows.iter().position(|&row| row != 0)?;
+        let last = self.rows.iter().rposition(|&row| row != 0)?;
+        Some((first, last))
+    }
+
+    // The nonempty columns, as a row.
+    fn columns(&self) -> u64 {
+        self.rows.iter().fold(0, |columns, row| columns | row)
+    }
+
+    // Moves the pattern to the middle of the box if it touches the edge.
+    // Returns `false` if it does not fit with a margin of one cell.
+    fn recenter(&mut self) -> bool {
+        let (first, last) = match self.row_range() {
+            Some(range) => range,
+            None => return true,
+        };
+        let columns = self.columns();
+        let edge = 1 << (SIZE - 1) | 1;
+        if first > 0 && last < SIZE as usize - 1 && columns & edge == 0 {
+            return true;
+        }
+        let (leftmost, rightmost) = (columns.leading_zeros(), 63 - columns.trailing_zeros());
+        let (width, height) = ((rightmost - leftmost + 1) as i64, (last - first + 1) as i64);
+        if width > SIZE - 2 || height > SIZE - 2 {
+            return false;
+        }
+        let (left, top) = (self.left + leftmost as i64, self.top + first as i64);
+        let (new_left, new_top) = match Bitboard::corner(left, top, width, height) {
+            Some(corner) => corner,
+            None => return false,
+        };
+        let (dx, dy) = (self.left - new_left, self.top - new_top);
+        let mut rows = [0; SIZE as usize];
+        for (y, &row) in self.rows[first..=last].iter().enumerate() {
+            let shifted = if dx >= 0 { row >> dx } else { row << -dx };
+            rows[(first as i64 + dy) as usize + y] = shifted;
+        }
+        self.rows = rows;
+        self.left = new_left;
+        self.top = new_top;
+        true
+    }
+
+    // The next generation of the middle row.
+    fn step_row(&self, up: u64, row: u64, down: u64) -> u64 {
+        // The number of living neighbors of each cell, in 4 bit planes.
+        let full_add = |a: u64, b: u64, c: u64| (a ^ b ^ c, (a & b) | (c & (a ^ b)));
+        let (ones_up, twos_up) = full_add(up << 1, up, up >> 1);
+        let (ones_down, twos_down) = full_add(down << 1, down, down >> 1);
+        let (ones_row, twos_row) = (row << 1 ^ row >> 1, row << 1 & row >> 1);
+        let (bit0, twos) = full_add(ones_up, ones_down, ones_row);
+        let (twos_sum, fours) = full_add(twos_up, twos_down, twos_row);
+        let (bit1, fours_carry) = (twos_sum ^ twos, twos_sum & twos);
+        let (bit2, bit3) = (fours ^ fours_carry, fours & fours_carry);
+        let mut next = 0;
+        for n in 0..9 {
+            let planes = [bit0, bit1, bit2, bit3];
+            let count = (0..4).fold(!0, |count, i| {
+                count
+                    & if n >> i & 1 != 0 {
+                        planes[i]
+                    } else {
+                        !planes[i]
+                    }
+            });
+            if self.birth & 1 << n != 0 {
+                next |= count & !row;
+            }
+            if self.survival & 1 << n != 0 {
+                next |= count & row;
+            }
+        }
+        next
+    }
+
+    // Returns `false` if the pattern no longer fits in the box, in which
+    // case nothing is done.
+    fn step(&mut self) -> bool {
+        if !self.recenter() {
+            return false;
+        }
+        if let Some((first, last)) = self.row_range() {
+            // Only the rows next to the pattern can change, and there is a
+            // row of margin on each side.
+            let mut up = if first >= 2 { self.rows[first - 2] } else { 0 };
+            for y in first - 1..=last + 1 {
+                let row = self.rows[y];
+                let down = self.rows.get(y + 1).copied().unwrap_or(0);
+                self.rows[y] = self.step_row(up, row, down);
+                up = row;
+            }
+        }
+        true
+    }
+
+    // The 4 cells of a row of a leaf whose left edge is the column `x` of
+    // the box, the leftmost in the highest bit.
+    fn nibble(row: u64, x: i128) -> u16 {
+        if x <= -4 || x >= SIZE as i128 {
+            return 0;
+        }
+        ((((row as u128) << 4) >> (64 - x)) & 0xf) as u16
+    }
+}
+
+impl SmallWorld {
+    // Returns `None` if the rule is not a Life-like rule without B0, or if
+    // the pattern does not fit in the box with a margin of one cell.
+    pub fn from_world(world: &World) -> Option<Self> {
+        let rule = world.get_rule();
+        Some(SmallWorld {
+            board: Bitboard::new(world, life_like(rule)?)?,
+            rule: Box::new(rule.clone()),
+            generation: world.get_generation(),
+        })
+    }
+
+    pub fn to_world(&self) -> World {
+        let mut world = World::new((*self.rule).clone());
+        world.set_cells(self.cells_vec(), true);
+        world.set_generation(self.generation);
+        world
+    }
+
+    pub fn get_generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn population(&self) -> u64 {
+        self.board.population()
+    }
+
+    pub fn get_cell(&self, x: i64, y: i64) -> bool {
+        self.board.get_cell(x, y)
+    }
+
+    // Returns `false` if the cell is outside of the box, in which case
+    // nothing is done.
+    pub fn set_cell(&mut self, x: i64, y: i64, state: bool) -> bool {
+        self.board.set_cell(x, y, state)
+    }
+
+    // In row-major order.
+    pub fn cells_vec(&self) -> Vec<(i64, i64)> {
+        self.board.cells_vec()
+    }
+
+    // Returns `false` if the pattern no longer fits in the box, in which
+    // case nothing is done; see `to_world`.
+    pub fn step(&mut self) -> bool {
+        let stepped = self.board.step();
+        if stepped {
+            self.generation += 1;
+        }
+        stepped
+    }
+}
+
+impl World {
+    // Steps a small pattern one generation as a `Bitboard`, and returns the
+    // new root, or `None` if the pattern or the world is not suited to it,
+    // in which case it is stepped as usual. The bitboard is kept between
+    // the steps, so that only the nodes of the result are looked up in the
+    // hash table, and not those of the step.
+    pub(crate) fn step_small(&mut self) -> Option<Node> {
+        let mut small = self.small_cache()?;
+        if !small.board.step() {
+            return None;
+        }
+        small.root = self.board_root(&small.board);
+        let root = small.root;
+        self.small = Some(small);
+        Some(root)
+    }
+
+    // Same as at most `k` steps of `step_small`, for `step_many`, but the
+    // root is only written after the last one. Nothing is done if anything
+    // records the roots or the populations in between, see `step_root_from`.
+    // Returns the number of steps.
+    pub(crate) fn step_small_many(&mut self, k: u32) -> u32 {
+        if self.history_capacity > 0
+            || self.population_every > 0
+            || self.extremes.is_some()
+            || self.bound_capacity > 0
+            || self.checkpoint_interval > 0
+        {
+            return 0;
+        }
+        let mut small = match self.small_cache() {
+            Some(small) => small,
+            None => return 0,
+        };
+        let steps = (0..k).take_while(|_| small.board.step()).count() as u32;
+        if steps > 0 {
+            small.root = self.board_root(&small.board);
+            self.root = small.root;
+            self.generation += steps as u64;
+            self.check_gc();
+        }
+        if steps == k {
+            self.small = Some(small);
+        }
+        steps
+    }
+
+    // The bitboard of the pattern, taken out of the world, if it can be
+    // stepped as one. It is not used for worlds in a shared store, nor with
+    // activity or age tracking, which follow the nodes of the step.
+    fn small_cache(&mut self) -> Option<Box<SmallCache>> {
+        if self.step != 0
+            || self.shared.is_some()
+            || self.activity.is_some()
+            || self.ages.is_some()
+            || self.population() > MAX_POPULATION
+        {
+            self.small = None;
+            return None;
+        }
+        let collections = self.nodes.collections;
+        match self.small.take() {
+            Some(small) if small.root == self.root && small.collections == collections => {
+                Some(small)
+            }
+            _ => {
+                let rule = match self.life_like {
+                    Some(rule) => rule,
+                    None => *self.life_like.insert(life_like(&self.rule)),
+                }?;
+                Some(Box::new(SmallCache {
+                    board: Bitboard::new(self, rule)?,
+                    root: self.root,
+                    collections,
+                }))
+            }
+        }
+    }
+
+    // A root with the cells of the bitboard.
+    fn board_root(&mut self, board: &Bitboard) -> Node {
+        let (first, last) = match board.row_range() {
+            Some(range) => range,
+            None => return Node::Leaf(0),
+        };
+        let columns = board.columns();
+        let bound = (
+            board.left as i128 + columns.leading_zeros() as i128,
+            board.left as i128 + SIZE as i128 - columns.trailing_zeros() as i128,
+            board.top as i128 + first as i128,
+            board.top as i128 + last as i128 + 1,
+        );
+        let mut level = 2;
+        while {
+            let half = 1i128 << (level - 1);
+            bound.0 < -half || bound.1 > half || bound.2 < -half || bound.3 > half
+        } {
+            level += 1;
+        }
+        let corner = -(1i128 << (level - 1));
+        self.board_node(board, bound, level, (corner, corner))
+    }
+
+    // Bound: (left, right, top, bottom) of the living cells of the board.
+    // The node of the level whose top-left cell is at `corner`.
+    fn board_node(
+        &mut self,
+        board: &Bitboard,
+        bound: (i128, i128, i128, i128),
+        level: u8,
+        corner: (i128, i128),
+    ) -> Node {
+        let (x, y) = corner;
+        let size = 1i128 << level;
+        let (left, right, top, bottom) = bound;
+        if x >= right || x + size <= left || y >= bottom || y + size <= top {
+            return self.empty_node(level);
+        }
+        if level == 2 {
+            let mut leaf = 0;
+            for i in 0..4 {
+                let row = y + i - board.top as i128;
+                if (0..SIZE as i128).contains(&row) {
+                    let cells = Bitboard::nibble(board.rows[row as usize], x - board.left as i128);
+                    leaf |= cells << ((3 - i) * 4);
+                }
+            }
+            return Node::Leaf(leaf);
+        }
+        let half = size / 2;
+        let [nw, ne, sw, se] = [(0, 0), (half, 0), (0, half), (half, half)]
+            .map(|(dx, dy)| self.board_node(board, bound, level - 1, (x + dx, y + dy)));
+        Node::NodeId(self.find_node(nw, ne, sw, se))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ordered_cells(world: &World) -> Vec<(i64, i64)> {
+        let mut cells = world.cells_vec();
+        cells.sort_by_key(|&(x, y)| (y, x));
+        cells
+    }
+
+    #[test]
+    fn test_small_world() {
+        let glider = World::from_bytes(b"bo$2bo$3o!").unwrap();
+        let mut world = glider.clone();
+        let mut small = SmallWorld::from_world(&glider).unwrap();
+        for _ in 0..1000 {
+            world.step();
+            assert!(small.step());
+        }
+        assert_eq!(small.get_generation(), 1000);
+        assert_eq!(small.population(), 5);
+        assert_eq!(small.cells_vec(), ordered_cells(&world));
+        assert!(small.get_cell(251, 250));
+
+        // The R-pentomino outgrows the box, and carries on as a `World`.
+        let mut world = World::from_bytes(b"b2o$2o$bo!").unwrap();
+        let mut small = SmallWorld::from_world(&world).unwrap();
+        while small.step() {
+            world.step();
+            assert_eq!(small.population(), world.population());
+        }
+        let mut promoted = small.to_world();
+        assert_eq!(promoted.get_generation(), world.get_generation());
+        promoted.step_by(100);
+        world.step_by(100);
+        assert_eq!(ordered_cells(&promoted), ordered_cells(&world));
+
+        // Not Life-like.
+        let world = World::new("B2a3/S23".parse().unwrap());
+        assert!(SmallWorld::from_world(&world).is_none());
+        // Another Life-like rule, with a cell that is born by 3 and 6.
+        let mut world = World::new("B36/S23".parse().unwrap());
+        world.set_cells(vec![(0, 0), (1, 0), (2, 0), (0, 2), (1, 2), (2, 2)], true);
+        let mut small = SmallWorld::from_world(&world).unwrap();
+        for _ in 0..50 {
+            world.step();
+            small.step();
+        }
+        assert_eq!(small.cells_vec(), ordered_cells(&world));
+    }
+
+    #[test]
+    fn test_step_small() {
+        // A glider never leaves the bitboard, which is kept between steps.
+        let glider = World::from_bytes(b"bo$2bo$3o!").unwrap();
+        let mut world = glider.clone();
+        let mut nodes = glider.clone();
+        nodes.life_like = Some(None);
+        world.step_many(1000);
+        assert!(world.small.is_some());
+        for _ in 0..1000 {
+            nodes.step();
+        }
+        assert_eq!(world.get_generation(), 1000);
+        assert_eq!(ordered_cells(&world), ordered_cells(&nodes));
+        assert_eq!(world.bound(), Some((250, 253, 250, 253)));
+
+        // Edits, garbage collections and other rules between the steps.
+        let mut world = World::from_bytes(b"b2o$2o$bo!").unwrap();
+        let mut nodes = world.clone();
+        nodes.life_like = Some(None);
+        world.set_history_capacity(4);
+        nodes.set_history_capacity(4);
+        for generation in 0..300 {
+            for world in [&mut world, &mut nodes] {
+                match generation {
+                    20 => {
+                        world.set_cell(-10, -10, true);
+                    }
+                    30 => assert!(world.rewind()),
+                    40 => world.garbage_collect(),
+                    60 => {
+                        world.set_rule("B36/S23".parse().unwrap());
+                    }
+                    _ => {}
+                }
+                world.step();
+            }
+            assert_eq!(ordered_cells(&world), ordered_cells(&nodes));
+        }
+        // The R-pentomino outgrows the box, and carries on as nodes.
+        assert!(world.small.is_none());
+        assert!(world
+            .bound()
+            .is_some_and(|(left, right, _, _)| right - left > 64));
+        assert!(world.rewind() && nodes.rewind());
+        assert_eq!(ordered_cells(&world), ordered_cells(&nodes));
+
+        // Many steps at once, past the size of the box.
+        let mut world = World::from_bytes(b"b2o$2o$bo!").unwrap();
+        let mut nodes = world.clone();
+        nodes.life_like = Some(None);
+        assert_eq!(world.step_many(500).steps, 500);
+        nodes.step_many(500);
+        assert_eq!(world.get_generation(), 500);
+        assert_eq!(ordered_cells(&world), ordered_cells(&nodes));
+
+        // Few cells, too far apart for the width to fit in an `i64`.
+        let mut world = World::default();
+        let far = (1 << 62) + 100;
+        let block = [(0, 0), (1, 0), (0, 1), (1, 1)];
+        let cells = block
+            .iter()
+            .flat_map(|&(x, y)| [(x - far, y), (x + far, y)]);
+        world.set_cells(cells.collect::<Vec<_>>(), true);
+        world.step();
+        assert!(world.small.is_none());
+        assert_eq!(world.population(), 8);
+
+        // Not Life-like.
+        let mut world = World::new("B2a3/S23".parse().unwrap());
+        world.set_cells(vec![(0, 0), (1, 0), (2, 0)], true);
+        world.step();
+        assert!(world.small.is_none());
+        assert_eq!(world.life_like, Some(None));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::world::{Node, World};
+
+// A handle to a pinned root of a world, together with its generation.
+//
+// Pinning is cheap: the nodes are shared with the live pattern, and GC never
+// removes them until the snapshot is released. A handle is only meaningful
+// for the world that created it, and becomes invalid after `World::clear(true)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Snapshot(u64);
+
+impl World {
+    // Pins the current root and generation.
+    pub fn snapshot(&mut self) -> Snapshot {
+        self.pin(self.root, self.generation)
+    }
+
+    pub(crate) fn pin(&mut self, root: Node, generation: u64) -> Snapshot {
+        let id = self.next_snapshot;
+        self.next_snapshot += 1;
+        self.snapshots.insert(id, (root, generation));
+        Snapshot(id)
+    }
+
+    // Restores the pattern and the generation of the snapshot. The step and
+    // the snapshot itself are kept. Returns `false` if the snapshot is invalid.
+    pub fn restore(&mut self, snapshot: Snapshot) -> bool {
+        match self.snapshots.get(&snapshot.0) {
+            Some(&(root, generation)) => {
+                self.root = root;
+                self.generation = generation;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Unpins the snapshot, so that its nodes can be garbage collected.
+    // Returns `false` if the snapshot is invalid.
+    pub fn release(&mut self, snapshot: Snapshot) -> bool {
+        self.snapshots.remove(&snapshot.0).is_some()
+    }
+
+    pub fn snapshot_generation(&self, snapshot: Snapshot) -> Option<u64> {
+        self.snapshots
+            .get(&snapshot.0)
+            .map(|&(_, generation)| generation)
+    }
+
+    pub(crate) fn snapshot_root(&self, snapshot: Snapshot) -> Option<Node> {
+        self.snapshots.get(&snapshot.0).map(|&(root, _)| root)
+    }
+
+    // Keeps a snapshot before each of the last `capacity` steps, so that they
+    // can be undone with `rewind`. Since the snapshots share nodes with each
+    // other, the memory cost is usually small. A capacity of `0` disables the
+    // history.
+    pub fn set_history_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.history_capacity = capacity;
+        while self.history.len() > capacity {
+            let snapshot = self.history.pop_front().unwrap();
+            self.release(snapshot);
+        }
+        self
+    }
+
+    pub fn history_capacity(&self) -> usize {
+        self.history_capacity
+    }
+
+    // The number of steps that can be rewound.
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    // Undoes the last recorded step. Returns `false` if the history is empty.
+    pub fn rewind(&mut self) -> bool {
+        match self.history.pop_back() {
+            Some(snapshot) => {
+                self.restore(snapshot);
+                self.release(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Rewinds until the generation is at most `generation`. Returns `false`,
+    // without rewinding anything, if the history does not reach back so far.
+    pub fn rewind_to(&mut self, generation: u64) -> bool {
+        if self.generation <= generation {
+            return true;
+        }
+        let earliest = self
+            .history
+            .front()
+            .and_then(|&snapshot| self.snapshot_generation(snapshot));
+        if earliest.is_none_or(|earliest| earliest > generation) {
+            return false;
+        }
+        while self.generation > generation {
+            self.rewind();
+        }
+        true
+    }
+
+    pub fn clear_history(&mut self) {
+        while let Some(snapshot) = self.history.pop_front() {
+            self.release(snapshot);
+        }
+    }
+
+    // Runs `f`, which may step the world, then puts back the pattern, the
+    // generation and the step size. The history, the population and bound
+    // recorders, the checkpoints, the activity map, the extremes, the ages,
+    // the recording and the scheduled edits are suspended meanwhile, so that
+    // none of them sees the steps that are taken back.
+    pub(crate) fn with_trackers_suspended<R>(&mut self, f: impl FnOnce(&mut World) -> R) -> R {
+        let original = self.snapshot();
+        let step = self.step;
+        let history_capacity = std::mem::replace(&mut self.history_capacity, 0);
+        let population_every = std::mem::replace(&mut self.population_every, 0);
+        let bound_capacity = std::mem::replace(&mut self.bound_capacity, 0);
+        let checkpoint_interval = std::mem::replace(&mut self.checkpoint_interval, 0);
+        let activity = self.activity.take();
+        let extremes = self.extremes.take();
+        let ages_paused = self.pause_ages(true);
+        let recorder = std::mem::take(&mut self.recorder);
+        let scheduled_edits = std::mem::take(&mut self.scheduled_edits);
+
+        let result = f(self);
+
+        self.restore(original);
+        self.release(original);
+        self.step = step;
+        self.history_capacity = history_capacity;
+        self.population_every = population_every;
+        self.bound_capacity = bound_capacity;
+        self.checkpoint_interval = checkpoint_interval;
+        self.activity = activity;
+        self.extremes = extremes;
+        self.pause_ages(ages_paused);
+        self.recorder = recorder;
+        self.scheduled_edits = scheduled_edits;
+        result
+    }
+
+    // Records the state before a step.
+    pub(crate) fn record_history(&mut self, root: Node, generation: u64) {
+        if self.history_capacity > 0 {
+            if self.history.len() == self.history_capacity {
+                let snapshot = self.history.pop_front().unwrap();
+                self.release(snapshot);
+            }
+            let snapshot = self.pin(root, generation);
+            self.history.push_back(snapshot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ca_formats::rle::Rle;
+
+    #[test]
+    fn test_snapshot() {
+        let rle = Rle::new("x = 3, y = 2\nb2o$2o$bo!").unwrap();
+        let mut world = World::from_rle(rle).unwrap();
+        let initial_cells = world.cells_vec();
+        let initial = world.snapshot();
+        world.set_step(6);
+        world.step();
+        let later = world.snapshot();
+        let later_population = world.population();
+        world.set_cell(1000, 1000, true);
+        world.garbage_collect();
+
+        assert!(world.restore(initial));
+        assert_eq!(world.get_generation(), 0);
+        assert_eq!(world.cells_vec(), initial_cells);
+        assert_eq!(world.get_step(), 6);
+        world.garbage_collect();
+        assert!(world.restore(later));
+        assert_eq!(world.population(), later_population);
+        assert_eq!(world.snapshot_generation(later), Some(64));
+
+        assert!(world.release(initial));
+        assert!(!world.release(initial));
+        assert!(!world.restore(initial));
+        assert_eq!(world.snapshot_generation(initial), None);
+        world.clear(true);
+        assert!(!world.restore(later));
+    }
+
+    #[test]
+    fn test_history() {
+        let rle = Rle::new("x = 3, y = 2\nb2o$2o$bo!").unwrap();
+        let mut world = World::from_rle(rle).unwrap();
+        world.set_history_capacity(3);
+        let mut populations = vec![world.population()];
+        for _ in 0..5 {
+            world.step();
+            populations.push(world.population());
+        }
+        assert_eq!(world.history_len(), 3);
+        for generation in (2..5).rev() {
+            assert!(world.rewind());
+            assert_eq!(world.get_generation(), generation);
+            assert_eq!(world.population(), populations[generation as usize]);
+        }
+        assert!(!world.rewind());
+        for _ in 0..4 {
+            world.step();
+        }
+        assert!(!world.rewind_to(2));
+        assert_eq!(world.get_generation(), 6);
+        assert!(world.rewind_to(4));
+        assert_eq!(world.get_generation(), 4);
+        assert_eq!(world.population(), populations[4]);
+        world.clear_history();
+        world.step();
+        assert_eq!(world.history_len(), 1);
+        world.set_history_capacity(0);
+        world.step();
+        assert_eq!(world.history_len(), 0);
+        assert!(world.snapshots.is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+use crate::rule::Rule;
+use crate::world::{Nodes, World};
+use std::{
+    mem,
+    ops::Deref,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+// Nodes shared by several worlds, so that identical subtrees, and the results
+// of stepping them, are stored only once. This saves a lot of memory for many
+// related patterns, such as soups that settle into the same ash.
+//
+// The caches are cleared whenever a world steps with another rule than the
+// previous one, so the worlds should agree on it. The steps of each step size
+// are cached separately, so the worlds may step by different sizes. Every
+// operation on a world locks the store: the worlds can be used from several
+// threads, but only one at a time. An iterator or a callback of a world also
+// holds the lock, so it must not use another world of the same store.
+#[derive(Debug)]
+pub struct NodeStore {
+    rule: Rule,
+    nodes: Mutex<Nodes>,
+}
+
+impl NodeStore {
+    // The rule is the initial rule of the worlds created in the store.
+    pub fn new(rule: Rule) -> Self {
+        NodeStore {
+            rule,
+            nodes: Mutex::new(Nodes::default()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Nodes> {
+        self.nodes.lock().unwrap()
+    }
+}
+
+// The membership of a world in a store.
+#[derive(Debug)]
+pub(crate) struct Shared {
+    store: Arc<NodeStore>,
+    // The key of the roots of the world in `Nodes::pinned`.
+    id: usize,
+    // Whether the nodes of the store are moved into the world.
+    checked_out: bool,
+}
+
+// A clone starts with the same roots as the original.
+impl Clone for Shared {
+    fn clone(&self) -> Self {
+        let mut nodes = self.store.lock();
+        let roots = nodes.pinned[self.id].clone();
+        let id = nodes.pinned.insert(roots);
+        Shared {
+            store: self.store.clone(),
+            id,
+            checked_out: false,
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        // After a panic, the store may be poisoned, or its nodes may be left
+        // in the world.
+        if let Ok(mut nodes) = self.store.nodes.lock() {
+            if nodes.pinned.contains(self.id) {
+                nodes.pinned.remove(self.id);
+            }
+        }
+    }
+}
+
+// The nodes of a world, locked if they are in a store.
+pub(crate) enum NodesRef<'a> {
+    Borrowed(&'a Nodes),
+    Locked(MutexGuard<'a, Nodes>),
+}
+
+impl Deref for NodesRef<'_> {
+    type Target = Nodes;
+
+    fn deref(&self) -> &Nodes {
+        match self {
+            NodesRef::Borrowed(nodes) => nodes,
+            NodesRef::Locked(nodes) => nodes,
+        }
+    }
+}
+
+impl World {
+    // An empty world whose nodes are in the store, with the rule of the store.
+    pub fn new_in(store: &Arc<NodeStore>) -> Self {
+        let mut world = World::new(store.rule.clone());
+        let id = store.lock().pinned.insert(Vec::new());
+        world.shared = Some(Shared {
+            store: store.clone(),
+            id,
+            checked_out: false,
+        });
+        world
+    }
+
+    pub(crate) fn nodes(&self) -> NodesRef<'_> {
+        match &self.shared {
+            Some(shared) if !shared.checked_out => NodesRef::Locked(shared.store.lock()),
+            _ => NodesRef::Borrowed(&self.nodes),
+        }
+    }
+
+    pub(crate) fn store(&self) -> Option<&Arc<NodeStore>> {
+        self.shared.as_ref().map(|shared| &shared.store)
+    }
+
+    // Runs `f`, which may modify the nodes. In a store, the nodes are moved
+    // into the world meanwhile, so that `f` can use them like its own, and
+    // then the roots of the world are published for GC in other worlds.
+    pub(crate) fn with_nodes<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut World) -> R,
+    {
+        let (store, id) = match &mut self.shared {
+            Some(shared) if !shared.checked_out => {
+                shared.checked_out = true;
+                (shared.store.clone(), shared.id)
+            }
+            _ => return f(self),
+        };
+        let mut nodes = store.lock();
+        mem::swap(&mut self.nodes, &mut nodes);
+        let result = f(self);
+        let roots = &mut self.nodes.pinned[id];
+        roots.clear();
+        roots.push(self.root);
+        roots.extend(self.snapshots.values().map(|&(node, _)| node));
+        roots.extend_from_slice(self.ages.as_ref().map_or(&[], |ages| ages.planes()));
+        mem::swap(&mut self.nodes, &mut nodes);
+        if let Some(shared) = &mut self.shared {
+            shared.checked_out = false;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ca_formats::rle::Rle;
+
+    const GLIDER: &str = "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!";
+
+    fn glider_in(store: &Arc<NodeStore>, offset: (i64, i64)) -> World {
+        let mut world = World::new_in(store);
+        world.paste(&World::from_rle(Rle::new(GLIDER).unwrap()).unwrap(), offset);
+        world
+    }
+
+    #[test]
+    fn test_shared_store() {
+        let store = Arc::new(NodeStore::new("B3/S23".parse().unwrap()));
+        let mut worlds: Vec<_> = (0..4).map(|i| glider_in(&store, (0, 8 * i))).collect();
+        for world in &mut worlds {
+            world.set_step(4);
+            world.step();
+        }
+        let mut alone = World::from_rle(Rle::new(GLIDER).unwrap()).unwrap();
+        alone.set_step(4);
+        alone.step();
+        let mut expected = alone.cells_vec();
+        expected.sort_unstable();
+        for (i, world) in worlds.iter().enumerate() {
+            assert_eq!(world.get_generation(), 16);
+            let mut cells = world.cells_vec();
+            cells.sort_unstable();
+            let shifted: Vec<_> = expected
+                .iter()
+                .map(|&(x, y)| (x, y + 8 * i as i64))
+                .collect();
+            assert_eq!(cells, shifted, "world {}", i);
+        }
+        // The gliders share their nodes, except near the root.
+        assert!(worlds[0].stats().nodes < 4 * alone.stats().nodes);
+
+        // Pasting from a world of the same store does not lock it twice.
+        let first = worlds[0].clone();
+        worlds[1].paste(&first, (0, 0));
+        assert_eq!(worlds[1].population(), 10);
+    }
+
+    #[test]
+    fn test_shared_gc() {
+        let store = Arc::new(NodeStore::new("B3/S23".parse().unwrap()));
+        let still = glider_in(&store, (0, 0));
+        let snapshot_world = {
+            let mut world = glider_in(&store, (100, 0));
+            world.snapshot();
+            world
+        };
+        let clone = still.clone();
+        drop(still);
+        let mut running = glider_in(&store, (-100, 0));
+        running.set_gc_threshold(0);
+        running.set_step(3);
+        for _ in 0..16 {
+            running.step();
+        }
+        running.garbage_collect();
+        assert_eq!(running.population(), 5);
+        assert_eq!(clone.population(), 5);
+        assert_eq!(clone.bound(), Some((0, 3, 0, 3)));
+        assert!(clone.get_cell(1, 0));
+        assert_eq!(snapshot_world.bound(), Some((100, 103, 0, 3)));
+
+        // Another step size has its own cached steps, and gives the same result.
+        let mut other = clone.clone();
+        other.set_step(2);
+        other.step();
+        other.step();
+        let mut expected = World::from_rle(Rle::new(GLIDER).unwrap()).unwrap();
+        expected.step_by(8);
+        assert_eq!(other.cells_vec().len(), 5);
+        assert_eq!(other.bound(), expected.bound());
+    }
+}
@@ -0,0 +1,209 @@
+// Experimental: how many nodes could be shared if the nodes were stored up to
+// rotation and reflection, see `World::symmetry_compression_ratio`.
+//
+// A store with canonical nodes would look up each node in the orientation
+// with the smallest id, and keep the orientation in the reference to the
+// node, so that a mirrored or rotated subtree is stored only once. Stepping
+// would then have to transform the children on the way down, and the cached
+// steps on the way up. Before paying for that, this measures what it would
+// save: each node reachable from the root is built in its 8 orientations in
+// a separate table, and the nodes whose orientations are the same are
+// counted once.
+//
+// For the bundled patterns, as loaded, after GC (see `examples/symmetry.rs`):
+//
+// | pattern                               | nodes  | ratio |
+// |---------------------------------------|--------|-------|
+// | metapixel-galaxy.mc                   | 5534   | 1.48  |
+// | totalperiodic.mc                      | 52     | 1.02  |
+// | demonoid-c512-hashlife-friendly.mc.gz | 13871  | 1.15  |
+// | c4-diag-switch-engines.rle            | 156    | 1.01  |
+// | switch-engine-breeder.rle             | 159    | 1.05  |
+// | zigzag-wickstretcher.rle              | 2040   | 1.11  |
+// | Sierpinski-builder.rle                | 1      | 1.00  |
+//
+// Only the metapixels share enough to be worth the orientations on every
+// reference.
+
+use crate::{
+    matching::Transform,
+    world::{Leaf, Node, NodeId, Nodes, World},
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+// The ids of the table start here, so that they differ from the leaves.
+const FIRST_ID: u32 = 1 << 16;
+
+// The quadrants of a node, in the order of its children.
+const QUADRANTS: [(i64, i64); 4] = [(0, 0), (1, 0), (0, 1), (1, 1)];
+
+// Where a cell of a square of side `size` goes when the square is
+// transformed around its center.
+fn transform_in(transform: Transform, size: i64, (x, y): (i64, i64)) -> (i64, i64) {
+    let (x, y) = transform.apply((2 * x - size + 1, 2 * y - size + 1));
+    ((x + size - 1) / 2, (y + size - 1) / 2)
+}
+
+// The bit of the cell `(x, y)` of a leaf, from its top-left corner, see
+// `Nodes::get_cell_rec`.
+fn leaf_bit(x: i64, y: i64) -> u32 {
+    (15 - (4 * y + x)) as u32
+}
+
+fn transform_leaf(leaf: Leaf, transform: Transform) -> Leaf {
+    let mut result = 0;
+    for y in 0..4 {
+        for x in 0..4 {
+            if leaf & 1 << leaf_bit(x, y) != 0 {
+                let (x, y) = transform_in(transform, 4, (x, y));
+                result |= 1 << leaf_bit(x, y);
+            }
+        }
+    }
+    result
+}
+
+// The nodes in every orientation, hash-consed by the ids of their children,
+// which are leaves for the nodes of level 3.
+struct Orientations<'a> {
+    nodes: &'a Nodes,
+    table: FxHashMap<[u32; 4], u32>,
+    memo: FxHashMap<(NodeId, Transform), u32>,
+}
+
+impl Orientations<'_> {
+    fn id(&mut self, node: Node, transform: Transform) -> u32 {
+        let id = match node {
+            Node::Leaf(leaf) => return transform_leaf(leaf, transform) as u32,
+            Node::NodeId(id) => id,
+        };
+        if let Some(&oriented) = self.memo.get(&(id, transform)) {
+            return oriented;
+        }
+        let data = &self.nodes[id];
+        let children = [data.nw(), data.ne(), data.sw(), data.se()];
+        let mut oriented_children = [0; 4];
+        for (&child, &quadrant) in children.iter().zip(&QUADRANTS) {
+            let (x, y) = transform_in(transform, 2, quadrant);
+            oriented_children[(2 * y + x) as usize] = self.id(child, transform);
+        }
+        let next = FIRST_ID + self.table.len() as u32;
+        let oriented = *self.table.entry(oriented_children).or_insert(next);
+        self.memo.insert((id, transform), oriented);
+        oriented
+    }
+
+    // The same for the nodes that are the same up to rotation and
+    // reflection.
+    fn canonical(&mut self, id: NodeId) -> u32 {
+        Transform::ALL
+            .iter()
+            .map(|&transform| self.id(Node::NodeId(id), transform))
+            .min()
+            .unwrap()
+    }
+}
+
+impl World {
+    // The number of nodes reachable from the root, divided by the number of
+    // them that are distinct up to rotation and reflection, i.e., how many
+    // times fewer nodes a store of canonical nodes would need for the
+    // current pattern. 1 for a pattern without repeated symmetric parts, and
+    // for an empty world.
+    pub fn symmetry_compression_ratio(&self) -> f64 {
+        let nodes = self.nodes();
+        let mut reachable = FxHashSet::default();
+        let mut stack = vec![self.root];
+        while let Some(node) = stack.pop() {
+            if let Node::NodeId(id) = node {
+                if reachable.insert(id) {
+                    let data = &nodes[id];
+                    stack.extend([data.nw(), data.ne(), data.sw(), data.se()]);
+                }
+            }
+        }
+        if reachable.is_empty() {
+            return 1.0;
+        }
+        let mut orientations = Orientations {
+            nodes: &nodes,
+            table: FxHashMap::default(),
+            memo: FxHashMap::default(),
+        };
+        let classes = reachable
+            .iter()
+            .map(|&id| orientations.canonical(id))
+            .collect::<FxHashSet<_>>();
+        reachable.len() as f64 / classes.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transform_leaf() {
+        // A glider in the top-left corner.
+        let glider: Leaf = 0b_0100_0010_1110_0000;
+        for transform in Transform::ALL {
+            assert_eq!(
+                transform_leaf(glider, transform).count_ones(),
+                glider.count_ones()
+            );
+        }
+        assert_eq!(transform_leaf(glider, Transform::Identity), glider);
+        assert_eq!(
+            transform_leaf(glider, Transform::FlipX),
+            0b_0010_0100_0111_0000
+        );
+        assert_eq!(
+            transform_leaf(glider, Transform::Transpose),
+            0b_0010_1010_0110_0000
+        );
+        for transform in Transform::ALL {
+            let twice = transform_leaf(transform_leaf(glider, transform), transform);
+            let rotation = matches!(transform, Transform::Rotate90 | Transform::Rotate270);
+            assert_eq!(twice == glider, !rotation);
+        }
+    }
+
+    #[test]
+    fn test_symmetry_compression_ratio() {
+        assert_eq!(World::default().symmetry_compression_ratio(), 1.0);
+
+        // Four copies of a glider, far apart, rotated around the center of
+        // the root, so that the nodes around them are the same up to
+        // rotation.
+        let glider = [
+            (1001, 1000),
+            (1002, 1001),
+            (1000, 1002),
+            (1001, 1002),
+            (1002, 1002),
+        ];
+        let mut world = World::default();
+        for transform in [
+            Transform::Identity,
+            Transform::Rotate90,
+            Transform::Rotate180,
+            Transform::Rotate270,
+        ] {
+            let cells = glider.iter().map(|&(x, y)| {
+                let (x, y) = transform.apply((2 * x + 1, 2 * y + 1));
+                ((x - 1) / 2, (y - 1) / 2)
+            });
+            world.set_cells(cells, true);
+        }
+        let ratio = world.symmetry_compression_ratio();
+        assert!(ratio > 1.5, "{}", ratio);
+
+        // The same glider four times, in the same orientation: nothing more
+        // is shared.
+        let mut world = World::default();
+        for (dx, dy) in [(0, 0), (-2000, 0), (0, -2000), (-2000, -2000)] {
+            world.set_cells(glider.iter().map(|&(x, y)| (x + dx, y + dy)), true);
+        }
+        assert!(world.symmetry_compression_ratio() < 1.2);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::world::{Node, World};
+
+impl World {
+    // Keeps a checkpoint, i.e., a pinned root, before each step that starts
+    // at least `interval` generations after the previous checkpoint, so that
+    // `population_at` can look into the past. A checkpoint is also taken of
+    // the current state.
+    //
+    // The interval trades memory for time: a checkpoint only pins a root,
+    // whose nodes are mostly shared with the other checkpoints and the live
+    // pattern, but the nodes that differ stay alive, roughly the changes of
+    // the pattern between two checkpoints. A query steps forward from the
+    // nearest earlier checkpoint, by less than `interval` plus the step size.
+    pub fn enable_time_index(&mut self, interval: u64) -> &mut Self {
+        self.checkpoint_interval = interval.max(1);
+        let (root, generation) = (self.root, self.generation);
+        self.take_checkpoint(root, generation);
+        self
+    }
+
+    // Stops taking checkpoints, and releases the existing ones.
+    pub fn disable_time_index(&mut self) {
+        self.checkpoint_interval = 0;
+        self.release_checkpoints();
+    }
+
+    pub fn checkpoint_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    pub(crate) fn release_checkpoints(&mut self) {
+        for (_, snapshot) in std::mem::take(&mut self.checkpoints) {
+            self.release(snapshot);
+        }
+    }
+
+    // Checkpoints from the generation on belong to a past that was rewound
+    // or edited.
+    fn release_checkpoints_from(&mut self, generation: u64) {
+        for (_, snapshot) in self.checkpoints.split_off(&generation) {
+            self.release(snapshot);
+        }
+    }
+
+    fn take_checkpoint(&mut self, root: Node, generation: u64) {
+        self.release_checkpoints_from(generation);
+        let snapshot = self.pin(root, generation);
+        self.checkpoints.insert(generation, snapshot);
+    }
+
+    // Called after each step from `root` at the `previous` generation.
+    pub(crate) fn sample_checkpoint(&mut self, root: Node, previous: u64) {
+        let interval = self.checkpoint_interval;
+        if interval == 0 {
+            return;
+        }
+        self.release_checkpoints_from(previous);
+        let last = self.checkpoints.keys().next_back();
+        if last.is_none_or(|&last| previous - last >= interval) {
+            self.take_checkpoint(root, previous);
+        }
+    }
+
+    // The population at a past generation, stepped forward from the nearest
+    // earlier checkpoint, see `enable_time_index`. The pattern, the
+    // generation and the step size are restored afterwards.
+    //
+    // Returns `None` for a generation in the future or before the first
+    // checkpoint, or if interrupted by the cancel flag.
+    pub fn population_at(&mut self, generation: u64) -> Option<u64> {
+        if generation >= self.generation {
+            return (generation == self.generation).then(|| self.population());
+        }
+        let (&start, &checkpoint) = self.checkpoints.range(..=generation).next_back()?;
+        self.with_trackers_suspended(|world| {
+            world.restore(checkpoint);
+            world
+                .step_by(generation - start)
+                .then(|| world.population())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::Edit;
+
+    #[test]
+    fn test_population_at() {
+        // The R-pentomino, stepped one generation at a time, then in jumps.
+        let mut world = World::from_bytes(b"b2o$2o$bo!").unwrap();
+        world.enable_time_index(16);
+        let mut populations = vec![world.population()];
+        for _ in 0..100 {
+            world.step();
+            populations.push(world.population());
+        }
+        let middle = world.snapshot();
+        world.set_step(3);
+        for _ in 0..10 {
+            world.step();
+        }
+        assert_eq!(world.get_generation(), 180);
+        // At 0, 16, ..., 96, then at 116, 132, 148 and 164.
+        assert_eq!(world.checkpoint_count(), 11);
+        let cells = world.cells_vec();
+
+        world.garbage_collect();
+        for (generation, &population) in populations.iter().enumerate() {
+            assert_eq!(world.population_at(generation as u64), Some(population));
+        }
+        assert_eq!(world.population_at(180), Some(world.population()));
+        assert_eq!(world.population_at(181), None);
+        assert_eq!(world.get_generation(), 180);
+        assert_eq!(world.get_step(), 3);
+        assert_eq!(world.cells_vec(), cells);
+
+        // No checkpoints are taken while looking ahead, and the scheduled
+        // edits stay pending.
+        world
+            .schedule_edit(200, Edit::SetCells(vec![(0, 0)], true))
+            .unwrap();
+        world.growth_rate(256);
+        world.detect_global_cycle(100);
+        assert_eq!(world.checkpoint_count(), 11);
+        assert_eq!(world.pending_edits().count(), 1);
+        assert_eq!(world.get_step(), 3);
+        assert_eq!(world.cells_vec(), cells);
+
+        // After going back, the later checkpoints are released.
+        world.restore(middle);
+        world.release(middle);
+        world.step();
+        assert_eq!(world.checkpoint_count(), 7);
+        assert_eq!(world.population_at(100), Some(populations[100]));
+
+        world.disable_time_index();
+        assert_eq!(world.checkpoint_count(), 0);
+        assert_eq!(world.population_at(50), None);
+        assert_eq!(world.stats().snapshots, 0);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::world::World;
+use std::collections::VecDeque;
+
+// `(left, right, top, bottom)`, as `World::bound`.
+type Bound = (i64, i64, i64, i64);
+
+impl World {
+    // Records `(generation, bound)` after each step, keeping the last
+    // `capacity` of them; steps after which the pattern is empty are
+    // skipped. A capacity of `0` stops recording and forgets them.
+    //
+    // There is no bound cached in the nodes: `bound` only descends along the
+    // edges of the pattern, which costs far less than the step itself.
+    pub fn record_bounds(&mut self, capacity: usize) -> &mut Self {
+        self.bound_capacity = capacity;
+        while self.bound_history.len() > capacity {
+            self.bound_history.pop_front();
+        }
+        self
+    }
+
+    // The recorded bounds, oldest first.
+    pub fn bound_history(&self) -> &VecDeque<(u64, Bound)> {
+        &self.bound_history
+    }
+
+    // Called after each step.
+    pub(crate) fn sample_bound(&mut self) {
+        if self.bound_capacity == 0 {
+            return;
+        }
+        if let Some(bound) = self.bound() {
+            if self.bound_history.len() == self.bound_capacity {
+                self.bound_history.pop_front();
+            }
+            self.bound_history.push_back((self.generation, bound));
+        }
+    }
+
+    // The velocity of the center of the bounding box in cells per
+    // generation, fitted by least squares over the recorded bounds, e.g.
+    // about `(0.25, 0.25)` for a glider going down and right. Returns `None`
+    // with fewer than two recorded generations.
+    pub fn estimate_drift(&self) -> Option<(f64, f64)> {
+        let &(first, _) = self.bound_history.front()?;
+        let points = self
+            .bound_history
+            .iter()
+            .map(|&(generation, (left, right, top, bottom))| {
+                (
+                    (generation - first) as f64,
+                    (left as f64 + right as f64) / 2.0,
+                    (top as f64 + bottom as f64) / 2.0,
+                )
+            })
+            .collect::<Vec<_>>();
+        let n = points.len() as f64;
+        let mean = points.iter().fold((0.0, 0.0, 0.0), |(g, x, y), p| {
+            (g + p.0 / n, x + p.1 / n, y + p.2 / n)
+        });
+        let (mut variance, mut dx, mut dy) = (0.0, 0.0, 0.0);
+        for &(g, x, y) in &points {
+            variance += (g - mean.0) * (g - mean.0);
+            dx += (g - mean.0) * (x - mean.1);
+            dy += (g - mean.0) * (y - mean.2);
+        }
+        (variance > 0.0).then(|| (dx / variance, dy / variance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_drift() {
+        let mut world = World::from_bytes(b"bo$2bo$3o!").unwrap();
+        world.record_bounds(64);
+        assert_eq!(world.estimate_drift(), None);
+        world.step_by(100);
+        // One step for each bit of 100.
+        assert_eq!(world.bound_history().len(), 3);
+        world.set_step(0);
+        for _ in 0..100 {
+            world.step();
+        }
+        assert_eq!(world.bound_history().len(), 64);
+        assert_eq!(world.bound_history().back().unwrap().0, 200);
+        let (dx, dy) = world.estimate_drift().unwrap();
+        assert!((dx - 0.25).abs() < 0.01 && (dy - 0.25).abs() < 0.01);
+
+        // The analyses that step ahead and come back record nothing.
+        let mut blinker = World::from_bytes(b"3o!").unwrap();
+        blinker.record_bounds(1000).set_step(0);
+        blinker.growth_rate(256);
+        assert!(blinker.bound_history().is_empty());
+        blinker.step();
+        blinker.step();
+        blinker.detect_global_cycle(100);
+        blinker.step();
+        let generations = blinker
+            .bound_history()
+            .iter()
+            .map(|&(generation, _)| generation);
+        assert_eq!(generations.collect::<Vec<_>>(), [1, 2, 3]);
+
+        world.record_bounds(0);
+        assert!(world.bound_history().is_empty());
+        world.step();
+        assert_eq!(world.estimate_drift(), None);
+    }
+}
@@ -0,0 +1,389 @@
+use crate::world::World;
+
+// A viewport maps screen pixels to world coordinates.
+//
+// Scale `s >= 0` means that one pixel covers a block of `2^s * 2^s` cells;
+// scale `s < 0` means that one cell covers `2^-s * 2^-s` pixels.
+//
+// Internally the position is stored in "pixel space": pixel `p` of the
+// screen is pixel `left + p` of an infinite canvas whose pixel `0` is the
+// block (or the cell) containing the origin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    width: i64,
+    height: i64,
+    left: i64,
+    top: i64,
+    scale: i8,
+}
+
+fn to_world(p: i64, scale: i8) -> i64 {
+    if scale >= 0 {
+        ((p as i128) << scale).clamp(i64::MIN as i128, i64::MAX as i128) as i64
+    } else {
+        p >> -scale
+    }
+}
+
+fn to_pixel(x: i64, scale: i8) -> i64 {
+    if scale >= 0 {
+        x >> scale.min(63)
+    } else {
+        ((x as i128) << -scale).clamp(i64::MIN as i128, i64::MAX as i128) as i64
+    }
+}
+
+impl Viewport {
+    pub const MIN_SCALE: i8 = -4;
+    pub const MAX_SCALE: i8 = 62;
+
+    pub fn new(width: u32, height: u32) -> Self {
+        let width = width as i64;
+        let height = height as i64;
+        Viewport {
+            width,
+            height,
+            left: -width / 2,
+            top: -height / 2,
+            scale: 0,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height as u32
+    }
+
+    pub fn scale(&self) -> i8 {
+        self.scale
+    }
+
+    pub fn center(&self) -> (i64, i64) {
+        self.screen_to_world(self.width / 2, self.height / 2)
+    }
+
+    // Resizes the viewport, keeping its center fixed.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let (width, height) = (width as i64, height as i64);
+        self.left -= width / 2 - self.width / 2;
+        self.top -= height / 2 - self.height / 2;
+        self.width = width;
+        self.height = height;
+    }
+
+    // Returns the world coordinates of the cell under the pixel; when zoomed
+    // out, returns the top-left cell of the block under the pixel.
+    pub fn screen_to_world(&self, px: i64, py: i64) -> (i64, i64) {
+        (
+            to_world(self.left + px, self.scale),
+            to_world(self.top + py, self.scale),
+        )
+    }
+
+    // Returns the pixel containing the cell; when zoomed in, returns the
+    // top-left pixel of the cell.
+    pub fn world_to_screen(&self, x: i64, y: i64) -> (i64, i64) {
+        (
+            to_pixel(x, self.scale) - self.left,
+            to_pixel(y, self.scale) - self.top,
+        )
+    }
+
+    // Moves the content of the viewport by `(dx, dy)` pixels.
+    pub fn pan(&mut self, dx: i64, dy: i64) {
+        self.left -= dx;
+        self.top -= dy;
+    }
+
+    // Zooms in by `delta` levels (zooms out if `delta` is negative), keeping
+    // the world point under the pixel fixed.
+    pub fn zoom_at(&mut self, pixel: (i64, i64), delta: i32) {
+        let (px, py) = pixel;
+        let new_scale = (self.scale as i32 - delta)
+            .max(Self::MIN_SCALE as i32)
+            .min(Self::MAX_SCALE as i32) as i8;
+        let shift = new_scale - self.scale;
+        self.left = to_pixel(self.left + px, shift) - px;
+        self.top = to_pixel(self.top + py, shift) - py;
+        self.scale = new_scale;
+    }
+
+    // Bound: (left, right, top, bottom).
+    // Chooses the smallest scale at which the bound fits, and centers it.
+    pub fn fit(&mut self, bound: Option<(i64, i64, i64, i64)>) {
+        if let Some((left, right, top, bottom)) = bound {
+            let pattern_width = right - left;
+            let pattern_height = bottom - top;
+            let center = (left + pattern_width / 2, top + pattern_height / 2);
+            let scale_diff = (pattern_width as f64 / self.width as f64)
+                .max(pattern_height as f64 / self.height as f64)
+                .log2()
+                .ceil() as i32;
+            self.scale = scale_diff
+                .max(Self::MIN_SCALE as i32)
+                .min(Self::MAX_SCALE as i32) as i8;
+            self.left = to_pixel(center.0, self.scale) - self.width / 2;
+            self.top = to_pixel(center.1, self.scale) - self.height / 2;
+        } else {
+            self.scale = 0;
+            self.left = -self.width / 2;
+            self.top = -self.height / 2;
+        }
+    }
+
+    // Bound: (left, right, top, bottom).
+    // Returns the smallest bound containing every cell that is visible,
+    // including cells that are only partially visible.
+    pub fn visible_bound(&self) -> (i64, i64, i64, i64) {
+        (
+            to_world(self.left, self.scale),
+            to_world(self.left + self.width - 1, self.scale).saturating_add(self.cell_size()),
+            to_world(self.top, self.scale),
+            to_world(self.top + self.height - 1, self.scale).saturating_add(self.cell_size()),
+        )
+    }
+
+    // Calls `f(px, py, size)` for every `size * size` square of pixels that
+    // should be drawn. The square may be partially outside of the viewport.
+    pub fn render<F>(&self, world: &World, f: F)
+    where
+        F: FnMut(i64, i64, u32),
+    {
+        let mut f = f;
+        let (left, top) = (self.left, self.top);
+        if self.scale >= 0 {
+            let bound = (left, left + self.width, top, top + self.height);
+            world.for_nodes(self.scale as u8, bound, |x, y| f(x - left, y - top, 1));
+        } else {
+            let magnification = -self.scale as u8;
+            let bound = (left, left + self.width, top, top + self.height);
+            world.for_cells_magnified(magnification, bound, |x, y| {
+                f(x - left, y - top, 1 << magnification)
+            });
+        }
+    }
+
+    // Same as `render`, but also gives the density of living cells in the
+    // block covered by each pixel, between `0` and `1`. When zoomed in, the
+    // density is always `1`.
+    pub fn render_with_density<F>(&self, world: &World, f: F)
+    where
+        F: FnMut(i64, i64, u32, f64),
+    {
+        let mut f = f;
+        let (left, top) = (self.left, self.top);
+        if self.scale >= 0 {
+            let bound = (left, left + self.width, top, top + self.height);
+            let area = 4f64.powi(self.scale as i32);
+            world.for_nodes_with_population(self.scale as u8, bound, |x, y, population| {
+                f(x - left, y - top, 1, population as f64 / area)
+            });
+        } else {
+            self.render(world, |x, y, size| f(x, y, size, 1.0));
+        }
+    }
+
+    // Renders exactly the pixels of the viewport, so that a front end can
+    // copy the image to its window as is. Returns `None` if the image would
+    // have more than `Raster::MAX_PIXELS` pixels.
+    pub fn rasterize(&self, world: &World) -> Option<Raster> {
+        let (width, height) = (self.width, self.height);
+        if width > u32::MAX as i64
+            || height > u32::MAX as i64
+            || width as u64 * height as u64 > Raster::MAX_PIXELS
+        {
+            return None;
+        }
+        let mut pixels = vec![0; (width * height) as usize];
+        self.render(world, |x, y, size| {
+            let size = size as i64;
+            for py in y.max(0)..(y + size).min(height) {
+                for px in x.max(0)..(x + size).min(width) {
+                    pixels[(py * width + px) as usize] = 255;
+                }
+            }
+        });
+        Some(Raster {
+            width: width as u32,
+            height: height as u32,
+            pixels,
+        })
+    }
+
+    fn cell_size(&self) -> i64 {
+        if self.scale >= 0 {
+            1 << self.scale
+        } else {
+            1
+        }
+    }
+}
+
+// An 8-bit grayscale image, in row-major order. Living cells are `255`, and
+// dead cells are `0`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Raster {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Raster {
+    // The most pixels that `rasterize` allocates, one byte each.
+    pub const MAX_PIXELS: u64 = 1 << 30;
+}
+
+impl World {
+    // Bound: (left, right, top, bottom).
+    // Renders the bound at the given scale, with the same meaning as the scale
+    // of a `Viewport`. The size of the image only depends on the bound and the
+    // scale, not on the size of any window. Returns `None` if it is more than
+    // `Raster::MAX_PIXELS`, e.g. for a large bound at a small scale.
+    pub fn rasterize(&self, bound: (i64, i64, i64, i64), scale: i8) -> Option<Raster> {
+        let (left, right, top, bottom) = bound;
+        let scale = scale.clamp(Viewport::MIN_SCALE, Viewport::MAX_SCALE);
+        let to_pixel_end = |x: i64| {
+            if scale >= 0 {
+                to_pixel(x - 1, scale) + 1
+            } else {
+                to_pixel(x, scale)
+            }
+        };
+        let view = Viewport {
+            width: (to_pixel_end(right) - to_pixel(left, scale)).max(0),
+            height: (to_pixel_end(bottom) - to_pixel(top, scale)).max(0),
+            left: to_pixel(left, scale),
+            top: to_pixel(top, scale),
+            scale,
+        };
+        view.rasterize(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coordinates() {
+        let mut view = Viewport::new(800, 600);
+        assert_eq!(view.screen_to_world(400, 300), (0, 0));
+        assert_eq!(view.world_to_screen(0, 0), (400, 300));
+        view.zoom_at((400, 300), -3);
+        assert_eq!(view.scale(), 3);
+        assert_eq!(view.screen_to_world(401, 300), (8, 0));
+        assert_eq!(view.world_to_screen(15, -1), (401, 299));
+        view.zoom_at((400, 300), 5);
+        assert_eq!(view.scale(), -2);
+        assert_eq!(view.screen_to_world(403, 304), (0, 1));
+        assert_eq!(view.world_to_screen(-1, 1), (396, 304));
+        view.pan(8, 0);
+        assert_eq!(view.screen_to_world(408, 300), (0, 0));
+    }
+
+    #[test]
+    fn test_zoom_at_anchors_pixel() {
+        let mut view = Viewport::new(640, 480);
+        view.pan(-123, 45);
+        let pixel = (37, 411);
+        let before = view.screen_to_world(pixel.0, pixel.1);
+        for &delta in &[1, 3, -2, -2] {
+            view.zoom_at(pixel, delta);
+            assert_eq!(view.screen_to_world(pixel.0, pixel.1), before);
+        }
+    }
+
+    #[test]
+    fn test_fit() {
+        let mut view = Viewport::new(100, 100);
+        view.fit(Some((1000, 1400, -300, -100)));
+        assert_eq!(view.scale(), 2);
+        let (left, right, top, bottom) = view.visible_bound();
+        assert!(left <= 1000 && right >= 1400 && top <= -300 && bottom >= -100);
+        view.fit(None);
+        assert_eq!(view.center(), (0, 0));
+    }
+
+    #[test]
+    fn test_render() {
+        let mut world = World::default();
+        world
+            .set_cell(0, 0, true)
+            .set_cell(1, 0, true)
+            .set_cell(-3, 2, true);
+        let mut view = Viewport::new(10, 10);
+        let mut pixels = Vec::new();
+        view.render(&world, |x, y, size| pixels.push((x, y, size)));
+        pixels.sort();
+        assert_eq!(pixels, vec![(2, 7, 1), (5, 5, 1), (6, 5, 1)]);
+        view.zoom_at((5, 5), 1);
+        pixels.clear();
+        view.render(&world, |x, y, size| pixels.push((x, y, size)));
+        pixels.sort();
+        assert_eq!(pixels, vec![(-1, 9, 2), (5, 5, 2), (7, 5, 2)]);
+    }
+
+    #[test]
+    fn test_render_with_density() {
+        let mut world = World::default();
+        world
+            .draw_rect((0, 4, 0, 4), true, true)
+            .set_cell(4, 0, true);
+        let mut view = Viewport::new(8, 8);
+        view.zoom_at((4, 4), -2);
+        let mut pixels = Vec::new();
+        view.render_with_density(&world, |x, y, size, density| {
+            pixels.push((x, y, size, density))
+        });
+        pixels.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(pixels, vec![(4, 4, 1, 1.0), (5, 4, 1, 1.0 / 16.0)]);
+    }
+
+    #[test]
+    fn test_rasterize() {
+        let mut world = World::default();
+        world.set_cell(0, 0, true).set_cell(3, 1, true);
+        let raster = world.rasterize((0, 4, 0, 2), 0).unwrap();
+        assert_eq!((raster.width, raster.height), (4, 2));
+        assert_eq!(raster.pixels, vec![255, 0, 0, 0, 0, 0, 0, 255]);
+        let raster = world.rasterize((0, 4, 0, 2), 1).unwrap();
+        assert_eq!((raster.width, raster.height), (2, 1));
+        assert_eq!(raster.pixels, vec![255, 255]);
+        let raster = world.rasterize((0, 2, 0, 1), -1).unwrap();
+        assert_eq!((raster.width, raster.height), (4, 2));
+        assert_eq!(raster.pixels, vec![255, 255, 0, 0, 255, 255, 0, 0]);
+    }
+
+    #[test]
+    fn test_rasterize_too_large() {
+        let mut world = World::default();
+        world.set_cell(0, 0, true).set_cell(1 << 40, 1 << 40, true);
+        let bound = world.bound().unwrap();
+        assert_eq!(world.rasterize(bound, 1), None);
+        // A single row that is too long.
+        assert_eq!(world.rasterize((0, 1 << 40, 0, 1), 0), None);
+        let raster = world.rasterize(bound, 30).unwrap();
+        assert_eq!((raster.width, raster.height), (1025, 1025));
+        assert_eq!(raster.pixels.iter().filter(|&&pixel| pixel > 0).count(), 2);
+        assert_eq!(Viewport::new(1 << 16, 1 << 15).rasterize(&world), None);
+    }
+
+    #[test]
+    fn test_rasterize_viewport() {
+        let mut world = World::default();
+        world.set_cell(0, 0, true);
+        let mut view = Viewport::new(4, 4);
+        view.zoom_at((2, 2), 1);
+        // The cell is cut by the right edge.
+        view.pan(1, 0);
+        let raster = view.rasterize(&world).unwrap();
+        assert_eq!((raster.width, raster.height), (4, 4));
+        let mut expected = vec![0; 16];
+        expected[11] = 255;
+        expected[15] = 255;
+        assert_eq!(raster.pixels, expected);
+    }
+}
@@ -1,11 +1,51 @@
+use crate::activity::Activity;
+use crate::age::Ages;
+use crate::arena::Arena;
+#[cfg(feature = "background-gc")]
+use crate::background_gc::{BackgroundGc, Links, NO_KEY};
+use crate::metadata::PatternMetadata;
+use crate::population::RunExtremes;
+use crate::recording::{Op, Recorder};
 use crate::rule::Rule;
-use rustc_hash::FxHashMap;
+use crate::schedule::Edit;
+use crate::small::SmallCache;
+use crate::snapshot::Snapshot;
+use crate::store::Shared;
+#[cfg(feature = "strip-table")]
+use crate::strip::StripTable;
+use hashbrown::hash_table::{Entry, HashTable};
+use rustc_hash::{FxHashMap, FxHasher};
 use slab::Slab;
-use std::ops::{Index, IndexMut};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    hash::{Hash, Hasher},
+    mem::size_of,
+    num::NonZeroU8,
+    ops::{Index, IndexMut},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 #[derive(Hash, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
-pub(crate) struct NodeId(u32);
+pub(crate) struct NodeId {
+    index: u32,
+    // The generation of the slot when the id was made, checked on each
+    // access, so that an id held across a GC panics instead of reading
+    // whatever node reuses the slot.
+    #[cfg(feature = "paranoid")]
+    generation: u32,
+}
+
+impl NodeId {
+    fn key(self) -> usize {
+        self.index as usize
+    }
+}
 pub(crate) type Leaf = u16;
+// The nodes already filled by `fill_region_rec`, with their clipped bounds.
+type FillMemo = FxHashMap<(Node, (i64, i64, i64, i64)), Node>;
 const GC_THRESHOLD: usize = 3 << 23;
 
 #[derive(Hash, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
@@ -14,6 +54,15 @@ pub(crate) enum Node {
     NodeId(NodeId),
 }
 
+// A side of a bound, see `Nodes::extreme`.
+#[derive(Clone, Copy)]
+enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
 #[derive(Hash, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
 pub(crate) enum QuadChildren {
     Leaf {
@@ -44,198 +93,1415 @@ impl QuadChildren {
     }
 }
 
-#[derive(Clone, Debug)]
+// Kept in the hash table with the children, so that they are hashed only
+// once, and growing the table only reads the entries. 32 bits are enough,
+// since so are the ids.
+fn hash_children(children: &QuadChildren) -> u32 {
+    let mut hasher = FxHasher::default();
+    children.hash(&mut hasher);
+    (hasher.finish() >> 32) as u32
+}
+
+// The children of the node of level 3 with the same center and the same
+// cells as the leaf.
+pub(crate) fn expand_leaf(leaf: Leaf) -> [Leaf; 4] {
+    [
+        (leaf & 0xcc00) >> 10,
+        (leaf & 0x3300) >> 6,
+        (leaf & 0x00cc) << 6,
+        (leaf & 0x0033) << 10,
+    ]
+}
+
+// A kept hash, spread over the 64 bits that the table uses: the low bits
+// for the bucket, the high bits for the tag.
+fn table_hash(hash: u32) -> u64 {
+    u64::from(hash).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+// No cached step, see `NodeData`.
+const NO_STEP: u32 = u32::MAX;
+
+// 32 bytes, and so is a slot of the arena, whose tag fits in the niche of
+// the level.
+#[derive(Debug)]
 pub(crate) struct NodeData {
-    pub(crate) level: u8,
-    population: u64,
-    pub(crate) children: QuadChildren,
-    pub(crate) cache_step: Option<Node>,
-    gc_mark: bool,
+    // Computed when it is first needed, see `Nodes::population`, since many
+    // nodes are only built to be stepped. Atomic so that `World` stays
+    // `Sync`: two threads may compute it at once, but they write the same.
+    population: AtomicU64,
+    // Leaves if the level is 3, ids otherwise.
+    children: [u32; 4],
+    // The cached step of `2^(level - 2)` generations, which is the same for
+    // every step size, a leaf if the level is 3, or `NO_STEP`. The steps of
+    // fewer generations are in `Nodes::step_cache`.
+    cache_step_max: u32,
+    level: NonZeroU8,
+    // Whether `population` is computed.
+    population_known: AtomicBool,
+    // The generations of the children and of the cached step.
+    #[cfg(feature = "paranoid")]
+    generations: [u32; 5],
+}
+
+impl Clone for NodeData {
+    fn clone(&self) -> Self {
+        NodeData {
+            population: AtomicU64::new(self.population.load(Ordering::Relaxed)),
+            children: self.children,
+            cache_step_max: self.cache_step_max,
+            level: self.level,
+            population_known: AtomicBool::new(self.population_known.load(Ordering::Acquire)),
+            #[cfg(feature = "paranoid")]
+            generations: self.generations,
+        }
+    }
 }
 
 impl NodeData {
-    pub(crate) fn nw(&self) -> Node {
-        match self.children {
-            QuadChildren::NodeId { nw, .. } => Node::NodeId(nw),
-            QuadChildren::Leaf { nw, .. } => Node::Leaf(nw),
+    // The population of a level 3 node, from its leaves, is known at once.
+    fn new(level: u8, children: QuadChildren) -> Self {
+        #[cfg(feature = "paranoid")]
+        let mut generations = [0; 5];
+        let (children, population) = match children {
+            QuadChildren::Leaf { nw, ne, sw, se } => {
+                let leaves = [nw, ne, sw, se];
+                let population = leaves.iter().map(|leaf| leaf.count_ones() as u64).sum();
+                (leaves.map(u32::from), Some(population))
+            }
+            QuadChildren::NodeId { nw, ne, sw, se } => {
+                #[cfg(feature = "paranoid")]
+                for (i, id) in [nw, ne, sw, se].iter().enumerate() {
+                    generations[i] = id.generation;
+                }
+                ([nw.index, ne.index, sw.index, se.index], None)
+            }
+        };
+        debug_assert!(level >= 3, "Level of a node must be >= 3");
+        NodeData {
+            population: AtomicU64::new(population.unwrap_or(0)),
+            children,
+            cache_step_max: NO_STEP,
+            level: NonZeroU8::new(level).unwrap(),
+            population_known: AtomicBool::new(population.is_some()),
+            #[cfg(feature = "paranoid")]
+            generations,
         }
     }
 
-    pub(crate) fn ne(&self) -> Node {
-        match self.children {
-            QuadChildren::NodeId { ne, .. } => Node::NodeId(ne),
-            QuadChildren::Leaf { ne, .. } => Node::Leaf(ne),
+    fn known_population(&self) -> Option<u64> {
+        self.population_known
+            .load(Ordering::Acquire)
+            .then(|| self.population.load(Ordering::Relaxed))
+    }
+
+    fn set_population(&self, population: u64) {
+        self.population.store(population, Ordering::Relaxed);
+        self.population_known.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn level(&self) -> u8 {
+        self.level.get()
+    }
+
+    // The `i`-th link, the children then the step, which is stored as
+    // `raw`.
+    #[cfg_attr(not(feature = "paranoid"), allow(unused_variables))]
+    fn link(&self, i: usize, raw: u32) -> NodeId {
+        NodeId {
+            index: raw,
+            #[cfg(feature = "paranoid")]
+            generation: self.generations[i],
         }
     }
 
-    pub(crate) fn sw(&self) -> Node {
-        match self.children {
-            QuadChildren::NodeId { sw, .. } => Node::NodeId(sw),
-            QuadChildren::Leaf { sw, .. } => Node::Leaf(sw),
+    // A child or the step, which are one level lower.
+    fn node(&self, i: usize, raw: u32) -> Node {
+        if self.level() == 3 {
+            Node::Leaf(raw as Leaf)
+        } else {
+            Node::NodeId(self.link(i, raw))
         }
     }
 
-    pub(crate) fn se(&self) -> Node {
-        match self.children {
-            QuadChildren::NodeId { se, .. } => Node::NodeId(se),
-            QuadChildren::Leaf { se, .. } => Node::Leaf(se),
+    pub(crate) fn children(&self) -> QuadChildren {
+        let [nw, ne, sw, se] = self.children;
+        if self.level() == 3 {
+            let [nw, ne, sw, se] = [nw, ne, sw, se].map(|leaf| leaf as Leaf);
+            QuadChildren::Leaf { nw, ne, sw, se }
+        } else {
+            let [nw, ne, sw, se] = [0, 1, 2, 3].map(|i| self.link(i, self.children[i]));
+            QuadChildren::NodeId { nw, ne, sw, se }
         }
     }
+
+    pub(crate) fn nw(&self) -> Node {
+        self.node(0, self.children[0])
+    }
+
+    pub(crate) fn ne(&self) -> Node {
+        self.node(1, self.children[1])
+    }
+
+    pub(crate) fn sw(&self) -> Node {
+        self.node(2, self.children[2])
+    }
+
+    pub(crate) fn se(&self) -> Node {
+        self.node(3, self.children[3])
+    }
+
+    pub(crate) fn cache_step_max(&self) -> Option<Node> {
+        (self.cache_step_max != NO_STEP).then(|| self.node(4, self.cache_step_max))
+    }
+
+    pub(crate) fn set_cache_step_max(&mut self, step: Option<Node>) {
+        self.cache_step_max = match step {
+            Some(Node::Leaf(leaf)) => leaf as u32,
+            Some(Node::NodeId(id)) => {
+                #[cfg(feature = "paranoid")]
+                {
+                    self.generations[4] = id.generation;
+                }
+                id.index
+            }
+            None => NO_STEP,
+        };
+    }
+}
+
+// The nodes of one or more worlds: each distinct node is stored once, and
+// found from its children through the hash table.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Nodes {
+    // The hash of the children, see `hash_children`, the children and the
+    // node.
+    hash_table: HashTable<(u32, QuadChildren, NodeId)>,
+    node_data: Arena<NodeData>,
+    empty_nodes: Vec<Node>,
+    // The cached steps of `2^step` generations of the nodes of level greater
+    // than `step + 2`, by node and step size. Most nodes are only stepped by
+    // `2^(level - 2)` generations, which is cached in `NodeData` instead.
+    step_cache: FxHashMap<(NodeId, u8), Node>,
+    // In a shared store, the roots of each world, which are kept alive by GC.
+    pub(crate) pinned: Slab<Vec<Node>>,
+    // In a shared store, the rule of the cached steps.
+    cache_key: Option<Box<Rule>>,
+    // The nodes ever added, and the garbage collections, see
+    // `World::step_many`.
+    pub(crate) created: u64,
+    pub(crate) collections: u64,
+    // The generation of each slot, bumped whenever its node is removed.
+    #[cfg(feature = "paranoid")]
+    generations: Vec<u32>,
 }
 
 #[derive(Clone, Debug)]
 pub struct World {
     pub(crate) rule: Rule,
+    // The strip table of the rule, looked up when it is first needed.
+    #[cfg(feature = "strip-table")]
+    pub(crate) strips: Option<Arc<StripTable>>,
     pub(crate) generation: u64,
     pub(crate) step: u8,
-    hash_table: FxHashMap<QuadChildren, NodeId>,
-    node_data: Slab<NodeData>,
-    empty_nodes: Vec<Node>,
+    // The nodes of a world in a shared store are only moved here while it is
+    // being modified, see `World::with_nodes`.
+    pub(crate) nodes: Nodes,
+    pub(crate) shared: Option<Shared>,
     pub(crate) root: Node,
     gc_threshold: usize,
+    // A step is abandoned when the number of nodes reaches this, see
+    // `World::set_node_limit`.
+    node_limit: usize,
+    pub(crate) node_limit_reached: bool,
+    // Pinned roots and their generations, which are kept alive by GC.
+    pub(crate) snapshots: FxHashMap<u64, (Node, u64)>,
+    pub(crate) next_snapshot: u64,
+    // Snapshots before the most recent steps, the newest at the back.
+    pub(crate) history: VecDeque<Snapshot>,
+    pub(crate) history_capacity: usize,
+    // The population is recorded whenever the generation crosses a multiple
+    // of `population_every`, unless it is 0.
+    pub(crate) population_every: u64,
+    pub(crate) population_history: Vec<(u64, u64)>,
+    // The extremes of the population, if tracked, and whether every
+    // generation is sampled for them.
+    pub(crate) extremes: Option<RunExtremes>,
+    pub(crate) exact_extremes: bool,
+    // The last `bound_capacity` bounds after a step, see
+    // `World::record_bounds`.
+    pub(crate) bound_capacity: usize,
+    pub(crate) bound_history: VecDeque<(u64, (i64, i64, i64, i64))>,
+    // Changed cells per tile, if enabled.
+    pub(crate) activity: Option<Box<Activity>>,
+    // The ages of the living cells, if tracked.
+    pub(crate) ages: Option<Box<Ages>>,
+    // Pinned roots by generation, see `World::enable_time_index`.
+    pub(crate) checkpoint_interval: u64,
+    pub(crate) checkpoints: BTreeMap<u64, Snapshot>,
+    // The name, author and comments of the loaded pattern.
+    pub(crate) metadata: PatternMetadata,
+    // The edits that `World::schedule_edit` applies at later generations,
+    // by generation and id.
+    pub(crate) scheduled_edits: BTreeMap<(u64, u64), Edit>,
+    pub(crate) next_edit: u64,
+    // The recording that the edits and steps are added to, if any.
+    pub(crate) recorder: Recorder,
+    // The births and survivals of the rule if it is Life-like, looked up
+    // when first needed, and the bitboard of the pattern while it is small,
+    // see `World::step_small`.
+    pub(crate) life_like: Option<Option<(u16, u16)>>,
+    pub(crate) small: Option<Box<SmallCache>>,
+    // Whether the steps smaller than the maximum still build the overlapping
+    // nodes, to compare the number of nodes in the tests.
+    #[cfg(test)]
+    pub(crate) build_overlapping: bool,
+    // When the flag becomes `true`, the current step is interrupted.
+    pub(crate) cancel: Option<Arc<AtomicBool>>,
+    pub(crate) cancelled: bool,
+    // The marking on another thread, if any, see `spawn_background_gc`.
+    #[cfg(feature = "background-gc")]
+    pub(crate) background_gc: BackgroundGc,
+}
+
+// Counters of the internal state of a world, see `World::stats`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stats {
+    // Nodes in the hash table, including garbage that is not collected yet.
+    pub nodes: usize,
+    // A node of level `n` covers `2^n * 2^n` cells.
+    pub root_level: u8,
+    // Snapshots, including the ones in the history.
+    pub snapshots: usize,
+    // Garbage is collected when `nodes` reaches this.
+    pub gc_threshold: usize,
+}
+
+impl Index<NodeId> for Nodes {
+    type Output = NodeData;
+
+    fn index(&self, id: NodeId) -> &Self::Output {
+        self.check_generation(id);
+        self.data(id)
+    }
+}
+
+impl IndexMut<NodeId> for Nodes {
+    fn index_mut(&mut self, id: NodeId) -> &mut Self::Output {
+        self.check_generation(id);
+        self.data_mut(id)
+    }
 }
 
 impl Index<NodeId> for World {
     type Output = NodeData;
 
     fn index(&self, id: NodeId) -> &Self::Output {
-        &self.node_data[id.0 as usize]
+        &self.nodes[id]
+    }
+}
+
+impl IndexMut<NodeId> for World {
+    fn index_mut(&mut self, id: NodeId) -> &mut Self::Output {
+        &mut self.nodes[id]
+    }
+}
+
+// Reads a cell, the same as `World::get_cell`. Every cell outside of the
+// root is dead.
+impl Index<(i64, i64)> for World {
+    type Output = bool;
+
+    fn index(&self, (x, y): (i64, i64)) -> &bool {
+        if self.get_cell(x, y) {
+            &true
+        } else {
+            &false
+        }
+    }
+}
+
+// The cells of a world, indexed by `(x, y)`, see `World::cells`.
+#[derive(Clone, Copy, Debug)]
+pub struct Cells<'a> {
+    world: &'a World,
+}
+
+impl Index<(i64, i64)> for Cells<'_> {
+    type Output = bool;
+
+    fn index(&self, (x, y): (i64, i64)) -> &bool {
+        &self.world[(x, y)]
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        World::new(Rule::default())
+    }
+}
+
+impl World {
+    pub fn new(rule: Rule) -> Self {
+        Self::new_with_step(rule, 0)
+    }
+
+    // An empty world with space reserved for `capacity` nodes, e.g. the
+    // number of nodes of a pattern that is about to be loaded.
+    pub fn with_capacity(rule: Rule, capacity: usize) -> Self {
+        let mut world = World::new(rule);
+        world.nodes.reserve(capacity);
+        world
+    }
+
+    pub fn new_with_step(rule: Rule, step: u8) -> Self {
+        World {
+            rule,
+            #[cfg(feature = "strip-table")]
+            strips: None,
+            generation: 0,
+            step,
+            nodes: Nodes::default(),
+            shared: None,
+            root: Node::Leaf(0),
+            gc_threshold: GC_THRESHOLD,
+            node_limit: usize::MAX,
+            node_limit_reached: false,
+            snapshots: FxHashMap::default(),
+            next_snapshot: 0,
+            history: VecDeque::new(),
+            history_capacity: 0,
+            population_every: 0,
+            population_history: Vec::new(),
+            extremes: None,
+            exact_extremes: false,
+            bound_capacity: 0,
+            bound_history: VecDeque::new(),
+            activity: None,
+            ages: None,
+            checkpoint_interval: 0,
+            checkpoints: BTreeMap::new(),
+            metadata: PatternMetadata::default(),
+            scheduled_edits: BTreeMap::new(),
+            next_edit: 0,
+            recorder: Recorder::default(),
+            life_like: None,
+            small: None,
+            #[cfg(test)]
+            build_overlapping: false,
+            cancel: None,
+            cancelled: false,
+            #[cfg(feature = "background-gc")]
+            background_gc: BackgroundGc::default(),
+        }
+    }
+
+    // Saturates at `u64::MAX`, e.g. for a full square of side `2^32`. The
+    // populations of the nodes saturate as well, so the pattern still
+    // evolves correctly.
+    pub fn population(&self) -> u64 {
+        self.nodes().node_population(self.root)
+    }
+
+    // `None` if the population is too large for a `u64`, see `population`.
+    pub fn exact_population(&self) -> Option<u64> {
+        Some(self.population()).filter(|&population| population != u64::MAX)
+    }
+
+    pub fn get_cell(&self, x: i64, y: i64) -> bool {
+        self.nodes().get_cell_rec(self.root, x, y)
+    }
+
+    // The cells, to be read as `world.cells()[(x, y)]`, e.g. by code that
+    // only takes something indexable.
+    pub fn cells(&self) -> Cells<'_> {
+        Cells { world: self }
+    }
+
+    pub fn set_cell(&mut self, x: i64, y: i64, state: bool) -> &mut Self {
+        self.apply(Op::SetCell((x, y), state));
+        self
+    }
+
+    pub(crate) fn set_cell_unrecorded(&mut self, x: i64, y: i64, state: bool) {
+        self.with_nodes(|world| {
+            world.expand_to_include((x, x, y, y));
+            world.root = world.set_cell_rec(world.root, x, y, state);
+        });
+    }
+
+    // Sets many cells at once, expanding the root only once.
+    pub fn set_cells<I>(&mut self, cells: I, state: bool) -> &mut Self
+    where
+        I: IntoIterator<Item = (i64, i64)>,
+    {
+        // Collected before locking the nodes, since the cells may come from
+        // another world of the same store.
+        self.apply(Op::SetCells(cells.into_iter().collect(), state));
+        self
+    }
+
+    pub(crate) fn set_cells_unrecorded(&mut self, mut cells: Vec<(i64, i64)>, state: bool) {
+        if let Some(&(x, y)) = cells.first() {
+            let bound = cells.iter().fold((x, x, y, y), |b, &(x, y)| {
+                (b.0.min(x), b.1.max(x), b.2.min(y), b.3.max(y))
+            });
+            self.with_nodes(|world| {
+                world.expand_to_include(bound);
+                world.root = world.set_cells_rec(world.root, &mut cells, state);
+            });
+        }
+    }
+
+    // Sets `len` cells from `(x_start, y)` to the right, from packed bits:
+    // bit `i % 64` of `bits[i / 64]` is the cell `(x_start + i, y)`, so the
+    // least significant bit is the leftmost. If `overwrite` is `false`, zero
+    // bits leave the existing cells untouched; otherwise they clear them.
+    pub fn set_row_bits(
+        &mut self,
+        x_start: i64,
+        y: i64,
+        bits: &[u64],
+        len: usize,
+        overwrite: bool,
+    ) -> &mut Self {
+        assert!(len <= bits.len() * 64, "Not enough bits for the row");
+        if len > 0 {
+            self.with_nodes(|world| {
+                world.expand_to_include((x_start, x_start + len as i64 - 1, y, y));
+                world.root = world.set_row_rec(world.root, x_start, y, bits, len, overwrite);
+            });
+        }
+        self
+    }
+
+    // Bound: (left, right, top, bottom).
+    pub(crate) fn fill_region(&mut self, bound: (i64, i64, i64, i64), state: bool) {
+        let (left, right, top, bottom) = bound;
+        if left < right && top < bottom {
+            self.with_nodes(|world| {
+                world.expand_to_include((left, right - 1, top, bottom - 1));
+                let memo = &mut FxHashMap::default();
+                world.root = world.fill_region_rec(world.root, bound, state, memo);
+            });
+        }
+    }
+
+    pub fn get_step(&self) -> u8 {
+        self.step
+    }
+
+    // The cached steps of each step size are kept, so switching back and
+    // forth between step sizes, e.g. in `step_by`, is cheap.
+    pub fn set_step(&mut self, step: u8) -> &mut Self {
+        self.apply(Op::SetStep(step));
+        self
+    }
+
+    pub(crate) fn set_step_unrecorded(&mut self, step: u8) {
+        self.step = step;
+    }
+
+    pub fn get_generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn set_generation(&mut self, generation: u64) -> &mut Self {
+        self.generation = generation;
+        self
+    }
+
+    pub fn get_rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    pub fn set_rule(&mut self, rule: Rule) -> &mut Self {
+        self.apply(Op::SetRule(Box::new(rule)));
+        self
+    }
+
+    pub(crate) fn set_rule_unrecorded(&mut self, rule: Rule) {
+        if self.shared.is_none() {
+            self.clear_cache();
+        }
+        self.rule = rule;
+        self.life_like = None;
+        self.small = None;
+        #[cfg(feature = "strip-table")]
+        {
+            self.strips = None;
+        }
+    }
+
+    pub fn get_gc_threshold(&self) -> usize {
+        self.gc_threshold
+    }
+
+    // Garbage collection runs after a step when the number of nodes reaches
+    // the threshold.
+    pub fn set_gc_threshold(&mut self, gc_threshold: usize) -> &mut Self {
+        self.gc_threshold = gc_threshold;
+        self
+    }
+
+    pub fn get_node_limit(&self) -> usize {
+        self.node_limit
+    }
+
+    // When a step would make the number of nodes reach the limit, garbage is
+    // collected, and the step tried again. If the limit is reached again,
+    // the step is abandoned like an interrupted one, and `try_step` returns
+    // `StepError::NodeLimit`. The limit is checked between the nodes being
+    // stepped, so it can be exceeded by a few nodes. `usize::MAX` by
+    // default, i.e., no limit.
+    pub fn set_node_limit(&mut self, max_nodes: usize) -> &mut Self {
+        self.node_limit = max_nodes;
+        self
+    }
+
+    pub(crate) fn over_node_limit(&self) -> bool {
+        self.nodes.node_data.len() >= self.node_limit
+    }
+
+    // In a shared store, `nodes` counts the nodes of every world in it.
+    pub fn stats(&self) -> Stats {
+        let nodes = self.nodes();
+        Stats {
+            nodes: nodes.node_data.len(),
+            root_level: nodes.node_level(self.root),
+            snapshots: self.snapshots.len(),
+            gc_threshold: self.gc_threshold,
+        }
+    }
+
+    // An estimate of the memory used by the nodes and the hash table, in
+    // bytes. Allocator overhead is not included.
+    pub fn memory_usage(&self) -> usize {
+        let node = size_of::<NodeData>();
+        let entry = size_of::<(u32, QuadChildren, NodeId)>() + 1;
+        let step = size_of::<((NodeId, u8), Node)>() + 1;
+        let nodes = self.nodes();
+        nodes.node_data.capacity() * node
+            + nodes.hash_table.capacity() * entry
+            + nodes.step_cache.capacity() * step
+    }
+
+    // The nodes of a shared store are never cleared, since other worlds may
+    // use them.
+    pub fn clear(&mut self, clear_nodes: bool) {
+        self.release_checkpoints();
+        if clear_nodes {
+            #[cfg(feature = "background-gc")]
+            self.cancel_background_gc();
+            if self.shared.is_none() {
+                self.nodes.clear();
+            }
+            self.snapshots.clear();
+            self.history.clear();
+        } else if self.shared.is_none() {
+            self.clear_cache();
+        }
+        self.generation = 0;
+        self.root = Node::Leaf(0);
+        self.small = None;
+        self.scheduled_edits.clear();
+        self.population_history.clear();
+        self.bound_history.clear();
+        self.reset_extremes();
+        if self.ages.is_some() {
+            self.enable_age_tracking();
+        }
+    }
+
+    pub fn garbage_collect(&mut self) {
+        #[cfg(feature = "background-gc")]
+        self.cancel_background_gc();
+        self.with_nodes(|world| {
+            let roots = world.gc_roots();
+            world.nodes.garbage_collect(&roots);
+        })
+    }
+
+    // The nodes that GC keeps alive for this world.
+    pub(crate) fn gc_roots(&self) -> Vec<Node> {
+        let mut roots = vec![self.root];
+        roots.extend(self.snapshots.values().map(|&(node, _)| node));
+        roots.extend_from_slice(self.age_planes());
+        roots
+    }
+
+    pub fn bound(&self) -> Option<(i64, i64, i64, i64)> {
+        let nodes = self.nodes();
+        let extreme = |side| {
+            let mut best = None;
+            nodes.extreme(self.root, side, 0, &mut best);
+            best
+        };
+        match (
+            extreme(Side::Left),
+            extreme(Side::Right),
+            extreme(Side::Top),
+            extreme(Side::Bottom),
+        ) {
+            (Some(left), Some(right), Some(top), Some(bottom)) => Some((left, right, top, bottom)),
+            (None, None, None, None) => None,
+            _ => unreachable!(),
+        }
+    }
+
+    // The average position of the living cells. To keep it cheap for big
+    // patterns, cells are grouped into blocks, at most 256 across the bound,
+    // so the result is only accurate to the size of a block.
+    pub fn centroid(&self) -> Option<(f64, f64)> {
+        let bound = self.bound()?;
+        let (left, right, top, bottom) = bound;
+        let size = (right.wrapping_sub(left) as u64).max(bottom.wrapping_sub(top) as u64);
+        let level = (64 - ((size - 1) / 256).leading_zeros()) as u8;
+        let block = (1u64 << level) as f64;
+        let offset = (block - 1.0) / 2.0;
+        let (mut sum_x, mut sum_y) = (0.0, 0.0);
+        self.for_nodes_with_population(level, bound, |x, y, population| {
+            sum_x += (x as f64 * block + offset) * population as f64;
+            sum_y += (y as f64 * block + offset) * population as f64;
+        });
+        let population = self.population() as f64;
+        Some((sum_x / population, sum_y / population))
+    }
+
+    // Bound: (left, right, top, bottom).
+    pub fn population_in(&self, bound: (i64, i64, i64, i64)) -> u64 {
+        self.nodes().population_in_rec(self.root, bound)
+    }
+
+    // The right and bottom edges of the blocks at the edges of the plane
+    // saturate, like any bound.
+    pub fn density_at(&self, level: u8, x: i64, y: i64) -> f64 {
+        debug_assert!(level < 63, "Level of a block must be < 63");
+        let left = x >> level << level;
+        let top = y >> level << level;
+        let size = 1 << level;
+        let bound = (
+            left,
+            left.saturating_add(size),
+            top,
+            top.saturating_add(size),
+        );
+        let population = self.population_in(bound);
+        population as f64 / (size as f64 * size as f64)
+    }
+
+    pub(crate) fn check_gc(&mut self) {
+        #[cfg(feature = "background-gc")]
+        self.poll_background_gc();
+        if self.nodes.node_data.len() >= self.gc_threshold {
+            self.garbage_collect();
+        }
+    }
+
+    pub(crate) fn empty_node(&mut self, level: u8) -> Node {
+        self.nodes.empty_node(level)
+    }
+
+    // Bound: (min_x, max_x, min_y, max_y), inclusive.
+    pub(crate) fn expand_to_include(&mut self, bound: (i64, i64, i64, i64)) {
+        let (min_x, max_x, min_y, max_y) = bound;
+        let mut level = self.node_level(self.root);
+        while {
+            let half = 1i128 << (level - 1);
+            (min_x as i128) < -half
+                || max_x as i128 >= half
+                || (min_y as i128) < -half
+                || max_y as i128 >= half
+        } {
+            level += 1;
+        }
+        self.expand_to_level(level);
+    }
+
+    pub(crate) fn full_node(&mut self, level: u8) -> Node {
+        debug_assert!(level >= 2, "Level of a node must be >= 2");
+        let mut node = Node::Leaf(0xffff);
+        for _ in 2..level {
+            node = Node::NodeId(self.find_node(node, node, node, node));
+        }
+        node
+    }
+
+    pub(crate) fn expand(&mut self) {
+        self.root = self.expand_node(self.root);
+    }
+
+    // Same as expanding until the root has the given level, but builds each
+    // quarter of the final root directly, without the roots in between.
+    pub(crate) fn expand_to_level(&mut self, level: u8) {
+        if self.node_level(self.root) >= level {
+            return;
+        }
+        if let Node::Leaf(_) = self.root {
+            self.expand();
+        }
+        let data = match self.root {
+            Node::NodeId(id) => &self[id],
+            Node::Leaf(_) => unreachable!("The root cannot be a leaf after expansion."),
+        };
+        let mut quarters = [data.nw(), data.ne(), data.sw(), data.se()];
+        // Each quarter is wrapped in empty nodes, at the corner that faces
+        // the center.
+        for quarter_level in data.level() - 1..level - 1 {
+            let empty = self.empty_node(quarter_level);
+            let [nw, ne, sw, se] = quarters;
+            quarters = [
+                Node::NodeId(self.find_node(empty, empty, empty, nw)),
+                Node::NodeId(self.find_node(empty, empty, ne, empty)),
+                Node::NodeId(self.find_node(empty, sw, empty, empty)),
+                Node::NodeId(self.find_node(se, empty, empty, empty)),
+            ];
+        }
+        let [nw, ne, sw, se] = quarters;
+        self.root = Node::NodeId(self.find_node(nw, ne, sw, se));
+    }
+
+    // The node of the next level with the same center and the same cells.
+    pub(crate) fn expand_node(&mut self, node: Node) -> Node {
+        match node {
+            Node::Leaf(leaf) => {
+                let [nw, ne, sw, se] = expand_leaf(leaf).map(Node::Leaf);
+                Node::NodeId(self.find_node(nw, ne, sw, se))
+            }
+            Node::NodeId(id) => {
+                let level = self[id].level();
+                let empty = self.empty_node(level - 1);
+                let nw = Node::NodeId(self.find_node(empty, empty, empty, self[id].nw()));
+                let ne = Node::NodeId(self.find_node(empty, empty, self[id].ne(), empty));
+                let sw = Node::NodeId(self.find_node(empty, self[id].sw(), empty, empty));
+                let se = Node::NodeId(self.find_node(self[id].se(), empty, empty, empty));
+                Node::NodeId(self.find_node(nw, ne, sw, se))
+            }
+        }
+    }
+
+    // Moves the whole universe one row down, or up, without expanding it into
+    // cells. The root is expanded first, so that no cell is lost.
+    pub(crate) fn shift_row(&mut self, down: bool) {
+        self.expand();
+        let empty = self.empty_node(self.node_level(self.root));
+        let (upper, lower) = if down {
+            (empty, self.root)
+        } else {
+            (self.root, empty)
+        };
+        self.root = self.shift_row_rec(upper, lower, down, &mut FxHashMap::default());
+    }
+
+    // `upper` and `lower` are vertically adjacent nodes of the same level.
+    // Returns the node made of the last row of `upper` and all but the last
+    // row of `lower` if `down`, or of all but the first row of `upper` and
+    // the first row of `lower` otherwise.
+    fn shift_row_rec(
+        &mut self,
+        upper: Node,
+        lower: Node,
+        down: bool,
+        memo: &mut FxHashMap<(Node, Node), Node>,
+    ) -> Node {
+        let (upper_id, lower_id) = match (upper, lower) {
+            (Node::Leaf(upper), Node::Leaf(lower)) => {
+                return Node::Leaf(if down {
+                    (upper & 0x000f) << 12 | lower >> 4
+                } else {
+                    upper << 4 | lower >> 12
+                });
+            }
+            (Node::NodeId(upper), Node::NodeId(lower)) => (upper, lower),
+            _ => unreachable!("Both nodes must have the same level."),
+        };
+        if let Some(&node) = memo.get(&(upper, lower)) {
+            return node;
+        }
+        let (u, l) = (&self[upper_id], &self[lower_id]);
+        let (top, bottom) = if down {
+            (
+                [u.sw(), l.nw(), u.se(), l.ne()],
+                [l.nw(), l.sw(), l.ne(), l.se()],
+            )
+        } else {
+            (
+                [u.nw(), u.sw(), u.ne(), u.se()],
+                [u.sw(), l.nw(), u.se(), l.ne()],
+            )
+        };
+        let nw = self.shift_row_rec(top[0], top[1], down, memo);
+        let ne = self.shift_row_rec(top[2], top[3], down, memo);
+        let sw = self.shift_row_rec(bottom[0], bottom[1], down, memo);
+        let se = self.shift_row_rec(bottom[2], bottom[3], down, memo);
+        let node = Node::NodeId(self.find_node(nw, ne, sw, se));
+        memo.insert((upper, lower), node);
+        node
+    }
+
+    // `nodes` are the four children of a node, `[nw, ne, sw, se]`. Returns
+    // the node of the level of the children whose top-left corner is
+    // `(x, y)` from the top-left corner of that node, with both coordinates
+    // at most the size of a child. The offsets at each level only depend on
+    // the offset at the top, so the memo is shared by the whole shift.
+    pub(crate) fn shift_rec(
+        &mut self,
+        nodes: [Node; 4],
+        (x, y): (i128, i128),
+        memo: &mut FxHashMap<[Node; 4], Node>,
+    ) -> Node {
+        if let [Node::Leaf(nw), Node::Leaf(ne), Node::Leaf(sw), Node::Leaf(se)] = nodes {
+            // The rows of the 8x8 square, leftmost cell first.
+            let row = |leaf: Leaf, i: i128| leaf >> (12 - 4 * i) & 0xf;
+            let rows: [Leaf; 8] = std::array::from_fn(|i| {
+                let i = i as i128;
+                if i < 4 {
+                    row(nw, i) << 4 | row(ne, i)
+                } else {
+                    row(sw, i - 4) << 4 | row(se, i - 4)
+                }
+            });
+            return Node::Leaf((0..4).fold(0, |leaf, i| {
+                leaf | (rows[(y + i) as usize] >> (4 - x) & 0xf) << (12 - 4 * i)
+            }));
+        }
+        if nodes.iter().all(|&node| self.node_population(node) == 0) {
+            return nodes[0];
+        }
+        if let Some(&node) = memo.get(&nodes) {
+            return node;
+        }
+        let grandchildren = nodes.map(|node| match node {
+            Node::NodeId(id) => {
+                let data = &self[id];
+                [data.nw(), data.ne(), data.sw(), data.se()]
+            }
+            Node::Leaf(_) => unreachable!("All nodes must have the same level."),
+        });
+        // The 4x4 grandchildren, row by row.
+        let grid = |i: usize, j: usize| grandchildren[i / 2 * 2 + j / 2][i % 2 * 2 + j % 2];
+        let half = 1i128 << (self.node_level(nodes[0]) - 1);
+        let (i, y) = if y < half { (0, y) } else { (1, y - half) };
+        let (j, x) = if x < half { (0, x) } else { (1, x - half) };
+        let [nw, ne, sw, se] = [(i, j), (i, j + 1), (i + 1, j), (i + 1, j + 1)].map(|(i, j)| {
+            let children = [
+                grid(i, j),
+                grid(i, j + 1),
+                grid(i + 1, j),
+                grid(i + 1, j + 1),
+            ];
+            self.shift_rec(children, (x, y), memo)
+        });
+        let node = Node::NodeId(self.find_node(nw, ne, sw, se));
+        memo.insert(nodes, node);
+        node
+    }
+
+    // Whether some living cells are outside the quarter of each child nearest
+    // to the center. Only emptiness is checked, which is exact even when
+    // the populations saturate.
+    pub(crate) fn should_expand(&self) -> bool {
+        let id = match self.root {
+            Node::Leaf(_) => return true,
+            Node::NodeId(id) => id,
+        };
+        let data = &self[id];
+        let children = [data.nw(), data.ne(), data.sw(), data.se()];
+        // The quarter of a leaf, and the cell of a leaf, at each corner.
+        let quarters: [Leaf; 4] = [0xcc00, 0x3300, 0x00cc, 0x0033];
+        let cells: [Leaf; 4] = [0x8000, 0x1000, 0x0008, 0x0001];
+        children.iter().enumerate().any(|(i, &child)| {
+            // The corner nearest to the center.
+            let inner = 3 - i;
+            match child {
+                Node::Leaf(leaf) => leaf & !cells[inner] != 0,
+                Node::NodeId(id) => {
+                    let data = &self[id];
+                    let grandchildren = [data.nw(), data.ne(), data.sw(), data.se()];
+                    let outer = |nodes: [Node; 4]| {
+                        (0..4).any(|j| j != inner && self.node_population(nodes[j]) > 0)
+                    };
+                    outer(grandchildren)
+                        || match grandchildren[inner] {
+                            Node::Leaf(leaf) => leaf & !quarters[inner] != 0,
+                            Node::NodeId(id) => {
+                                let data = &self[id];
+                                outer([data.nw(), data.ne(), data.sw(), data.se()])
+                            }
+                        }
+                }
+            }
+        })
+    }
+
+    fn clear_cache(&mut self) {
+        self.nodes.clear_cache();
+    }
+
+    pub(crate) fn find_node(&mut self, nw: Node, ne: Node, sw: Node, se: Node) -> NodeId {
+        self.nodes.find_node(nw, ne, sw, se)
+    }
+
+    pub(crate) fn node_level(&self, node: Node) -> u8 {
+        self.nodes.node_level(node)
+    }
+
+    pub(crate) fn node_population(&self, node: Node) -> u64 {
+        self.nodes.node_population(node)
+    }
+
+    fn set_cell_rec(&mut self, node: Node, x: i64, y: i64, state: bool) -> Node {
+        let node_size = 1 << (self.node_level(node) - 2);
+        debug_assert!(
+            x < 2 * node_size && x >= -2 * node_size && y < 2 * node_size && y >= -2 * node_size,
+            "Cannot set cell outside of the node."
+        );
+        match node {
+            Node::Leaf(leaf) => {
+                if state {
+                    Node::Leaf(leaf | 1 << ((1 - y) * 4 + (1 - x)))
+                } else {
+                    Node::Leaf(leaf & !(1 << ((1 - y) * 4 + (1 - x))))
+                }
+            }
+            Node::NodeId(id) => {
+                let data = &self[id];
+                let mut nw = data.nw();
+                let mut ne = data.ne();
+                let mut sw = data.sw();
+                let mut se = data.se();
+                match (x.is_negative(), y.is_negative()) {
+                    (true, true) => nw = self.set_cell_rec(nw, x + node_size, y + node_size, state),
+                    (false, true) => {
+                        ne = self.set_cell_rec(ne, x - node_size, y + node_size, state)
+                    }
+                    (true, false) => {
+                        sw = self.set_cell_rec(sw, x + node_size, y - node_size, state)
+                    }
+                    (false, false) => {
+                        se = self.set_cell_rec(se, x - node_size, y - node_size, state)
+                    }
+                }
+                Node::NodeId(self.find_node(nw, ne, sw, se))
+            }
+        }
+    }
+
+    fn set_cells_rec(&mut self, node: Node, cells: &mut [(i64, i64)], state: bool) -> Node {
+        if cells.is_empty() {
+            return node;
+        }
+        match node {
+            Node::Leaf(leaf) => {
+                let mask = cells
+                    .iter()
+                    .fold(0, |mask, &(x, y)| mask | 1 << ((1 - y) * 4 + (1 - x)));
+                if state {
+                    Node::Leaf(leaf | mask)
+                } else {
+                    Node::Leaf(leaf & !mask)
+                }
+            }
+            Node::NodeId(id) => {
+                let node_size = 1 << (self.node_level(node) - 2);
+                let quadrant = |&(x, y): &(i64, i64)| (y >= 0) as usize * 2 + (x >= 0) as usize;
+                cells.sort_unstable_by_key(quadrant);
+                let data = &self[id];
+                let mut children = [data.nw(), data.ne(), data.sw(), data.se()];
+                let mut rest = cells;
+                for (i, child) in children.iter_mut().enumerate() {
+                    let len = rest.iter().take_while(|c| quadrant(c) == i).count();
+                    let (quad, tail) = rest.split_at_mut(len);
+                    let dx = if i % 2 == 0 { node_size } else { -node_size };
+                    let dy = if i / 2 == 0 { node_size } else { -node_size };
+                    quad.iter_mut().for_each(|c| *c = (c.0 + dx, c.1 + dy));
+                    *child = self.set_cells_rec(*child, quad, state);
+                    rest = tail;
+                }
+                let [nw, ne, sw, se] = children;
+                Node::NodeId(self.find_node(nw, ne, sw, se))
+            }
+        }
+    }
+
+    fn set_row_rec(
+        &mut self,
+        node: Node,
+        x_start: i64,
+        y: i64,
+        bits: &[u64],
+        len: usize,
+        overwrite: bool,
+    ) -> Node {
+        let node_size = 1 << (self.node_level(node) - 2);
+        if x_start >= 2 * node_size || x_start + len as i64 <= -2 * node_size {
+            return node;
+        }
+        match node {
+            Node::Leaf(leaf) => {
+                let (mut set, mut clear): (Leaf, Leaf) = (0, 0);
+                for x in x_start.max(-2)..(x_start + len as i64).min(2) {
+                    let i = (x - x_start) as usize;
+                    let bit = 1 << ((1 - y) * 4 + (1 - x));
+                    if bits[i / 64] & 1 << (i % 64) != 0 {
+                        set |= bit;
+                    } else if overwrite {
+                        clear |= bit;
+                    }
+                }
+                Node::Leaf((leaf & !clear) | set)
+            }
+            Node::NodeId(id) => {
+                let data = &self[id];
+                let (mut nw, mut ne, mut sw, mut se) = (data.nw(), data.ne(), data.sw(), data.se());
+                if y < 0 {
+                    nw = self.set_row_rec(
+                        nw,
+                        x_start + node_size,
+                        y + node_size,
+                        bits,
+                        len,
+                        overwrite,
+                    );
+                    ne = self.set_row_rec(
+                        ne,
+                        x_start - node_size,
+                        y + node_size,
+                        bits,
+                        len,
+                        overwrite,
+                    );
+                } else {
+                    sw = self.set_row_rec(
+                        sw,
+                        x_start + node_size,
+                        y - node_size,
+                        bits,
+                        len,
+                        overwrite,
+                    );
+                    se = self.set_row_rec(
+                        se,
+                        x_start - node_size,
+                        y - node_size,
+                        bits,
+                        len,
+                        overwrite,
+                    );
+                }
+                Node::NodeId(self.find_node(nw, ne, sw, se))
+            }
+        }
+    }
+
+    // Bound: (left, right, top, bottom), relative to the center of the node.
+    // The bound is clipped to the node, so that the nodes filled the same
+    // way, e.g. all along a long row, are only filled once.
+    fn fill_region_rec(
+        &mut self,
+        node: Node,
+        bound: (i64, i64, i64, i64),
+        state: bool,
+        memo: &mut FillMemo,
+    ) -> Node {
+        let level = self.node_level(node);
+        let node_size = 1 << (level - 2);
+        let (left, right, top, bottom) = bound;
+        // How far the edges of the node are from its center, in `i128` since
+        // it does not fit in `i64` for a root of level 64.
+        let half = 1i128 << (level - 1);
+        let outside = |low: i64, high: i64| low as i128 >= half || high as i128 <= -half;
+        let covers = |low: i64, high: i64| low as i128 <= -half && high as i128 >= half;
+        if outside(left, right) || outside(top, bottom) {
+            return node;
+        }
+        if covers(left, right) && covers(top, bottom) {
+            return if state {
+                self.full_node(level)
+            } else {
+                self.empty_node(level)
+            };
+        }
+        match node {
+            Node::Leaf(leaf) => {
+                let mut mask: Leaf = 0;
+                for y in top.max(-2)..bottom.min(2) {
+                    for x in left.max(-2)..right.min(2) {
+                        mask |= 1 << ((1 - y) * 4 + (1 - x));
+                    }
+                }
+                if state {
+                    Node::Leaf(leaf | mask)
+                } else {
+                    Node::Leaf(leaf & !mask)
+                }
+            }
+            Node::NodeId(id) => {
+                let clip = |x: i64| (x as i128).clamp(-half, half) as i64;
+                let bound = (clip(left), clip(right), clip(top), clip(bottom));
+                if let Some(&node) = memo.get(&(node, bound)) {
+                    return node;
+                }
+                let (left, right, top, bottom) = bound;
+                let data = &self[id];
+                let (nw, ne, sw, se) = (data.nw(), data.ne(), data.sw(), data.se());
+                let shift = |dx: i64, dy: i64| {
+                    (
+                        left.saturating_add(dx),
+                        right.saturating_add(dx),
+                        top.saturating_add(dy),
+                        bottom.saturating_add(dy),
+                    )
+                };
+                let nw = self.fill_region_rec(nw, shift(node_size, node_size), state, memo);
+                let ne = self.fill_region_rec(ne, shift(-node_size, node_size), state, memo);
+                let sw = self.fill_region_rec(sw, shift(node_size, -node_size), state, memo);
+                let se = self.fill_region_rec(se, shift(-node_size, -node_size), state, memo);
+                let result = Node::NodeId(self.find_node(nw, ne, sw, se));
+                memo.insert((node, bound), result);
+                result
+            }
+        }
+    }
+}
+
+impl Nodes {
+    // Removes the nodes that cannot be reached from the roots, from the roots
+    // of the worlds sharing the nodes, or from the empty nodes.
+    fn garbage_collect(&mut self, roots: &[Node]) {
+        // The marks are indexed by the key in the arena, and kept out of
+        // `NodeData` so that nodes stay small and `World` stays `Sync`.
+        let mut marks = vec![false; self.node_data.capacity()];
+        for node in self.all_roots(roots) {
+            self.mark_gc(node, &mut marks);
+        }
+        self.mark_step_cache(&mut marks);
+        self.sweep(&marks);
+    }
+
+    // The roots, with those of the worlds sharing the nodes, and the empty
+    // nodes.
+    pub(crate) fn all_roots(&self, roots: &[Node]) -> Vec<Node> {
+        let pinned = self.pinned.iter().flat_map(|(_, roots)| roots);
+        let empty = self.empty_nodes.last();
+        roots.iter().chain(pinned).chain(empty).copied().collect()
+    }
+
+    // Marks the cached steps in `step_cache` of the marked nodes, like
+    // `mark_gc` does for the cached steps in `NodeData`. This may mark more
+    // nodes with cached steps, which are added to the work list, so each
+    // entry is looked at once.
+    pub(crate) fn mark_step_cache(&self, marks: &mut [bool]) {
+        // The cached steps that are not leaves, by the key of their node.
+        let mut steps: FxHashMap<usize, Vec<Node>> = FxHashMap::default();
+        for (&(id, _), &step) in &self.step_cache {
+            if let Node::NodeId(_) = step {
+                steps.entry(id.key()).or_default().push(step);
+            }
+        }
+        let mut pending: Vec<usize> = steps.keys().copied().filter(|&key| marks[key]).collect();
+        while let Some(key) = pending.pop() {
+            for step in steps.remove(&key).into_iter().flatten() {
+                self.mark_gc_with(step, marks, &mut |key| {
+                    if steps.contains_key(&key) {
+                        pending.push(key);
+                    }
+                });
+            }
+        }
+    }
+
+    // Removes the nodes that are not marked, and the cached steps that are
+    // not marked either.
+    pub(crate) fn sweep(&mut self, marks: &[bool]) {
+        self.collections += 1;
+        // The live entries stay where they are, without being hashed again.
+        self.hash_table.retain(|&mut (_, _, id)| marks[id.key()]);
+        self.step_cache.retain(|&(id, _), &mut step| {
+            marks[id.key()]
+                && match step {
+                    Node::Leaf(_) => true,
+                    Node::NodeId(step_id) => marks[step_id.key()],
+                }
+        });
+        #[cfg(feature = "paranoid")]
+        let generations = &mut self.generations;
+        self.node_data.retain(|i, data| {
+            if let Some(Node::NodeId(id)) = data.cache_step_max() {
+                if !marks[id.key()] {
+                    data.set_cache_step_max(None);
+                }
+            }
+            #[cfg(feature = "paranoid")]
+            if !marks[i] {
+                generations[i] = generations[i].wrapping_add(1);
+            }
+            marks[i]
+        });
     }
-}
 
-impl IndexMut<NodeId> for World {
-    fn index_mut(&mut self, id: NodeId) -> &mut Self::Output {
-        &mut self.node_data[id.0 as usize]
+    #[cfg(feature = "background-gc")]
+    // The links of each slot, for marking on another thread, and the keys of
+    // the roots, see `World::spawn_background_gc`.
+    pub(crate) fn links(&self, roots: &[Node]) -> (Vec<Option<Links>>, Vec<u32>) {
+        let key = |node: Node| match node {
+            Node::Leaf(_) => NO_KEY,
+            Node::NodeId(id) => id.index,
+        };
+        let links = (0..self.node_data.capacity())
+            .map(|i| {
+                let data = self.node_data.get(i)?;
+                let step = data.cache_step_max().map_or(NO_KEY, key);
+                let [nw, ne, sw, se] = [data.nw(), data.ne(), data.sw(), data.se()].map(key);
+                Some([nw, ne, sw, se, step])
+            })
+            .collect();
+        let roots = self.all_roots(roots).into_iter().map(key).collect();
+        (links, roots)
     }
-}
 
-impl Default for World {
-    fn default() -> Self {
-        World::new("B3/S23".parse().unwrap())
+    #[cfg(feature = "background-gc")]
+    // Completes the marks of a copy of the links: the nodes created since
+    // the copy, and whatever the roots and those nodes reach, are marked as
+    // well.
+    pub(crate) fn remark(&self, roots: &[Node], copied: &[Option<bool>]) -> Vec<bool> {
+        let mut marks = vec![false; self.node_data.capacity()];
+        let mut created = Vec::new();
+        for (i, mark) in marks.iter_mut().enumerate() {
+            match copied.get(i) {
+                Some(&Some(live)) => *mark = live,
+                _ if self.node_data.get(i).is_some() => created.push(i),
+                _ => {}
+            }
+        }
+        let created = created.into_iter().map(|i| Node::NodeId(self.id(i)));
+        for node in self.all_roots(roots).into_iter().chain(created) {
+            self.mark_gc(node, &mut marks);
+        }
+        self.mark_step_cache(&mut marks);
+        marks
     }
-}
 
-impl World {
-    pub fn new(rule: Rule) -> Self {
-        Self::new_with_step(rule, 0)
+    pub(crate) fn mark_gc(&self, node: Node, marks: &mut [bool]) {
+        self.mark_gc_with(node, marks, &mut |_| {});
     }
 
-    pub fn new_with_step(rule: Rule, step: u8) -> Self {
-        let hash_table = FxHashMap::default();
-        let node_data = Slab::new();
-        let empty_nodes = Vec::new();
-        let root = Node::Leaf(0);
-        World {
-            rule,
-            generation: 0,
-            step,
-            hash_table,
-            node_data,
-            empty_nodes,
-            root,
-            gc_threshold: GC_THRESHOLD,
+    // Same as `mark_gc`, and calls `on_mark` with the key of each node that
+    // it marks.
+    fn mark_gc_with<F: FnMut(usize)>(&self, node: Node, marks: &mut [bool], on_mark: &mut F) {
+        if let Node::NodeId(id) = node {
+            if !marks[id.key()] {
+                marks[id.key()] = true;
+                on_mark(id.key());
+                self.mark_gc_with(self[id].nw(), marks, on_mark);
+                self.mark_gc_with(self[id].ne(), marks, on_mark);
+                self.mark_gc_with(self[id].sw(), marks, on_mark);
+                self.mark_gc_with(self[id].se(), marks, on_mark);
+                if let Some(node) = self[id].cache_step_max() {
+                    self.mark_gc_with(node, marks, on_mark);
+                }
+            }
         }
     }
 
-    pub fn population(&self) -> u64 {
-        self.node_population(self.root)
+    fn reserve(&mut self, additional: usize) {
+        self.hash_table
+            .reserve(additional, |&(hash, _, _)| table_hash(hash));
+        self.node_data.reserve(additional);
     }
 
-    pub fn get_cell(&mut self, x: i64, y: i64) -> bool {
-        self.get_cell_rec(self.root, x, y)
+    fn clear(&mut self) {
+        self.hash_table.clear();
+        self.step_cache.clear();
+        self.empty_nodes.clear();
+        self.node_data.clear();
+        #[cfg(feature = "paranoid")]
+        self.generations
+            .iter_mut()
+            .for_each(|generation| *generation = generation.wrapping_add(1));
     }
 
-    pub fn set_cell(&mut self, x: i64, y: i64, state: bool) -> &mut Self {
-        while {
-            let offset = 1 << (self.node_level(self.root) - 2);
-            x >= 2 * offset || x < -2 * offset || y >= 2 * offset || y < -2 * offset
-        } {
-            self.expand();
+    // The id of the node in a slot.
+    #[cfg_attr(
+        not(any(feature = "paranoid", feature = "background-gc")),
+        allow(dead_code)
+    )]
+    fn id(&self, key: usize) -> NodeId {
+        NodeId {
+            index: key as u32,
+            #[cfg(feature = "paranoid")]
+            generation: self.generations[key],
         }
-        self.root = self.set_cell_rec(self.root, x, y, state);
-        self
     }
 
-    pub fn get_step(&self) -> u8 {
-        self.step
+    #[cfg(not(all(feature = "fast-index", not(miri))))]
+    fn data(&self, id: NodeId) -> &NodeData {
+        &self.node_data[id.key()]
     }
 
-    pub fn set_step(&mut self, step: u8) -> &mut Self {
-        self.clear_cache();
-        self.garbage_collect();
-        self.step = step;
-        self
+    #[cfg(not(all(feature = "fast-index", not(miri))))]
+    fn data_mut(&mut self, id: NodeId) -> &mut NodeData {
+        &mut self.node_data[id.key()]
     }
 
-    pub fn get_generation(&self) -> u64 {
-        self.generation
+    // Without the bounds check and the check that the slot is occupied,
+    // which are on every access in the hottest loops.
+    //
+    // SAFETY: an id is only made by `find_node`, for a node that it finds
+    // or inserts, or for an occupied slot, see `id`. GC and `clear` only
+    // remove the nodes that the world no longer refers to, and the ids that
+    // the world keeps, e.g. its root, snapshots and cached steps, are the
+    // roots of GC. An id held across a GC by a caller is the exception,
+    // which is a bug that the `paranoid` feature catches.
+    #[cfg(all(feature = "fast-index", not(miri)))]
+    fn data(&self, id: NodeId) -> &NodeData {
+        unsafe { self.node_data.get_unchecked(id.key()) }
     }
 
-    pub fn set_generation(&mut self, generation: u64) -> &mut Self {
-        self.generation = generation;
-        self
+    #[cfg(all(feature = "fast-index", not(miri)))]
+    fn data_mut(&mut self, id: NodeId) -> &mut NodeData {
+        unsafe { self.node_data.get_unchecked_mut(id.key()) }
     }
 
-    pub fn clear(&mut self, clear_nodes: bool) {
-        if clear_nodes {
-            self.hash_table.clear();
-            self.empty_nodes.clear();
-            self.node_data.clear();
-        } else {
-            self.clear_cache();
-        }
-        self.generation = 0;
-        self.root = Node::Leaf(0);
+    #[cfg(feature = "paranoid")]
+    fn check_generation(&self, id: NodeId) {
+        let current = self.id(id.key());
+        assert_eq!(id, current, "Stale node id, its slot was reused after GC.");
     }
 
-    pub fn garbage_collect(&mut self) {
-        if let Some(&node) = self.empty_nodes.last() {
-            self.mark_gc(node);
-        };
-        self.mark_gc(self.root);
-        let hash_table = &mut self.hash_table;
-        hash_table.clear();
-        self.node_data.retain(|i, data| {
-            let mark = data.gc_mark;
-            if mark {
-                hash_table.insert(data.children, NodeId(i as u32));
-                data.gc_mark = false;
-            }
-            mark
-        });
-    }
+    #[cfg(not(feature = "paranoid"))]
+    fn check_generation(&self, _id: NodeId) {}
 
-    pub fn bound(&self) -> Option<(i64, i64, i64, i64)> {
-        match (
-            self.left_bound(self.root),
-            self.right_bound(self.root),
-            self.top_bound(self.root),
-            self.bottom_bound(self.root),
-        ) {
-            (Some(left), Some(right), Some(top), Some(bottom)) => Some((left, right, top, bottom)),
-            (None, None, None, None) => None,
-            _ => unreachable!(),
+    // Clears the caches if they were computed with another rule. Only needed
+    // in a shared store: a world with its own nodes clears them whenever its
+    // rule changes. The step size is part of the key of `step_cache`.
+    pub(crate) fn use_cache_for(&mut self, rule: &Rule) {
+        let valid = self
+            .cache_key
+            .as_ref()
+            .is_some_and(|key| key.rule_table[..] == rule.rule_table[..]);
+        if !valid {
+            self.clear_cache();
+            self.cache_key = Some(Box::new(rule.clone()));
         }
     }
 
-    pub(crate) fn check_gc(&mut self) {
-        if self.node_data.len() >= self.gc_threshold {
-            self.garbage_collect();
+    // The cached step of `2^step` generations of a node, or of
+    // `2^(level - 2)` generations if that is fewer.
+    pub(crate) fn cached_step(&self, id: NodeId, step: u8) -> Option<Node> {
+        let data = &self[id];
+        if step + 2 >= data.level() {
+            data.cache_step_max()
+        } else {
+            self.step_cache.get(&(id, step)).copied()
         }
     }
 
-    fn mark_gc(&mut self, node: Node) {
-        if let Node::NodeId(id) = node {
-            if !self[id].gc_mark {
-                self[id].gc_mark = true;
-                self.mark_gc(self[id].nw());
-                self.mark_gc(self[id].ne());
-                self.mark_gc(self[id].sw());
-                self.mark_gc(self[id].se());
-                if let Some(node) = self[id].cache_step {
-                    self.mark_gc(node);
-                }
-            }
+    pub(crate) fn set_cached_step(&mut self, id: NodeId, step: u8, node: Node) {
+        if step + 2 >= self[id].level() {
+            self[id].set_cache_step_max(Some(node));
+        } else {
+            self.step_cache.insert((id, step), node);
         }
     }
 
@@ -252,122 +1518,81 @@ impl World {
         self.empty_nodes[level as usize - 2]
     }
 
-    pub(crate) fn expand(&mut self) {
-        match self.root {
-            Node::Leaf(leaf) => {
-                let nw = Node::Leaf((leaf & 0xcc00) >> 10);
-                let ne = Node::Leaf((leaf & 0x3300) >> 6);
-                let sw = Node::Leaf((leaf & 0x00cc) << 6);
-                let se = Node::Leaf((leaf & 0x0033) << 10);
-                self.root = Node::NodeId(self.find_node(nw, ne, sw, se));
-            }
-            Node::NodeId(id) => {
-                let level = self[id].level;
-                let empty = self.empty_node(level - 1);
-                let nw = Node::NodeId(self.find_node(empty, empty, empty, self[id].nw()));
-                let ne = Node::NodeId(self.find_node(empty, empty, self[id].ne(), empty));
-                let sw = Node::NodeId(self.find_node(empty, self[id].sw(), empty, empty));
-                let se = Node::NodeId(self.find_node(self[id].se(), empty, empty, empty));
-                self.root = Node::NodeId(self.find_node(nw, ne, sw, se));
-            }
-        }
-    }
-
-    pub(crate) fn should_expand(&self) -> bool {
-        match self.root {
-            Node::Leaf(_) => true,
-            Node::NodeId(id) => match self[id].children {
-                QuadChildren::Leaf { nw, ne, sw, se } => {
-                    nw & 0xfffe != 0 || ne & 0xfff7 != 0 || sw & 0xefff != 0 || se & 0x7fff != 0
-                }
-                QuadChildren::NodeId { nw, ne, sw, se } => {
-                    let nw_se_se = match self[nw].se() {
-                        Node::Leaf(leaf) => (leaf & 0x0033).count_ones() as u64,
-                        Node::NodeId(id) => self.node_population(self[id].se()),
-                    };
-                    let ne_sw_sw = match self[ne].sw() {
-                        Node::Leaf(leaf) => (leaf & 0x00cc).count_ones() as u64,
-                        Node::NodeId(id) => self.node_population(self[id].sw()),
-                    };
-                    let sw_ne_ne = match self[sw].ne() {
-                        Node::Leaf(leaf) => (leaf & 0x3300).count_ones() as u64,
-                        Node::NodeId(id) => self.node_population(self[id].ne()),
-                    };
-                    let se_nw_nw = match self[se].nw() {
-                        Node::Leaf(leaf) => (leaf & 0xcc00).count_ones() as u64,
-                        Node::NodeId(id) => self.node_population(self[id].nw()),
-                    };
-                    self[nw].population != nw_se_se
-                        || self[ne].population != ne_sw_sw
-                        || self[sw].population != sw_ne_ne
-                        || self[se].population != se_nw_nw
-                }
-            },
-        }
-    }
-
-    fn clear_cache(&mut self) {
+    pub(crate) fn clear_cache(&mut self) {
         self.node_data.iter_mut().for_each(|node| {
-            node.1.cache_step.take();
-        })
+            node.1.set_cache_step_max(None);
+        });
+        self.step_cache.clear();
     }
 
+    // The children are hashed and looked up only once, also for a new node.
     pub(crate) fn find_node(&mut self, nw: Node, ne: Node, sw: Node, se: Node) -> NodeId {
         let children = QuadChildren::new(nw, ne, sw, se);
-        self.hash_table.get(&children).copied().unwrap_or_else(|| {
-            let level = self.children_level(children) + 1;
-            let population = self.children_population(children);
-            let id = NodeId(self.node_data.insert(NodeData {
-                level,
-                population,
-                children,
-                cache_step: None,
-                gc_mark: false,
-            }) as u32);
-            self.hash_table.insert(children, id);
-            id
-        })
+        let node_data = &mut self.node_data;
+        let hash = hash_children(&children);
+        let entry = self.hash_table.entry(
+            table_hash(hash),
+            |&(other_hash, other, _)| other_hash == hash && other == children,
+            |&(hash, _, _)| table_hash(hash),
+        );
+        match entry {
+            Entry::Occupied(entry) => entry.get().2,
+            Entry::Vacant(entry) => {
+                let level = Nodes::children_level(node_data, children) + 1;
+                let key = node_data.insert(NodeData::new(level, children));
+                self.created += 1;
+                #[cfg(feature = "paranoid")]
+                if self.generations.len() <= key {
+                    self.generations.resize(key + 1, 0);
+                }
+                let id = NodeId {
+                    index: key as u32,
+                    #[cfg(feature = "paranoid")]
+                    generation: self.generations[key],
+                };
+                entry.insert((hash, children, id));
+                id
+            }
+        }
     }
 
     pub(crate) fn node_level(&self, node: Node) -> u8 {
         match node {
             Node::Leaf(_) => 2,
-            Node::NodeId(id) => self[id].level,
+            Node::NodeId(id) => self[id].level(),
         }
     }
 
-    fn children_level(&self, children: QuadChildren) -> u8 {
+    fn children_level(node_data: &Arena<NodeData>, children: QuadChildren) -> u8 {
         match children {
             QuadChildren::Leaf { .. } => 2,
-            QuadChildren::NodeId { nw, .. } => self[nw].level,
+            QuadChildren::NodeId { nw, .. } => node_data[nw.key()].level(),
         }
     }
 
     pub(crate) fn node_population(&self, node: Node) -> u64 {
         match node {
             Node::Leaf(leaf) => leaf.count_ones() as u64,
-            Node::NodeId(id) => self[id].population,
+            Node::NodeId(id) => self.population(id),
         }
     }
 
-    fn children_population(&self, children: QuadChildren) -> u64 {
-        match children {
-            QuadChildren::Leaf { nw, ne, sw, se } => {
-                nw.count_ones() as u64
-                    + ne.count_ones() as u64
-                    + sw.count_ones() as u64
-                    + se.count_ones() as u64
-            }
-            QuadChildren::NodeId { nw, ne, sw, se } => {
-                self[nw].population
-                    + self[ne].population
-                    + self[sw].population
-                    + self[se].population
-            }
+    // Computed from the children, and kept, when it is first needed.
+    pub(crate) fn population(&self, id: NodeId) -> u64 {
+        let data = &self[id];
+        if let Some(population) = data.known_population() {
+            return population;
         }
+        // Saturates, see `World::population`.
+        let population = [data.nw(), data.ne(), data.sw(), data.se()]
+            .iter()
+            .map(|&child| self.node_population(child))
+            .fold(0, u64::saturating_add);
+        data.set_population(population);
+        population
     }
 
-    fn get_cell_rec(&self, node: Node, x: i64, y: i64) -> bool {
+    pub(crate) fn get_cell_rec(&self, node: Node, x: i64, y: i64) -> bool {
         if self.node_population(node) == 0 {
             return false;
         }
@@ -386,194 +1611,148 @@ impl World {
         }
     }
 
-    fn set_cell_rec(&mut self, node: Node, x: i64, y: i64, state: bool) -> Node {
+    // Bound: (left, right, top, bottom), relative to the center of the node.
+    pub(crate) fn population_in_rec(&self, node: Node, bound: (i64, i64, i64, i64)) -> u64 {
+        let population = self.node_population(node);
+        if population == 0 {
+            return 0;
+        }
         let node_size = 1 << (self.node_level(node) - 2);
-        debug_assert!(
-            x < 2 * node_size && x >= -2 * node_size && y < 2 * node_size && y >= -2 * node_size,
-            "Cannot set cell outside of the node."
-        );
-        match node {
-            Node::Leaf(leaf) => {
-                if state {
-                    Node::Leaf(leaf | 1 << ((1 - y) * 4 + (1 - x)))
-                } else {
-                    Node::Leaf(leaf & !(1 << ((1 - y) * 4 + (1 - x))))
-                }
-            }
-            Node::NodeId(id) => {
-                let data = &self[id];
-                let mut nw = data.nw();
-                let mut ne = data.ne();
-                let mut sw = data.sw();
-                let mut se = data.se();
-                match (x.is_negative(), y.is_negative()) {
-                    (true, true) => nw = self.set_cell_rec(nw, x + node_size, y + node_size, state),
-                    (false, true) => {
-                        ne = self.set_cell_rec(ne, x - node_size, y + node_size, state)
-                    }
-                    (true, false) => {
-                        sw = self.set_cell_rec(sw, x + node_size, y - node_size, state)
-                    }
-                    (false, false) => {
-                        se = self.set_cell_rec(se, x - node_size, y - node_size, state)
-                    }
-                }
-                Node::NodeId(self.find_node(nw, ne, sw, se))
-            }
+        let (left, right, top, bottom) = bound;
+        let left = left.max(-2 * node_size);
+        let right = right.min(2 * node_size);
+        let top = top.max(-2 * node_size);
+        let bottom = bottom.min(2 * node_size);
+        if left >= right || top >= bottom {
+            return 0;
         }
-    }
-
-    fn left_bound(&self, node: Node) -> Option<i64> {
-        if self.node_population(node) == 0 {
-            return None;
+        if left == -2 * node_size
+            && right == 2 * node_size
+            && top == -2 * node_size
+            && bottom == 2 * node_size
+        {
+            return population;
         }
         match node {
             Node::Leaf(leaf) => {
-                if leaf & 0x8888 != 0 {
-                    Some(-2)
-                } else if leaf & 0x4444 != 0 {
-                    Some(-1)
-                } else if leaf & 0x2222 != 0 {
-                    Some(0)
-                } else if leaf & 0x1111 != 0 {
-                    Some(1)
-                } else {
-                    None
+                let mut mask: Leaf = 0;
+                for y in top..bottom {
+                    for x in left..right {
+                        mask |= 1 << ((1 - y) * 4 + (1 - x));
+                    }
                 }
+                (leaf & mask).count_ones() as u64
             }
             Node::NodeId(id) => {
-                let node_size = 1 << (self.node_level(node) - 2);
                 let data = &self[id];
-                self.left_bound(data.nw())
-                    .into_iter()
-                    .chain(self.left_bound(data.sw()).into_iter())
-                    .min()
-                    .map(|min| min - node_size)
-                    .or_else(|| {
-                        self.left_bound(data.ne())
-                            .into_iter()
-                            .chain(self.left_bound(data.se()).into_iter())
-                            .min()
-                            .map(|min| min + node_size)
-                    })
+                [
+                    (data.nw(), node_size, node_size),
+                    (data.ne(), -node_size, node_size),
+                    (data.sw(), node_size, -node_size),
+                    (data.se(), -node_size, -node_size),
+                ]
+                .iter()
+                .map(|&(child, dx, dy)| {
+                    let bound = (left + dx, right + dx, top + dy, bottom + dy);
+                    self.population_in_rec(child, bound)
+                })
+                // Saturates, see `World::population`.
+                .fold(0, u64::saturating_add)
             }
         }
     }
 
-    fn right_bound(&self, node: Node) -> Option<i64> {
-        if self.node_population(node) == 0 {
-            return None;
+    // Whether a node has no living cells. As the empty nodes are unique, its
+    // population is only computed when neither the empty node of its level
+    // nor that of its children is known, e.g. not for a root that was just
+    // expanded.
+    fn is_empty(&self, node: Node) -> bool {
+        let id = match node {
+            Node::Leaf(leaf) => return leaf == 0,
+            Node::NodeId(id) => id,
+        };
+        let data = &self[id];
+        if let Some(population) = data.known_population() {
+            return population == 0;
         }
-        match node {
-            Node::Leaf(leaf) => {
-                if leaf & 0x1111 != 0 {
-                    Some(2)
-                } else if leaf & 0x2222 != 0 {
-                    Some(1)
-                } else if leaf & 0x4444 != 0 {
-                    Some(0)
-                } else if leaf & 0x8888 != 0 {
-                    Some(-1)
-                } else {
-                    None
-                }
-            }
-            Node::NodeId(id) => {
-                let node_size = 1 << (self.node_level(node) - 2);
-                let data = &self[id];
-                self.right_bound(data.ne())
-                    .into_iter()
-                    .chain(self.right_bound(data.se()).into_iter())
-                    .max()
-                    .map(|max| max + node_size)
-                    .or_else(|| {
-                        self.right_bound(data.nw())
-                            .into_iter()
-                            .chain(self.right_bound(data.sw()).into_iter())
-                            .max()
-                            .map(|max| max - node_size)
-                    })
+        let level = data.level() as usize;
+        match self.empty_nodes.get(level - 2) {
+            Some(&empty) => node == empty,
+            None if self.empty_nodes.len() == level - 2 => {
+                [data.nw(), data.ne(), data.sw(), data.se()]
+                    .iter()
+                    .all(|&child| self.is_empty(child))
             }
+            None => self.population(id) == 0,
         }
     }
 
-    fn top_bound(&self, node: Node) -> Option<i64> {
-        if self.node_population(node) == 0 {
-            return None;
+    // Lowers `best`, or raises it on the right and the bottom, to the extreme
+    // coordinate of the living cells of `node` on `side`, where `center` is
+    // the coordinate of the center of the node across that side. The
+    // children nearer to the side are tried first, and the subtrees that
+    // cannot beat `best` are skipped, so only the nodes along the side are
+    // visited.
+    fn extreme(&self, node: Node, side: Side, center: i64, best: &mut Option<i64>) {
+        if self.is_empty(node) {
+            return;
         }
-        match node {
-            Node::Leaf(leaf) => {
-                if leaf & 0xf000 != 0 {
-                    Some(-2)
-                } else if leaf & 0x0f00 != 0 {
-                    Some(-1)
-                } else if leaf & 0x00f0 != 0 {
-                    Some(0)
-                } else if leaf & 0x000f != 0 {
-                    Some(1)
-                } else {
-                    None
-                }
-            }
-            Node::NodeId(id) => {
-                let node_size = 1 << (self.node_level(node) - 2);
-                let data = &self[id];
-                self.top_bound(data.nw())
-                    .into_iter()
-                    .chain(self.top_bound(data.ne()).into_iter())
-                    .min()
-                    .map(|min| min - node_size)
-                    .or_else(|| {
-                        self.top_bound(data.sw())
-                            .into_iter()
-                            .chain(self.top_bound(data.se()).into_iter())
-                            .min()
-                            .map(|min| min + node_size)
-                    })
+        let quarter = 1 << (self.node_level(node) - 2);
+        let is_max = matches!(side, Side::Right | Side::Bottom);
+        if let Some(best) = *best {
+            // The right and the bottom are exclusive; the subtraction does
+            // not overflow at level 64.
+            let beaten = if is_max {
+                center + quarter <= best.saturating_sub(quarter)
+            } else {
+                center - quarter - quarter >= best
+            };
+            if beaten {
+                return;
             }
         }
-    }
-
-    fn bottom_bound(&self, node: Node) -> Option<i64> {
-        if self.node_population(node) == 0 {
-            return None;
-        }
         match node {
             Node::Leaf(leaf) => {
-                if leaf & 0x000f != 0 {
-                    Some(2)
-                } else if leaf & 0x00f0 != 0 {
-                    Some(1)
-                } else if leaf & 0x0f00 != 0 {
-                    Some(0)
-                } else if leaf & 0xf000 != 0 {
-                    Some(-1)
+                // The columns or the rows, from the left or the top.
+                let lines = match side {
+                    Side::Left | Side::Right => [0x8888, 0x4444, 0x2222, 0x1111],
+                    Side::Top | Side::Bottom => [0xf000, 0x0f00, 0x00f0, 0x000f],
+                };
+                let occupied = |&i: &i64| leaf & lines[i as usize] != 0;
+                let extreme = if is_max {
+                    center - 1 + (0..4).rfind(occupied).unwrap()
                 } else {
-                    None
-                }
+                    center - 2 + (0..4).find(occupied).unwrap()
+                };
+                *best = Some(match *best {
+                    Some(best) if is_max => best.max(extreme),
+                    Some(best) => best.min(extreme),
+                    None => extreme,
+                });
             }
             Node::NodeId(id) => {
-                let node_size = 1 << (self.node_level(node) - 2);
                 let data = &self[id];
-                self.bottom_bound(data.sw())
-                    .into_iter()
-                    .chain(self.bottom_bound(data.se()).into_iter())
-                    .max()
-                    .map(|max| max + node_size)
-                    .or_else(|| {
-                        self.bottom_bound(data.nw())
-                            .into_iter()
-                            .chain(self.bottom_bound(data.ne()).into_iter())
-                            .max()
-                            .map(|max| max - node_size)
-                    })
+                // The halves of the node across the side, the lower first.
+                let (lower, upper) = match side {
+                    Side::Left | Side::Right => ([data.nw(), data.sw()], [data.ne(), data.se()]),
+                    Side::Top | Side::Bottom => ([data.nw(), data.ne()], [data.sw(), data.se()]),
+                };
+                let mut halves = [(lower, center - quarter), (upper, center + quarter)];
+                if is_max {
+                    halves.reverse();
+                }
+                for &(children, center) in halves.iter() {
+                    for &child in children.iter() {
+                        self.extreme(child, side, center, best);
+                    }
+                }
             }
         }
     }
 }
 
 #[cfg(test)]
-#[allow(clippy::field_reassign_with_default)]
+#[allow(clippy::field_reassign_with_default, clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -592,6 +1771,18 @@ mod tests {
         assert_eq!(world.get_cell(21, -6), true);
     }
 
+    #[test]
+    fn test_index() {
+        let world = World::from_bytes(b"x = 3, y = 3\nbo$2bo$3o!").unwrap();
+        assert!(world[(1, 0)] && world[(2, 1)]);
+        assert!(!world[(0, 0)]);
+        assert!(!world[(i64::MAX, i64::MIN)]);
+        let cells = world.cells();
+        let row: Vec<bool> = (0..3).map(|x| cells[(x, 2)]).collect();
+        assert_eq!(row, [true, true, true]);
+        assert!(!cells[(-1 << 40, 1 << 40)]);
+    }
+
     #[test]
     fn test_set_cell() {
         let mut world = World::default();
@@ -651,6 +1842,103 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_step_cache() {
+        let mut world = World::default();
+        world.root = Node::Leaf(0b_0000_0011_0110_0010);
+        let mut expected = world.clone();
+        world.set_step(1);
+        world.step();
+        let cached = world.nodes.step_cache.len();
+        assert!(cached > 0);
+        // The steps of another step size are cached separately, when they
+        // are stepped as nodes rather than as a bitboard.
+        world.life_like = Some(None);
+        world.set_step(0);
+        world.step();
+        world.step();
+        assert!(world.nodes.step_cache.keys().any(|&(_, step)| step == 1));
+        assert!(world.nodes.step_cache.keys().any(|&(_, step)| step == 0));
+        assert!(world.nodes.step_cache.len() > cached);
+        expected.step_by(4);
+        assert_eq!(world.cells_vec(), expected.cells_vec());
+
+        // The work list marks the same nodes as repeating until nothing
+        // changes.
+        let nodes = &world.nodes;
+        let mut marks = vec![false; nodes.node_data.capacity()];
+        for node in nodes.all_roots(&[world.root]) {
+            nodes.mark_gc(node, &mut marks);
+        }
+        let mut repeated = marks.clone();
+        nodes.mark_step_cache(&mut marks);
+        while let Some(&step) = nodes
+            .step_cache
+            .iter()
+            .find_map(|(&(id, _), step)| match step {
+                Node::NodeId(step_id) if repeated[id.key()] && !repeated[step_id.key()] => {
+                    Some(step)
+                }
+                _ => None,
+            })
+        {
+            nodes.mark_gc(step, &mut repeated);
+        }
+        assert_eq!(marks, repeated);
+
+        world.garbage_collect();
+        for &(id, _) in world.nodes.step_cache.keys() {
+            assert!(world.nodes.node_data.get(id.key()).is_some());
+        }
+        world.set_step(1);
+        world.step();
+        expected.step_by(2);
+        assert_eq!(world.cells_vec(), expected.cells_vec());
+    }
+
+    #[cfg(feature = "paranoid")]
+    #[test]
+    #[should_panic(expected = "Stale node id")]
+    fn test_stale_node_id() {
+        let mut world = World::default();
+        let stale = world.find_node(Node::Leaf(1), Node::Leaf(0), Node::Leaf(0), Node::Leaf(0));
+        assert_eq!(world[stale].level(), 3);
+        world.garbage_collect();
+        // The slot is reused by another node.
+        let id = world.find_node(Node::Leaf(2), Node::Leaf(0), Node::Leaf(0), Node::Leaf(0));
+        assert_eq!(id.key(), stale.key());
+        world[stale].level();
+    }
+
+    #[test]
+    fn test_expand_to_level() {
+        let mut world = World::default();
+        world.set_cells(vec![(0, 0), (1, 0), (2, 1)], true);
+        let mut expected = world.clone();
+        world.set_cell(1 << 40, -(1 << 40), true);
+        while expected.node_level(expected.root) < world.node_level(world.root) {
+            expected.expand();
+        }
+        expected.set_cell(1 << 40, -(1 << 40), true);
+        assert_eq!(world.cells_vec(), expected.cells_vec());
+        assert!(world.stats().nodes < expected.stats().nodes);
+
+        // A leaf as the root.
+        let mut world = World::from_bytes(b"bo$2bo$3o!").unwrap();
+        let cells = world.cells_vec();
+        world.expand_to_level(10);
+        assert_eq!(world.node_level(world.root), 10);
+        assert_eq!(world.cells_vec(), cells);
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+        assert_send::<World>();
+        assert_sync::<World>();
+    }
+
     #[test]
     fn test_bound() {
         let mut world = World::default();
@@ -659,5 +1947,170 @@ mod tests {
         assert_eq!(world.bound(), Some((-1, 2, -1, 2)));
         world.step();
         assert_eq!(world.bound(), Some((-41, 48, -47, 54)));
+
+        // The right and the bottom are exclusive, and the cells inside do
+        // not matter.
+        let mut world = World::default();
+        assert_eq!(world.bound(), None);
+        world.set_cells(
+            vec![(5, -3), (-70, 9), (3, 200), (-1, -1), (1 << 40, 7)],
+            true,
+        );
+        assert_eq!(world.bound(), Some((-70, (1 << 40) + 1, -3, 201)));
+    }
+
+    #[test]
+    fn test_centroid() {
+        let mut world = World::default();
+        assert_eq!(world.centroid(), None);
+        world.set_cells(vec![(0, 0), (2, 0), (1, 3)], true);
+        assert_eq!(world.centroid(), Some((1.0, 1.0)));
+        world.set_cells(vec![(1000, 2000), (1001, 2000), (1002, 2000)], true);
+        let (x, y) = world.centroid().unwrap();
+        assert!((x - 501.0).abs() <= 8.0 && (y - 1001.5).abs() <= 8.0);
+    }
+
+    #[test]
+    fn test_saturated_population() {
+        // A full square of side `2^33`, i.e. `2^66` cells.
+        let mut mc = String::from("[M2]\n#R B3/S23\n");
+        mc.push_str(&"********$".repeat(8));
+        mc.push('\n');
+        for level in 4..=33 {
+            let child = level - 3;
+            mc.push_str(&format!(
+                "{} {} {} {} {}\n",
+                level, child, child, child, child
+            ));
+        }
+        let mut world = World::from_macrocell_bytes(mc.as_bytes()).unwrap();
+        let half = 1 << 32;
+        assert_eq!(world.population(), u64::MAX);
+        assert_eq!(world.exact_population(), None);
+        assert_eq!(world.population_in((0, half, 0, half)), u64::MAX);
+        assert_eq!(world.population_in((0, 1 << 16, 0, 1 << 16)), 1 << 32);
+        assert!(world.get_cell(-half, half - 1));
+        assert!(!world.get_cell(half, 0));
+
+        // Only the corners survive, and cells are born along the edges.
+        world.step();
+        assert_eq!(world.exact_population(), Some(4 * (1 << 33) - 4));
+        assert!(world.get_cell(-half, -half));
+        assert!(!world.get_cell(-half + 1, -half));
+        assert!(world.get_cell(-half - 1, 0));
+        assert!(world.get_cell(0, half));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut world = World::default();
+        assert_eq!(world.stats().nodes, 0);
+        assert_eq!(world.stats().root_level, 2);
+        world.set_cell(100, 100, true);
+        let stats = world.stats();
+        assert!(stats.nodes > 0);
+        assert!(stats.root_level >= 8);
+        assert!(world.memory_usage() >= stats.nodes * size_of::<NodeData>());
+        world.snapshot();
+        assert_eq!(world.stats().snapshots, 1);
+        world.clear(true);
+        assert_eq!(world.stats().nodes, 0);
+        assert_eq!(world.stats().snapshots, 0);
+    }
+
+    #[test]
+    fn test_node_data_size() {
+        // Like a slot of the arena.
+        #[allow(dead_code)]
+        enum Entry {
+            Vacant(usize),
+            Occupied(NodeData),
+        }
+        // The generations of the `paranoid` feature make it larger.
+        #[cfg(not(feature = "paranoid"))]
+        assert!(size_of::<NodeData>() <= 32);
+        assert_eq!(size_of::<Entry>(), size_of::<NodeData>());
+    }
+
+    #[test]
+    fn test_density_at() {
+        let mut world = World::default();
+        world.root = Node::Leaf(0b_0000_0011_0110_0010);
+        assert_eq!(world.density_at(0, -1, 0), 1.0);
+        assert_eq!(world.density_at(0, -2, -2), 0.0);
+        assert_eq!(world.density_at(1, 0, 0), 0.5);
+        assert_eq!(world.density_at(2, 1, 1), 2.0 / 16.0);
+        assert_eq!(world.density_at(3, 5, -5), 2.0 / 64.0);
+        assert_eq!(world.density_at(3, -5, -5), 0.0);
+        assert_eq!(world.density_at(1, 100, 100), 0.0);
+        world.set_step(8);
+        world.step();
+        for &(x, y) in &[(-29, -17), (21, -6), (0, 0)] {
+            let expected = if world.get_cell(x, y) { 1.0 } else { 0.0 };
+            assert_eq!(world.density_at(0, x, y), expected);
+        }
+        let population = world.population() as f64;
+        let total: f64 = [(-1, -1), (0, -1), (-1, 0), (0, 0)]
+            .iter()
+            .map(|&(x, y)| world.density_at(12, x, y))
+            .sum();
+        assert_eq!(total * 4096.0 * 4096.0, population);
+    }
+
+    #[test]
+    fn test_density_at_edges() {
+        let mut world = World::default();
+        world.set_cell(-(1 << 62), 1 << 61, true);
+        assert_eq!(world.density_at(2, i64::MAX - 1, 0), 0.0);
+        assert_eq!(world.density_at(0, i64::MAX, i64::MAX), 0.0);
+        assert_eq!(world.density_at(62, i64::MAX, i64::MIN), 0.0);
+        assert_eq!(world.density_at(2, -(1 << 62) + 3, 1 << 61), 1.0 / 16.0);
+        let density = world.density_at(62, -1, 1 << 61);
+        assert_eq!(density, 1.0 / (1u128 << 124) as f64);
+    }
+
+    #[test]
+    fn test_population_in() {
+        let mut world = World::default();
+        world.draw_rect((0, 10, 0, 10), false, true);
+        assert_eq!(world.population_in((0, 10, 0, 10)), 36);
+        assert_eq!(world.population_in((0, 1, 0, 10)), 10);
+        assert_eq!(world.population_in((1, 9, 1, 9)), 0);
+        assert_eq!(
+            world.population_in((i64::MIN, i64::MAX, i64::MIN, i64::MAX)),
+            36
+        );
+        assert_eq!(world.population_in((5, 5, 0, 10)), 0);
+    }
+
+    #[test]
+    fn test_set_row_bits() {
+        let bits = [0x8000_0000_0000_0001_u64 | 0b_1011_0110 << 20, 0b_1101];
+        let len = 68;
+        let is_set = |i: i64| bits[i as usize / 64] & 1 << (i % 64) != 0;
+        let mut world = World::default();
+        world
+            .set_cell(-4, 3, true)
+            .set_cell(-3, 3, true)
+            .set_cell(50, 3, true);
+        world.set_row_bits(-37, 3, &bits, len, false);
+        for x in -40..40 {
+            let expected =
+                (x >= -37 && x < -37 + len as i64 && is_set(x + 37)) || x == -4 || x == -3;
+            assert_eq!(world.get_cell(x, 3), expected, "cell ({}, 3)", x);
+        }
+        assert_eq!(world.get_cell(50, 3), true);
+        world.set_row_bits(-37, 3, &bits, len, true);
+        for x in -40..40 {
+            let expected = x >= -37 && x < -37 + len as i64 && is_set(x + 37);
+            assert_eq!(world.get_cell(x, 3), expected, "cell ({}, 3)", x);
+        }
+        assert_eq!(world.population(), 11);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough bits for the row")]
+    fn test_set_row_bits_too_short() {
+        World::default().set_row_bits(0, 0, &[u64::MAX], 65, true);
     }
 }
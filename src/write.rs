@@ -0,0 +1,481 @@
+use crate::{
+    read::MacrocellOrigin,
+    world::{Leaf, Node, NodeId, Nodes, QuadChildren, World},
+};
+#[cfg(feature = "gz")]
+use flate2::{write::GzEncoder, Compression};
+use rustc_hash::FxHashMap;
+use std::io::{Result as IoResult, Write};
+
+const RLE_LINE_WIDTH: usize = 70;
+
+struct RleWriter<W: Write> {
+    writer: W,
+    line_len: usize,
+    run: (u8, u64),
+}
+
+impl<W: Write> RleWriter<W> {
+    fn push(&mut self, tag: u8, count: u64) -> IoResult<()> {
+        if count == 0 {
+            return Ok(());
+        }
+        if self.run.0 == tag {
+            self.run.1 += count;
+            Ok(())
+        } else {
+            self.flush_run()?;
+            self.run = (tag, count);
+            Ok(())
+        }
+    }
+
+    fn flush_run(&mut self) -> IoResult<()> {
+        let (tag, count) = self.run;
+        if count == 0 {
+            return Ok(());
+        }
+        let token = if count == 1 {
+            (tag as char).to_string()
+        } else {
+            format!("{}{}", count, tag as char)
+        };
+        if self.line_len + token.len() > RLE_LINE_WIDTH {
+            self.writer.write_all(b"\n")?;
+            self.line_len = 0;
+        }
+        self.writer.write_all(token.as_bytes())?;
+        self.line_len += token.len();
+        self.run = (0, 0);
+        Ok(())
+    }
+}
+
+struct MacrocellWriter<'a, W: Write> {
+    nodes: &'a Nodes,
+    writer: W,
+    ids: FxHashMap<NodeId, usize>,
+}
+
+impl<'a, W: Write> MacrocellWriter<'a, W> {
+    // Writes a level 3 leaf. Each row is a byte, whose highest bit is the
+    // leftmost cell.
+    fn write_leaf(&mut self, rows: [u8; 8]) -> IoResult<()> {
+        let last_row = rows.iter().rposition(|&row| row != 0).unwrap_or(0);
+        let mut line = String::new();
+        for &row in &rows[..=last_row] {
+            for x in 0..8 - row.trailing_zeros().min(8) {
+                line.push(if row & 0x80 >> x != 0 { '*' } else { '.' });
+            }
+            line.push('$');
+        }
+        writeln!(self.writer, "{}", line)
+    }
+
+    // Writes the node and all its descendants that are not written yet, and
+    // returns the id of the node. Empty nodes have id `0`.
+    fn write_node(&mut self, id: NodeId) -> IoResult<usize> {
+        let data = &self.nodes[id];
+        if self.nodes.population(id) == 0 {
+            return Ok(0);
+        }
+        if let Some(&index) = self.ids.get(&id) {
+            return Ok(index);
+        }
+        match data.children() {
+            QuadChildren::Leaf { nw, ne, sw, se } => {
+                let mut rows = [0; 8];
+                for y in 0..4 {
+                    rows[y] = (leaf_row(nw, y) << 4) | leaf_row(ne, y);
+                    rows[y + 4] = (leaf_row(sw, y) << 4) | leaf_row(se, y);
+                }
+                self.write_leaf(rows)?;
+            }
+            QuadChildren::NodeId { nw, ne, sw, se } => {
+                let level = data.level();
+                let nw = self.write_node(nw)?;
+                let ne = self.write_node(ne)?;
+                let sw = self.write_node(sw)?;
+                let se = self.write_node(se)?;
+                writeln!(self.writer, "{} {} {} {} {}", level, nw, ne, sw, se)?;
+            }
+        }
+        let index = self.ids.len() + 1;
+        self.ids.insert(id, index);
+        Ok(index)
+    }
+}
+
+// Returns the row `y` of a leaf, whose highest bit is the leftmost cell.
+fn leaf_row(leaf: Leaf, y: usize) -> u8 {
+    (leaf >> (12 - 4 * y) & 0xf) as u8
+}
+
+impl World {
+    // Writes the whole universe in Golly's Macrocell format, including the
+    // rule, the generation and the metadata, see `PatternMetadata`.
+    pub fn write_macrocell<W: Write>(&self, writer: W) -> IoResult<()> {
+        let mut writer = writer;
+        writeln!(writer, "[M2] (hashlife)")?;
+        writeln!(writer, "#R {}", self.rule.rulestring().unwrap_or("B3/S23"))?;
+        writeln!(writer, "#G {}", self.generation)?;
+        self.metadata.write(&mut writer, self.generation)?;
+        let nodes = self.nodes();
+        let mut mc = MacrocellWriter {
+            nodes: &nodes,
+            writer,
+            ids: FxHashMap::default(),
+        };
+        match self.root {
+            // The format has no level 2 nodes, so a leaf root is written as
+            // the center of a level 3 node.
+            Node::Leaf(leaf) => {
+                let mut rows = [0; 8];
+                for y in 0..4 {
+                    rows[y + 2] = leaf_row(leaf, y) << 2;
+                }
+                mc.write_leaf(rows)
+            }
+            Node::NodeId(id) => {
+                if mc.write_node(id)? == 0 {
+                    // An empty universe still needs a root node.
+                    writeln!(mc.writer, "$")?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Same as `write_macrocell`, but for a pattern that is loaded with the
+    // given origin. For Golly's origin, the pattern is moved one row up while
+    // it is written, which creates some nodes.
+    pub fn write_macrocell_with_origin<W: Write>(
+        &mut self,
+        writer: W,
+        origin: MacrocellOrigin,
+    ) -> IoResult<()> {
+        match origin {
+            MacrocellOrigin::Center => self.write_macrocell(writer),
+            MacrocellOrigin::Golly => self.with_nodes(|world| {
+                let root = world.root;
+                world.shift_row(false);
+                let result = world.write_macrocell(writer);
+                world.root = root;
+                result
+            }),
+        }
+    }
+
+    // Same as `write_macrocell`, but gzipped. The lines are compressed as
+    // they are written, so the whole text is never in memory.
+    #[cfg(feature = "gz")]
+    pub fn write_macrocell_gz<W: Write>(&self, writer: W, level: Compression) -> IoResult<()> {
+        let mut encoder = GzEncoder::new(writer, level);
+        self.write_macrocell(&mut encoder)?;
+        encoder.finish()?.flush()
+    }
+
+    pub fn to_macrocell(&self) -> String {
+        let mut bytes = Vec::new();
+        self.write_macrocell(&mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    // Writes the pattern in Golly's extended RLE format. The position and the
+    // generation are stored in the `#CXRLE` line, followed by the metadata,
+    // see `PatternMetadata`.
+    pub fn write_rle<W: Write>(&self, writer: W) -> IoResult<()> {
+        let mut writer = writer;
+        let rule = self.rule.rulestring().unwrap_or("B3/S23");
+        let bound = match self.bound() {
+            Some(bound) => bound,
+            None => {
+                self.metadata.write(&mut writer, self.generation)?;
+                writeln!(writer, "x = 0, y = 0, rule = {}", rule)?;
+                return writeln!(writer, "!");
+            }
+        };
+        let (left, right, top, bottom) = bound;
+        writeln!(
+            writer,
+            "#CXRLE Pos={},{} Gen={}",
+            left, top, self.generation
+        )?;
+        self.metadata.write(&mut writer, self.generation)?;
+        writeln!(
+            writer,
+            "x = {}, y = {}, rule = {}",
+            right - left,
+            bottom - top,
+            rule
+        )?;
+        let mut rle = RleWriter {
+            writer,
+            line_len: 0,
+            run: (0, 0),
+        };
+        let mut result = Ok(());
+        let (mut x, mut y) = (left, top);
+        self.for_living_cells_ordered(bound, |cell_x, cell_y| {
+            if result.is_ok() {
+                result = (|| {
+                    if cell_y > y {
+                        rle.push(b'$', (cell_y - y) as u64)?;
+                        x = left;
+                        y = cell_y;
+                    }
+                    rle.push(b'b', (cell_x - x) as u64)?;
+                    x = cell_x + 1;
+                    rle.push(b'o', 1)
+                })();
+            }
+        });
+        result?;
+        rle.flush_run()?;
+        rle.writer.write_all(b"!\n")
+    }
+
+    pub fn to_rle(&self) -> String {
+        let mut bytes = Vec::new();
+        self.write_rle(&mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    // Writes the bounding box of the pattern in Plaintext, with `.` for dead
+    // cells and `O` for living cells. Dead cells at the end of a row are
+    // omitted. The position, the rule and the generation are lost.
+    pub fn write_plaintext<W: Write>(&self, writer: W) -> IoResult<()> {
+        let mut writer = writer;
+        writeln!(writer, "! (hashlife)")?;
+        let bound = match self.bound() {
+            Some(bound) => bound,
+            None => return Ok(()),
+        };
+        let (left, _, top, bottom) = bound;
+        let mut result = Ok(());
+        let (mut x, mut y) = (left, top);
+        self.for_living_cells_ordered(bound, |cell_x, cell_y| {
+            if result.is_ok() {
+                result = (|| {
+                    while y < cell_y {
+                        writer.write_all(b"\n")?;
+                        x = left;
+                        y += 1;
+                    }
+                    for _ in x..cell_x {
+                        writer.write_all(b".")?;
+                    }
+                    x = cell_x + 1;
+                    writer.write_all(b"O")
+                })();
+            }
+        });
+        result?;
+        debug_assert_eq!(y + 1, bottom);
+        writer.write_all(b"\n")
+    }
+
+    pub fn to_plaintext(&self) -> String {
+        let mut bytes = Vec::new();
+        self.write_plaintext(&mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    // Writes the living cells in Life 1.06, one `x y` pair per line, in
+    // row-major order. The rule and the generation are lost.
+    pub fn write_life106<W: Write>(&self, writer: W) -> IoResult<()> {
+        let mut writer = writer;
+        writeln!(writer, "#Life 1.06")?;
+        let mut result = Ok(());
+        if let Some(bound) = self.bound() {
+            self.for_living_cells_ordered(bound, |x, y| {
+                if result.is_ok() {
+                    result = writeln!(writer, "{} {}", x, y);
+                }
+            });
+        }
+        result
+    }
+
+    pub fn to_life106(&self) -> String {
+        let mut bytes = Vec::new();
+        self.write_life106(&mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ca_formats::{macrocell::Macrocell, rle::Rle};
+
+    #[test]
+    fn test_write_rle() {
+        let mut world = World::default();
+        assert_eq!(world.to_rle(), "x = 0, y = 0, rule = B3/S23\n!\n");
+        let glider = Rle::new("x = 3, y = 3\nbo$2bo$3o!").unwrap();
+        world = World::from_rle(glider).unwrap();
+        world.set_generation(4);
+        assert_eq!(
+            world.to_rle(),
+            "#CXRLE Pos=0,0 Gen=4\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n"
+        );
+        world.set_step(6);
+        world.step();
+        let rle = world.to_rle();
+        let loaded = World::from_rle(Rle::new(rle.as_str()).unwrap()).unwrap();
+        assert_eq!(loaded.bound(), world.bound());
+        assert_eq!(loaded.cells_vec(), world.cells_vec());
+    }
+
+    #[test]
+    fn test_write_rle_sparse() {
+        let mut world = World::default();
+        world.set_cell(0, 0, true).set_cell(1, 1 << 34, true);
+        let rle = world.to_rle();
+        assert_eq!(
+            rle,
+            "#CXRLE Pos=0,0 Gen=0\nx = 2, y = 17179869185, rule = B3/S23\no17179869184$bo!\n"
+        );
+        let loaded = World::from_rle(Rle::new(rle.as_str()).unwrap()).unwrap();
+        assert_eq!(loaded.cells_vec(), world.cells_vec());
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let mut world = World::from_file("patterns/c4-diag-switch-engines.rle").unwrap();
+        let comments = [
+            "c/4 diagonal switch-engine breeder",
+            "two of Hartmut Holzwart's diagonal c/4 tub-and-beehive puffer",
+            "and a c/2 period 392 backward rake interact to produce",
+            "block-laying switch engines: David Bell, 23 July 2005",
+        ];
+        assert_eq!(world.get_metadata().comments, comments);
+        let mut metadata = world.get_metadata().clone();
+        metadata.name = Some("c4-diag-switch-engines".to_string());
+        metadata.author = Some("David Bell".to_string());
+        world.set_metadata(metadata.clone()).set_step(4);
+        world.step();
+
+        let rle = world.to_rle();
+        assert!(rle.starts_with("#CXRLE"));
+        assert!(rle.contains("Gen=16\n#N c4-diag-switch-engines\n#O David Bell\n#C c/4 diagonal"));
+        let mut loaded = World::from_bytes(rle.as_bytes()).unwrap();
+        metadata
+            .comments
+            .push("Saved by hashlife at generation 16".to_string());
+        assert_eq!(loaded.get_metadata(), &metadata);
+        assert_eq!(loaded.cells_vec(), world.cells_vec());
+        // The comment of the previous save is replaced.
+        let mc = loaded.set_generation(16).to_macrocell();
+        assert_eq!(mc.matches("Saved by hashlife").count(), 1);
+        let loaded = World::from_bytes(mc.as_bytes()).unwrap();
+        assert_eq!(loaded.get_metadata(), &metadata);
+        let loaded = World::from_reader(mc.as_bytes()).unwrap();
+        assert_eq!(loaded.get_metadata(), &metadata);
+
+        let world = World::from_file("patterns/totalperiodic.mc").unwrap();
+        assert_eq!(
+            world.get_metadata().comments[..3],
+            [
+                "Total periodic, by Bill Gosper.",
+                "",
+                "Without the block, every cell in the universe is aperiodic."
+            ]
+        );
+        let loaded = World::from_bytes(world.to_macrocell().as_bytes()).unwrap();
+        let comments = &loaded.get_metadata().comments;
+        assert_eq!(
+            comments[..comments.len() - 1],
+            world.get_metadata().comments[..]
+        );
+    }
+
+    #[test]
+    fn test_write_rle_line_width() {
+        let mut world = World::default();
+        for x in 0..200 {
+            world.set_cell(2 * x, x % 3, true);
+        }
+        let rle = world.to_rle();
+        assert!(rle.lines().all(|line| line.len() <= RLE_LINE_WIDTH));
+        let loaded = World::from_rle(Rle::new(rle.as_str()).unwrap()).unwrap();
+        assert_eq!(loaded.cells_vec(), world.cells_vec());
+    }
+
+    #[test]
+    fn test_write_plaintext_and_life106() {
+        let mut world = World::default();
+        assert_eq!(world.to_plaintext(), "! (hashlife)\n");
+        assert_eq!(world.to_life106(), "#Life 1.06\n");
+        world
+            .set_cell(-1, -1, true)
+            .set_cell(1, -1, true)
+            .set_cell(0, 1, true);
+        assert_eq!(world.to_plaintext(), "! (hashlife)\nO.O\n\n.O\n");
+        assert_eq!(world.to_life106(), "#Life 1.06\n-1 -1\n1 -1\n0 1\n");
+        let loaded = World::from_bytes(world.to_life106().as_bytes()).unwrap();
+        assert_eq!(loaded.bound(), world.bound());
+        let loaded = World::from_bytes(world.to_plaintext().as_bytes()).unwrap();
+        assert_eq!(loaded.bound(), Some((0, 3, 0, 3)));
+        assert_eq!(loaded.population(), 3);
+    }
+
+    #[test]
+    fn test_write_macrocell() {
+        let mut world = World::default();
+        world.set_cell(0, 0, true).set_cell(-2, 1, true);
+        let mc = world.to_macrocell();
+        let loaded = World::from_macrocell(Macrocell::new(mc.as_str()).unwrap()).unwrap();
+        assert_eq!(loaded.bound(), world.bound());
+        assert_eq!(loaded.population(), 2);
+
+        let macrocell = Macrocell::new(include_str!("../patterns/totalperiodic.mc")).unwrap();
+        let mut world = World::from_macrocell(macrocell).unwrap();
+        world.set_step(10);
+        world.step();
+        let mc = world.to_macrocell();
+        assert!(mc.starts_with("[M2] (hashlife)\n#R B3/S23\n#G 1024\n"));
+        let loaded = World::from_macrocell(Macrocell::new(mc.as_str()).unwrap()).unwrap();
+        assert_eq!(loaded.get_generation(), 1024);
+        assert_eq!(loaded.population(), world.population());
+        let mut cells = world.cells_vec();
+        let mut loaded_cells = loaded.cells_vec();
+        cells.sort_unstable();
+        loaded_cells.sort_unstable();
+        assert_eq!(loaded_cells, cells);
+    }
+
+    #[test]
+    #[cfg(feature = "gz")]
+    fn test_write_macrocell_gz() {
+        let macrocell = Macrocell::new(include_str!("../patterns/metapixel-galaxy.mc")).unwrap();
+        let world = World::from_macrocell(macrocell).unwrap();
+        let mut bytes = Vec::new();
+        world
+            .write_macrocell_gz(&mut bytes, Compression::best())
+            .unwrap();
+        assert!(bytes.starts_with(&[0x1f, 0x8b]));
+        let loaded = World::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.to_macrocell(), world.to_macrocell());
+    }
+
+    #[test]
+    fn test_write_macrocell_with_origin() {
+        let macrocell = Macrocell::new(include_str!("../patterns/totalperiodic.mc")).unwrap();
+        let mut world =
+            World::from_macrocell_with_origin(macrocell, MacrocellOrigin::Golly).unwrap();
+        let mut bytes = Vec::new();
+        world
+            .write_macrocell_with_origin(&mut bytes, MacrocellOrigin::Golly)
+            .unwrap();
+        let macrocell = Macrocell::new(&bytes[..]).unwrap();
+        let loaded = World::from_macrocell_with_origin(macrocell, MacrocellOrigin::Golly).unwrap();
+        assert_eq!(loaded.bound(), world.bound());
+        let mut cells = world.cells_vec();
+        let mut loaded_cells = loaded.cells_vec();
+        cells.sort_unstable();
+        loaded_cells.sort_unstable();
+        assert_eq!(loaded_cells, cells);
+    }
+}
@@ -0,0 +1,16 @@
+// Runs in a browser or in Node.js with `wasm-pack test --node`, or with
+// `cargo test --target wasm32-unknown-unknown` and `wasm-bindgen-test-runner`.
+#![cfg(target_arch = "wasm32")]
+
+use hashlife::World;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn glider() {
+    let mut world = World::from_bytes(b"x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!").unwrap();
+    world.set_step(2);
+    world.step();
+    assert_eq!(world.get_generation(), 4);
+    assert_eq!(world.population(), 5);
+    assert_eq!(world.bound(), Some((1, 4, 1, 4)));
+}